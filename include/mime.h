@@ -0,0 +1,60 @@
+/* C interface for the mime map format, enabled by the `ffi` feature
+ *
+ * The crate is only built as a Rust library, build the shared library for C
+ * with:
+ *
+ *     cargo rustc --release --lib --features ffi --crate-type cdylib
+ *
+ * The library is written to target/release, named libmime.so, libmime.dylib
+ * or mime.dll depending on the platform */
+
+#ifndef MIME_H
+#define MIME_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The meshes of a sector */
+#define MIME_MESH_FLOOR 0
+#define MIME_MESH_CEILING 1
+#define MIME_MESH_WALL 2
+
+typedef struct MimeMap MimeMap;
+
+typedef struct MimeVertex {
+    float pos[3];
+    float uv[2];
+    float color[4];
+} MimeVertex;
+
+/* Load a map from a mime file containing a single map, NULL on failure */
+MimeMap* mime_map_load(const char* filename);
+MimeMap* mime_map_load_bytes(const uint8_t* data, size_t len);
+
+/* Free a map returned by one of the load functions, NULL is ignored */
+void mime_map_free(MimeMap* map);
+
+size_t mime_map_sector_count(const MimeMap* map);
+
+/* The pointers are valid until the map is freed, NULL and 0 are returned
+ * when the sector or the mesh doesn't exist */
+size_t mime_map_vertex_count(const MimeMap* map, size_t sector,
+                             uint32_t role);
+const MimeVertex* mime_map_vertices(const MimeMap* map, size_t sector,
+                                    uint32_t role);
+size_t mime_map_index_count(const MimeMap* map, size_t sector,
+                            uint32_t role);
+const uint32_t* mime_map_indices(const MimeMap* map, size_t sector,
+                                 uint32_t role);
+uint64_t mime_map_texture_id(const MimeMap* map, size_t sector,
+                             uint32_t role);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,271 @@
+//! Moving floors and ceilings of sectors, like doors and lifts
+
+use crate::*;
+use crate::buffer::{ Reader, write_string, write_usize, string_size };
+
+/// The part of a sector an animation moves
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SectorPlane {
+    /// The floor mesh, like a lift
+    Floor,
+
+    /// The ceiling mesh, like a door
+    Ceiling,
+}
+
+/// The height of the animated plane at a point in time
+#[derive(Clone, PartialEq, Debug)]
+pub struct Keyframe {
+    /// The name of the state the keyframe represents, like "open" or
+    /// "closed", empty for keyframes in the middle of a movement
+    pub state: String,
+
+    /// The time of the keyframe in seconds from the start of the animation
+    pub time: f32,
+
+    /// How far the plane has moved along the up direction from the
+    /// geometry stored inside the map
+    pub offset: f32,
+}
+
+impl Keyframe {
+    /// Creates a new keyframe
+    ///
+    /// # Arguments
+    ///
+    /// * `state`  - The name of the state, empty if the keyframe isn't a
+    ///   state
+    /// * `time`   - The time of the keyframe in seconds
+    /// * `offset` - How far the plane has moved along the up direction
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new keyframe
+    pub fn new(state: &str, time: f32, offset: f32) -> Self {
+        Self {
+            state: state.to_string(),
+            time,
+            offset,
+        }
+    }
+}
+
+/// Moves the floor or ceiling of the sectors with a tag between keyframes,
+/// the engine decides when the animation plays
+#[derive(Clone, PartialEq, Debug)]
+pub struct SectorAnimation {
+    /// The name used by triggers and scripts to reference the animation
+    pub name: String,
+
+    /// The tag of the sectors the animation moves
+    pub tag: u32,
+
+    /// The plane of the sectors that moves
+    pub plane: SectorPlane,
+
+    /// The keyframes ordered by time
+    pub keyframes: Vec<Keyframe>,
+
+    /// The animation starts over after the last keyframe
+    pub looping: bool,
+}
+
+impl SectorAnimation {
+    /// Creates a new animation without keyframes
+    ///
+    /// # Arguments
+    ///
+    /// * `name`  - The name used to reference the animation
+    /// * `tag`   - The tag of the sectors the animation moves
+    /// * `plane` - The plane of the sectors that moves
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new animation
+    pub fn new(name: &str, tag: u32, plane: SectorPlane) -> Self {
+        Self {
+            name: name.to_string(),
+            tag,
+            plane,
+            keyframes: Vec::new(),
+            looping: false,
+        }
+    }
+
+    /// Get the length of the animation in seconds, the time of the last
+    /// keyframe
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+    }
+
+    /// Get the keyframe of a state
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The name of the state
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[Keyframe]`)` - The first keyframe with the state
+    /// * `None` - The animation doesn't have the state
+    pub fn state(&self, state: &str) -> Option<&Keyframe> {
+        self.keyframes.iter().find(|keyframe| keyframe.state == state)
+    }
+
+    /// Get the offset of the plane at a point in time, linearly
+    /// interpolated between the keyframes
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - The time in seconds from the start of the animation,
+    ///   wrapped around the duration for looping animations
+    ///
+    /// # Returns
+    ///
+    /// * The offset along the up direction, 0 without keyframes
+    pub fn offset_at(&self, time: f32) -> f32 {
+        let (Some(first), Some(last)) =
+            (self.keyframes.first(), self.keyframes.last()) else {
+            return 0.0;
+        };
+
+        let time = if self.looping && last.time > 0.0 {
+            time.rem_euclid(last.time)
+        } else {
+            time
+        };
+
+        let next = self.keyframes.partition_point(|k| k.time <= time);
+        if next == 0 {
+            return first.offset;
+        }
+
+        let Some(b) = self.keyframes.get(next) else {
+            return last.offset;
+        };
+
+        let a = &self.keyframes[next - 1];
+        let t = (time - a.time) / (b.time - a.time);
+        a.offset + (b.offset - a.offset) * t
+    }
+
+    /// Get the number of bytes [SectorAnimation::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        let keyframes = self.keyframes.iter()
+            .map(|keyframe| {
+                string_size(&keyframe.state) + 2 * std::mem::size_of::<f32>()
+            })
+            .sum::<usize>();
+
+        // The name, tag, plane, looping flag and the keyframe count
+        string_size(&self.name) + 4 + 1 + 1 + 8 + keyframes
+    }
+
+    /// Serialize the animation to a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - Successfully serialized the animation
+    /// * `Err(`[Error]`)` - Failed to serialize the animation
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        write_string(buffer, &self.name)?;
+        buffer.extend_from_slice(&self.tag.to_le_bytes());
+        buffer.push(match self.plane {
+            SectorPlane::Floor => 0,
+            SectorPlane::Ceiling => 1,
+        });
+        buffer.push(self.looping as u8);
+
+        write_usize(buffer, self.keyframes.len())?;
+        for keyframe in &self.keyframes {
+            write_string(buffer, &keyframe.state)?;
+            buffer.extend_from_slice(&keyframe.time.to_le_bytes());
+            buffer.extend_from_slice(&keyframe.offset.to_le_bytes());
+        }
+
+        Ok(())
+    }
+
+    /// Deserialize the animation from a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the animation
+    /// * `Err(`[Error]`)` - Failed to deserialize the animation
+    pub fn deserialize(buffer: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallChunk);
+
+        let name = reader.string()?;
+        let tag = reader.u32()?;
+        let plane = match reader.u8()? {
+            0 => SectorPlane::Floor,
+            1 => SectorPlane::Ceiling,
+            plane => return Err(Error::UnknownSectorPlane(plane)),
+        };
+        let looping = reader.u8()? != 0;
+
+        let count = reader.usize()?;
+
+        let mut keyframes = Vec::new();
+        for _i in 0..count {
+            keyframes.push(Keyframe {
+                state: reader.string()?,
+                time: reader.f32()?,
+                offset: reader.f32()?,
+            });
+        }
+
+        Ok(Self {
+            name,
+            tag,
+            plane,
+            keyframes,
+            looping,
+        })
+    }
+}
+
+impl Map {
+    /// Get an animation
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the animation
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[SectorAnimation]`)` - The first animation with the name
+    /// * `None` - The map doesn't have an animation with the name
+    pub fn animation(&self, name: &str) -> Option<&SectorAnimation> {
+        self.animations.iter().find(|animation| animation.name == name)
+    }
+
+    /// Get the animations moving a sector
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index of the sector
+    ///
+    /// # Returns
+    ///
+    /// * An iterator over the animations using the tag of the sector,
+    ///   empty for untagged sectors
+    pub fn sector_animations(&self, index: usize)
+        -> impl Iterator<Item = &SectorAnimation>
+    {
+        let tag = self.sectors.get(index).map_or(0, |sector| sector.tag);
+
+        self.animations.iter()
+            .filter(move |animation| tag != 0 && animation.tag == tag)
+    }
+}
@@ -0,0 +1,104 @@
+//! Concatenating meshes, the building block for merging and batching
+
+use crate::*;
+
+impl Mesh {
+    /// Append the vertices and triangles of another mesh to this mesh, the
+    /// indices of `other` are rebased past the vertices of this mesh
+    ///
+    /// The texture, material and vertex descriptor of this mesh are kept.
+    /// The lightmap texture coordinates, extra texture coordinate sets and
+    /// custom attributes are joined, a set or attribute only one of the
+    /// meshes has is filled with zeros for the vertices of the other mesh.
+    /// Custom attributes with the same name but another format are filled
+    /// with zeros as well. The submeshes of `other` are appended with their
+    /// ranges moved past the indices of this mesh.
+    ///
+    /// Both meshes should have the same [Topology], triangle strips are
+    /// joined with degenerate triangles.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The mesh to append
+    pub fn append(&mut self, other: &Mesh) {
+        let base = self.vertex_buffer.len();
+        let total = base + other.vertex_buffer.len();
+
+        // NOTE(patrik): Repeating the last index of this strip and the
+        // first index of the other strip gives degenerate triangles between
+        // the strips, one more index keeps the winding of the other strip
+        if self.topology == Topology::TriangleStrip {
+            if let (Some(&last), Some(&first)) =
+                (self.index_buffer.last(), other.index_buffer.first()) {
+                let first = first + base as u32;
+                if self.index_buffer.len() % 2 == 1 {
+                    self.index_buffer.extend_from_slice(&[last, last, first]);
+                } else {
+                    self.index_buffer.extend_from_slice(&[last, first]);
+                }
+            }
+        }
+
+        let index_base = self.index_buffer.len() as u32;
+
+        self.submeshes.extend(other.submeshes.iter().map(|submesh| {
+            Submesh {
+                index_start: submesh.index_start + index_base,
+                ..submesh.clone()
+            }
+        }));
+
+        self.vertex_buffer.extend_from_slice(&other.vertex_buffer);
+        self.index_buffer.extend(
+            other.index_buffer.iter().map(|&index| index + base as u32));
+
+        if !self.lightmap_uvs.is_empty() || !other.lightmap_uvs.is_empty() {
+            append_values(&mut self.lightmap_uvs, base, total,
+                          &other.lightmap_uvs, [0.0; 2]);
+        }
+
+        for set in &other.uv_sets {
+            if self.uv_set(&set.name).is_none() {
+                self.uv_sets.push(UvSet::new(&set.name, Vec::new()));
+            }
+        }
+
+        for set in &mut self.uv_sets {
+            let uvs = other.uv_set(&set.name).unwrap_or_default();
+            append_values(&mut set.uvs, base, total, uvs, [0.0; 2]);
+        }
+
+        for attribute in &other.custom_attributes {
+            if self.custom_attribute(&attribute.name).is_none() {
+                self.custom_attributes.push(
+                    CustomAttribute::new(&attribute.name, attribute.format,
+                                         Vec::new()));
+            }
+        }
+
+        for attribute in &mut self.custom_attributes {
+            let size = attribute.format.size();
+            let data = other.custom_attribute(&attribute.name)
+                .filter(|other| other.format == attribute.format)
+                .map_or(&[][..], |other| other.data.as_slice());
+
+            attribute.data.resize(base * size, 0);
+            attribute.data.extend_from_slice(data);
+            attribute.data.resize(total * size, 0);
+        }
+    }
+}
+
+/// Append per-vertex values of another mesh, the values are padded or cut
+/// so this mesh has `base` values before and `total` values after
+fn append_values<T>(values: &mut Vec<T>,
+                    base: usize,
+                    total: usize,
+                    other: &[T],
+                    fill: T)
+    where T: Copy
+{
+    values.resize(base, fill);
+    values.extend_from_slice(other);
+    values.resize(total, fill);
+}
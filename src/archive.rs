@@ -0,0 +1,491 @@
+//! Archive storing multiple named maps and shared resources in one file
+
+use crate::*;
+use crate::buffer::{ Reader, write_string };
+use crate::map::{ CURRENT_VERSION, is_readable_version };
+
+use std::io::{ Read, Seek, SeekFrom };
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::path::Path;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::fs::File;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::io::Write;
+
+/// The current version of the archive format
+///
+/// Version 2 added the format version of the maps to the header
+pub const ARCHIVE_VERSION: u32 = 2;
+
+/// The archive magic
+const ARCHIVE_MAGIC: &[u8] = b"MIMA";
+
+/// The size of the archive header (magic, version, map format version,
+/// entry count, index offset)
+const ARCHIVE_HEADER_SIZE: usize = 4 + 4 + 4 + 8 + 8;
+
+/// The kind of data an archive entry holds
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum EntryKind {
+    /// A serialized map
+    Map,
+
+    /// A shared resource, the content is decided by the user
+    Resource,
+}
+
+/// A single entry inside the index of the archive
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct IndexEntry {
+    /// The name of the entry
+    pub name: String,
+
+    /// The kind of data the entry holds
+    pub kind: EntryKind,
+
+    /// The offset of the data from the start of the archive
+    pub offset: u64,
+
+    /// The size of the data
+    pub size: u64,
+}
+
+/// The index of an archive, used to read single entries without loading
+/// the whole archive
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ArchiveIndex {
+    /// The entries of the archive
+    pub entries: Vec<IndexEntry>,
+}
+
+impl ArchiveIndex {
+    /// Read the index of an archive
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The archive to read the index from
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully read the index
+    /// * `Err(`[Error]`)` - Failed to read or parse the index
+    pub fn read<R>(reader: &mut R) -> Result<Self>
+        where R: Read + Seek
+    {
+        let mut header = [0; ARCHIVE_HEADER_SIZE];
+        reader.seek(SeekFrom::Start(0))
+            .and_then(|_| reader.read_exact(&mut header))
+            .map_err(Error::FileReadFailed)?;
+
+        let (count, index_offset) = parse_header(&header)?;
+
+        let mut index = Vec::new();
+        reader.seek(SeekFrom::Start(index_offset))
+            .and_then(|_| reader.read_to_end(&mut index))
+            .map_err(Error::FileReadFailed)?;
+
+        Self::deserialize(&index, count)
+    }
+
+    /// Find an entry by name
+    pub fn find(&self, name: &str) -> Option<&IndexEntry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    /// Read the data of a single entry
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The archive the index was read from
+    /// * `name`   - The name of the entry
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Vec]`)` - The data of the entry
+    /// * `Err(`[Error]`)` - The entry doesn't exist or reading failed
+    pub fn read_entry<R>(&self, reader: &mut R, name: &str) -> Result<Vec<u8>>
+        where R: Read + Seek
+    {
+        let entry = self.find(name).ok_or(Error::EntryNotFound)?;
+
+        // NOTE(patrik): The size comes from the file, the data only grows
+        // as it is read so a corrupt size can't allocate more than the
+        // archive contains
+        let mut data = Vec::new();
+        reader.seek(SeekFrom::Start(entry.offset))
+            .and_then(|_| reader.by_ref().take(entry.size)
+                                .read_to_end(&mut data))
+            .map_err(Error::FileReadFailed)?;
+
+        if data.len() as u64 != entry.size {
+            let size = usize::try_from(entry.size).unwrap_or(usize::MAX);
+            let size = SizeMismatch::new(size, data.len());
+            return Err(Error::BufferToSmallArchive(size));
+        }
+
+        Ok(data)
+    }
+
+    /// Read and deserialize a single map
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The archive the index was read from
+    /// * `name`   - The name of the map
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Map]`)` - The map
+    /// * `Err(`[Error]`)` - The map doesn't exist or reading failed
+    pub fn read_map<R>(&self, reader: &mut R, name: &str) -> Result<Map>
+        where R: Read + Seek
+    {
+        match self.find(name) {
+            Some(entry) if entry.kind == EntryKind::Map => {
+                Map::deserialize(&self.read_entry(reader, name)?)
+            }
+
+            _ => Err(Error::EntryNotFound),
+        }
+    }
+
+    /// Deserialize `count` index entries from a buffer
+    fn deserialize(buffer: &[u8], count: usize) -> Result<Self> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallArchive);
+
+        let mut entries = Vec::new();
+        for _i in 0..count {
+            let name = reader.string()?;
+            let kind = match reader.u8()? {
+                0 => EntryKind::Map,
+                1 => EntryKind::Resource,
+                kind => return Err(Error::UnknownEntryKind(kind)),
+            };
+            let offset = reader.u64()?;
+            let size = reader.u64()?;
+
+            entries.push(IndexEntry {
+                name,
+                kind,
+                offset,
+                size,
+            });
+        }
+
+        Ok(Self {
+            entries,
+        })
+    }
+}
+
+/// Parse the archive header
+///
+/// # Returns
+///
+/// * `Ok((count, index_offset))` - The number of entries and the offset
+///   of the index
+/// * `Err(`[Error]`)` - The header is invalid, [Error::IncorrectVersion]
+///   if the maps use a format version that can't be read
+fn parse_header(buffer: &[u8]) -> Result<(usize, u64)> {
+    let mut reader = Reader::new(buffer, Error::BufferToSmallArchive);
+
+    if reader.bytes(4)? != ARCHIVE_MAGIC {
+        return Err(Error::IncorrectMagic);
+    }
+
+    let version = reader.u32()?;
+    if version != ARCHIVE_VERSION {
+        return Err(Error::IncorrectVersion(version));
+    }
+
+    // NOTE(patrik): The maps are stored without the header of a mime file,
+    // the archive records their format version instead
+    let map_version = reader.u32()?;
+    if !is_readable_version(map_version) {
+        return Err(Error::IncorrectVersion(map_version));
+    }
+
+    Ok((reader.usize()?, reader.u64()?))
+}
+
+/// An entry of an archive with its data loaded
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Entry {
+    /// The name of the entry
+    name: String,
+
+    /// The kind of data the entry holds
+    kind: EntryKind,
+
+    /// The data of the entry
+    data: Vec<u8>,
+}
+
+/// A WAD like archive storing multiple named maps and shared resources
+///
+/// The archive starts with a header followed by the data of all entries and
+/// ends with an index of the names, offsets and sizes of the entries, see
+/// [ArchiveIndex] for reading single entries without loading everything.
+/// The header records the format version of the maps, all the maps of an
+/// archive use the version of the crate that wrote it.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct MimeArchive {
+    /// The entries of the archive in the order they were added
+    entries: Vec<Entry>,
+}
+
+impl MimeArchive {
+    /// Create a new empty archive
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Add or replace an entry
+    fn insert(&mut self, name: &str, kind: EntryKind, data: Vec<u8>) {
+        let entry = Entry {
+            name: name.to_string(),
+            kind,
+            data,
+        };
+
+        match self.entries.iter_mut().find(|e| e.name == name) {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+    }
+
+    /// Serialize a map and add it to the archive, replacing any entry with
+    /// the same name
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the map
+    /// * `map`  - The map to add
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - Successfully added the map
+    /// * `Err(`[Error]`)` - Failed to serialize the map
+    pub fn add_map(&mut self, name: &str, map: &Map) -> Result<()> {
+        let mut data = Vec::new();
+        map.serialize(&mut data)?;
+
+        self.insert(name, EntryKind::Map, data);
+
+        Ok(())
+    }
+
+    /// Add a shared resource to the archive, replacing any entry with the
+    /// same name
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the resource
+    /// * `data` - The content of the resource
+    pub fn add_resource(&mut self, name: &str, data: Vec<u8>) {
+        self.insert(name, EntryKind::Resource, data);
+    }
+
+    /// Remove an entry from the archive
+    ///
+    /// # Returns
+    ///
+    /// * `true` - The entry was removed
+    /// * `false` - The archive doesn't have an entry with the name
+    pub fn remove(&mut self, name: &str) -> bool {
+        let count = self.entries.len();
+        self.entries.retain(|entry| entry.name != name);
+
+        self.entries.len() != count
+    }
+
+    /// Get the names of all the maps inside the archive
+    pub fn map_names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter()
+            .filter(|entry| entry.kind == EntryKind::Map)
+            .map(|entry| entry.name.as_str())
+    }
+
+    /// Get the names of all the resources inside the archive
+    pub fn resource_names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter()
+            .filter(|entry| entry.kind == EntryKind::Resource)
+            .map(|entry| entry.name.as_str())
+    }
+
+    /// Deserialize a map from the archive
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the map
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Map]`)` - The map
+    /// * `Err(`[Error]`)` - The map doesn't exist or failed to deserialize
+    pub fn map(&self, name: &str) -> Result<Map> {
+        let entry = self.entries.iter()
+            .find(|e| e.name == name && e.kind == EntryKind::Map)
+            .ok_or(Error::EntryNotFound)?;
+
+        Map::deserialize(&entry.data)
+    }
+
+    /// Get the content of a shared resource
+    pub fn resource(&self, name: &str) -> Option<&[u8]> {
+        self.entries.iter()
+            .find(|e| e.name == name && e.kind == EntryKind::Resource)
+            .map(|entry| entry.data.as_slice())
+    }
+
+    /// Serialize the archive to a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - Successfully serialized the archive
+    /// * `Err(`[Error]`)` - Failed to serialize the archive
+    pub fn serialize(&self, buffer: &mut Vec<u8>) -> Result<()> {
+        let start = buffer.len();
+
+        buffer.extend_from_slice(ARCHIVE_MAGIC);
+        buffer.extend_from_slice(&ARCHIVE_VERSION.to_le_bytes());
+        buffer.extend_from_slice(&CURRENT_VERSION.to_le_bytes());
+
+        let count: u64 = self.entries.len().try_into()
+            .map_err(Error::IntegerConvertionError)?;
+        buffer.extend_from_slice(&count.to_le_bytes());
+
+        // The index offset is patched when we know it
+        let index_offset_pos = buffer.len();
+        buffer.extend_from_slice(&0u64.to_le_bytes());
+
+        let mut offsets = Vec::with_capacity(self.entries.len());
+        for entry in &self.entries {
+            let offset: u64 = (buffer.len() - start).try_into()
+                .map_err(Error::IntegerConvertionError)?;
+            offsets.push(offset);
+
+            buffer.extend_from_slice(&entry.data);
+        }
+
+        let index_offset: u64 = (buffer.len() - start).try_into()
+            .map_err(Error::IntegerConvertionError)?;
+        buffer[index_offset_pos..index_offset_pos + 8]
+            .copy_from_slice(&index_offset.to_le_bytes());
+
+        for (entry, offset) in self.entries.iter().zip(offsets) {
+            write_string(buffer, &entry.name)?;
+            buffer.push(match entry.kind {
+                EntryKind::Map => 0,
+                EntryKind::Resource => 1,
+            });
+
+            let size: u64 = entry.data.len().try_into()
+                .map_err(Error::IntegerConvertionError)?;
+            buffer.extend_from_slice(&offset.to_le_bytes());
+            buffer.extend_from_slice(&size.to_le_bytes());
+        }
+
+        Ok(())
+    }
+
+    /// Deserialize the buffer and create an archive
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the archive
+    /// * `Err(`[Error]`)` - Failed to deserialize the archive
+    pub fn deserialize(buffer: &[u8]) -> Result<Self> {
+        let (count, index_offset) = parse_header(buffer)?;
+
+        let index_offset: usize = index_offset.try_into()
+            .map_err(Error::IntegerConvertionError)?;
+        let index = buffer.get(index_offset..)
+            .ok_or_else(|| {
+                let size = SizeMismatch::new(index_offset, buffer.len());
+                Error::BufferToSmallArchive(size)
+            })?;
+        let index = ArchiveIndex::deserialize(index, count)?;
+
+        let mut entries = Vec::with_capacity(index.entries.len());
+        for entry in index.entries {
+            let offset: usize = entry.offset.try_into()
+                .map_err(Error::IntegerConvertionError)?;
+            let size: usize = entry.size.try_into()
+                .map_err(Error::IntegerConvertionError)?;
+
+            let end = offset.saturating_add(size);
+            let data = buffer.get(offset..end)
+                .ok_or_else(|| {
+                    let size = SizeMismatch::new(end, buffer.len());
+                    Error::BufferToSmallArchive(size)
+                })?;
+
+            entries.push(Entry {
+                name: entry.name,
+                kind: entry.kind,
+                data: data.to_vec(),
+            });
+        }
+
+        Ok(Self {
+            entries,
+        })
+    }
+
+    /// Serialize the archive and write it to a file
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - Filename of the file we should create
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - Successfully wrote the archive
+    /// * `Err(`[Error]`)` - Failed to serialize or write the archive
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn save_to_file<P>(&self, filename: P) -> Result<()>
+        where P: AsRef<Path>
+    {
+        let mut buffer = Vec::new();
+        self.serialize(&mut buffer)?;
+
+        let mut file = File::create(filename)
+            .map_err(Error::FileCreationFailed)?;
+        file.write_all(&buffer[..])
+            .map_err(Error::FileWriteFailed)?;
+
+        Ok(())
+    }
+
+    /// Read a whole archive from a file
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - Filename of the archive
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully read the archive
+    /// * `Err(`[Error]`)` - Failed to read or deserialize the archive
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn load_from_file<P>(filename: P) -> Result<Self>
+        where P: AsRef<Path>
+    {
+        let buffer = std::fs::read(filename)
+            .map_err(Error::FileReadFailed)?;
+
+        Self::deserialize(&buffer)
+    }
+}
@@ -0,0 +1,288 @@
+//! Assets embedded inside the map file and external assets the map depends
+//! on
+
+use crate::*;
+use crate::buffer::{ Reader, write_string, string_size };
+
+/// A small binary asset (texture, palette, ...) embedded inside the map
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct EmbeddedAsset {
+    /// The name used to reference the asset
+    pub name: String,
+
+    /// The content of the asset
+    pub data: Vec<u8>,
+}
+
+impl EmbeddedAsset {
+    /// Creates a new embedded asset
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name used to reference the asset
+    /// * `data` - The content of the asset
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new asset
+    pub fn new(name: &str, data: Vec<u8>) -> Self {
+        Self {
+            name: name.to_string(),
+            data,
+        }
+    }
+
+    /// Get the number of bytes [EmbeddedAsset::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        string_size(&self.name) + self.data.len()
+    }
+
+    /// Serialize the asset to a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - Successfully serialized the asset
+    /// * `Err(`[Error]`)` - Failed to serialize the asset
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        write_string(buffer, &self.name)?;
+        buffer.extend_from_slice(&self.data);
+
+        Ok(())
+    }
+
+    /// Deserialize the asset from a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the asset
+    /// * `Err(`[Error]`)` - Failed to deserialize the asset
+    pub fn deserialize(buffer: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallChunk);
+
+        let name = reader.string()?;
+
+        Ok(Self {
+            name,
+            data: reader.remaining().to_vec(),
+        })
+    }
+}
+
+/// The kind of an external asset
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum AssetKind {
+    /// A texture
+    Texture,
+
+    /// A sound
+    Sound,
+
+    /// Any other kind of asset
+    Other,
+}
+
+/// An external asset the map references
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct AssetDependency {
+    /// The kind of the asset
+    pub kind: AssetKind,
+
+    /// The name of the asset
+    pub name: String,
+}
+
+impl AssetDependency {
+    /// Creates a new dependency
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - The kind of the asset
+    /// * `name` - The name of the asset
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new dependency
+    pub fn new(kind: AssetKind, name: &str) -> Self {
+        Self {
+            kind,
+            name: name.to_string(),
+        }
+    }
+
+    /// Get the number of bytes [AssetDependency::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        1 + string_size(&self.name)
+    }
+
+    /// Serialize the dependency to a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - Successfully serialized the dependency
+    /// * `Err(`[Error]`)` - Failed to serialize the dependency
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        buffer.push(match self.kind {
+            AssetKind::Texture => 0,
+            AssetKind::Sound => 1,
+            AssetKind::Other => 2,
+        });
+        write_string(buffer, &self.name)?;
+
+        Ok(())
+    }
+
+    /// Deserialize the dependency from a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the dependency
+    /// * `Err(`[Error]`)` - Failed to deserialize the dependency
+    pub fn deserialize(buffer: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallChunk);
+
+        let kind = match reader.u8()? {
+            0 => AssetKind::Texture,
+            1 => AssetKind::Sound,
+            2 => AssetKind::Other,
+            kind => return Err(Error::UnknownAssetKind(kind)),
+        };
+
+        Ok(Self {
+            kind,
+            name: reader.string()?,
+        })
+    }
+}
+
+impl Map {
+    /// Collect the external assets referenced by the content of the map,
+    /// assets embedded inside the map are not included
+    ///
+    /// # Returns
+    ///
+    /// * The sorted list of unique dependencies
+    pub fn referenced_assets(&self) -> Vec<AssetDependency> {
+        let mut result = std::collections::BTreeSet::new();
+
+        let mut add = |kind, name: &str| {
+            if !name.is_empty() && self.embedded_asset(name).is_none() {
+                result.insert(AssetDependency::new(kind, name));
+            }
+        };
+
+        for sector in &self.sectors {
+            for wall in &sector.walls {
+                add(AssetKind::Texture, &wall.texture);
+            }
+        }
+
+        if let Some(Sky::Skybox { texture }) = &self.sky {
+            add(AssetKind::Texture, texture);
+        }
+
+        for material in &self.materials {
+            let textures = [&material.base_color_texture,
+                            &material.metallic_roughness_texture,
+                            &material.normal_texture];
+            for texture in textures.into_iter().flatten() {
+                add(AssetKind::Texture, texture);
+            }
+        }
+
+        for emitter in &self.sound_emitters {
+            add(AssetKind::Sound, &emitter.sound);
+        }
+
+        result.into_iter().collect()
+    }
+
+    /// Replace the dependency manifest with the assets currently referenced
+    /// by the map, see [Map::referenced_assets]
+    pub fn update_dependencies(&mut self) {
+        self.dependencies = self.referenced_assets();
+    }
+
+    /// Get the dependencies inside the manifest that don't exist
+    ///
+    /// # Arguments
+    ///
+    /// * `exists` - Checks if an asset exists
+    ///
+    /// # Returns
+    ///
+    /// * The dependencies where `exists` returned false
+    pub fn missing_dependencies<F>(&self, mut exists: F)
+        -> Vec<&AssetDependency>
+        where F: FnMut(&AssetDependency) -> bool
+    {
+        self.dependencies.iter()
+            .filter(|dependency| !exists(dependency))
+            .collect()
+    }
+
+    /// Embed an asset inside the map, replacing any asset with the same name
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name used to reference the asset
+    /// * `data` - The content of the asset
+    pub fn embed_asset(&mut self, name: &str, data: Vec<u8>) {
+        let asset = EmbeddedAsset::new(name, data);
+
+        match self.embedded_assets.iter_mut().find(|a| a.name == name) {
+            Some(existing) => *existing = asset,
+            None => self.embedded_assets.push(asset),
+        }
+    }
+
+    /// Get the content of an embedded asset
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the asset
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[u8]`)` - The content of the asset
+    /// * `None` - The map doesn't embed an asset with the name
+    pub fn embedded_asset(&self, name: &str) -> Option<&[u8]> {
+        self.embedded_assets.iter()
+            .find(|asset| asset.name == name)
+            .map(|asset| asset.data.as_slice())
+    }
+
+    /// Remove an embedded asset
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[EmbeddedAsset]`)` - The removed asset
+    /// * `None` - The map doesn't embed an asset with the name
+    pub fn remove_embedded_asset(&mut self, name: &str)
+        -> Option<EmbeddedAsset>
+    {
+        let index = self.embedded_assets.iter()
+            .position(|asset| asset.name == name)?;
+
+        Some(self.embedded_assets.remove(index))
+    }
+}
@@ -0,0 +1,61 @@
+//! Loading and saving maps with tokio, enabled by the `async` feature
+
+use crate::*;
+
+use std::path::Path;
+use tokio::io::AsyncWriteExt;
+
+impl Map {
+    /// Serialize the map as a mime file with a single map and write it to a
+    /// file without blocking, see [Map::save_to_file]
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - Filename of the file we should create to write the
+    ///   serialized data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - Successfully serialized the map and wrote the data to
+    ///   the file
+    /// * `Err(`[Error]`)` - Failed to serialize the map or write the data
+    ///   to the file
+    pub async fn save_to_file_async<P>(&mut self, filename: P) -> Result<()>
+        where P: AsRef<Path>
+    {
+        let (buffer, layout) = self.serialize_file()?;
+
+        let mut file = tokio::fs::File::create(filename).await
+            .map_err(Error::FileCreationFailed)?;
+        file.write_all(&buffer[..]).await
+            .map_err(Error::FileWriteFailed)?;
+        file.flush().await
+            .map_err(Error::FileWriteFailed)?;
+
+        self.layout = Some(layout);
+        self.dirty_sectors.clear();
+
+        Ok(())
+    }
+
+    /// Load a map from a mime file containing a single map without
+    /// blocking, see [Map::load_from_file]
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - Filename of the file to read
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Map]`)` - Successfully read and deserialized the map
+    /// * `Err(`[Error]`)` - Failed to read the file, deserialize the map or
+    ///   the file doesn't contain exactly one map
+    pub async fn load_from_file_async<P>(filename: P) -> Result<Self>
+        where P: AsRef<Path>
+    {
+        let buffer = tokio::fs::read(filename).await
+            .map_err(Error::FileReadFailed)?;
+
+        Self::load_from_bytes(&buffer)
+    }
+}
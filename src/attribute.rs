@@ -0,0 +1,603 @@
+//! Per-vertex attributes of meshes besides the fixed vertex layout
+
+use crate::*;
+use crate::buffer::{ Reader, write_usize, write_string, string_size,
+                     write_list, list_size, write_f32s };
+
+/// Describes which attributes the serialized vertices of a mesh store and
+/// how they are encoded, the attributes the descriptor leaves out get their
+/// default value when the mesh is deserialized
+///
+/// The position has to use [VertexFormat::Float32x3], the texture
+/// coordinates [VertexFormat::Float32x2] and the color
+/// [VertexFormat::Float32x4] or [VertexFormat::Unorm8x4].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct VertexDescriptor {
+    /// The attributes in the order they are stored inside a vertex
+    pub attributes: Vec<(VertexSemantic, VertexFormat)>,
+}
+
+impl Default for VertexDescriptor {
+    fn default() -> Self {
+        Self::full()
+    }
+}
+
+impl VertexDescriptor {
+    /// Get the descriptor storing every attribute without any loss
+    pub fn full() -> Self {
+        Self {
+            attributes: vec![
+                (VertexSemantic::Position, VertexFormat::Float32x3),
+                (VertexSemantic::TexCoord, VertexFormat::Float32x2),
+                (VertexSemantic::Color, VertexFormat::Float32x4),
+            ],
+        }
+    }
+
+    /// Get the descriptor storing the color as four bytes, the color is
+    /// clamped to the range 0 to 1
+    pub fn compact() -> Self {
+        Self {
+            attributes: vec![
+                (VertexSemantic::Position, VertexFormat::Float32x3),
+                (VertexSemantic::TexCoord, VertexFormat::Float32x2),
+                (VertexSemantic::Color, VertexFormat::Unorm8x4),
+            ],
+        }
+    }
+
+    /// Get the format of an attribute
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[VertexFormat]`)` - The format the attribute is stored in
+    /// * `None` - The vertices don't store the attribute
+    pub fn format(&self, semantic: VertexSemantic) -> Option<VertexFormat> {
+        self.attributes.iter()
+            .find(|(s, _)| *s == semantic)
+            .map(|&(_, format)| format)
+    }
+
+    /// Get the size of a single serialized vertex in bytes
+    pub fn stride(&self) -> usize {
+        self.attributes.iter()
+            .map(|(_, format)| format.size())
+            .sum()
+    }
+
+    /// Check that the descriptor stores the position, every attribute only
+    /// once and only in formats the attribute can use
+    pub fn is_valid(&self) -> bool {
+        let supported = self.attributes.iter()
+            .all(|&(semantic, format)| is_supported(semantic, format));
+        let unique = self.attributes.iter()
+            .enumerate()
+            .all(|(i, (a, _))| {
+                self.attributes[..i].iter().all(|(b, _)| a != b)
+            });
+
+        supported && unique &&
+            self.format(VertexSemantic::Position).is_some()
+    }
+
+    /// Get the number of bytes [VertexDescriptor::serialize] writes
+    pub(crate) fn serialized_size(&self) -> usize {
+        1 + self.attributes.len() * 2
+    }
+
+    /// Serialize the descriptor, the attribute count followed by the
+    /// semantic and format of every attribute
+    pub(crate) fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        if !self.is_valid() {
+            return Err(Error::InvalidVertexDescriptor);
+        }
+
+        let count = u8::try_from(self.attributes.len())
+            .map_err(Error::IntegerConvertionError)?;
+        buffer.push(count);
+
+        for &(semantic, format) in &self.attributes {
+            buffer.push(semantic.to_u8());
+            buffer.push(format.to_u8());
+        }
+
+        Ok(())
+    }
+
+    /// Encode a vertex with the attributes of the descriptor
+    pub(crate) fn encode<O>(&self, vertex: &Vertex, buffer: &mut O)
+        where O: Output + ?Sized
+    {
+        for &(semantic, format) in &self.attributes {
+            let values: &[f32] = match semantic {
+                VertexSemantic::Position => &vertex.pos,
+                VertexSemantic::TexCoord => &vertex.uv,
+                VertexSemantic::Color => &vertex.color,
+            };
+
+            if format == VertexFormat::Unorm8x4 {
+                for value in values {
+                    buffer.push((value.clamp(0.0, 1.0) * 255.0).round() as u8);
+                }
+            } else {
+                write_f32s(buffer, values);
+            }
+        }
+    }
+}
+
+/// Check if an attribute can be stored in a format
+fn is_supported(semantic: VertexSemantic, format: VertexFormat) -> bool {
+    matches!((semantic, format),
+             (VertexSemantic::Position, VertexFormat::Float32x3) |
+             (VertexSemantic::TexCoord, VertexFormat::Float32x2) |
+             (VertexSemantic::Color, VertexFormat::Float32x4) |
+             (VertexSemantic::Color, VertexFormat::Unorm8x4))
+}
+
+/// Decodes the vertices of a serialized mesh, created from the vertex
+/// descriptor at the start of the mesh
+pub(crate) struct VertexDecoder {
+    /// The attributes stored inside a vertex, the attributes with a
+    /// semantic from a newer version of the library are skipped
+    attributes: Vec<(Option<VertexSemantic>, VertexFormat)>,
+
+    /// The size of a single vertex in bytes
+    pub(crate) stride: usize,
+}
+
+impl VertexDecoder {
+    /// Read the vertex descriptor
+    ///
+    /// # Returns
+    ///
+    /// * `Ok((`[VertexDescriptor]`, `[VertexDecoder]`))` - The known
+    ///   attributes and the decoder for the vertices
+    /// * `Err(`[Error]`)` - The descriptor is invalid or uses an unknown
+    ///   format
+    pub(crate) fn read(reader: &mut Reader)
+        -> Result<(VertexDescriptor, Self)>
+    {
+        let start = reader.offset();
+        let count = reader.u8()?;
+
+        let mut attributes = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let semantic = VertexSemantic::from_u8(reader.u8()?);
+            let format = reader.u8()?;
+            let format = VertexFormat::from_u8(format)
+                .ok_or(Error::UnknownVertexFormat(format))?;
+
+            attributes.push((semantic, format));
+        }
+
+        let descriptor = VertexDescriptor {
+            attributes: attributes.iter()
+                .filter_map(|&(semantic, format)| Some((semantic?, format)))
+                .collect(),
+        };
+        if !descriptor.is_valid() {
+            return Err(Error::InvalidVertexDescriptor.at_offset(start));
+        }
+
+        let stride = attributes.iter()
+            .map(|(_, format)| format.size())
+            .sum();
+
+        Ok((descriptor, Self { attributes, stride }))
+    }
+
+    /// Decode a single vertex from [VertexDecoder::stride] bytes
+    pub(crate) fn decode(&self, bytes: &[u8]) -> Vertex {
+        let mut vertex = Vertex::new([0.0; 3], [0.0; 2], [1.0; 4]);
+
+        let mut offset = 0;
+        for &(semantic, format) in &self.attributes {
+            let bytes = &bytes[offset..offset + format.size()];
+            offset += format.size();
+
+            let values: &mut [f32] = match semantic {
+                Some(VertexSemantic::Position) => &mut vertex.pos,
+                Some(VertexSemantic::TexCoord) => &mut vertex.uv,
+                Some(VertexSemantic::Color) => &mut vertex.color,
+                None => continue,
+            };
+
+            if format == VertexFormat::Unorm8x4 {
+                for (value, &byte) in values.iter_mut().zip(bytes) {
+                    *value = byte as f32 / 255.0;
+                }
+            } else {
+                for (value, bytes) in values.iter_mut()
+                    .zip(bytes.chunks_exact(4))
+                {
+                    *value = f32::from_le_bytes(bytes.try_into().unwrap());
+                }
+            }
+        }
+
+        vertex
+    }
+}
+
+/// An extra set of texture coordinates, like detail UVs
+#[derive(Clone, PartialEq, Debug)]
+pub struct UvSet {
+    /// The name used to reference the set
+    pub name: String,
+
+    /// The texture coordinates (u, v) of every vertex of the mesh
+    pub uvs: Vec<[f32; 2]>,
+}
+
+impl UvSet {
+    /// Creates a new set of texture coordinates
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name used to reference the set
+    /// * `uvs`  - The texture coordinates of every vertex
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new set
+    pub fn new(name: &str, uvs: Vec<[f32; 2]>) -> Self {
+        Self {
+            name: name.to_string(),
+            uvs,
+        }
+    }
+}
+
+/// A named per-vertex attribute the library doesn't interpret, like wind
+/// weights or ambient occlusion
+#[derive(Clone, PartialEq, Debug)]
+pub struct CustomAttribute {
+    /// The name used to reference the attribute
+    pub name: String,
+
+    /// The format of the value of every vertex
+    pub format: VertexFormat,
+
+    /// The little endian values of every vertex packed after each other,
+    /// [VertexFormat::size] bytes per vertex
+    pub data: Vec<u8>,
+}
+
+impl CustomAttribute {
+    /// Creates a new custom attribute
+    ///
+    /// # Arguments
+    ///
+    /// * `name`   - The name used to reference the attribute
+    /// * `format` - The format of the value of every vertex
+    /// * `data`   - The little endian values of every vertex
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new attribute
+    pub fn new(name: &str, format: VertexFormat, data: Vec<u8>) -> Self {
+        Self {
+            name: name.to_string(),
+            format,
+            data,
+        }
+    }
+
+    /// Creates a new custom attribute with a single float per vertex
+    ///
+    /// # Arguments
+    ///
+    /// * `name`   - The name used to reference the attribute
+    /// * `values` - The value of every vertex
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new attribute with the [VertexFormat::Float32] format
+    pub fn from_f32s(name: &str, values: &[f32]) -> Self {
+        let data = values.iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+
+        Self::new(name, VertexFormat::Float32, data)
+    }
+
+    /// Get the number of vertices the data has values for
+    pub fn len(&self) -> usize {
+        self.data.len() / self.format.size()
+    }
+
+    /// Check if the attribute doesn't have any values
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the bytes of the value of a vertex
+    ///
+    /// # Arguments
+    ///
+    /// * `vertex` - The index of the vertex
+    ///
+    /// # Returns
+    ///
+    /// * `Some([u8])` - The [VertexFormat::size] bytes of the value
+    /// * `None` - The attribute doesn't have a value for the vertex
+    pub fn value(&self, vertex: usize) -> Option<&[u8]> {
+        let size = self.format.size();
+        self.data.get(vertex * size..(vertex + 1) * size)
+    }
+
+    /// Get the values as floats
+    ///
+    /// # Returns
+    ///
+    /// * `Some(Vec<f32>)` - The components of every vertex after each
+    ///   other
+    /// * `None` - The format doesn't store floats
+    pub fn to_f32s(&self) -> Option<Vec<f32>> {
+        match self.format {
+            VertexFormat::Float32 |
+            VertexFormat::Float32x2 |
+            VertexFormat::Float32x3 |
+            VertexFormat::Float32x4 => {}
+
+            _ => return None,
+        }
+
+        let values = self.data.chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+
+        Some(values)
+    }
+
+    /// Get the number of bytes [CustomAttribute::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        string_size(&self.name) + 1 + self.data.len()
+    }
+
+    /// Serialize the attribute to a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - Successfully serialized the attribute
+    /// * `Err(`[Error]`)` - Failed to serialize the attribute
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        write_string(buffer, &self.name)?;
+        buffer.push(self.format.to_u8());
+        buffer.extend_from_slice(&self.data);
+
+        Ok(())
+    }
+
+    /// Deserialize the attribute from a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(`[Self]`))` - Successfully deserialized the attribute
+    /// * `Ok(None)` - The format of the attribute is unknown, written by a
+    ///   newer version of the library
+    /// * `Err(`[Error]`)` - Failed to deserialize the attribute
+    pub fn deserialize(buffer: &[u8]) -> Result<Option<Self>> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallMesh);
+
+        let name = reader.string()?;
+        let Some(format) = VertexFormat::from_u8(reader.u8()?) else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self {
+            name,
+            format,
+            data: reader.remaining().to_vec(),
+        }))
+    }
+}
+
+impl Mesh {
+    /// Get a custom attribute
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the attribute
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[CustomAttribute]`)` - The attribute
+    /// * `None` - The mesh doesn't have an attribute with the name
+    pub fn custom_attribute(&self, name: &str) -> Option<&CustomAttribute> {
+        self.custom_attributes.iter()
+            .find(|attribute| attribute.name == name)
+    }
+
+    /// Add a custom attribute, replacing any attribute with the same name
+    ///
+    /// # Arguments
+    ///
+    /// * `attribute` - The attribute, it should have a value for every
+    ///   vertex
+    pub fn set_custom_attribute(&mut self, attribute: CustomAttribute) {
+        let existing = self.custom_attributes.iter_mut()
+            .find(|existing| existing.name == attribute.name);
+
+        match existing {
+            Some(existing) => *existing = attribute,
+            None => self.custom_attributes.push(attribute),
+        }
+    }
+
+    /// Get the number of texture coordinate sets of the mesh, the set
+    /// inside the vertices, the lightmap set if present and the extra sets
+    pub fn uv_set_count(&self) -> usize {
+        let lightmap = usize::from(!self.lightmap_uvs.is_empty());
+        1 + lightmap + self.uv_sets.len()
+    }
+
+    /// Get an extra set of texture coordinates
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the set
+    ///
+    /// # Returns
+    ///
+    /// * `Some([[f32; 2]])` - The texture coordinates of every vertex
+    /// * `None` - The mesh doesn't have a set with the name
+    pub fn uv_set(&self, name: &str) -> Option<&[[f32; 2]]> {
+        self.uv_sets.iter()
+            .find(|set| set.name == name)
+            .map(|set| set.uvs.as_slice())
+    }
+
+    /// Add an extra set of texture coordinates, replacing any set with the
+    /// same name
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the set
+    /// * `uvs`  - The texture coordinates of every vertex
+    pub fn set_uv_set(&mut self, name: &str, uvs: Vec<[f32; 2]>) {
+        let set = UvSet::new(name, uvs);
+
+        match self.uv_sets.iter_mut().find(|set| set.name == name) {
+            Some(existing) => *existing = set,
+            None => self.uv_sets.push(set),
+        }
+    }
+}
+
+/// Get the number of bytes [write_uv_sets] writes
+pub(crate) fn uv_sets_size(sets: &[UvSet]) -> usize {
+    // The header has the count and the name and coordinate count of every
+    // set, the coordinates of all the sets follow the header
+    8 + sets.iter()
+        .map(|set| {
+            string_size(&set.name) + 8 +
+                set.uvs.len() * 2 * std::mem::size_of::<f32>()
+        })
+        .sum::<usize>()
+}
+
+/// Write the header declaring the extra texture coordinate sets followed by
+/// the coordinates
+pub(crate) fn write_uv_sets<O>(buffer: &mut O, sets: &[UvSet]) -> Result<()>
+    where O: Output + ?Sized
+{
+    write_usize(buffer, sets.len())?;
+    for set in sets {
+        write_string(buffer, &set.name)?;
+        write_usize(buffer, set.uvs.len())?;
+    }
+
+    for uv in sets.iter().flat_map(|set| &set.uvs) {
+        buffer.extend_from_slice(&uv[0].to_le_bytes());
+        buffer.extend_from_slice(&uv[1].to_le_bytes());
+    }
+
+    Ok(())
+}
+
+/// Read the extra texture coordinate sets written by [write_uv_sets]
+///
+/// # Arguments
+///
+/// * `reader`       - The reader positioned at the header
+/// * `vertex_count` - The number of vertices of the mesh, every set needs a
+///   coordinate for every vertex
+pub(crate) fn read_uv_sets(reader: &mut Reader, vertex_count: usize)
+    -> Result<Vec<UvSet>>
+{
+    let count = reader.usize()?;
+
+    // NOTE(patrik): The coordinates are only allocated after the header
+    // has been checked so a corrupt count can't allocate more than the
+    // buffer contains
+    let mut names = Vec::new();
+    for _ in 0..count {
+        names.push(reader.string()?);
+
+        let offset = reader.offset();
+        if reader.usize()? != vertex_count {
+            return Err(Error::UvSetCountMismatch.at_offset(offset));
+        }
+    }
+
+    let mut sets = Vec::with_capacity(names.len());
+    for (set, name) in names.into_iter().enumerate() {
+        let mut uvs = Vec::with_capacity(vertex_count);
+        for vertex in 0..vertex_count {
+            let element = |error: Error| {
+                error.with_context(|context| {
+                    context.element = Some(ErrorElement::UvSet(set, vertex));
+                })
+            };
+
+            uvs.push([reader.f32().map_err(element)?,
+                      reader.f32().map_err(element)?]);
+        }
+
+        sets.push(UvSet { name, uvs });
+    }
+
+    Ok(sets)
+}
+
+/// Get the number of bytes [write_custom_attributes] writes
+pub(crate) fn custom_attributes_size(attributes: &[CustomAttribute])
+    -> usize
+{
+    list_size(attributes, CustomAttribute::serialized_size)
+}
+
+/// Write the custom attributes, every attribute is prefixed with its size
+/// so readers can skip the ones with unknown formats
+pub(crate) fn write_custom_attributes<O>(buffer: &mut O,
+                                         attributes: &[CustomAttribute])
+    -> Result<()>
+    where O: Output + ?Sized
+{
+    write_list(buffer, attributes, CustomAttribute::serialize)
+}
+
+/// Read the custom attributes written by [write_custom_attributes], the
+/// attributes with unknown formats are skipped
+///
+/// # Arguments
+///
+/// * `reader`       - The reader positioned at the attribute list
+/// * `vertex_count` - The number of vertices of the mesh, every attribute
+///   needs a value for every vertex
+pub(crate) fn read_custom_attributes(reader: &mut Reader,
+                                     vertex_count: usize)
+    -> Result<Vec<CustomAttribute>>
+{
+    let offset = reader.offset();
+    let attributes = reader.list(CustomAttribute::deserialize)?;
+
+    let attributes = attributes.into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+
+    let mismatch = attributes.iter()
+        .any(|attribute| {
+            attribute.data.len() != vertex_count * attribute.format.size()
+        });
+    if mismatch {
+        return Err(Error::CustomAttributeSizeMismatch.at_offset(offset));
+    }
+
+    Ok(attributes)
+}
@@ -0,0 +1,220 @@
+//! The axis convention of the map coordinates
+//!
+//! Tools disagree on which axis points up and on the handedness of the
+//! coordinate system, Blender is Z-up and right-handed while most engines
+//! are Y-up. The map stores its [AxisConvention] and
+//! [Map::convert_axes] moves the geometry between conventions.
+//!
+//! The left-handed conventions are the right-handed ones with the forward
+//! axis mirrored, -Z for Y-up and -Y for Z-up.
+
+use crate::*;
+use crate::transform::IDENTITY;
+
+/// The axis pointing up
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub enum UpAxis {
+    /// The Y axis points up, the default for maps saved before the axis
+    /// convention was stored
+    #[default]
+    Y,
+
+    /// The Z axis points up, like Blender
+    Z,
+}
+
+/// The handedness of the coordinate system
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub enum Handedness {
+    /// Right-handed, the default for maps saved before the axis convention
+    /// was stored
+    #[default]
+    Right,
+
+    /// Left-handed
+    Left,
+}
+
+/// The orientation of the coordinate system of a map
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct AxisConvention {
+    /// The axis pointing up
+    pub up: UpAxis,
+
+    /// The handedness of the coordinate system
+    pub handedness: Handedness,
+}
+
+impl AxisConvention {
+    /// Y-up and right-handed, the default
+    pub const Y_UP_RIGHT_HANDED: Self = Self::new(UpAxis::Y, Handedness::Right);
+
+    /// Y-up and left-handed
+    pub const Y_UP_LEFT_HANDED: Self = Self::new(UpAxis::Y, Handedness::Left);
+
+    /// Z-up and right-handed, like Blender
+    pub const Z_UP_RIGHT_HANDED: Self = Self::new(UpAxis::Z, Handedness::Right);
+
+    /// Z-up and left-handed
+    pub const Z_UP_LEFT_HANDED: Self = Self::new(UpAxis::Z, Handedness::Left);
+
+    /// Create a new axis convention
+    ///
+    /// # Arguments
+    ///
+    /// * `up`         - The axis pointing up
+    /// * `handedness` - The handedness of the coordinate system
+    pub const fn new(up: UpAxis, handedness: Handedness) -> Self {
+        Self {
+            up,
+            handedness,
+        }
+    }
+
+    /// Get the byte the axis convention is serialized as
+    pub fn to_u8(self) -> u8 {
+        let up = match self.up {
+            UpAxis::Y => 0,
+            UpAxis::Z => 1,
+        };
+
+        let handedness = match self.handedness {
+            Handedness::Right => 0,
+            Handedness::Left => 2,
+        };
+
+        up | handedness
+    }
+
+    /// Get the axis convention from its serialized byte
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The serialized byte
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - The axis convention
+    /// * `Err(`[Error]`)` - [Error::UnknownAxisConvention] if the byte isn't
+    ///   a known axis convention
+    pub fn from_u8(value: u8) -> Result<Self> {
+        if value > 3 {
+            return Err(Error::UnknownAxisConvention(value));
+        }
+
+        let up = if value & 1 == 0 { UpAxis::Y } else { UpAxis::Z };
+        let handedness = if value & 2 == 0 {
+            Handedness::Right
+        } else {
+            Handedness::Left
+        };
+
+        Ok(Self::new(up, handedness))
+    }
+
+    /// The matrix converting coordinates in this convention to Y-up
+    /// right-handed coordinates
+    fn to_y_up_right_handed(self) -> Mat4 {
+        let mut m = IDENTITY;
+
+        match (self.up, self.handedness) {
+            (UpAxis::Y, Handedness::Right) => {}
+
+            // (x, y, z) -> (x, y, -z)
+            (UpAxis::Y, Handedness::Left) => m[2][2] = -1.0,
+
+            // (x, y, z) -> (x, z, -y)
+            (UpAxis::Z, Handedness::Right) => {
+                m[1] = [0.0, 0.0, -1.0, 0.0];
+                m[2] = [0.0, 1.0, 0.0, 0.0];
+            }
+
+            // (x, y, z) -> (x, z, y)
+            (UpAxis::Z, Handedness::Left) => {
+                m[1] = [0.0, 0.0, 1.0, 0.0];
+                m[2] = [0.0, 1.0, 0.0, 0.0];
+            }
+        }
+
+        m
+    }
+
+    /// Get the matrix converting coordinates in this convention to another
+    /// convention
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The convention to convert to
+    ///
+    /// # Returns
+    ///
+    /// * [Mat4] - The conversion, a rotation when the handedness is the
+    ///   same and a mirror otherwise
+    pub fn conversion_to(self, target: AxisConvention) -> Mat4 {
+        let from = self.to_y_up_right_handed();
+        let to = target.to_y_up_right_handed();
+
+        // NOTE(patrik): The matrices only swap and negate axes so the
+        // inverse is the transpose
+        let mut m = IDENTITY;
+        for column in 0..3 {
+            for row in 0..3 {
+                m[column][row] = (0..3)
+                    .map(|i| to[row][i] * from[column][i])
+                    .sum();
+            }
+        }
+
+        m
+    }
+}
+
+impl Mesh {
+    /// Convert the positions of the mesh between axis conventions
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - The axis convention the positions are in
+    /// * `to`   - The axis convention to convert to
+    pub fn convert_axes(&mut self, from: AxisConvention, to: AxisConvention) {
+        if from != to {
+            self.transform(&from.conversion_to(to));
+        }
+    }
+}
+
+impl Sector {
+    /// Convert all the geometry of the sector between axis conventions, see
+    /// [Sector::transform]
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - The axis convention the geometry is in
+    /// * `to`   - The axis convention to convert to
+    pub fn convert_axes(&mut self, from: AxisConvention, to: AxisConvention) {
+        if from != to {
+            self.transform(&from.conversion_to(to));
+        }
+    }
+}
+
+impl Map {
+    /// Convert the content of the map to another axis convention and
+    /// update [Map::axes]
+    ///
+    /// Use this instead of setting [Map::axes] when the map should look the
+    /// same afterwards. The changed sectors are marked for the next
+    /// [Map::save_incremental].
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The axis convention to convert to
+    pub fn convert_axes(&mut self, target: AxisConvention) {
+        if self.axes == target {
+            return;
+        }
+
+        self.transform(&self.axes.conversion_to(target));
+        self.axes = target;
+    }
+}
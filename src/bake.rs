@@ -0,0 +1,207 @@
+//! Baking of lighting into the vertex colors
+
+use crate::*;
+use crate::math::*;
+
+/// Options for the vertex light baking
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct BakeOptions {
+    /// The light every vertex receives without any lights (r, g, b)
+    pub ambient: [f32; 3],
+
+    /// Cast shadow rays against the map geometry
+    pub shadows: bool,
+
+    /// How far along the normal the shadow rays start, prevents surfaces
+    /// from shadowing themselves
+    pub shadow_bias: f32,
+}
+
+impl Default for BakeOptions {
+    fn default() -> Self {
+        Self {
+            ambient: [0.1, 0.1, 0.1],
+            shadows: true,
+            shadow_bias: 0.01,
+        }
+    }
+}
+
+/// Compute the normal of every vertex of the mesh from the faces using it,
+/// triangles referencing vertices outside the vertex buffer are skipped
+fn vertex_normals(mesh: &Mesh) -> Vec<Vec3> {
+    let mut normals = vec![[0.0; 3]; mesh.vertex_buffer.len()];
+    let position = |index: u32| {
+        mesh.vertex_buffer.get(index as usize).map(|vertex| vertex.pos)
+    };
+
+    for triangle in mesh.triangle_indices() {
+        let (Some(a), Some(b), Some(c)) = (position(triangle[0]),
+                                           position(triangle[1]),
+                                           position(triangle[2]))
+        else {
+            continue;
+        };
+
+        // NOTE(patrik): The normal is weighted by the area of the face
+        let normal = triangle_normal(a, b, c);
+        for index in triangle {
+            normals[index as usize] = add(normals[index as usize], normal);
+        }
+    }
+
+    normals.into_iter().map(normalize).collect()
+}
+
+/// Check if a ray hits a triangle closer than `max_distance`
+fn ray_hits_triangle(origin: Vec3,
+                     direction: Vec3,
+                     max_distance: f32,
+                     triangle: &[Vec3; 3])
+    -> bool
+{
+    // Möller–Trumbore intersection
+    let e0 = sub(triangle[1], triangle[0]);
+    let e1 = sub(triangle[2], triangle[0]);
+
+    let p = cross(direction, e1);
+    let det = dot(e0, p);
+    if det.abs() < f32::EPSILON {
+        return false;
+    }
+
+    let inv_det = 1.0 / det;
+    let t0 = sub(origin, triangle[0]);
+
+    let u = dot(t0, p) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return false;
+    }
+
+    let q = cross(t0, e0);
+    let v = dot(direction, q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return false;
+    }
+
+    let t = dot(e1, q) * inv_det;
+    t > 0.0 && t < max_distance
+}
+
+/// Compute the light a single light contributes to a point
+fn light_contribution(light: &Light,
+                      pos: Vec3,
+                      normal: Vec3,
+                      options: &BakeOptions,
+                      occluders: &[[Vec3; 3]])
+    -> Vec3
+{
+    // The direction and distance from the point towards the light
+    let (to_light, distance, attenuation) = match light.kind {
+        LightKind::Directional { direction } => {
+            (normalize(scale(direction, -1.0)), f32::MAX, 1.0)
+        }
+
+        LightKind::Point | LightKind::Spot { .. } => {
+            let delta = sub(light.position, pos);
+            let distance = length(delta);
+            if distance >= light.radius {
+                return [0.0; 3];
+            }
+
+            let falloff = 1.0 - distance / light.radius;
+            (normalize(delta), distance, falloff * falloff)
+        }
+    };
+
+    let cone = match light.kind {
+        LightKind::Spot { direction, inner_angle, outer_angle } => {
+            let cos_angle =
+                dot(normalize(direction), scale(to_light, -1.0));
+            let cos_inner = inner_angle.cos();
+            let cos_outer = outer_angle.cos();
+
+            if cos_angle >= cos_inner {
+                1.0
+            } else if cos_angle <= cos_outer {
+                0.0
+            } else {
+                (cos_angle - cos_outer) / (cos_inner - cos_outer)
+            }
+        }
+
+        _ => 1.0,
+    };
+
+    let lambert = dot(normal, to_light).max(0.0);
+    let factor = light.intensity * attenuation * cone * lambert;
+    if factor <= 0.0 {
+        return [0.0; 3];
+    }
+
+    if options.shadows {
+        let origin = add(pos, scale(normal, options.shadow_bias));
+        let shadowed = occluders.iter().any(|triangle| {
+            ray_hits_triangle(origin, to_light, distance, triangle)
+        });
+
+        if shadowed {
+            return [0.0; 3];
+        }
+    }
+
+    scale(light.color, factor)
+}
+
+impl Map {
+    /// Bake lighting into the vertex colors of the floor, ceiling and wall
+    /// meshes of all sectors
+    ///
+    /// The red, green and blue channels of every vertex color are replaced
+    /// with the baked light, the alpha channel is kept. Baking again with
+    /// other lights replaces the old result. The light is computed in
+    /// linear space and stored in [Map::color_space].
+    ///
+    /// # Arguments
+    ///
+    /// * `lights`  - The lights to bake
+    /// * `options` - Controls the ambient light and the shadows
+    pub fn bake_vertex_lighting(&mut self,
+                                lights: &[Light],
+                                options: &BakeOptions)
+    {
+        let occluders = if options.shadows {
+            self.sectors.iter()
+                .flat_map(|sector| [&sector.floor_mesh,
+                                    &sector.ceiling_mesh,
+                                    &sector.wall_mesh])
+                .flat_map(|mesh| mesh.triangle_positions())
+                .collect::<Vec<_>>()
+        } else {
+            Vec::new()
+        };
+
+        let color_space = self.color_space;
+        let meshes = self.sectors.iter_mut()
+            .flat_map(|sector| [&mut sector.floor_mesh,
+                                &mut sector.ceiling_mesh,
+                                &mut sector.wall_mesh]);
+        for mesh in meshes {
+            let normals = vertex_normals(mesh);
+
+            for (vertex, normal) in mesh.vertex_buffer.iter_mut()
+                .zip(normals)
+            {
+                let mut color = options.ambient;
+                for light in lights {
+                    let light = light_contribution(light, vertex.pos, normal,
+                                                   options, &occluders);
+                    color = add(color, light);
+                }
+
+                let color = [color[0], color[1], color[2], vertex.color[3]];
+                vertex.color = ColorSpace::Linear.convert(color, color_space);
+            }
+        }
+    }
+}
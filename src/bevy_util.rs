@@ -0,0 +1,119 @@
+//! Converting maps to Bevy meshes and entities, enabled by the `bevy`
+//! feature
+
+use crate::*;
+
+use bevy::prelude::{ Assets, BuildChildren, Commands, Component, Entity,
+                     Handle, SpatialBundle };
+use bevy::render::mesh::{ Indices, PrimitiveTopology };
+use bevy::render::render_asset::RenderAssetUsages;
+
+/// Component added to the entities spawned for sectors by [spawn_map]
+#[derive(Component, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct MimeSector {
+    /// The ID of the sector inside the map
+    pub id: SectorId,
+
+    /// The tag of the sector
+    pub tag: u32,
+}
+
+/// Component added to the entities spawned for the meshes of a sector by
+/// [spawn_map], the texture ID can be used to pick a material
+#[derive(Component, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct MimeMesh {
+    /// The role of the mesh inside the sector
+    pub role: MeshRole,
+
+    /// The texture ID of the mesh
+    pub texture_id: u64,
+}
+
+impl From<&Mesh> for bevy::render::mesh::Mesh {
+    fn from(mesh: &Mesh) -> Self {
+        let topology = match mesh.topology {
+            Topology::TriangleList => PrimitiveTopology::TriangleList,
+            Topology::TriangleStrip => PrimitiveTopology::TriangleStrip,
+            Topology::LineList => PrimitiveTopology::LineList,
+            Topology::PointList => PrimitiveTopology::PointList,
+        };
+
+        let mut result = Self::new(topology, RenderAssetUsages::default());
+
+        let positions = mesh.vertex_buffer.iter()
+            .map(|vertex| vertex.pos)
+            .collect::<Vec<_>>();
+        let uvs = mesh.vertex_buffer.iter()
+            .map(|vertex| vertex.uv)
+            .collect::<Vec<_>>();
+        let colors = mesh.vertex_buffer.iter()
+            .map(|vertex| vertex.color)
+            .collect::<Vec<_>>();
+
+        result.insert_attribute(Self::ATTRIBUTE_POSITION, positions);
+        result.insert_attribute(Self::ATTRIBUTE_UV_0, uvs);
+        result.insert_attribute(Self::ATTRIBUTE_COLOR, colors);
+        result.insert_indices(Indices::U32(mesh.index_buffer.clone()));
+
+        result
+    }
+}
+
+/// Spawn the sectors of a map as entities
+///
+/// Every sector gets an entity with a [MimeSector] component, the floor,
+/// ceiling and wall meshes are spawned as children with a [MimeMesh]
+/// component and the handle of the converted mesh. Meshes without indices
+/// are skipped. No materials are added, use the texture ID of the
+/// [MimeMesh] to add them.
+///
+/// # Arguments
+///
+/// * `commands` - The commands used to spawn the entities
+/// * `meshes`   - The mesh assets the converted meshes are added to
+/// * `map`      - The map to spawn
+///
+/// # Returns
+///
+/// * `Vec<Entity>` - The entity of every sector in the same order as the
+///   sectors of the map
+pub fn spawn_map(commands: &mut Commands,
+                 meshes: &mut Assets<bevy::render::mesh::Mesh>,
+                 map: &Map)
+    -> Vec<Entity>
+{
+    let mut entities = Vec::with_capacity(map.sectors.len());
+
+    for sector in &map.sectors {
+        let handles = MeshRole::ALL.iter()
+            .map(|&role| (role, sector.mesh(role)))
+            .filter(|(_, mesh)| !mesh.index_buffer.is_empty())
+            .map(|(role, mesh)| {
+                let handle: Handle<bevy::render::mesh::Mesh> =
+                    meshes.add(bevy::render::mesh::Mesh::from(mesh));
+                let component = MimeMesh {
+                    role,
+                    texture_id: mesh.texture_id,
+                };
+
+                (handle, component)
+            })
+            .collect::<Vec<_>>();
+
+        let entity = commands.spawn((
+            SpatialBundle::default(),
+            MimeSector {
+                id: sector.id(),
+                tag: sector.tag,
+            },
+        )).with_children(|parent| {
+            for (handle, component) in handles {
+                parent.spawn((SpatialBundle::default(), handle, component));
+            }
+        }).id();
+
+        entities.push(entity);
+    }
+
+    entities
+}
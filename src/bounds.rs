@@ -0,0 +1,147 @@
+//! Axis aligned bounding boxes of sectors
+
+use crate::*;
+use crate::buffer::{ Reader, write_f32s };
+
+/// The size of the serialized bounds (min, max)
+pub(crate) const BOUNDS_SIZE: usize = 6 * std::mem::size_of::<f32>();
+
+/// An axis aligned bounding box
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct Bounds {
+    /// The minimum corner of the box
+    pub min: [f32; 3],
+
+    /// The maximum corner of the box
+    pub max: [f32; 3],
+}
+
+impl Bounds {
+    /// Creates the smallest box containing all the points
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - The points the box should contain
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[Bounds]`)` - The box around the points
+    /// * `None` - There are no points
+    pub fn from_points<I>(points: I) -> Option<Self>
+        where I: IntoIterator<Item = [f32; 3]>
+    {
+        points.into_iter().fold(None, |bounds, p| {
+            Some(match bounds {
+                Some(bounds) => bounds.union(&Bounds { min: p, max: p }),
+                None => Bounds { min: p, max: p },
+            })
+        })
+    }
+
+    /// Get the smallest box containing both boxes
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        Bounds {
+            min: std::array::from_fn(|i| self.min[i].min(other.min[i])),
+            max: std::array::from_fn(|i| self.max[i].max(other.max[i])),
+        }
+    }
+
+    /// Get the point in the middle of the box
+    pub fn center(&self) -> [f32; 3] {
+        std::array::from_fn(|i| (self.min[i] + self.max[i]) * 0.5)
+    }
+
+    /// Check if a point is inside the box, points on the surface count as
+    /// inside
+    pub fn contains(&self, point: [f32; 3]) -> bool {
+        (0..3).all(|i| point[i] >= self.min[i] && point[i] <= self.max[i])
+    }
+
+    /// Check if two boxes overlap, touching boxes count as overlapping
+    pub fn intersects(&self, other: &Bounds) -> bool {
+        (0..3).all(|i| {
+            self.min[i] <= other.max[i] && other.min[i] <= self.max[i]
+        })
+    }
+
+    /// Serialize the bounds to a buffer
+    pub(crate) fn serialize<O>(&self, buffer: &mut O)
+        where O: Output + ?Sized
+    {
+        write_f32s(buffer, &self.min);
+        write_f32s(buffer, &self.max);
+    }
+
+    /// Deserialize the bounds from a reader
+    pub(crate) fn read(reader: &mut Reader) -> Result<Self> {
+        Ok(Bounds {
+            min: reader.vec3()?,
+            max: reader.vec3()?,
+        })
+    }
+}
+
+impl Mesh {
+    /// Get the bounding box of the vertices of the mesh
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[Bounds]`)` - The box around the vertices
+    /// * `None` - The mesh doesn't have any vertices
+    pub fn bounds(&self) -> Option<Bounds> {
+        Bounds::from_points(self.vertex_buffer.iter().map(Vertex::position))
+    }
+}
+
+impl Sector {
+    /// Get the bounding box of the full detail meshes of the sector, the
+    /// box is stored at the start of every serialized sector
+    ///
+    /// # Returns
+    ///
+    /// * [Bounds] - The box around the meshes, an empty box at the origin
+    ///   for sectors without any vertices
+    pub fn bounds(&self) -> Bounds {
+        MeshRole::ALL.iter()
+            .filter_map(|&role| self.mesh(role).bounds())
+            .reduce(|a, b| a.union(&b))
+            .unwrap_or_default()
+    }
+
+    /// Read the bounding box of a serialized sector without deserializing
+    /// the meshes
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The serialized sector, only the start of the sector is
+    ///   needed
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Bounds]`)` - The bounding box stored with the sector
+    /// * `Err(`[Error]`)` - The buffer is too small
+    pub fn read_bounds(buffer: &[u8]) -> Result<Bounds> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallSector);
+        Bounds::read(&mut reader)
+    }
+}
+
+impl Map {
+    /// Get the sectors whose bounding box overlaps a box
+    ///
+    /// # Arguments
+    ///
+    /// * `bounds` - The box to test against
+    ///
+    /// # Returns
+    ///
+    /// * An iterator over the index and the sector of every overlapping
+    ///   sector
+    pub fn sectors_in_bounds(&self, bounds: Bounds)
+        -> impl Iterator<Item = (usize, &Sector)>
+    {
+        self.sectors.iter()
+            .enumerate()
+            .filter(move |(_, sector)| sector.bounds().intersects(&bounds))
+    }
+}
@@ -0,0 +1,495 @@
+//! Helpers for reading and writing the binary format
+
+use crate::*;
+
+/// The largest number of bytes a serialized string can have, a larger
+/// length inside a buffer is treated as corrupt instead of being allocated
+pub const MAX_STRING_SIZE: usize = u16::MAX as usize;
+
+/// A destination for serialized data
+///
+/// Implemented for `Vec<u8>`, which grows as data is written. The method
+/// names mirror the ones of `Vec<u8>`.
+pub trait Output {
+    /// Get the number of bytes written so far
+    fn position(&self) -> usize;
+
+    /// Append bytes to the output
+    fn extend_from_slice(&mut self, bytes: &[u8]);
+
+    /// Append a single byte to the output
+    fn push(&mut self, byte: u8) {
+        self.extend_from_slice(&[byte]);
+    }
+
+    /// Overwrite bytes that were already written, used to fill in sizes
+    /// and counts once they are known
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The position of the first byte to overwrite
+    /// * `bytes`  - The new bytes
+    fn patch(&mut self, offset: usize, bytes: &[u8]);
+
+    /// Reserve space for at least `additional` more bytes, does nothing by
+    /// default
+    fn reserve(&mut self, additional: usize) {
+        let _ = additional;
+    }
+}
+
+impl Output for Vec<u8> {
+    fn position(&self) -> usize {
+        self.len()
+    }
+
+    fn extend_from_slice(&mut self, bytes: &[u8]) {
+        Vec::extend_from_slice(self, bytes);
+    }
+
+    fn push(&mut self, byte: u8) {
+        Vec::push(self, byte);
+    }
+
+    fn patch(&mut self, offset: usize, bytes: &[u8]) {
+        self[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        Vec::reserve(self, additional);
+    }
+}
+
+/// An [Output] writing into a fixed size slice
+///
+/// Writes past the end of the slice are dropped but still counted so
+/// [SliceOutput::finish] can report that the slice was too small.
+pub(crate) struct SliceOutput<'a> {
+    /// The slice we are writing to
+    out: &'a mut [u8],
+
+    /// The number of bytes written so far, can be past the end of the slice
+    position: usize,
+}
+
+impl<'a> SliceOutput<'a> {
+    /// Creates a new output writing to the start of the slice
+    pub(crate) fn new(out: &'a mut [u8]) -> Self {
+        Self {
+            out,
+            position: 0,
+        }
+    }
+
+    /// Finish writing
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(usize)` - The number of bytes written
+    /// * `Err(`[Error]`)` - The slice was too small for the data
+    pub(crate) fn finish(self) -> Result<usize> {
+        if self.position > self.out.len() {
+            let size = SizeMismatch::new(self.position, self.out.len());
+            return Err(Error::BufferToSmallOutput(size));
+        }
+
+        Ok(self.position)
+    }
+}
+
+impl Output for SliceOutput<'_> {
+    fn position(&self) -> usize {
+        self.position
+    }
+
+    fn extend_from_slice(&mut self, bytes: &[u8]) {
+        self.patch(self.position, bytes);
+        self.position += bytes.len();
+    }
+
+    fn patch(&mut self, offset: usize, bytes: &[u8]) {
+        let end = offset + bytes.len();
+        if let Some(out) = self.out.get_mut(offset..end) {
+            out.copy_from_slice(bytes);
+        }
+    }
+}
+
+/// A cursor over a buffer used when deserializing
+pub(crate) struct Reader<'a> {
+    /// The buffer we are reading from
+    buffer: &'a [u8],
+
+    /// The current offset inside the buffer
+    offset: usize,
+
+    /// The error we return when the buffer is too small
+    error: fn(SizeMismatch) -> Error,
+}
+
+impl<'a> Reader<'a> {
+    /// Creates a new reader
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer to read from
+    /// * `error`  - Creates the error returned when the buffer runs out of
+    ///   data from the needed and the actual size of the buffer
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new reader positioned at the start of the buffer
+    pub(crate) fn new(buffer: &'a [u8], error: fn(SizeMismatch) -> Error)
+        -> Self
+    {
+        Self {
+            buffer,
+            offset: 0,
+            error,
+        }
+    }
+
+    /// Get the number of bytes read so far
+    pub(crate) fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Get the bytes that have not been read yet
+    pub(crate) fn remaining(&self) -> &'a [u8] {
+        &self.buffer[self.offset..]
+    }
+
+    /// Read `count` bytes from the buffer, the error has the offset of the
+    /// first byte we couldn't read
+    pub(crate) fn bytes(&mut self, count: usize) -> Result<&'a [u8]> {
+        if self.buffer.len() - self.offset < count {
+            let size = SizeMismatch::new(self.offset.saturating_add(count),
+                                         self.buffer.len());
+            return Err((self.error)(size).at_offset(self.offset));
+        }
+
+        let start = self.offset;
+        self.offset += count;
+
+        Ok(&self.buffer[start..self.offset])
+    }
+
+    /// Read a fixed size array from the buffer
+    pub(crate) fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        self.bytes(N)?.try_into()
+            .map_err(Error::SliceConvertionError)
+    }
+
+    /// Read a single byte
+    pub(crate) fn u8(&mut self) -> Result<u8> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    /// Read a little endian u32
+    pub(crate) fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    /// Read a little endian u64
+    pub(crate) fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    /// Read a little endian f32
+    pub(crate) fn f32(&mut self) -> Result<f32> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+
+    /// Read three little endian f32s
+    pub(crate) fn vec3(&mut self) -> Result<[f32; 3]> {
+        Ok([self.f32()?, self.f32()?, self.f32()?])
+    }
+
+    /// Read a little endian u64 and convert it to a usize
+    pub(crate) fn usize(&mut self) -> Result<usize> {
+        let start = self.offset;
+        self.u64()?.try_into()
+            .map_err(|error| {
+                Error::IntegerConvertionError(error).at_offset(start)
+            })
+    }
+
+    /// Read a block prefixed with its size as a u64
+    pub(crate) fn sized(&mut self) -> Result<&'a [u8]> {
+        let size = self.usize()?;
+        self.bytes(size)
+    }
+
+    /// Read a block prefixed with its size and deserialize it, the offset
+    /// of the block is added to the errors returned by `f`
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Deserializes the content of the block
+    pub(crate) fn block<T, F>(&mut self, f: F) -> Result<T>
+        where F: FnOnce(&'a [u8]) -> Result<T>
+    {
+        let block = self.sized()?;
+        let start = self.offset - block.len();
+
+        f(block).map_err(|error| error.at_offset(start))
+    }
+
+    /// Read a list written by [write_list]
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Deserializes a single item from its block
+    pub(crate) fn list<T, F>(&mut self, f: F) -> Result<Vec<T>>
+        where F: Fn(&'a [u8]) -> Result<T>
+    {
+        let count = self.usize()?;
+
+        let mut items = Vec::new();
+        for _i in 0..count {
+            items.push(self.block(&f)?);
+        }
+
+        Ok(items)
+    }
+
+    /// Read a UTF-8 string written by [write_string], the length is checked
+    /// against [MAX_STRING_SIZE] before reading the bytes
+    pub(crate) fn string(&mut self) -> Result<String> {
+        let start = self.offset;
+        let size = self.usize()?;
+        if size > MAX_STRING_SIZE {
+            return Err(Error::StringTooLong(size).at_offset(start));
+        }
+
+        let bytes = self.bytes(size)?;
+        let value = std::str::from_utf8(bytes)
+            .map_err(|error| {
+                Error::Utf8ConvertionError(error).at_offset(self.offset - size)
+            })?;
+
+        Ok(value.to_string())
+    }
+
+    /// Read an optional string written by [write_optional_string]
+    pub(crate) fn optional_string(&mut self) -> Result<Option<String>> {
+        let used = self.u8()? != 0;
+        let value = self.string()?;
+
+        Ok(used.then_some(value))
+    }
+}
+
+/// Get the size of `count` elements of `size` bytes, the counts come from
+/// the buffer so the size can overflow
+///
+/// # Returns
+///
+/// * `Ok(usize)` - The size of the elements
+/// * `Err(`[Error]`)` - The size doesn't fit inside a usize
+pub(crate) fn checked_size(count: usize, size: usize) -> Result<usize> {
+    count.checked_mul(size).ok_or(Error::OffsetOverflow)
+}
+
+/// Write a count or size as a little endian u64
+pub(crate) fn write_usize<O>(buffer: &mut O, value: usize) -> Result<()>
+    where O: Output + ?Sized
+{
+    let value: u64 = value.try_into()
+        .map_err(Error::IntegerConvertionError)?;
+    buffer.extend_from_slice(&value.to_le_bytes());
+
+    Ok(())
+}
+
+/// Write `count` zero bytes
+pub(crate) fn write_zeros<O>(buffer: &mut O, count: usize)
+    where O: Output + ?Sized
+{
+    const ZEROS: [u8; 64] = [0; 64];
+
+    let mut left = count;
+    while left > 0 {
+        let size = left.min(ZEROS.len());
+        buffer.extend_from_slice(&ZEROS[..size]);
+        left -= size;
+    }
+}
+
+/// Write a list of little endian f32s
+pub(crate) fn write_f32s<O>(buffer: &mut O, values: &[f32])
+    where O: Output + ?Sized
+{
+    for value in values {
+        buffer.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+/// Write a list of items, the count followed by every item as a size
+/// prefixed block
+///
+/// # Arguments
+///
+/// * `buffer` - The buffer we use to append the data to
+/// * `items`  - The items to write
+/// * `f`      - Serializes a single item
+pub(crate) fn write_list<O, T, F>(buffer: &mut O, items: &[T], f: F)
+    -> Result<()>
+    where O: Output + ?Sized,
+          F: Fn(&T, &mut O) -> Result<()>
+{
+    write_usize(buffer, items.len())?;
+    for item in items {
+        write_sized(buffer, |buffer| f(item, buffer))?;
+    }
+
+    Ok(())
+}
+
+/// Check that a string can be serialized by [write_string]
+///
+/// # Returns
+///
+/// * `Ok()` - The string can be serialized
+/// * `Err(`[Error]`)` - [Error::StringTooLong] if the string is longer than
+///   [MAX_STRING_SIZE] bytes
+pub(crate) fn check_string(value: &str) -> Result<()> {
+    if value.len() > MAX_STRING_SIZE {
+        return Err(Error::StringTooLong(value.len()));
+    }
+
+    Ok(())
+}
+
+/// Write a UTF-8 string prefixed with its length in bytes as a u64, every
+/// string of the format goes through here so they are encoded the same way
+///
+/// # Returns
+///
+/// * `Ok()` - Successfully wrote the string
+/// * `Err(`[Error]`)` - The string can't be serialized, see [check_string]
+pub(crate) fn write_string<O>(buffer: &mut O, value: &str) -> Result<()>
+    where O: Output + ?Sized
+{
+    check_string(value)?;
+
+    write_usize(buffer, value.len())?;
+    buffer.extend_from_slice(value.as_bytes());
+
+    Ok(())
+}
+
+/// Get the number of bytes [write_string] writes for a string
+pub(crate) fn string_size(value: &str) -> usize {
+    8 + value.len()
+}
+
+/// Write an optional string as a byte telling if the string is used
+/// followed by the string, an empty string is written for `None`
+pub(crate) fn write_optional_string<O>(buffer: &mut O, value: Option<&str>)
+    -> Result<()>
+    where O: Output + ?Sized
+{
+    buffer.push(value.is_some() as u8);
+    write_string(buffer, value.unwrap_or_default())
+}
+
+/// Get the number of bytes [write_optional_string] writes for a string
+pub(crate) fn optional_string_size(value: Option<&str>) -> usize {
+    1 + string_size(value.unwrap_or_default())
+}
+
+/// Get the number of bytes [write_list] writes for a list of items
+///
+/// # Arguments
+///
+/// * `items` - The items of the list
+/// * `f`     - Gets the serialized size of a single item
+pub(crate) fn list_size<T, F>(items: &[T], f: F) -> usize
+    where F: Fn(&T) -> usize
+{
+    8 + items.iter().map(|item| 8 + f(item)).sum::<usize>()
+}
+
+/// Write a block prefixed with its size as a u64
+///
+/// # Arguments
+///
+/// * `buffer` - The buffer we use to append the data to
+/// * `f`      - Writes the content of the block
+///
+/// # Returns
+///
+/// * `Ok(())` - Successfully wrote the block
+/// * `Err(`[Error]`)` - `f` failed or the size could not be converted
+pub(crate) fn write_sized<O, F>(buffer: &mut O, f: F) -> Result<()>
+    where O: Output + ?Sized,
+          F: FnOnce(&mut O) -> Result<()>
+{
+    // Reserve the space for the size and patch it when we know it
+    let start = buffer.position();
+    buffer.extend_from_slice(&0u64.to_le_bytes());
+
+    f(buffer)?;
+
+    let size: u64 = (buffer.position() - start - 8).try_into()
+        .map_err(Error::IntegerConvertionError)?;
+    buffer.patch(start, &size.to_le_bytes());
+
+    Ok(())
+}
+
+/// The size of the tag and the size prefix written before every chunk
+pub(crate) const CHUNK_HEADER_SIZE: usize = 4 + 8;
+
+/// Writes a list of tagged chunks, the chunk count is written up front and
+/// patched when the writer is finished
+pub(crate) struct ChunkWriter<'a, O>
+    where O: Output + ?Sized
+{
+    /// The buffer we use to append the chunks to
+    buffer: &'a mut O,
+
+    /// The offset of the chunk count inside the buffer
+    count_offset: usize,
+
+    /// The number of chunks written so far
+    count: u64,
+}
+
+impl<'a, O> ChunkWriter<'a, O>
+    where O: Output + ?Sized
+{
+    /// Creates a new chunk writer and reserves the space for the count
+    pub(crate) fn new(buffer: &'a mut O) -> Self {
+        let count_offset = buffer.position();
+        buffer.extend_from_slice(&0u64.to_le_bytes());
+
+        Self {
+            buffer,
+            count_offset,
+            count: 0,
+        }
+    }
+
+    /// Write a single chunk, the tag followed by the size prefixed content
+    ///
+    /// # Arguments
+    ///
+    /// * `tag` - The tag identifying the chunk
+    /// * `f`   - Writes the content of the chunk
+    pub(crate) fn chunk<F>(&mut self, tag: &[u8; 4], f: F) -> Result<()>
+        where F: FnOnce(&mut O) -> Result<()>
+    {
+        self.buffer.extend_from_slice(tag);
+        write_sized(self.buffer, f)?;
+        self.count += 1;
+
+        Ok(())
+    }
+
+    /// Patch the chunk count
+    pub(crate) fn finish(self) {
+        self.buffer.patch(self.count_offset, &self.count.to_le_bytes());
+    }
+}
@@ -0,0 +1,350 @@
+//! Canonical serialization, identical content gives identical bytes
+//!
+//! [Map::serialize] is deterministic: the sectors and list items are
+//! written in the order they are stored, the chunks in a fixed order and
+//! every byte of the output is written, there is no uninitialized padding.
+//! Two things can still make logically equal maps serialize differently,
+//! the sign of zero and the payload of NaN floats, and the unused space
+//! [Map::save_to_file] and [Map::save_incremental] leave after the sectors.
+//!
+//! [Map::save_canonical_bytes] removes both so the same content always
+//! gives the same file, which content addressed caches and reproducible
+//! builds depend on. [Map::content_hash] hashes the same bytes so two
+//! sides can compare maps without sending the whole file.
+
+use crate::*;
+use crate::light::LightKind;
+use crate::buffer::write_sized;
+use crate::map::{ CURRENT_VERSION, HEADER_MAGIC };
+use crate::trigger::TriggerShape;
+
+/// Get the canonical form of a float, negative zero becomes zero and every
+/// NaN becomes the same NaN
+pub fn canonical_f32(value: f32) -> f32 {
+    if value == 0.0 {
+        0.0
+    } else if value.is_nan() {
+        f32::NAN
+    } else {
+        value
+    }
+}
+
+/// Replace a float with its canonical form
+///
+/// # Returns
+///
+/// * `true` - The bits of the float changed
+fn canonicalize_f32(value: &mut f32) -> bool {
+    let canonical = canonical_f32(*value);
+    let changed = canonical.to_bits() != value.to_bits();
+    *value = canonical;
+
+    changed
+}
+
+/// Replace the floats with their canonical form
+///
+/// # Returns
+///
+/// * `true` - The bits of any of the floats changed
+fn canonicalize_f32s(values: &mut [f32]) -> bool {
+    values.iter_mut().fold(false, |changed, value| {
+        canonicalize_f32(value) | changed
+    })
+}
+
+impl Mesh {
+    /// Replace every float of the mesh with its canonical form, see
+    /// [canonical_f32]
+    ///
+    /// # Returns
+    ///
+    /// * `true` - The bits of any of the floats changed
+    pub fn canonicalize(&mut self) -> bool {
+        let mut changed = false;
+
+        for vertex in &mut self.vertex_buffer {
+            changed |= canonicalize_f32s(&mut vertex.pos);
+            changed |= canonicalize_f32s(&mut vertex.uv);
+            changed |= canonicalize_f32s(&mut vertex.color);
+        }
+
+        for uv in &mut self.lightmap_uvs {
+            changed |= canonicalize_f32s(uv);
+        }
+
+        for set in &mut self.uv_sets {
+            for uv in &mut set.uvs {
+                changed |= canonicalize_f32s(uv);
+            }
+        }
+
+        changed
+    }
+}
+
+impl Sector {
+    /// Replace every float of the sector and its levels of detail with its
+    /// canonical form, see [canonical_f32]
+    ///
+    /// # Returns
+    ///
+    /// * `true` - The bits of any of the floats changed
+    pub fn canonicalize(&mut self) -> bool {
+        let mut changed = false;
+
+        for role in MeshRole::ALL {
+            changed |= self.mesh_mut(role).canonicalize();
+        }
+
+        for lod in &mut self.lods {
+            changed |= canonicalize_f32(&mut lod.switch_distance);
+            changed |= lod.floor_mesh.canonicalize();
+            changed |= lod.ceiling_mesh.canonicalize();
+            changed |= lod.wall_mesh.canonicalize();
+        }
+
+        for wall in &mut self.walls {
+            changed |= canonicalize_f32s(&mut wall.start);
+            changed |= canonicalize_f32s(&mut wall.end);
+            changed |= canonicalize_f32s(&mut wall.uv_offset);
+        }
+
+        let planes = [&mut self.floor_plane, &mut self.ceiling_plane];
+        for plane in planes.into_iter().flatten() {
+            changed |= canonicalize_f32s(plane);
+        }
+
+        for prop in &mut self.props {
+            for column in &mut prop.transform {
+                changed |= canonicalize_f32s(column);
+            }
+        }
+
+        for occluder in &mut self.occluders {
+            for vertex in &mut occluder.vertices {
+                changed |= canonicalize_f32s(vertex);
+            }
+        }
+
+        if let Some(liquid) = &mut self.liquid {
+            changed |= canonicalize_f32(&mut liquid.surface_height);
+            changed |= canonicalize_f32s(&mut liquid.flow);
+            changed |= canonicalize_f32s(&mut liquid.color);
+            changed |= canonicalize_f32(&mut liquid.fog_density);
+        }
+
+        let environment = &mut self.environment;
+        let colors = [&mut environment.fog_color,
+                      &mut environment.ambient_color];
+        for color in colors.into_iter().flatten() {
+            changed |= canonicalize_f32s(color);
+        }
+        if let Some(density) = &mut environment.fog_density {
+            changed |= canonicalize_f32(density);
+        }
+
+        changed |= canonicalize_f32(&mut self.acoustics.occlusion);
+
+        changed
+    }
+}
+
+impl Map {
+    /// Replace every float of the map with its canonical form, see
+    /// [canonical_f32]
+    ///
+    /// The changed sectors are marked for the next [Map::save_incremental].
+    pub fn canonicalize(&mut self) {
+        for index in 0..self.sectors.len() {
+            if self.sectors[index].canonicalize() {
+                self.mark_sector_dirty(index);
+            }
+        }
+
+        for light in &mut self.lights {
+            canonicalize_f32s(&mut light.position);
+            canonicalize_f32s(&mut light.color);
+            canonicalize_f32(&mut light.intensity);
+            canonicalize_f32(&mut light.radius);
+
+            match &mut light.kind {
+                LightKind::Point => {}
+
+                LightKind::Spot { direction, inner_angle, outer_angle } => {
+                    canonicalize_f32s(direction);
+                    canonicalize_f32(inner_angle);
+                    canonicalize_f32(outer_angle);
+                }
+
+                LightKind::Directional { direction } => {
+                    canonicalize_f32s(direction);
+                }
+            }
+        }
+
+        if let Some(Sky::Procedural {
+            zenith_color,
+            horizon_color,
+            sun_direction,
+            sun_color,
+            sun_size,
+        }) = &mut self.sky {
+            canonicalize_f32s(zenith_color);
+            canonicalize_f32s(horizon_color);
+            canonicalize_f32s(sun_direction);
+            canonicalize_f32s(sun_color);
+            canonicalize_f32(sun_size);
+        }
+
+        for emitter in &mut self.sound_emitters {
+            canonicalize_f32s(&mut emitter.position);
+            canonicalize_f32(&mut emitter.radius);
+            canonicalize_f32(&mut emitter.volume);
+        }
+
+        for trigger in &mut self.triggers {
+            match &mut trigger.shape {
+                TriggerShape::Aabb { min, max } => {
+                    canonicalize_f32s(min);
+                    canonicalize_f32s(max);
+                }
+
+                TriggerShape::Convex { planes } => {
+                    for plane in planes {
+                        canonicalize_f32s(plane);
+                    }
+                }
+            }
+        }
+
+        for entity in &mut self.entities {
+            canonicalize_f32s(&mut entity.position);
+
+            for value in entity.properties.values_mut() {
+                match value {
+                    PropertyValue::Float(value) => {
+                        if *value == 0.0 {
+                            *value = 0.0;
+                        } else if value.is_nan() {
+                            *value = f64::NAN;
+                        }
+                    }
+
+                    PropertyValue::Vec3(value) => {
+                        canonicalize_f32s(value);
+                    }
+
+                    PropertyValue::Color(value) => {
+                        canonicalize_f32s(value);
+                    }
+
+                    _ => {}
+                }
+            }
+        }
+
+        let waypoints = self.waypoints.iter_mut()
+            .flat_map(|waypoints| &mut waypoints.nodes);
+        for node in waypoints {
+            canonicalize_f32s(&mut node.position);
+
+            for link in &mut node.links {
+                canonicalize_f32(&mut link.cost);
+            }
+        }
+
+        for mesh in &mut self.mesh_library {
+            mesh.canonicalize();
+        }
+
+        for prop in &mut self.props {
+            for column in &mut prop.transform {
+                canonicalize_f32s(column);
+            }
+        }
+
+        canonicalize_f32s(&mut self.environment.fog_color);
+        canonicalize_f32(&mut self.environment.fog_density);
+        canonicalize_f32s(&mut self.environment.ambient_color);
+
+        for decal in &mut self.decals {
+            canonicalize_f32s(&mut decal.position);
+            canonicalize_f32s(&mut decal.normal);
+            canonicalize_f32s(&mut decal.up);
+            canonicalize_f32s(&mut decal.size);
+        }
+
+        for interactive in &mut self.interactives {
+            canonicalize_f32s(&mut interactive.position);
+            canonicalize_f32(&mut interactive.radius);
+        }
+
+        for material in &mut self.materials {
+            canonicalize_f32s(&mut material.base_color);
+            canonicalize_f32s(&mut material.emissive);
+            canonicalize_f32(&mut material.metallic);
+            canonicalize_f32(&mut material.roughness);
+
+            if let BlendMode::Masked { cutoff } = &mut material.blend_mode {
+                canonicalize_f32(cutoff);
+            }
+        }
+
+        for animation in &mut self.animations {
+            for keyframe in &mut animation.keyframes {
+                canonicalize_f32(&mut keyframe.time);
+                canonicalize_f32(&mut keyframe.offset);
+            }
+        }
+    }
+
+    /// Canonicalize the map and serialize it as a mime file with a single
+    /// map without any unused space after the sectors
+    ///
+    /// Maps with the same content always give the same bytes, across runs,
+    /// platforms and with or without the `rayon` feature. The file can be
+    /// loaded with [Map::load_from_bytes].
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<u8>)` - The content of the file
+    /// * `Err(`[Error]`)` - Failed to serialize the map
+    pub fn save_canonical_bytes(&mut self) -> Result<Vec<u8>> {
+        self.canonicalize();
+
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(HEADER_MAGIC);
+        buffer.extend_from_slice(&CURRENT_VERSION.to_le_bytes());
+        buffer.extend_from_slice(&1u64.to_le_bytes());
+        write_sized(&mut buffer, |buffer| self.serialize(buffer))?;
+
+        Ok(buffer)
+    }
+
+    /// Hash the canonical serialization of the map with 64 bit xxHash, see
+    /// [Map::save_canonical_bytes]
+    ///
+    /// The map itself is left untouched, a canonicalized copy is hashed.
+    /// Maps with the same content give the same hash on every platform,
+    /// but the hash changes when the format version changes so only
+    /// compare hashes from the same version of the crate.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(u64)` - The hash of the map content
+    /// * `Err(`[Error]`)` - Failed to serialize the map
+    pub fn content_hash(&self) -> Result<u64> {
+        // NOTE(patrik): The compression only changes how the content is
+        // stored
+        let mut map = self.clone();
+        map.compression = SectorCompression::None;
+        map.canonicalize();
+
+        let mut buffer = Vec::with_capacity(map.max_serialized_size());
+        map.serialize(&mut buffer)?;
+
+        Ok(xxhash_rust::xxh64::xxh64(&buffer, 0))
+    }
+}
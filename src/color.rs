@@ -0,0 +1,143 @@
+//! The color space of the vertex colors
+
+use crate::*;
+
+/// How the vertex colors of a map are encoded
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub enum ColorSpace {
+    /// The red, green and blue channels are sRGB encoded, the default for
+    /// maps saved before the color space was stored since most art tools
+    /// paint sRGB colors
+    #[default]
+    Srgb,
+
+    /// The red, green and blue channels are linear, the space
+    /// [Map::bake_vertex_lighting] computes the light in
+    Linear,
+}
+
+impl ColorSpace {
+    /// Get the byte the color space is serialized as
+    pub fn to_u8(self) -> u8 {
+        match self {
+            ColorSpace::Srgb => 0,
+            ColorSpace::Linear => 1,
+        }
+    }
+
+    /// Get the color space from its serialized byte
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The serialized byte
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - The color space
+    /// * `Err(`[Error]`)` - [Error::UnknownColorSpace] if the byte isn't a
+    ///   known color space
+    pub fn from_u8(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(ColorSpace::Srgb),
+            1 => Ok(ColorSpace::Linear),
+            value => Err(Error::UnknownColorSpace(value)),
+        }
+    }
+
+    /// Convert a color from this color space to another, the alpha channel
+    /// is always linear and kept as it is
+    ///
+    /// # Arguments
+    ///
+    /// * `color`  - The color (r, g, b, a)
+    /// * `target` - The color space to convert to
+    ///
+    /// # Returns
+    ///
+    /// * The converted color
+    pub fn convert(self, color: [f32; 4], target: ColorSpace) -> [f32; 4] {
+        let f = match (self, target) {
+            (ColorSpace::Srgb, ColorSpace::Linear) => srgb_to_linear,
+            (ColorSpace::Linear, ColorSpace::Srgb) => linear_to_srgb,
+            _ => return color,
+        };
+
+        [f(color[0]), f(color[1]), f(color[2]), color[3]]
+    }
+}
+
+/// Decode a single sRGB encoded channel to linear
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encode a single linear channel to sRGB
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+impl Mesh {
+    /// Convert the vertex colors of the mesh between color spaces
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - The color space the colors are in
+    /// * `to`   - The color space to convert to
+    pub fn convert_color_space(&mut self, from: ColorSpace, to: ColorSpace) {
+        for vertex in &mut self.vertex_buffer {
+            vertex.color = from.convert(vertex.color, to);
+        }
+    }
+}
+
+impl Sector {
+    /// Convert the vertex colors of all the meshes of the sector and its
+    /// levels of detail between color spaces
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - The color space the colors are in
+    /// * `to`   - The color space to convert to
+    pub fn convert_color_space(&mut self, from: ColorSpace, to: ColorSpace) {
+        for mesh in self.meshes_mut() {
+            mesh.convert_color_space(from, to);
+        }
+    }
+}
+
+impl Map {
+    /// Convert the vertex colors of every sector and library mesh to another
+    /// color space and update [Map::color_space]
+    ///
+    /// Use this instead of setting [Map::color_space] when the colors
+    /// should look the same afterwards. The changed sectors are marked for
+    /// the next [Map::save_incremental].
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The color space to convert to
+    pub fn convert_color_space(&mut self, target: ColorSpace) {
+        if self.color_space == target {
+            return;
+        }
+
+        for index in 0..self.sectors.len() {
+            self.sectors[index].convert_color_space(self.color_space, target);
+            self.mark_sector_dirty(index);
+        }
+
+        for mesh in &mut self.mesh_library {
+            mesh.convert_color_space(self.color_space, target);
+        }
+
+        self.color_space = target;
+    }
+}
@@ -0,0 +1,408 @@
+//! Compressing every sector of a map on its own
+//!
+//! Every sector block inside the map starts with a flag telling how the
+//! sector is stored. Compressed blocks keep the bounding box uncompressed
+//! and compress the rest of the sector, so [LazyMap] and
+//! [Map::sector_stream] only decompress the sectors they read and
+//! [LazyMap::sector_bounds] doesn't have to decompress anything.
+//!
+//! The codecs are behind the `lz4`, `zstd` and `deflate` features, only
+//! `lz4` is enabled by default. The map header records the codec of the
+//! map so a build without it fails with
+//! [Error::UnsupportedCompression] before reading any sector.
+
+use crate::*;
+use crate::buffer::Reader;
+use crate::bounds::BOUNDS_SIZE;
+use crate::limits::Budget;
+
+/// The size of the flag at the start of every sector block
+pub(crate) const SECTOR_BLOCK_HEADER_SIZE: usize = 1;
+
+/// The largest size a block of LZ4 compressed data can decompress to per
+/// compressed byte
+const LZ4_MAX_RATIO: usize = 255;
+
+/// The largest size a block of deflate compressed data can decompress to per
+/// compressed byte
+const DEFLATE_MAX_RATIO: usize = 1032;
+
+/// The compression level used by deflate, from 0 to 10
+#[cfg(feature = "deflate")]
+const DEFLATE_LEVEL: u8 = 6;
+
+/// How the sectors of a map are stored when the map is saved
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default, Debug)]
+pub enum SectorCompression {
+    /// The sectors are stored uncompressed
+    #[default]
+    None,
+
+    /// The sectors are compressed with LZ4, sectors that don't get smaller
+    /// are stored uncompressed
+    Lz4,
+
+    /// The sectors are compressed with Zstandard, smaller than LZ4 but
+    /// slower to decompress
+    Zstd,
+
+    /// The sectors are compressed with deflate, supported by most platform
+    /// libraries
+    Deflate,
+}
+
+impl SectorCompression {
+    /// Convert the flag of a sector block or the map header to the
+    /// compression
+    pub(crate) fn from_u8(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(SectorCompression::None),
+            1 => Ok(SectorCompression::Lz4),
+            2 => Ok(SectorCompression::Zstd),
+            3 => Ok(SectorCompression::Deflate),
+            _ => Err(Error::UnknownSectorCompression(value)),
+        }
+    }
+
+    /// Convert the compression to the flag of a sector block or the map
+    /// header
+    pub(crate) fn to_u8(self) -> u8 {
+        match self {
+            SectorCompression::None => 0,
+            SectorCompression::Lz4 => 1,
+            SectorCompression::Zstd => 2,
+            SectorCompression::Deflate => 3,
+        }
+    }
+
+    /// Check if the codec was enabled when the crate was built, maps using
+    /// a codec that isn't supported can't be saved or loaded
+    pub fn is_supported(self) -> bool {
+        match self {
+            SectorCompression::None => true,
+            SectorCompression::Lz4 => cfg!(feature = "lz4"),
+            SectorCompression::Zstd => cfg!(feature = "zstd"),
+            SectorCompression::Deflate => cfg!(feature = "deflate"),
+        }
+    }
+
+    /// Get an error if the codec isn't supported
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The codec is supported
+    /// * `Err(`[Error]`)` - [Error::UnsupportedCompression] if the codec
+    ///   wasn't enabled
+    pub(crate) fn check_supported(self) -> Result<()> {
+        if !self.is_supported() {
+            return Err(Error::UnsupportedCompression(self));
+        }
+
+        Ok(())
+    }
+
+    /// Get the largest size a compressed byte can decompress to, used to
+    /// reject sizes that can't be right before allocating
+    fn max_ratio(self) -> usize {
+        match self {
+            SectorCompression::None => 1,
+            SectorCompression::Lz4 => LZ4_MAX_RATIO,
+            SectorCompression::Deflate => DEFLATE_MAX_RATIO,
+
+            // NOTE(patrik): Runs of the same byte have no real limit, the
+            // output of zstd only grows as the frame decompresses, see
+            // [SectorCompression::decompress_append]
+            SectorCompression::Zstd => usize::MAX,
+        }
+    }
+
+    /// Compress data with the codec
+    fn compress(self, data: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            SectorCompression::None => Ok(data.to_vec()),
+
+            #[cfg(feature = "lz4")]
+            SectorCompression::Lz4 => Ok(lz4_flex::block::compress(data)),
+
+            #[cfg(feature = "zstd")]
+            SectorCompression::Zstd => {
+                use ruzstd::encoding::{ compress_to_vec, CompressionLevel };
+                Ok(compress_to_vec(data, CompressionLevel::Fastest))
+            }
+
+            #[cfg(feature = "deflate")]
+            SectorCompression::Deflate => {
+                Ok(miniz_oxide::deflate::compress_to_vec(data, DEFLATE_LEVEL))
+            }
+
+            #[allow(unreachable_patterns)]
+            compression => Err(Error::UnsupportedCompression(compression)),
+        }
+    }
+
+    /// Decompress data with the codec to the end of a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `compressed` - The compressed data
+    /// * `size`       - The size of the decompressed data
+    /// * `out`        - Extended with the decompressed data
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - Successfully decompressed the data
+    /// * `Err(`[Error]`)` - Same as [SectorCompression::decompress_into]
+    fn decompress_append(self,
+                         compressed: &[u8],
+                         size: usize,
+                         out: &mut Vec<u8>)
+        -> Result<()>
+    {
+        #[cfg(feature = "zstd")]
+        if self == SectorCompression::Zstd {
+            return decompress_zstd(compressed, size, out);
+        }
+
+        // NOTE(patrik): The size was checked against the ratio of the codec
+        // so the buffer can be allocated up front
+        let start = out.len();
+        let end = start.checked_add(size)
+            .ok_or(Error::DecompressionFailed)?;
+        out.resize(end, 0);
+        self.decompress_into(compressed, &mut out[start..])
+    }
+
+    /// Decompress data with the codec
+    ///
+    /// # Arguments
+    ///
+    /// * `compressed` - The compressed data
+    /// * `out`        - Filled with the decompressed data
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - Successfully decompressed the data
+    /// * `Err(`[Error]`)` - [Error::DecompressionFailed] if the data is
+    ///   corrupt or doesn't decompress to exactly the size of `out`,
+    ///   [Error::UnsupportedCompression] if the codec wasn't enabled
+    fn decompress_into(self, compressed: &[u8], out: &mut [u8]) -> Result<()> {
+        let written = match self {
+            SectorCompression::None => {
+                let size = compressed.len().min(out.len());
+                out[..size].copy_from_slice(&compressed[..size]);
+                Some(compressed.len())
+            }
+
+            #[cfg(feature = "lz4")]
+            SectorCompression::Lz4 => {
+                lz4_flex::block::decompress_into(compressed, out).ok()
+            }
+
+            #[cfg(feature = "zstd")]
+            SectorCompression::Zstd => {
+                ruzstd::decoding::FrameDecoder::new()
+                    .decode_all(compressed, out)
+                    .ok()
+            }
+
+            #[cfg(feature = "deflate")]
+            SectorCompression::Deflate => {
+                miniz_oxide::inflate::decompress_slice_iter_to_slice(
+                    out, std::iter::once(compressed), false, true)
+                    .ok()
+            }
+
+            #[allow(unreachable_patterns)]
+            compression => {
+                return Err(Error::UnsupportedCompression(compression));
+            }
+        };
+
+        match written {
+            Some(written) if written == out.len() => Ok(()),
+            _ => Err(Error::DecompressionFailed),
+        }
+    }
+}
+
+/// Decompress a zstd frame to the end of a buffer
+///
+/// A zstd frame can decompress to any size so the size from the file can't
+/// be checked up front. The frame content size is compared when the frame
+/// header has it and the buffer only grows as the frame decompresses, a
+/// hostile size fails after reading the frame instead of allocating it.
+///
+/// # Arguments
+///
+/// * `compressed` - The zstd frame
+/// * `size`       - The size of the decompressed data
+/// * `out`        - Extended with the decompressed data
+///
+/// # Returns
+///
+/// * `Ok(())` - Successfully decompressed the data
+/// * `Err(`[Error]`)` - [Error::DecompressionFailed] if the frame is
+///   corrupt or doesn't decompress to exactly `size` bytes
+#[cfg(feature = "zstd")]
+fn decompress_zstd(compressed: &[u8], size: usize, out: &mut Vec<u8>)
+    -> Result<()>
+{
+    use std::io::Read;
+    use ruzstd::decoding::StreamingDecoder;
+
+    let mut decoder = StreamingDecoder::new(compressed)
+        .map_err(|_| Error::DecompressionFailed)?;
+
+    // NOTE(patrik): The encoder of ruzstd leaves the content size out, it
+    // reads as 0 then
+    let content_size = decoder.decoder.content_size();
+    if content_size != 0 && content_size != size as u64 {
+        return Err(Error::DecompressionFailed);
+    }
+
+    let start = out.len();
+    let limit = (size as u64).saturating_add(1);
+    decoder.by_ref()
+        .take(limit)
+        .read_to_end(out)
+        .map_err(|_| Error::DecompressionFailed)?;
+
+    if out.len() - start != size {
+        return Err(Error::DecompressionFailed);
+    }
+
+    Ok(())
+}
+
+/// Serialize a sector as a sector block
+///
+/// # Arguments
+///
+/// * `sector`      - The sector to serialize
+/// * `compression` - How the sector should be stored
+///
+/// # Returns
+///
+/// * `Ok(Vec<u8>)` - The serialized block
+/// * `Err(`[Error]`)` - Failed to serialize the sector
+pub(crate) fn sector_block(sector: &Sector, compression: SectorCompression)
+    -> Result<Vec<u8>>
+{
+    let mut sector_data = Vec::with_capacity(sector.serialized_size());
+    sector.serialize(&mut sector_data)?;
+
+    if compression != SectorCompression::None {
+        let (bounds, rest) = sector_data.split_at(BOUNDS_SIZE);
+        let compressed = compression.compress(rest)?;
+
+        let size = SECTOR_BLOCK_HEADER_SIZE + BOUNDS_SIZE + 8 +
+            compressed.len();
+        if size < SECTOR_BLOCK_HEADER_SIZE + sector_data.len() {
+            let mut block = Vec::with_capacity(size);
+            block.push(compression.to_u8());
+            block.extend_from_slice(bounds);
+            block.extend_from_slice(&(rest.len() as u64).to_le_bytes());
+            block.extend_from_slice(&compressed);
+
+            return Ok(block);
+        }
+    }
+
+    let mut block = Vec::with_capacity(SECTOR_BLOCK_HEADER_SIZE +
+                                       sector_data.len());
+    block.push(SectorCompression::None.to_u8());
+    block.extend_from_slice(&sector_data);
+
+    Ok(block)
+}
+
+/// Get the size of the block [sector_block] creates, sectors are
+/// compressed to get their size
+pub(crate) fn sector_block_size(sector: &Sector,
+                                compression: SectorCompression)
+    -> usize
+{
+    match compression {
+        SectorCompression::None => {
+            SECTOR_BLOCK_HEADER_SIZE + sector.serialized_size()
+        }
+
+        // NOTE(patrik): Serializing the sector only fails when it doesn't
+        // fit the format, the serializer fails the same way later
+        compression => sector_block(sector, compression)
+            .map_or(SECTOR_BLOCK_HEADER_SIZE + sector.serialized_size(),
+                    |block| block.len()),
+    }
+}
+
+/// Deserialize a sector block
+///
+/// # Arguments
+///
+/// * `block`  - The serialized block
+/// * `budget` - The limits the sector is taken from
+///
+/// # Returns
+///
+/// * `Ok(`[Sector]`)` - The deserialized sector
+/// * `Err(`[Error]`)` - Failed to decompress or deserialize the sector, the
+///   offsets of errors inside a compressed sector are relative to the start
+///   of the decompressed sector
+pub(crate) fn read_sector_block(block: &[u8], budget: &Budget)
+    -> Result<Sector>
+{
+    let mut reader = Reader::new(block, Error::BufferToSmallSector);
+
+    match SectorCompression::from_u8(reader.u8()?)? {
+        SectorCompression::None => {
+            Sector::deserialize_with_budget(reader.remaining(), budget)
+                .map_err(|error| error.at_offset(SECTOR_BLOCK_HEADER_SIZE))
+        }
+
+        compression => {
+            compression.check_supported()?;
+
+            let bounds = reader.bytes(BOUNDS_SIZE)?;
+            let size = reader.usize()?;
+            let compressed = reader.remaining();
+
+            // NOTE(patrik): The size comes from the file, check it before
+            // allocating
+            let max_size = compressed.len()
+                .saturating_mul(compression.max_ratio());
+            if size > max_size {
+                return Err(Error::DecompressionFailed
+                    .at_offset(reader.offset() - 8));
+            }
+
+            budget.allocate_bytes(size)
+                .map_err(|error| error.at_offset(reader.offset() - 8))?;
+
+            let mut sector_data = Vec::new();
+            sector_data.extend_from_slice(bounds);
+            compression.decompress_append(compressed, size, &mut sector_data)
+                .map_err(|error| error.at_offset(reader.offset()))?;
+
+            Sector::deserialize_with_budget(&sector_data, budget)
+        }
+    }
+}
+
+/// Read the bounding box of a sector block without decompressing or
+/// deserializing the sector
+///
+/// # Arguments
+///
+/// * `block` - The start of the serialized block
+///
+/// # Returns
+///
+/// * `Ok(`[Bounds]`)` - The bounding box stored with the sector
+/// * `Err(`[Error]`)` - The block is too small or the flag is unknown
+pub(crate) fn read_block_bounds(block: &[u8]) -> Result<Bounds> {
+    let mut reader = Reader::new(block, Error::BufferToSmallSector);
+    SectorCompression::from_u8(reader.u8()?)?;
+
+    Sector::read_bounds(reader.remaining())
+        .map_err(|error| error.at_offset(SECTOR_BLOCK_HEADER_SIZE))
+}
@@ -0,0 +1,271 @@
+//! Reading maps stored back to back
+//!
+//! Replays and save games bundle many snapshots of a map by calling
+//! [Map::serialize] for every snapshot into the same buffer or file. There
+//! is no header or index, every map ends with its chunk list and the next
+//! map starts right after it, see [Map::deserialize_prefix]. The
+//! [DeserializeOptions] passed to [Map::concatenated_with_options] and
+//! [Map::concatenated_stream_with_options] limit every map on its own.
+
+use crate::*;
+use crate::map::MAP_HEADER_SIZE;
+
+use std::io::Read;
+
+/// An iterator deserializing the maps stored back to back inside a buffer
+struct ConcatenatedMaps<'a> {
+    /// The buffer with the maps
+    buffer: &'a [u8],
+
+    /// The offset of the next map
+    position: usize,
+
+    /// The limits every map is deserialized with
+    options: DeserializeOptions,
+
+    /// All the maps were read or an error happened
+    done: bool,
+}
+
+impl Iterator for ConcatenatedMaps<'_> {
+    type Item = Result<Map>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.position >= self.buffer.len() {
+            return None;
+        }
+
+        let buffer = &self.buffer[self.position..];
+        match Map::deserialize_prefix_with_options(buffer, &self.options) {
+            Ok((map, size)) => {
+                self.position += size;
+                Some(Ok(map))
+            }
+
+            // NOTE(patrik): The end of a broken map is unknown so the next
+            // map can't be found
+            Err(error) => {
+                self.done = true;
+                Some(Err(error.at_offset(self.position)))
+            }
+        }
+    }
+}
+
+/// An iterator deserializing the maps stored back to back inside a reader,
+/// only the map currently being deserialized is kept in memory
+struct ConcatenatedStream<R>
+    where R: Read
+{
+    /// The reader we read the maps from
+    reader: R,
+
+    /// The number of bytes read so far
+    position: usize,
+
+    /// The limits every map is deserialized with
+    options: DeserializeOptions,
+
+    /// All the maps were read or an error happened
+    done: bool,
+}
+
+impl<R> ConcatenatedStream<R>
+    where R: Read
+{
+    /// Read `size` bytes to the end of the map, the map only grows as data
+    /// arrives so a corrupt size can't allocate more than the reader
+    /// contains
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(usize)` - The number of bytes read, less than `size` at the
+    ///   end of the reader
+    /// * `Err(`[Error]`)` - Failed to read
+    fn read(&mut self, map: &mut Vec<u8>, size: usize) -> Result<usize> {
+        let read = self.reader.by_ref()
+            .take(size as u64)
+            .read_to_end(map)
+            .map_err(Error::FileReadFailed)?;
+        self.position += read;
+
+        Ok(read)
+    }
+
+    /// Read until the map has `end` bytes and fail if the reader ends
+    /// first
+    fn read_to(&mut self, map: &mut Vec<u8>, end: usize) -> Result<()> {
+        let size = end.saturating_sub(map.len());
+        if self.read(map, size)? < size {
+            let size = SizeMismatch::new(end, map.len());
+            return Err(Error::BufferToSmallMap(size).at_offset(map.len()));
+        }
+
+        Ok(())
+    }
+
+    /// Read the bytes of the next map without deserializing it
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(Vec<u8>))` - The serialized map
+    /// * `Ok(None)` - The reader ended before the map
+    /// * `Err(`[Error]`)` - Failed to read or the reader ended inside the
+    ///   map, the offset is relative to the start of the map
+    fn read_map(&mut self) -> Result<Option<Vec<u8>>> {
+        let u64_at = |map: &[u8], offset: usize| {
+            let value = u64::from_le_bytes(
+                map[offset..offset + 8].try_into().unwrap());
+            usize::try_from(value).unwrap_or(usize::MAX)
+        };
+
+        let mut map = Vec::new();
+        if self.read(&mut map, MAP_HEADER_SIZE)? == 0 {
+            return Ok(None);
+        }
+        self.read_to(&mut map, MAP_HEADER_SIZE)?;
+
+        // NOTE(patrik): The sector table and the sectors are read in one
+        // go, a chunks offset inside the header leaves the map as is and
+        // the deserializer reports it
+        let chunks_offset = u64_at(&map, 8);
+        self.read_to(&mut map, chunks_offset)?;
+
+        // NOTE(patrik): The chunks are walked to find where the map ends
+        let start = map.len();
+        self.read_to(&mut map, start + 8)?;
+        for _i in 0..u64_at(&map, start) {
+            // Tag and size
+            let header = map.len();
+            self.read_to(&mut map, header + 4 + 8)?;
+
+            let size = u64_at(&map, header + 4);
+            self.read_to(&mut map, (header + 4 + 8).saturating_add(size))?;
+        }
+
+        Ok(Some(map))
+    }
+}
+
+impl<R> Iterator for ConcatenatedStream<R>
+    where R: Read
+{
+    type Item = Result<Map>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let start = self.position;
+        let map = match self.read_map() {
+            Ok(Some(map)) => {
+                Map::deserialize_strict_with_options(&map, &self.options)
+            }
+
+            Ok(None) => {
+                self.done = true;
+                return None;
+            }
+
+            Err(error) => Err(error),
+        };
+
+        // NOTE(patrik): The reader can't go back to find the next map after
+        // an error
+        if map.is_err() {
+            self.done = true;
+        }
+
+        Some(map.map_err(|error| error.at_offset(start)))
+    }
+}
+
+impl Map {
+    /// Deserialize the maps stored back to back inside a buffer
+    ///
+    /// The iterator stops after the first error.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The maps, each written by [Map::serialize]
+    ///
+    /// # Returns
+    ///
+    /// * An iterator over the maps in the order they are stored
+    pub fn concatenated(buffer: &[u8])
+        -> impl Iterator<Item = Result<Map>> + '_
+    {
+        Self::concatenated_with_options(buffer, &DeserializeOptions::default())
+    }
+
+    /// Deserialize the maps stored back to back inside a buffer with
+    /// limits, see [Map::concatenated]
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer`  - The maps, each written by [Map::serialize]
+    /// * `options` - The limits and index check applied to every map on its
+    ///   own
+    ///
+    /// # Returns
+    ///
+    /// * An iterator over the maps in the order they are stored
+    pub fn concatenated_with_options<'a>(buffer: &'a [u8],
+                                         options: &DeserializeOptions)
+        -> impl Iterator<Item = Result<Map>> + 'a
+    {
+        ConcatenatedMaps {
+            buffer,
+            position: 0,
+            options: *options,
+            done: false,
+        }
+    }
+
+    /// Deserialize the maps stored back to back inside a reader one at a
+    /// time, without reading all of them into memory
+    ///
+    /// The iterator stops at the end of the reader or after the first
+    /// error.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The reader positioned at the start of the first map
+    ///
+    /// # Returns
+    ///
+    /// * An iterator over the maps in the order they are stored
+    pub fn concatenated_stream<R>(reader: R)
+        -> impl Iterator<Item = Result<Map>>
+        where R: Read
+    {
+        Self::concatenated_stream_with_options(reader,
+                                               &DeserializeOptions::default())
+    }
+
+    /// Deserialize the maps stored back to back inside a reader one at a
+    /// time with limits, see [Map::concatenated_stream]
+    ///
+    /// # Arguments
+    ///
+    /// * `reader`  - The reader positioned at the start of the first map
+    /// * `options` - The limits and index check applied to every map on its
+    ///   own
+    ///
+    /// # Returns
+    ///
+    /// * An iterator over the maps in the order they are stored
+    pub fn concatenated_stream_with_options<R>(reader: R,
+                                               options: &DeserializeOptions)
+        -> impl Iterator<Item = Result<Map>>
+        where R: Read
+    {
+        ConcatenatedStream {
+            reader,
+            position: 0,
+            options: *options,
+            done: false,
+        }
+    }
+}
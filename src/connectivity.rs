@@ -0,0 +1,209 @@
+//! Which sectors connect through the openings between them, with path
+//! finding over the centers of the sectors
+//!
+//! The graph is built from [Map::portals], good enough for simple AI and
+//! checks like if a sound can reach a sector without a navigation mesh.
+
+use crate::*;
+use crate::math::{ length, sub };
+
+use std::cmp::Ordering;
+use std::collections::{ BinaryHeap, VecDeque };
+
+/// The sectors of a map and the sectors they connect to
+#[derive(Clone, PartialEq, Debug)]
+pub struct SectorGraph {
+    /// The center of the bounding box of every sector
+    centers: Vec<[f32; 3]>,
+
+    /// The sectors every sector connects to, sorted and without duplicates
+    neighbors: Vec<Vec<usize>>,
+}
+
+impl SectorGraph {
+    /// Get the number of sectors of the graph
+    pub fn len(&self) -> usize {
+        self.centers.len()
+    }
+
+    /// Check if the graph doesn't have any sectors
+    pub fn is_empty(&self) -> bool {
+        self.centers.is_empty()
+    }
+
+    /// Get the sectors a sector connects to
+    ///
+    /// # Arguments
+    ///
+    /// * `sector` - The index of the sector
+    ///
+    /// # Returns
+    ///
+    /// * The indices of the connected sectors in ascending order, empty if
+    ///   the sector doesn't exist
+    pub fn neighbors(&self, sector: usize) -> &[usize] {
+        self.neighbors.get(sector).map_or(&[], Vec::as_slice)
+    }
+
+    /// Get the center of the bounding box of a sector, the points paths
+    /// move between
+    pub fn center(&self, sector: usize) -> Option<[f32; 3]> {
+        self.centers.get(sector).copied()
+    }
+
+    /// Get every sector that can be reached from a sector
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - The index of the sector to start at
+    ///
+    /// # Returns
+    ///
+    /// * One flag for every sector, `true` if the sector can be reached,
+    ///   the start counts as reached
+    pub fn reachable(&self, from: usize) -> Vec<bool> {
+        let mut reached = vec![false; self.len()];
+        if from >= self.len() {
+            return reached;
+        }
+
+        reached[from] = true;
+        let mut queue = VecDeque::from([from]);
+        while let Some(sector) = queue.pop_front() {
+            for &next in &self.neighbors[sector] {
+                if !reached[next] {
+                    reached[next] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        reached
+    }
+
+    /// Check if a sector can be reached from another sector
+    pub fn is_reachable(&self, from: usize, to: usize) -> bool {
+        self.reachable(from).get(to).copied().unwrap_or(false)
+    }
+
+    /// Find the shortest path between two sectors with A*, the cost of a
+    /// step is the distance between the centers of the sectors
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - The index of the sector to start at
+    /// * `to`   - The index of the sector to reach
+    ///
+    /// # Returns
+    ///
+    /// * `Some(Vec<usize>)` - The sectors of the path starting with `from`
+    ///   and ending with `to`
+    /// * `None` - One of the sectors doesn't exist or `to` can't be reached
+    pub fn find_path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        if from >= self.len() || to >= self.len() {
+            return None;
+        }
+
+        let distance = |a: usize, b: usize| {
+            length(sub(self.centers[b], self.centers[a]))
+        };
+
+        let mut costs = vec![f32::INFINITY; self.len()];
+        let mut previous = vec![usize::MAX; self.len()];
+        let mut open = BinaryHeap::new();
+
+        costs[from] = 0.0;
+        open.push(OpenSector { estimate: distance(from, to), sector: from });
+
+        while let Some(OpenSector { sector, .. }) = open.pop() {
+            if sector == to {
+                let mut path = vec![to];
+                let mut step = to;
+                while step != from {
+                    step = previous[step];
+                    path.push(step);
+                }
+                path.reverse();
+
+                return Some(path);
+            }
+
+            for &next in &self.neighbors[sector] {
+                let cost = costs[sector] + distance(sector, next);
+                if cost < costs[next] {
+                    costs[next] = cost;
+                    previous[next] = sector;
+                    open.push(OpenSector {
+                        estimate: cost + distance(next, to),
+                        sector: next,
+                    });
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// A sector waiting to be visited by [SectorGraph::find_path], the heap
+/// pops the lowest estimate first
+#[derive(Copy, Clone, PartialEq, Debug)]
+struct OpenSector {
+    /// The cost to reach the sector plus the distance left to the goal
+    estimate: f32,
+
+    /// The index of the sector
+    sector: usize,
+}
+
+impl Eq for OpenSector {}
+
+impl Ord for OpenSector {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+            .then_with(|| other.sector.cmp(&self.sector))
+    }
+}
+
+impl PartialOrd for OpenSector {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Map {
+    /// Build the graph of the sectors connected by portals, see
+    /// [Map::portals]
+    ///
+    /// The graph is built for the current sectors, build it again after
+    /// changing the sectors or their walls.
+    pub fn sector_graph(&self) -> SectorGraph {
+        let mut neighbors = vec![Vec::new(); self.sectors.len()];
+        for portal in self.portals() {
+            neighbors[portal.from].push(portal.to);
+        }
+
+        for list in &mut neighbors {
+            list.sort_unstable();
+            list.dedup();
+        }
+
+        SectorGraph {
+            centers: self.sectors.iter()
+                .map(|sector| sector.bounds().center())
+                .collect(),
+            neighbors,
+        }
+    }
+
+    /// Find the shortest path between two sectors, see
+    /// [SectorGraph::find_path]
+    ///
+    /// Build the graph once with [Map::sector_graph] when finding multiple
+    /// paths.
+    pub fn find_sector_path(&self, from: usize, to: usize)
+        -> Option<Vec<usize>>
+    {
+        self.sector_graph().find_path(from, to)
+    }
+}
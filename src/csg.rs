@@ -0,0 +1,603 @@
+//! Constructive solid geometry on convex brushes
+//!
+//! Like the brushes of Quake-style editors a [Brush] is a convex volume
+//! bounded by planes. Brushes are combined into a [Solid] with union,
+//! subtraction and intersection, and the solid is compiled into the meshes
+//! of a sector with [Solid::compile].
+//!
+//! The solid is the open space of the sector, a room with a corridor is the
+//! union of the two and a pillar is subtracted from the room. When the solid
+//! is compiled the faces are turned to face into the open space.
+
+use crate::*;
+use crate::math::{ add, sub, dot, cross, length, normalize,
+                   triangle_normal };
+
+/// How far a point can be from a plane and still count as lying on it
+const CSG_EPSILON: f64 = 1e-4;
+
+/// The half size of the polygon every face starts as before it's clipped by
+/// the other planes of the brush, larger than any map
+const FACE_EXTENT: f64 = 1e6;
+
+/// The smallest Y of the normal of a face that is part of the floor or the
+/// ceiling, steeper faces are walls
+const FLOOR_NORMAL_Y: f32 = 0.7;
+
+/// A point in double precision, the starting polygons of the faces are huge
+/// and would lose too much precision when clipped as `f32`
+type Point = [f64; 3];
+
+/// A convex polygon, counter clockwise seen from the front
+type Polygon = Vec<Point>;
+
+/// A plane bounding a brush
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Plane {
+    /// The unit normal of the plane, pointing out of the brush
+    pub normal: [f32; 3],
+
+    /// The distance from the origin to the plane along the normal, points
+    /// where `dot(normal, point) <= distance` are inside
+    pub distance: f32,
+}
+
+impl Plane {
+    /// Creates a new plane, the normal doesn't have to be unit length
+    ///
+    /// # Arguments
+    ///
+    /// * `normal`   - The normal pointing out of the brush
+    /// * `distance` - The distance from the origin along the normal, in
+    ///   multiples of the length of the normal
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new plane with a unit normal
+    pub fn new(normal: [f32; 3], distance: f32) -> Self {
+        let len = length(normal);
+        if len > 0.0 {
+            Self {
+                normal: normalize(normal),
+                distance: distance / len,
+            }
+        } else {
+            Self { normal, distance }
+        }
+    }
+
+    /// Creates the plane through three points, counter clockwise seen from
+    /// outside of the brush
+    pub fn from_points(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> Self {
+        let normal = normalize(cross(sub(b, a), sub(c, a)));
+        Self {
+            normal,
+            distance: dot(normal, a),
+        }
+    }
+
+    /// Get the signed distance from the plane to a point, positive outside
+    /// of the brush
+    pub fn distance_to(&self, point: [f32; 3]) -> f32 {
+        dot(self.normal, point) - self.distance
+    }
+
+    /// Get the plane facing the other way, bounding the space outside
+    pub fn flipped(&self) -> Self {
+        Self {
+            normal: [-self.normal[0], -self.normal[1], -self.normal[2]],
+            distance: -self.distance,
+        }
+    }
+
+    /// Get the signed distance to a point in double precision
+    fn side(&self, point: Point) -> f64 {
+        let [x, y, z] = self.normal.map(f64::from);
+        x * point[0] + y * point[1] + z * point[2] - self.distance as f64
+    }
+
+    /// Check if two planes are the same within [CSG_EPSILON]
+    fn same_as(&self, other: &Plane) -> bool {
+        dot(self.normal, other.normal) as f64 >= 1.0 - CSG_EPSILON &&
+            ((self.distance - other.distance) as f64).abs() <= CSG_EPSILON
+    }
+
+    /// Create a huge square on the plane, counter clockwise seen from the
+    /// side the normal points to
+    fn polygon(&self) -> Polygon {
+        let normal = self.normal.map(f64::from);
+        let axis = if normal[0].abs() < 0.9 {
+            [1.0, 0.0, 0.0]
+        } else {
+            [0.0, 1.0, 0.0]
+        };
+
+        // NOTE(patrik): cross(u, v) is the normal so walking -u-v, u-v,
+        // u+v, -u+v is counter clockwise seen from the front
+        let u = normalize64(cross64(normal, axis));
+        let v = cross64(normal, u);
+        let center = normal.map(|n| n * self.distance as f64);
+
+        [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].iter()
+            .map(|&(a, b)| {
+                std::array::from_fn(|i| {
+                    center[i] + (u[i] * a + v[i] * b) * FACE_EXTENT
+                })
+            })
+            .collect()
+    }
+}
+
+/// A convex volume bounded by planes
+#[derive(Clone, PartialEq, Default, Debug)]
+pub struct Brush {
+    /// The planes bounding the brush, the brush is the space inside all of
+    /// them
+    pub planes: Vec<Plane>,
+}
+
+impl Brush {
+    /// Creates a new brush
+    ///
+    /// # Arguments
+    ///
+    /// * `planes` - The planes bounding the brush
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new brush, planes that don't touch the volume are
+    ///   kept
+    pub fn new(planes: Vec<Plane>) -> Self {
+        Self { planes }
+    }
+
+    /// Check if a point is inside the brush or on its surface
+    pub fn contains(&self, point: [f32; 3]) -> bool {
+        self.planes.iter()
+            .all(|plane| plane.distance_to(point) as f64 <= CSG_EPSILON)
+    }
+
+    /// Check if the brush doesn't have any volume
+    pub fn is_empty(&self) -> bool {
+        self.face_polygons().len() < 4
+    }
+
+    /// Get the faces of the brush
+    ///
+    /// # Returns
+    ///
+    /// * The polygons of the faces, counter clockwise seen from outside of
+    ///   the brush, empty if the brush doesn't have any volume
+    pub fn faces(&self) -> Vec<Vec<[f32; 3]>> {
+        self.face_polygons().iter()
+            .map(|(_, polygon)| to_points(polygon))
+            .collect()
+    }
+
+    /// Get the space inside both brushes
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[Brush]`)` - The intersection
+    /// * `None` - The brushes don't overlap
+    pub fn intersect(&self, other: &Brush) -> Option<Brush> {
+        let planes = self.planes.iter().chain(&other.planes).copied();
+        Brush::trimmed(planes.collect())
+    }
+
+    /// Cut a brush out of this brush
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The brush to cut out
+    ///
+    /// # Returns
+    ///
+    /// * The convex pieces left, they don't overlap, empty if nothing is
+    ///   left
+    pub fn subtract(&self, other: &Brush) -> Vec<Brush> {
+        if self.intersect(other).is_none() {
+            return Brush::trimmed(self.planes.clone()).into_iter().collect();
+        }
+
+        // NOTE(patrik): Every plane of the other brush cuts off the piece
+        // in front of it, what's left after the last plane is inside the
+        // other brush
+        let mut pieces = Vec::new();
+        let mut rest = self.planes.clone();
+        for plane in &other.planes {
+            let mut outside = rest.clone();
+            outside.push(plane.flipped());
+            pieces.extend(Brush::trimmed(outside));
+
+            rest.push(*plane);
+        }
+
+        pieces
+    }
+
+    /// Create a brush from planes, dropping the planes that don't touch the
+    /// volume
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[Brush]`)` - The brush
+    /// * `None` - The planes don't enclose any volume
+    fn trimmed(planes: Vec<Plane>) -> Option<Brush> {
+        let brush = Brush::new(planes);
+        let faces = brush.face_polygons();
+        if faces.len() < 4 {
+            return None;
+        }
+
+        Some(Brush::new(faces.iter().map(|&(i, _)| brush.planes[i]).collect()))
+    }
+
+    /// Get the faces of the brush with the index of their plane, the
+    /// polygons are counter clockwise seen from outside
+    fn face_polygons(&self) -> Vec<(usize, Polygon)> {
+        let mut faces = Vec::new();
+
+        'planes: for (index, plane) in self.planes.iter().enumerate() {
+            let mut polygon = plane.polygon();
+
+            for (other_index, other) in self.planes.iter().enumerate() {
+                if other_index == index {
+                    continue;
+                }
+
+                // NOTE(patrik): The first of the duplicated planes gets the
+                // face
+                if other.same_as(plane) {
+                    if other_index < index {
+                        continue 'planes;
+                    }
+
+                    continue;
+                }
+
+                polygon = split(&polygon, other).1;
+                if polygon.len() < 3 {
+                    continue 'planes;
+                }
+            }
+
+            if is_polygon(&polygon) {
+                faces.push((index, polygon));
+            }
+        }
+
+        faces
+    }
+}
+
+/// The options used by [Solid::compile]
+#[derive(Clone, PartialEq, Debug)]
+pub struct CsgOptions {
+    /// The texture ID of the floor mesh
+    pub floor_texture: u64,
+
+    /// The texture ID of the ceiling mesh
+    pub ceiling_texture: u64,
+
+    /// The texture ID of the wall mesh
+    pub wall_texture: u64,
+
+    /// The size of a single repeat of the textures in world units, the
+    /// textures are projected along the axis closest to the normal
+    pub texture_size: f32,
+}
+
+impl Default for CsgOptions {
+    fn default() -> Self {
+        Self {
+            floor_texture: 0,
+            ceiling_texture: 0,
+            wall_texture: 0,
+            texture_size: 1.0,
+        }
+    }
+}
+
+/// A volume made of convex brushes
+#[derive(Clone, PartialEq, Default, Debug)]
+pub struct Solid {
+    /// The brushes of the solid, they must not overlap, combine solids with
+    /// [Solid::union] instead of adding brushes
+    pub brushes: Vec<Brush>,
+}
+
+impl From<Brush> for Solid {
+    fn from(brush: Brush) -> Self {
+        Self {
+            brushes: Brush::trimmed(brush.planes).into_iter().collect(),
+        }
+    }
+}
+
+impl Solid {
+    /// Creates a new solid without any volume
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check if a point is inside the solid or on its surface
+    pub fn contains(&self, point: [f32; 3]) -> bool {
+        self.brushes.iter().any(|brush| brush.contains(point))
+    }
+
+    /// Check if the solid doesn't have any volume
+    pub fn is_empty(&self) -> bool {
+        self.brushes.is_empty()
+    }
+
+    /// Get the space inside either solid
+    pub fn union(&self, other: &Solid) -> Solid {
+        let mut brushes = self.brushes.clone();
+        brushes.extend(other.subtract(self).brushes);
+
+        Solid { brushes }
+    }
+
+    /// Get the space inside this solid but not inside the other solid
+    pub fn subtract(&self, other: &Solid) -> Solid {
+        let mut brushes = self.brushes.clone();
+        for cut in &other.brushes {
+            brushes = brushes.iter()
+                .flat_map(|brush| brush.subtract(cut))
+                .collect();
+        }
+
+        Solid { brushes }
+    }
+
+    /// Get the space inside both solids
+    pub fn intersect(&self, other: &Solid) -> Solid {
+        let brushes = self.brushes.iter()
+            .flat_map(|a| other.brushes.iter().filter_map(|b| a.intersect(b)))
+            .collect();
+
+        Solid { brushes }
+    }
+
+    /// Get the surface of the solid, the faces between two brushes are
+    /// removed
+    ///
+    /// # Returns
+    ///
+    /// * The polygons of the faces, counter clockwise seen from outside of
+    ///   the solid
+    pub fn faces(&self) -> Vec<Vec<[f32; 3]>> {
+        self.surface().iter().map(|polygon| to_points(polygon)).collect()
+    }
+
+    /// Compile the solid into a sector, the solid is the open space of the
+    /// sector
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - The textures of the meshes and the size of the
+    ///   textures
+    ///
+    /// # Returns
+    ///
+    /// * [Sector] - The sector with the faces facing up in the floor mesh,
+    ///   the faces facing down in the ceiling mesh and the rest in the wall
+    ///   mesh, every face faces into the solid
+    pub fn compile(&self, options: &CsgOptions) -> Sector {
+        let mut floor = (Vec::new(), Vec::new());
+        let mut ceiling = (Vec::new(), Vec::new());
+        let mut walls = (Vec::new(), Vec::new());
+
+        for polygon in self.surface() {
+            // NOTE(patrik): The faces of the surface face out of the solid,
+            // reversed they face into the open space
+            let mut points = to_points(&polygon);
+            points.reverse();
+
+            let normal = polygon_normal(&points);
+            let (vertices, indices): &mut (Vec<Vertex>, Vec<u32>) =
+                if normal[1] >= FLOOR_NORMAL_Y {
+                    &mut floor
+                } else if normal[1] <= -FLOOR_NORMAL_Y {
+                    &mut ceiling
+                } else {
+                    &mut walls
+                };
+
+            let base = vertices.len() as u32;
+            for &point in &points {
+                let uv = planar_uv(point, normal, options.texture_size);
+                vertices.push(Vertex::new(point, uv, [1.0; 4]));
+            }
+
+            for i in 1..points.len() as u32 - 1 {
+                indices.extend_from_slice(&[base, base + i, base + i + 1]);
+            }
+        }
+
+        Sector::new(Mesh::new(floor.0, floor.1, options.floor_texture),
+                    Mesh::new(ceiling.0, ceiling.1, options.ceiling_texture),
+                    Mesh::new(walls.0, walls.1, options.wall_texture))
+    }
+
+    /// Get the faces of the brushes without the parts inside other brushes
+    fn surface(&self) -> Vec<Polygon> {
+        let mut surface = Vec::new();
+
+        for (index, brush) in self.brushes.iter().enumerate() {
+            for (plane, polygon) in brush.face_polygons() {
+                let face = &brush.planes[plane];
+
+                let mut fragments = vec![polygon];
+                for (other_index, other) in self.brushes.iter().enumerate() {
+                    if other_index == index {
+                        continue;
+                    }
+
+                    fragments = fragments.iter()
+                        .flat_map(|fragment| outside(fragment, face, other))
+                        .collect();
+                }
+
+                surface.extend(fragments);
+            }
+        }
+
+        surface
+    }
+}
+
+/// Split a polygon by a plane, points on the plane are part of both sides
+///
+/// # Returns
+///
+/// * The part in front of the plane and the part behind it, a side with
+///   less than three points is empty
+fn split(polygon: &[Point], plane: &Plane) -> (Polygon, Polygon) {
+    let mut front = Vec::new();
+    let mut back = Vec::new();
+
+    for (i, &a) in polygon.iter().enumerate() {
+        let b = polygon[(i + 1) % polygon.len()];
+        let side_a = plane.side(a);
+        let side_b = plane.side(b);
+
+        if side_a >= -CSG_EPSILON {
+            front.push(a);
+        }
+
+        if side_a <= CSG_EPSILON {
+            back.push(a);
+        }
+
+        if (side_a > CSG_EPSILON && side_b < -CSG_EPSILON) ||
+            (side_a < -CSG_EPSILON && side_b > CSG_EPSILON)
+        {
+            let t = side_a / (side_a - side_b);
+            let point = std::array::from_fn(|i| a[i] + (b[i] - a[i]) * t);
+            front.push(point);
+            back.push(point);
+        }
+    }
+
+    (front, back)
+}
+
+/// Get the parts of a face outside of a brush
+///
+/// # Arguments
+///
+/// * `polygon` - The polygon of the face
+/// * `face`    - The plane of the face
+/// * `brush`   - The brush to remove
+///
+/// # Returns
+///
+/// * The pieces of the polygon outside of the brush, a face lying on a face
+///   of the brush facing the other way counts as inside
+fn outside(polygon: &Polygon, face: &Plane, brush: &Brush) -> Vec<Polygon> {
+    let mut pieces = Vec::new();
+    let mut rest = polygon.clone();
+
+    for plane in &brush.planes {
+        if rest.iter().all(|&point| plane.side(point).abs() <= CSG_EPSILON) {
+            if dot(plane.normal, face.normal) > 0.0 {
+                pieces.push(rest);
+                return pieces;
+            }
+
+            continue;
+        }
+
+        let (front, back) = split(&rest, plane);
+        if is_polygon(&front) {
+            pieces.push(front);
+        }
+
+        if !is_polygon(&back) {
+            return pieces;
+        }
+
+        rest = back;
+    }
+
+    pieces
+}
+
+/// Check if a polygon has at least three points and an area
+fn is_polygon(polygon: &[Point]) -> bool {
+    if polygon.len() < 3 {
+        return false;
+    }
+
+    let mut normal = [0.0; 3];
+    for i in 1..polygon.len() - 1 {
+        let edge_a = sub64(polygon[i], polygon[0]);
+        let edge_b = sub64(polygon[i + 1], polygon[0]);
+        let n = cross64(edge_a, edge_b);
+        normal = std::array::from_fn(|i| normal[i] + n[i]);
+    }
+
+    dot64(normal, normal).sqrt() * 0.5 > CSG_EPSILON
+}
+
+/// Convert a polygon to single precision
+fn to_points(polygon: &[Point]) -> Vec<[f32; 3]> {
+    polygon.iter().map(|point| point.map(|v| v as f32)).collect()
+}
+
+/// Get the unit normal of a convex polygon
+fn polygon_normal(points: &[[f32; 3]]) -> [f32; 3] {
+    let mut normal = [0.0; 3];
+    for i in 1..points.len() - 1 {
+        let n = triangle_normal(points[0], points[i], points[i + 1]);
+        normal = add(normal, n);
+    }
+
+    normalize(normal)
+}
+
+/// Project a point to texture coordinates along the axis closest to the
+/// normal
+fn planar_uv(point: [f32; 3], normal: [f32; 3], size: f32) -> [f32; 2] {
+    let [x, y, z] = normal.map(f32::abs);
+    let uv = if y >= x && y >= z {
+        [point[0], point[2]]
+    } else if x >= z {
+        [point[2], point[1]]
+    } else {
+        [point[0], point[1]]
+    };
+
+    uv.map(|v| v / size)
+}
+
+/// Subtract `b` from `a` in double precision
+fn sub64(a: Point, b: Point) -> Point {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+/// The dot product of two vectors in double precision
+fn dot64(a: Point, b: Point) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+/// The cross product of two vectors in double precision
+fn cross64(a: Point, b: Point) -> Point {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// Normalize a vector in double precision
+fn normalize64(a: Point) -> Point {
+    let len = dot64(a, a).sqrt();
+    if len > 0.0 {
+        a.map(|v| v / len)
+    } else {
+        a
+    }
+}
@@ -0,0 +1,148 @@
+//! Decals projected onto the geometry of a sector
+//!
+//! Bullet holes, posters and grime placed in the editor are stored with the
+//! map, see [Map::decals]. A decal is a box centered on its position, the
+//! material is projected along the normal onto the surfaces of the target
+//! sector inside the box.
+
+use crate::*;
+use crate::buffer::{ Reader, write_f32s };
+use crate::math::cross;
+
+/// The size of a serialized decal (position, normal, up, size, material,
+/// sector)
+pub(crate) const DECAL_SIZE: usize =
+    12 * std::mem::size_of::<f32>() + std::mem::size_of::<u32>() + 8;
+
+/// A decal placed inside the map
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Decal {
+    /// The center of the decal
+    pub position: [f32; 3],
+
+    /// The direction pointing out of the surface, the material is
+    /// projected along the opposite direction
+    pub normal: [f32; 3],
+
+    /// The direction of the top edge of the material, perpendicular to the
+    /// normal
+    pub up: [f32; 3],
+
+    /// The width and height of the material followed by the depth of the
+    /// projection
+    pub size: [f32; 3],
+
+    /// The index of the material inside [Map::materials]
+    pub material: u32,
+
+    /// The sector the decal is projected onto
+    pub sector: SectorId,
+}
+
+impl Decal {
+    /// Creates a new decal
+    ///
+    /// # Arguments
+    ///
+    /// * `sector`   - The sector the decal is projected onto
+    /// * `material` - The index of the material inside [Map::materials]
+    /// * `position` - The center of the decal
+    /// * `normal`   - The direction pointing out of the surface
+    /// * `up`       - The direction of the top edge of the material
+    /// * `size`     - The width, height and projection depth
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new decal
+    pub fn new(sector: SectorId,
+               material: u32,
+               position: [f32; 3],
+               normal: [f32; 3],
+               up: [f32; 3],
+               size: [f32; 3])
+        -> Self
+    {
+        Self {
+            position,
+            normal,
+            up,
+            size,
+            material,
+            sector,
+        }
+    }
+
+    /// Get the direction of the right edge of the material, seen from the
+    /// front of the decal
+    pub fn right(&self) -> [f32; 3] {
+        cross(self.up, self.normal)
+    }
+
+    /// Get the number of bytes [Decal::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        DECAL_SIZE
+    }
+
+    /// Serialize the decal to a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - Successfully serialized the decal
+    /// * `Err(`[Error]`)` - Failed to serialize the decal
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        write_f32s(buffer, &self.position);
+        write_f32s(buffer, &self.normal);
+        write_f32s(buffer, &self.up);
+        write_f32s(buffer, &self.size);
+        buffer.extend_from_slice(&self.material.to_le_bytes());
+        buffer.extend_from_slice(&self.sector.0.to_le_bytes());
+
+        Ok(())
+    }
+
+    /// Deserialize the decal from a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the decal
+    /// * `Err(`[Error]`)` - Failed to deserialize the decal
+    pub fn deserialize(buffer: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallChunk);
+
+        Ok(Self {
+            position: reader.vec3()?,
+            normal: reader.vec3()?,
+            up: reader.vec3()?,
+            size: reader.vec3()?,
+            material: reader.u32()?,
+            sector: SectorId(reader.u64()?),
+        })
+    }
+}
+
+impl Map {
+    /// Get the decals projected onto a sector
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the sector
+    ///
+    /// # Returns
+    ///
+    /// * An iterator over the decals with the sector as target
+    pub fn sector_decals(&self, id: SectorId)
+        -> impl Iterator<Item = &Decal> + '_
+    {
+        self.decals.iter().filter(move |decal| decal.sector == id)
+    }
+}
@@ -0,0 +1,289 @@
+//! Structured differences between two maps
+
+use crate::*;
+use crate::map::MeshRole;
+
+use std::collections::{ HashMap, HashSet };
+
+/// The differences between two versions of a mesh
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MeshDiff {
+    /// The role of the mesh inside the sector
+    pub role: MeshRole,
+
+    /// The number of vertices added at the end of the vertex buffer
+    pub added_vertices: usize,
+
+    /// The number of vertices removed from the end of the vertex buffer
+    pub removed_vertices: usize,
+
+    /// The indices of the vertices that exist in both versions but changed
+    pub changed_vertices: Vec<usize>,
+
+    /// The index buffer changed
+    pub indices_changed: bool,
+
+    /// The texture ID changed
+    pub texture_changed: bool,
+
+    /// The lightmap texture coordinates changed
+    pub lightmap_uvs_changed: bool,
+
+    /// The material index changed
+    pub material_changed: bool,
+
+    /// The extra texture coordinate sets changed
+    pub uv_sets_changed: bool,
+
+    /// The custom attributes changed
+    pub custom_attributes_changed: bool,
+
+    /// The submeshes changed
+    pub submeshes_changed: bool,
+
+    /// The primitive topology changed
+    pub topology_changed: bool,
+
+    /// The vertex descriptor changed
+    pub vertex_descriptor_changed: bool,
+}
+
+impl MeshDiff {
+    /// Compare two versions of a mesh
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[MeshDiff]`)` - The differences between the meshes
+    /// * `None` - The meshes are equal
+    fn compare(role: MeshRole, old: &Mesh, new: &Mesh) -> Option<Self> {
+        let old_count = old.vertex_buffer.len();
+        let new_count = new.vertex_buffer.len();
+
+        let changed_vertices = old.vertex_buffer.iter()
+            .zip(&new.vertex_buffer)
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(index, _)| index)
+            .collect();
+
+        let diff = Self {
+            role,
+            added_vertices: new_count.saturating_sub(old_count),
+            removed_vertices: old_count.saturating_sub(new_count),
+            changed_vertices,
+            indices_changed: old.index_buffer != new.index_buffer,
+            texture_changed: old.texture_id != new.texture_id,
+            lightmap_uvs_changed: old.lightmap_uvs != new.lightmap_uvs,
+            material_changed: old.material != new.material,
+            uv_sets_changed: old.uv_sets != new.uv_sets,
+            custom_attributes_changed:
+                old.custom_attributes != new.custom_attributes,
+            submeshes_changed: old.submeshes != new.submeshes,
+            topology_changed: old.topology != new.topology,
+            vertex_descriptor_changed:
+                old.vertex_descriptor != new.vertex_descriptor,
+        };
+
+        let unchanged = diff.added_vertices == 0 &&
+            diff.removed_vertices == 0 &&
+            diff.changed_vertices.is_empty() &&
+            !diff.indices_changed &&
+            !diff.texture_changed &&
+            !diff.lightmap_uvs_changed &&
+            !diff.material_changed &&
+            !diff.uv_sets_changed &&
+            !diff.custom_attributes_changed &&
+            !diff.submeshes_changed &&
+            !diff.topology_changed &&
+            !diff.vertex_descriptor_changed;
+
+        if unchanged {
+            None
+        } else {
+            Some(diff)
+        }
+    }
+}
+
+/// The differences between two versions of a sector
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SectorDiff {
+    /// The ID of the sector
+    pub id: SectorId,
+
+    /// The meshes that changed
+    pub meshes: Vec<MeshDiff>,
+
+    /// The levels of detail changed
+    pub lods_changed: bool,
+
+    /// The tag changed
+    pub tag_changed: bool,
+
+    /// The gameplay flags changed
+    pub flags_changed: bool,
+
+    /// The wall segments changed
+    pub walls_changed: bool,
+
+    /// The floor or ceiling plane changed
+    pub planes_changed: bool,
+
+    /// The name changed
+    pub name_changed: bool,
+
+    /// The props changed
+    pub props_changed: bool,
+
+    /// The occluders changed
+    pub occluders_changed: bool,
+
+    /// The liquid changed
+    pub liquid_changed: bool,
+
+    /// The environment override changed
+    pub environment_changed: bool,
+
+    /// The acoustics changed
+    pub acoustics_changed: bool,
+
+    /// The bounding box of the full detail meshes changed
+    pub bounds_changed: bool,
+}
+
+impl SectorDiff {
+    /// Compare two versions of a sector
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[SectorDiff]`)` - The differences between the sectors
+    /// * `None` - The sectors are equal
+    fn compare(old: &Sector, new: &Sector) -> Option<Self> {
+        let meshes = MeshRole::ALL.iter()
+            .filter_map(|&role| {
+                MeshDiff::compare(role, old.mesh(role), new.mesh(role))
+            })
+            .collect::<Vec<_>>();
+
+        let lods_changed = old.lods.len() != new.lods.len() ||
+            old.lods.iter().zip(&new.lods).any(|(a, b)| {
+                a.switch_distance != b.switch_distance ||
+                    MeshDiff::compare(MeshRole::Floor,
+                                      &a.floor_mesh, &b.floor_mesh)
+                        .is_some() ||
+                    MeshDiff::compare(MeshRole::Ceiling,
+                                      &a.ceiling_mesh, &b.ceiling_mesh)
+                        .is_some() ||
+                    MeshDiff::compare(MeshRole::Wall,
+                                      &a.wall_mesh, &b.wall_mesh)
+                        .is_some()
+            });
+
+        let diff = Self {
+            id: new.id,
+            meshes,
+            lods_changed,
+            tag_changed: old.tag != new.tag,
+            flags_changed: old.flags != new.flags,
+            walls_changed: old.walls != new.walls,
+            planes_changed: old.floor_plane != new.floor_plane ||
+                old.ceiling_plane != new.ceiling_plane,
+            name_changed: old.name != new.name,
+            props_changed: old.props != new.props,
+            occluders_changed: old.occluders != new.occluders,
+            liquid_changed: old.liquid != new.liquid,
+            environment_changed: old.environment != new.environment,
+            acoustics_changed: old.acoustics != new.acoustics,
+            bounds_changed: old.bounds() != new.bounds(),
+        };
+
+        let unchanged = diff.meshes.is_empty() &&
+            !diff.lods_changed &&
+            !diff.tag_changed &&
+            !diff.flags_changed &&
+            !diff.walls_changed &&
+            !diff.planes_changed &&
+            !diff.name_changed &&
+            !diff.props_changed &&
+            !diff.occluders_changed &&
+            !diff.liquid_changed &&
+            !diff.environment_changed &&
+            !diff.acoustics_changed &&
+            !diff.bounds_changed;
+
+        if unchanged {
+            None
+        } else {
+            Some(diff)
+        }
+    }
+}
+
+/// The differences between two versions of a map, sectors are matched by
+/// their [SectorId] so removing a sector doesn't change the sectors after it
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct MapDiff {
+    /// The IDs of the sectors that only exist in the new map
+    pub added_sectors: Vec<SectorId>,
+
+    /// The IDs of the sectors that only exist in the old map
+    pub removed_sectors: Vec<SectorId>,
+
+    /// The sectors that exist in both maps but changed
+    pub changed_sectors: Vec<SectorDiff>,
+
+    /// The sector animations changed
+    pub animations_changed: bool,
+}
+
+impl MapDiff {
+    /// Check if the maps had no differences
+    pub fn is_empty(&self) -> bool {
+        self.added_sectors.is_empty() &&
+            self.removed_sectors.is_empty() &&
+            self.changed_sectors.is_empty() &&
+            !self.animations_changed
+    }
+}
+
+impl Map {
+    /// Compare the sectors of this map with a newer version of the map
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The newer version of the map
+    ///
+    /// # Returns
+    ///
+    /// * [MapDiff] - The sectors, meshes and vertices that were added,
+    ///   removed or changed
+    pub fn diff(&self, other: &Map) -> MapDiff {
+        let old_ids = self.sectors.iter()
+            .map(|sector| sector.id)
+            .collect::<HashSet<_>>();
+        let new_sectors = other.sectors.iter()
+            .map(|sector| (sector.id, sector))
+            .collect::<HashMap<_, _>>();
+
+        let mut diff = MapDiff {
+            added_sectors: other.sectors.iter()
+                .map(|sector| sector.id)
+                .filter(|id| !old_ids.contains(id))
+                .collect(),
+            animations_changed: self.animations != other.animations,
+            ..Default::default()
+        };
+
+        for old in &self.sectors {
+            match new_sectors.get(&old.id) {
+                Some(new) => {
+                    diff.changed_sectors.extend(SectorDiff::compare(old, new));
+                }
+
+                None => diff.removed_sectors.push(old.id),
+            }
+        }
+
+        diff
+    }
+}
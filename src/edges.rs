@@ -0,0 +1,70 @@
+//! Line meshes of the unique edges of the triangles, for wireframe overlays
+//! and debug rendering
+
+use crate::*;
+
+use std::collections::HashSet;
+
+impl Mesh {
+    /// Get the unique edges of the triangles of the mesh
+    ///
+    /// # Returns
+    ///
+    /// * The two vertex indices of every edge in the order the edges are
+    ///   first used, an edge shared by multiple triangles is returned once
+    ///   no matter the direction and edges between the same vertex are
+    ///   skipped
+    pub fn unique_edges(&self) -> Vec<[u32; 2]> {
+        let mut seen = HashSet::new();
+        let mut edges = Vec::new();
+
+        for [a, b, c] in self.triangle_indices() {
+            for (start, end) in [(a, b), (b, c), (c, a)] {
+                if start != end && seen.insert((start.min(end),
+                                                start.max(end))) {
+                    edges.push([start, end]);
+                }
+            }
+        }
+
+        edges
+    }
+
+    /// Create a line mesh of the unique edges of the triangles of the mesh,
+    /// see [Mesh::unique_edges]
+    ///
+    /// # Returns
+    ///
+    /// * [Mesh] - A [Topology::LineList] mesh with the vertices and texture
+    ///   of this mesh
+    pub fn edges(&self) -> Mesh {
+        let mut mesh = Mesh::new(self.vertex_buffer.clone(),
+                                 self.unique_edges().concat(),
+                                 self.texture_id);
+        mesh.topology = Topology::LineList;
+
+        mesh
+    }
+}
+
+impl Map {
+    /// Create a single line mesh of the unique edges of the floor, ceiling
+    /// and wall meshes of every sector, see [Mesh::edges]
+    ///
+    /// # Returns
+    ///
+    /// * [Mesh] - A [Topology::LineList] mesh with the edges of the whole
+    ///   map
+    pub fn edges(&self) -> Mesh {
+        let mut edges = Mesh::new(Vec::new(), Vec::new(), 0);
+        edges.topology = Topology::LineList;
+
+        for sector in &self.sectors {
+            for role in MeshRole::ALL {
+                edges.append(&sector.mesh(role).edges());
+            }
+        }
+
+        edges
+    }
+}
@@ -0,0 +1,541 @@
+//! Recording edits to a map so they can be undone, redone and replayed
+
+use crate::*;
+use crate::buffer::{ Reader, write_sized, write_usize, write_list };
+use crate::map::{ CURRENT_VERSION, is_readable_version };
+
+/// A single reversible change to a map
+///
+/// Sectors are stored serialized so the edits can be written to an edit log
+/// with [Edit::serialize] and replayed later with [Map::apply_edit].
+#[derive(Clone, PartialEq, Debug)]
+pub enum Edit {
+    /// A sector was added to the map
+    AddSector {
+        /// The index the sector was inserted at
+        index: usize,
+
+        /// The serialized sector, including its ID
+        sector: Vec<u8>,
+    },
+
+    /// A sector was removed from the map
+    RemoveSector {
+        /// The index the sector was removed from
+        index: usize,
+
+        /// The serialized sector, including its ID
+        sector: Vec<u8>,
+    },
+
+    /// A sector was changed
+    ModifySector {
+        /// The ID of the changed sector
+        id: SectorId,
+
+        /// The serialized sector before the change
+        before: Vec<u8>,
+
+        /// The serialized sector after the change
+        after: Vec<u8>,
+    },
+
+    /// A light was added to the map
+    AddLight {
+        /// The index the light was inserted at
+        index: usize,
+
+        /// The added light
+        light: Light,
+    },
+
+    /// A light was removed from the map
+    RemoveLight {
+        /// The index the light was removed from
+        index: usize,
+
+        /// The removed light
+        light: Light,
+    },
+}
+
+impl Edit {
+    /// Get the edit that reverts this edit
+    pub fn inverse(&self) -> Edit {
+        match self.clone() {
+            Edit::AddSector { index, sector } => {
+                Edit::RemoveSector { index, sector }
+            }
+
+            Edit::RemoveSector { index, sector } => {
+                Edit::AddSector { index, sector }
+            }
+
+            Edit::ModifySector { id, before, after } => {
+                Edit::ModifySector { id, before: after, after: before }
+            }
+
+            Edit::AddLight { index, light } => {
+                Edit::RemoveLight { index, light }
+            }
+
+            Edit::RemoveLight { index, light } => {
+                Edit::AddLight { index, light }
+            }
+        }
+    }
+
+    /// Serialize the edit to a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - Successfully serialized the edit
+    /// * `Err(`[Error]`)` - Failed to serialize the edit
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        let write_bytes = |buffer: &mut O, bytes: &[u8]| {
+            write_sized(buffer, |buffer| {
+                buffer.extend_from_slice(bytes);
+                Ok(())
+            })
+        };
+
+        match self {
+            Edit::AddSector { index, sector } => {
+                buffer.push(0);
+                write_usize(buffer, *index)?;
+                write_bytes(buffer, sector)?;
+            }
+
+            Edit::RemoveSector { index, sector } => {
+                buffer.push(1);
+                write_usize(buffer, *index)?;
+                write_bytes(buffer, sector)?;
+            }
+
+            Edit::ModifySector { id, before, after } => {
+                buffer.push(2);
+                buffer.extend_from_slice(&id.0.to_le_bytes());
+                write_bytes(buffer, before)?;
+                write_bytes(buffer, after)?;
+            }
+
+            Edit::AddLight { index, light } => {
+                buffer.push(3);
+                write_usize(buffer, *index)?;
+                write_sized(buffer, |buffer| light.serialize(buffer))?;
+            }
+
+            Edit::RemoveLight { index, light } => {
+                buffer.push(4);
+                write_usize(buffer, *index)?;
+                write_sized(buffer, |buffer| light.serialize(buffer))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deserialize the edit from a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the edit
+    /// * `Err(`[Error]`)` - Failed to deserialize the edit
+    pub fn deserialize(buffer: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallEdit);
+
+        let kind = reader.u8()?;
+        let edit = match kind {
+            0 => Edit::AddSector {
+                index: reader.usize()?,
+                sector: reader.sized()?.to_vec(),
+            },
+
+            1 => Edit::RemoveSector {
+                index: reader.usize()?,
+                sector: reader.sized()?.to_vec(),
+            },
+
+            2 => Edit::ModifySector {
+                id: SectorId(reader.u64()?),
+                before: reader.sized()?.to_vec(),
+                after: reader.sized()?.to_vec(),
+            },
+
+            3 => Edit::AddLight {
+                index: reader.usize()?,
+                light: Light::deserialize(reader.sized()?)?,
+            },
+
+            4 => Edit::RemoveLight {
+                index: reader.usize()?,
+                light: Light::deserialize(reader.sized()?)?,
+            },
+
+            _ => return Err(Error::UnknownEditKind(kind)),
+        };
+
+        Ok(edit)
+    }
+
+    /// Serialize a list of edits as an edit log, the log starts with the
+    /// format version since the edits hold serialized sectors
+    ///
+    /// # Arguments
+    ///
+    /// * `edits`  - The edits to serialize
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - Successfully serialized the edit log
+    /// * `Err(`[Error]`)` - Failed to serialize the edit log
+    pub fn serialize_log(edits: &[Edit], buffer: &mut Vec<u8>) -> Result<()> {
+        buffer.extend_from_slice(&CURRENT_VERSION.to_le_bytes());
+        write_list(buffer, edits, Edit::serialize)
+    }
+
+    /// Deserialize an edit log written by [Edit::serialize_log]
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<`[Edit]`>)` - Successfully deserialized the edits
+    /// * `Err(`[Error]`)` - Failed to deserialize the edit log,
+    ///   [Error::IncorrectVersion] if it was written by another major
+    ///   version
+    pub fn deserialize_log(buffer: &[u8]) -> Result<Vec<Edit>> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallEdit);
+
+        let version = reader.u32()?;
+        if !is_readable_version(version) {
+            return Err(Error::IncorrectVersion(version));
+        }
+
+        reader.list(Edit::deserialize)
+    }
+}
+
+impl Map {
+    /// Apply a recorded edit to the map
+    ///
+    /// # Arguments
+    ///
+    /// * `edit` - The edit to apply
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - Successfully applied the edit
+    /// * `Err(`[Error]`)` - The edit doesn't match the map, like adding a
+    ///   sector with an ID the map already has, or the sector data couldn't
+    ///   be deserialized
+    pub fn apply_edit(&mut self, edit: &Edit) -> Result<()> {
+        match edit {
+            Edit::AddSector { index, sector } => {
+                let sector = Sector::deserialize(sector)?;
+                if *index > self.sectors.len() ||
+                    self.sector_index(sector.id).is_some()
+                {
+                    return Err(Error::EditMismatch);
+                }
+
+                self.insert_sector(*index, sector);
+            }
+
+            Edit::RemoveSector { index, sector } => {
+                let id = Sector::deserialize(sector)?.id();
+                if self.sector_index(id) != Some(*index) {
+                    return Err(Error::EditMismatch);
+                }
+
+                self.remove_sector(id);
+            }
+
+            Edit::ModifySector { id, after, .. } => {
+                let mut sector = Sector::deserialize(after)?;
+                sector.id = *id;
+
+                let current = self.sector_mut(*id)
+                    .ok_or(Error::EditMismatch)?;
+                *current = sector;
+            }
+
+            Edit::AddLight { index, light } => {
+                if *index > self.lights.len() {
+                    return Err(Error::EditMismatch);
+                }
+
+                self.lights.insert(*index, *light);
+            }
+
+            Edit::RemoveLight { index, light } => {
+                if self.lights.get(*index) != Some(light) {
+                    return Err(Error::EditMismatch);
+                }
+
+                self.lights.remove(*index);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Apply a list of recorded edits in order, see [Map::apply_edit]
+    ///
+    /// # Arguments
+    ///
+    /// * `edits` - The edits to apply
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - Successfully applied all the edits
+    /// * `Err(`[Error]`)` - Failed to apply an edit, the edits before it
+    ///   stay applied
+    pub fn replay(&mut self, edits: &[Edit]) -> Result<()> {
+        for edit in edits {
+            self.apply_edit(edit)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Records the edits made to a map so they can be undone and redone
+pub struct MapEditor {
+    /// The map being edited
+    map: Map,
+
+    /// The applied edits, the last one is undone first
+    undo: Vec<Edit>,
+
+    /// The undone edits, the last one is redone first
+    redo: Vec<Edit>,
+}
+
+impl MapEditor {
+    /// Create a new editor for a map
+    ///
+    /// # Arguments
+    ///
+    /// * `map` - The map to edit
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new editor without any recorded edits
+    pub fn new(map: Map) -> Self {
+        Self {
+            map,
+            undo: Vec::new(),
+            redo: Vec::new(),
+        }
+    }
+
+    /// Get the map being edited
+    pub fn map(&self) -> &Map {
+        &self.map
+    }
+
+    /// Stop editing and take the map out of the editor
+    pub fn into_map(self) -> Map {
+        self.map
+    }
+
+    /// Get the applied edits in the order they were made, this is the edit
+    /// log that can be replayed with [Map::replay]
+    pub fn edits(&self) -> &[Edit] {
+        &self.undo
+    }
+
+    /// Apply an edit and record it, the redo history is cleared
+    fn record(&mut self, edit: Edit) -> Result<()> {
+        self.map.apply_edit(&edit)?;
+        self.undo.push(edit);
+        self.redo.clear();
+
+        Ok(())
+    }
+
+    /// Add a sector to the map and give it a new ID
+    ///
+    /// # Arguments
+    ///
+    /// * `sector` - The sector to add
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[SectorId]`)` - The ID of the added sector
+    /// * `Err(`[Error]`)` - Failed to serialize the sector
+    pub fn add_sector(&mut self, sector: Sector) -> Result<SectorId> {
+        let index = self.map.sectors.len();
+        let id = self.map.add_sector(sector);
+
+        // NOTE(patrik): The sector is added by the map to get a new ID and
+        // then recorded as already applied
+        let mut data = Vec::new();
+        self.map.sectors[index].serialize(&mut data)?;
+        self.undo.push(Edit::AddSector { index, sector: data });
+        self.redo.clear();
+
+        Ok(id)
+    }
+
+    /// Remove a sector from the map
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the sector to remove
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - The sector was removed
+    /// * `Ok(false)` - The map doesn't have a sector with the ID
+    /// * `Err(`[Error]`)` - Failed to serialize the sector
+    pub fn remove_sector(&mut self, id: SectorId) -> Result<bool> {
+        let Some(index) = self.map.sector_index(id) else {
+            return Ok(false);
+        };
+
+        let mut sector = Vec::new();
+        self.map.sectors[index].serialize(&mut sector)?;
+        self.record(Edit::RemoveSector { index, sector })?;
+
+        Ok(true)
+    }
+
+    /// Change a sector, the sector is recorded before and after `f`
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the sector to change
+    /// * `f`  - Changes the sector
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - The sector was changed
+    /// * `Ok(false)` - The map doesn't have a sector with the ID
+    /// * `Err(`[Error]`)` - Failed to serialize the sector, the changes of
+    ///   `f` are undone and nothing is recorded
+    pub fn modify_sector<F>(&mut self, id: SectorId, f: F) -> Result<bool>
+        where F: FnOnce(&mut Sector)
+    {
+        let Some(sector) = self.map.sector_mut(id) else {
+            return Ok(false);
+        };
+
+        let mut before = Vec::new();
+        sector.serialize(&mut before)?;
+
+        f(sector);
+
+        // NOTE(patrik): The ID is part of the map and can't be changed here
+        sector.id = id;
+
+        // NOTE(patrik): A sector that can't be serialized can't be recorded
+        // either, put the old one back so the map matches the edits
+        let mut after = Vec::new();
+        if let Err(error) = sector.serialize(&mut after) {
+            *sector = Sector::deserialize(&before)?;
+            return Err(error);
+        }
+
+        self.undo.push(Edit::ModifySector { id, before, after });
+        self.redo.clear();
+
+        Ok(true)
+    }
+
+    /// Add a light to the map
+    ///
+    /// # Arguments
+    ///
+    /// * `light` - The light to add
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The light was added
+    /// * `Err(`[Error]`)` - Failed to add the light
+    pub fn add_light(&mut self, light: Light) -> Result<()> {
+        let index = self.map.lights.len();
+        self.record(Edit::AddLight { index, light })
+    }
+
+    /// Remove a light from the map
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index of the light to remove
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(`[Light]`))` - The removed light
+    /// * `Ok(None)` - The map doesn't have a light with the index
+    /// * `Err(`[Error]`)` - Failed to remove the light
+    pub fn remove_light(&mut self, index: usize) -> Result<Option<Light>> {
+        let Some(&light) = self.map.lights.get(index) else {
+            return Ok(None);
+        };
+
+        self.record(Edit::RemoveLight { index, light })?;
+
+        Ok(Some(light))
+    }
+
+    /// Check if there is an edit to undo
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    /// Check if there is an edit to redo
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Revert the last applied edit
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - The edit was reverted
+    /// * `Ok(false)` - There was nothing to undo
+    /// * `Err(`[Error]`)` - Failed to revert the edit
+    pub fn undo(&mut self) -> Result<bool> {
+        let Some(edit) = self.undo.pop() else {
+            return Ok(false);
+        };
+
+        self.map.apply_edit(&edit.inverse())?;
+        self.redo.push(edit);
+
+        Ok(true)
+    }
+
+    /// Apply the last reverted edit again
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - The edit was applied
+    /// * `Ok(false)` - There was nothing to redo
+    /// * `Err(`[Error]`)` - Failed to apply the edit
+    pub fn redo(&mut self) -> Result<bool> {
+        let Some(edit) = self.redo.pop() else {
+            return Ok(false);
+        };
+
+        self.map.apply_edit(&edit)?;
+        self.undo.push(edit);
+
+        Ok(true)
+    }
+}
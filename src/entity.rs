@@ -0,0 +1,130 @@
+//! Entities placed inside the map, a class name with typed properties
+//!
+//! The meaning of the classes and properties is decided by the engine, the
+//! format only stores them.
+
+use crate::*;
+use crate::buffer::{ Reader, write_f32s, write_string, write_list,
+                     string_size, list_size };
+
+use std::collections::BTreeMap;
+
+/// An entity placed inside the map
+#[derive(Clone, PartialEq, Debug)]
+pub struct Entity {
+    /// The class of the entity, like `light_torch` or `monster_grunt`
+    pub class: String,
+
+    /// The position of the entity
+    pub position: [f32; 3],
+
+    /// The properties of the entity by name, sorted so the entity always
+    /// serializes the same way
+    pub properties: BTreeMap<String, PropertyValue>,
+}
+
+impl Entity {
+    /// Creates a new entity without any properties
+    ///
+    /// # Arguments
+    ///
+    /// * `class`    - The class of the entity
+    /// * `position` - The position of the entity
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new entity
+    pub fn new(class: &str, position: [f32; 3]) -> Self {
+        Self {
+            class: class.to_string(),
+            position,
+            properties: BTreeMap::new(),
+        }
+    }
+
+    /// Get a property of the entity
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the property
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[PropertyValue]`)` - The value of the property
+    /// * `None` - The entity doesn't have the property
+    pub fn property(&self, name: &str) -> Option<&PropertyValue> {
+        self.properties.get(name)
+    }
+
+    /// Set a property of the entity, replacing the old value
+    ///
+    /// # Arguments
+    ///
+    /// * `name`  - The name of the property
+    /// * `value` - The value, anything that converts to a [PropertyValue]
+    pub fn set_property<V>(&mut self, name: &str, value: V)
+        where V: Into<PropertyValue>
+    {
+        self.properties.insert(name.to_string(), value.into());
+    }
+
+    /// Get the number of bytes [Entity::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        let properties = self.properties.iter().collect::<Vec<_>>();
+
+        string_size(&self.class) + 3 * std::mem::size_of::<f32>() +
+            list_size(&properties, |(name, value)| {
+                string_size(name) + value.serialized_size()
+            })
+    }
+
+    /// Serialize the entity to a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - Successfully serialized the entity
+    /// * `Err(`[Error]`)` - Failed to serialize the entity
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        write_string(buffer, &self.class)?;
+        write_f32s(buffer, &self.position);
+
+        let properties = self.properties.iter().collect::<Vec<_>>();
+        write_list(buffer, &properties, |(name, value), buffer| {
+            write_string(buffer, name)?;
+            value.serialize(buffer)
+        })
+    }
+
+    /// Deserialize the entity from a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the entity
+    /// * `Err(`[Error]`)` - Failed to deserialize the entity
+    pub fn deserialize(buffer: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallChunk);
+
+        let class = reader.string()?;
+        let position = reader.vec3()?;
+        let properties = reader.list(|buffer| {
+            let mut reader = Reader::new(buffer, Error::BufferToSmallChunk);
+            Ok((reader.string()?, PropertyValue::read(&mut reader)?))
+        })?;
+
+        Ok(Self {
+            class,
+            position,
+            properties: properties.into_iter().collect(),
+        })
+    }
+}
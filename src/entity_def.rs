@@ -0,0 +1,322 @@
+//! Definitions of the entity classes an engine knows about, used to check
+//! the entities of a map before it ships
+//!
+//! Like the FGD files of Quake-style editors, every class lists the
+//! properties it takes and their types, see [Map::validate_entities].
+
+use crate::*;
+
+use std::collections::BTreeMap;
+
+/// A property an entity class takes
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PropertyDef {
+    /// The name of the property
+    pub name: String,
+
+    /// The type the value must have, an integer is accepted for
+    /// [PropertyType::Float]
+    pub property_type: PropertyType,
+
+    /// The entities of the class must have the property
+    pub required: bool,
+}
+
+impl PropertyDef {
+    /// Check if a value has the type of the property
+    pub fn accepts(&self, value: &PropertyValue) -> bool {
+        let found = value.property_type();
+
+        found == self.property_type ||
+            (self.property_type == PropertyType::Float &&
+             found == PropertyType::Int)
+    }
+}
+
+/// The definition of an entity class
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct EntityDef {
+    /// The class the definition is for
+    pub class: String,
+
+    /// The properties of the class
+    pub properties: Vec<PropertyDef>,
+}
+
+impl EntityDef {
+    /// Creates a new class definition without any properties
+    ///
+    /// # Arguments
+    ///
+    /// * `class` - The name of the class
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new definition
+    pub fn new(class: &str) -> Self {
+        Self {
+            class: class.to_string(),
+            properties: Vec::new(),
+        }
+    }
+
+    /// Add a property every entity of the class must have
+    ///
+    /// # Arguments
+    ///
+    /// * `name`          - The name of the property
+    /// * `property_type` - The type the value must have
+    pub fn required(mut self, name: &str, property_type: PropertyType)
+        -> Self
+    {
+        self.properties.push(PropertyDef {
+            name: name.to_string(),
+            property_type,
+            required: true,
+        });
+
+        self
+    }
+
+    /// Add a property the entities of the class can have
+    ///
+    /// # Arguments
+    ///
+    /// * `name`          - The name of the property
+    /// * `property_type` - The type the value must have when it's set
+    pub fn optional(mut self, name: &str, property_type: PropertyType)
+        -> Self
+    {
+        self.properties.push(PropertyDef {
+            name: name.to_string(),
+            property_type,
+            required: false,
+        });
+
+        self
+    }
+
+    /// Get a property of the class
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the property
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[PropertyDef]`)` - The property
+    /// * `None` - The class doesn't take the property
+    pub fn property(&self, name: &str) -> Option<&PropertyDef> {
+        self.properties.iter().find(|property| property.name == name)
+    }
+
+    /// Check an entity against the definition, the class of the entity
+    /// isn't checked
+    ///
+    /// # Arguments
+    ///
+    /// * `index`  - The index of the entity, used in the issues
+    /// * `entity` - The entity to check
+    ///
+    /// # Returns
+    ///
+    /// * Every problem with the entity, empty if the entity is fine
+    pub fn validate(&self, index: usize, entity: &Entity) -> Vec<EntityIssue> {
+        let mut issues = Vec::new();
+
+        for property in &self.properties {
+            if property.required && entity.property(&property.name).is_none()
+            {
+                issues.push(EntityIssue::MissingProperty {
+                    entity: index,
+                    name: property.name.clone(),
+                });
+            }
+        }
+
+        for (name, value) in &entity.properties {
+            match self.property(name) {
+                Some(property) if !property.accepts(value) => {
+                    issues.push(EntityIssue::WrongType {
+                        entity: index,
+                        name: name.clone(),
+                        expected: property.property_type,
+                        found: value.property_type(),
+                    });
+                }
+
+                Some(_) => {}
+
+                None => issues.push(EntityIssue::UnknownProperty {
+                    entity: index,
+                    name: name.clone(),
+                }),
+            }
+        }
+
+        issues
+    }
+}
+
+/// The entity classes an engine knows about
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+pub struct EntityDefs {
+    /// The definitions by class
+    defs: BTreeMap<String, EntityDef>,
+}
+
+impl EntityDefs {
+    /// Creates a new registry without any classes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a class, replacing an earlier definition of the same class
+    ///
+    /// # Arguments
+    ///
+    /// * `def` - The definition of the class
+    pub fn add(&mut self, def: EntityDef) {
+        self.defs.insert(def.class.clone(), def);
+    }
+
+    /// Get the definition of a class
+    ///
+    /// # Arguments
+    ///
+    /// * `class` - The name of the class
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[EntityDef]`)` - The definition
+    /// * `None` - The class isn't known
+    pub fn get(&self, class: &str) -> Option<&EntityDef> {
+        self.defs.get(class)
+    }
+
+    /// Iterate over the definitions sorted by class
+    pub fn iter(&self) -> impl Iterator<Item = &EntityDef> {
+        self.defs.values()
+    }
+}
+
+impl FromIterator<EntityDef> for EntityDefs {
+    fn from_iter<I>(iter: I) -> Self
+        where I: IntoIterator<Item = EntityDef>
+    {
+        let mut defs = Self::new();
+        for def in iter {
+            defs.add(def);
+        }
+
+        defs
+    }
+}
+
+/// A problem found by [Map::validate_entities]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum EntityIssue {
+    /// The class of the entity isn't known
+    UnknownClass {
+        /// The index of the entity
+        entity: usize,
+
+        /// The class of the entity
+        class: String,
+    },
+
+    /// The entity doesn't have a required property
+    MissingProperty {
+        /// The index of the entity
+        entity: usize,
+
+        /// The name of the property
+        name: String,
+    },
+
+    /// The value of a property has the wrong type
+    WrongType {
+        /// The index of the entity
+        entity: usize,
+
+        /// The name of the property
+        name: String,
+
+        /// The type of the definition
+        expected: PropertyType,
+
+        /// The type of the value
+        found: PropertyType,
+    },
+
+    /// The entity has a property its class doesn't take
+    UnknownProperty {
+        /// The index of the entity
+        entity: usize,
+
+        /// The name of the property
+        name: String,
+    },
+}
+
+impl EntityIssue {
+    /// Get the index of the entity with the problem
+    pub fn entity(&self) -> usize {
+        match *self {
+            EntityIssue::UnknownClass { entity, .. } |
+            EntityIssue::MissingProperty { entity, .. } |
+            EntityIssue::WrongType { entity, .. } |
+            EntityIssue::UnknownProperty { entity, .. } => entity,
+        }
+    }
+}
+
+impl std::fmt::Display for EntityIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EntityIssue::UnknownClass { entity, class } => {
+                write!(f, "entity {}: unknown class '{}'", entity, class)
+            }
+            EntityIssue::MissingProperty { entity, name } => {
+                write!(f, "entity {}: missing property '{}'", entity, name)
+            }
+            EntityIssue::WrongType { entity, name, expected, found } => {
+                write!(f, "entity {}: property '{}' is {:?} instead of {:?}",
+                       entity, name, found, expected)
+            }
+            EntityIssue::UnknownProperty { entity, name } => {
+                write!(f, "entity {}: unknown property '{}'", entity, name)
+            }
+        }
+    }
+}
+
+impl Map {
+    /// Check the entities of the map against the entity classes of an
+    /// engine
+    ///
+    /// # Arguments
+    ///
+    /// * `defs` - The known entity classes
+    ///
+    /// # Returns
+    ///
+    /// * Every problem found in the order of the entities, empty if every
+    ///   entity is fine
+    pub fn validate_entities(&self, defs: &EntityDefs) -> Vec<EntityIssue> {
+        let mut issues = Vec::new();
+
+        for (index, entity) in self.entities.iter().enumerate() {
+            match defs.get(&entity.class) {
+                Some(def) => issues.extend(def.validate(index, entity)),
+
+                None => issues.push(EntityIssue::UnknownClass {
+                    entity: index,
+                    class: entity.class.clone(),
+                }),
+            }
+        }
+
+        issues
+    }
+}
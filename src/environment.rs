@@ -0,0 +1,161 @@
+//! Atmosphere of the map and the sectors, fog and ambient light
+//!
+//! The map sets the defaults in [Map::environment] and a sector only stores
+//! the values it changes in [Sector::environment], so a cave inside an
+//! outdoor map only needs to override the fog.
+
+use crate::*;
+use crate::buffer::{ Reader, write_f32s };
+
+/// The size of a serialized environment (fog color, fog density and ambient
+/// color)
+pub(crate) const ENVIRONMENT_SIZE: usize = 7 * std::mem::size_of::<f32>();
+
+/// The size of a serialized override, a byte with the used values followed
+/// by every value
+pub(crate) const ENVIRONMENT_OVERRIDE_SIZE: usize = 1 + ENVIRONMENT_SIZE;
+
+/// The bit of the override flags telling the fog color is used
+const FOG_COLOR_BIT: u8 = 1 << 0;
+
+/// The bit of the override flags telling the fog density is used
+const FOG_DENSITY_BIT: u8 = 1 << 1;
+
+/// The bit of the override flags telling the ambient color is used
+const AMBIENT_COLOR_BIT: u8 = 1 << 2;
+
+/// The atmospheric parameters of an area
+#[derive(Copy, Clone, PartialEq, Default, Debug)]
+pub struct Environment {
+    /// The color (r, g, b) distant geometry fades to
+    pub fog_color: [f32; 3],
+
+    /// How quickly the geometry fades to the fog color, 0.0 for no fog
+    pub fog_density: f32,
+
+    /// The light (r, g, b) reaching every surface
+    pub ambient_color: [f32; 3],
+}
+
+impl Environment {
+    /// Serialize the environment to a buffer
+    pub(crate) fn serialize<O>(&self, buffer: &mut O)
+        where O: Output + ?Sized
+    {
+        write_f32s(buffer, &self.fog_color);
+        buffer.extend_from_slice(&self.fog_density.to_le_bytes());
+        write_f32s(buffer, &self.ambient_color);
+    }
+
+    /// Read an environment written by [Environment::serialize]
+    pub(crate) fn read(reader: &mut Reader) -> Result<Self> {
+        Ok(Self {
+            fog_color: reader.vec3()?,
+            fog_density: reader.f32()?,
+            ambient_color: reader.vec3()?,
+        })
+    }
+}
+
+/// The parameters a sector changes, `None` inherits the value from
+/// [Map::environment]
+#[derive(Copy, Clone, PartialEq, Default, Debug)]
+pub struct EnvironmentOverride {
+    /// The fog color of the sector
+    pub fog_color: Option<[f32; 3]>,
+
+    /// The fog density of the sector
+    pub fog_density: Option<f32>,
+
+    /// The ambient color of the sector
+    pub ambient_color: Option<[f32; 3]>,
+}
+
+impl EnvironmentOverride {
+    /// Check if the override doesn't change anything
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Get the environment with the values of the override replacing the
+    /// defaults
+    ///
+    /// # Arguments
+    ///
+    /// * `defaults` - The environment the unset values are taken from
+    ///
+    /// # Returns
+    ///
+    /// * [Environment] - The resolved environment
+    pub fn resolve(&self, defaults: &Environment) -> Environment {
+        Environment {
+            fog_color: self.fog_color.unwrap_or(defaults.fog_color),
+            fog_density: self.fog_density.unwrap_or(defaults.fog_density),
+            ambient_color: self.ambient_color
+                .unwrap_or(defaults.ambient_color),
+        }
+    }
+
+    /// Serialize the override to a buffer
+    pub(crate) fn serialize<O>(&self, buffer: &mut O)
+        where O: Output + ?Sized
+    {
+        let mut flags = 0;
+        if self.fog_color.is_some() {
+            flags |= FOG_COLOR_BIT;
+        }
+        if self.fog_density.is_some() {
+            flags |= FOG_DENSITY_BIT;
+        }
+        if self.ambient_color.is_some() {
+            flags |= AMBIENT_COLOR_BIT;
+        }
+
+        buffer.push(flags);
+        self.resolve(&Environment::default()).serialize(buffer);
+    }
+
+    /// Read an override written by [EnvironmentOverride::serialize]
+    pub(crate) fn read(reader: &mut Reader) -> Result<Self> {
+        let flags = reader.u8()?;
+        let values = Environment::read(reader)?;
+
+        Ok(Self {
+            fog_color: (flags & FOG_COLOR_BIT != 0)
+                .then_some(values.fog_color),
+            fog_density: (flags & FOG_DENSITY_BIT != 0)
+                .then_some(values.fog_density),
+            ambient_color: (flags & AMBIENT_COLOR_BIT != 0)
+                .then_some(values.ambient_color),
+        })
+    }
+}
+
+impl From<Environment> for EnvironmentOverride {
+    /// An override replacing every value
+    fn from(environment: Environment) -> Self {
+        Self {
+            fog_color: Some(environment.fog_color),
+            fog_density: Some(environment.fog_density),
+            ambient_color: Some(environment.ambient_color),
+        }
+    }
+}
+
+impl Map {
+    /// Get the environment of a sector, the values the sector doesn't
+    /// override are inherited from [Map::environment]
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index of the sector
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[Environment]`)` - The environment of the sector
+    /// * `None` - The sector doesn't exist
+    pub fn sector_environment(&self, index: usize) -> Option<Environment> {
+        let sector = self.sectors.get(index)?;
+        Some(sector.environment.resolve(&self.environment))
+    }
+}
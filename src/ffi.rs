@@ -0,0 +1,229 @@
+//! A C interface for loading maps, enabled by the `ffi` feature
+//!
+//! The declarations for C and C++ are in `include/mime.h`. A map is loaded
+//! with [mime_map_load] or [mime_map_load_bytes] and has to be released
+//! with [mime_map_free]. The pointers returned by the accessors point into
+//! the map and are only valid until the map is freed.
+
+use crate::*;
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::ffi::{ c_char, CStr };
+
+/// The floor mesh of a sector, see [MeshRole::Floor]
+pub const MIME_MESH_FLOOR: u32 = 0;
+
+/// The ceiling mesh of a sector, see [MeshRole::Ceiling]
+pub const MIME_MESH_CEILING: u32 = 1;
+
+/// The wall mesh of a sector, see [MeshRole::Wall]
+pub const MIME_MESH_WALL: u32 = 2;
+
+/// Get a mesh of a sector, `None` if the map is null or the sector or the
+/// role doesn't exist
+unsafe fn mesh<'a>(map: *const Map, sector: usize, role: u32)
+    -> Option<&'a Mesh>
+{
+    let role = match role {
+        MIME_MESH_FLOOR => MeshRole::Floor,
+        MIME_MESH_CEILING => MeshRole::Ceiling,
+        MIME_MESH_WALL => MeshRole::Wall,
+        _ => return None,
+    };
+
+    map.as_ref()?.sectors.get(sector).map(|sector| sector.mesh(role))
+}
+
+/// Load a map from a mime file containing a single map
+///
+/// # Arguments
+///
+/// * `filename` - Null terminated filename of the file to read
+///
+/// # Returns
+///
+/// * The map, free it with [mime_map_free]
+/// * Null if the file couldn't be read or doesn't contain exactly one map
+///
+/// # Safety
+///
+/// `filename` has to be a valid null terminated string
+#[no_mangle]
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub unsafe extern "C" fn mime_map_load(filename: *const c_char)
+    -> *mut Map
+{
+    if filename.is_null() {
+        return std::ptr::null_mut();
+    }
+
+    let Ok(filename) = CStr::from_ptr(filename).to_str() else {
+        return std::ptr::null_mut();
+    };
+
+    match Map::load_from_file(filename) {
+        Ok(map) => Box::into_raw(Box::new(map)),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Load a map from a mime file containing a single map that is already in
+/// memory
+///
+/// # Arguments
+///
+/// * `data` - The content of the file
+/// * `len` - The number of bytes in `data`
+///
+/// # Returns
+///
+/// * The map, free it with [mime_map_free]
+/// * Null if the data couldn't be deserialized
+///
+/// # Safety
+///
+/// `data` has to point to `len` readable bytes
+#[no_mangle]
+pub unsafe extern "C" fn mime_map_load_bytes(data: *const u8, len: usize)
+    -> *mut Map
+{
+    if data.is_null() {
+        return std::ptr::null_mut();
+    }
+
+    let buffer = std::slice::from_raw_parts(data, len);
+    match Map::load_from_bytes(buffer) {
+        Ok(map) => Box::into_raw(Box::new(map)),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Free a map loaded by [mime_map_load] or [mime_map_load_bytes], null is
+/// ignored
+///
+/// # Safety
+///
+/// `map` has to be returned by one of the load functions and not already
+/// freed
+#[no_mangle]
+pub unsafe extern "C" fn mime_map_free(map: *mut Map) {
+    if !map.is_null() {
+        drop(Box::from_raw(map));
+    }
+}
+
+/// Get the number of sectors in a map, 0 if the map is null
+///
+/// # Safety
+///
+/// `map` has to be null or a map that isn't freed
+#[no_mangle]
+pub unsafe extern "C" fn mime_map_sector_count(map: *const Map) -> usize {
+    map.as_ref().map_or(0, |map| map.sectors.len())
+}
+
+/// Get the number of vertices in a mesh of a sector, 0 if the mesh doesn't
+/// exist
+///
+/// # Arguments
+///
+/// * `map` - The map
+/// * `sector` - The index of the sector
+/// * `role` - The mesh of the sector, one of the `MIME_MESH_*` constants
+///
+/// # Safety
+///
+/// `map` has to be null or a map that isn't freed
+#[no_mangle]
+pub unsafe extern "C" fn mime_map_vertex_count(map: *const Map,
+                                               sector: usize,
+                                               role: u32)
+    -> usize
+{
+    mesh(map, sector, role).map_or(0, |mesh| mesh.vertex_buffer.len())
+}
+
+/// Get the vertices of a mesh of a sector, laid out like [Vertex] with
+/// [mime_map_vertex_count] entries, null if the mesh doesn't exist
+///
+/// # Arguments
+///
+/// * `map` - The map
+/// * `sector` - The index of the sector
+/// * `role` - The mesh of the sector, one of the `MIME_MESH_*` constants
+///
+/// # Safety
+///
+/// `map` has to be null or a map that isn't freed
+#[no_mangle]
+pub unsafe extern "C" fn mime_map_vertices(map: *const Map,
+                                           sector: usize,
+                                           role: u32)
+    -> *const Vertex
+{
+    mesh(map, sector, role)
+        .map_or(std::ptr::null(), |mesh| mesh.vertex_buffer.as_ptr())
+}
+
+/// Get the number of indices in a mesh of a sector, 0 if the mesh doesn't
+/// exist
+///
+/// # Arguments
+///
+/// * `map` - The map
+/// * `sector` - The index of the sector
+/// * `role` - The mesh of the sector, one of the `MIME_MESH_*` constants
+///
+/// # Safety
+///
+/// `map` has to be null or a map that isn't freed
+#[no_mangle]
+pub unsafe extern "C" fn mime_map_index_count(map: *const Map,
+                                              sector: usize,
+                                              role: u32)
+    -> usize
+{
+    mesh(map, sector, role).map_or(0, |mesh| mesh.index_buffer.len())
+}
+
+/// Get the indices of a mesh of a sector with [mime_map_index_count]
+/// entries, null if the mesh doesn't exist
+///
+/// # Arguments
+///
+/// * `map` - The map
+/// * `sector` - The index of the sector
+/// * `role` - The mesh of the sector, one of the `MIME_MESH_*` constants
+///
+/// # Safety
+///
+/// `map` has to be null or a map that isn't freed
+#[no_mangle]
+pub unsafe extern "C" fn mime_map_indices(map: *const Map,
+                                          sector: usize,
+                                          role: u32)
+    -> *const u32
+{
+    mesh(map, sector, role)
+        .map_or(std::ptr::null(), |mesh| mesh.index_buffer.as_ptr())
+}
+
+/// Get the texture ID of a mesh of a sector, 0 if the mesh doesn't exist
+///
+/// # Arguments
+///
+/// * `map` - The map
+/// * `sector` - The index of the sector
+/// * `role` - The mesh of the sector, one of the `MIME_MESH_*` constants
+///
+/// # Safety
+///
+/// `map` has to be null or a map that isn't freed
+#[no_mangle]
+pub unsafe extern "C" fn mime_map_texture_id(map: *const Map,
+                                             sector: usize,
+                                             role: u32)
+    -> u64
+{
+    mesh(map, sector, role).map_or(0, |mesh| mesh.texture_id)
+}
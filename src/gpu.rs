@@ -0,0 +1,284 @@
+//! Packing meshes into byte buffers ready to be uploaded to the GPU
+
+use crate::*;
+
+/// What a vertex attribute is used for
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum VertexSemantic {
+    /// The position of the vertex
+    Position,
+
+    /// The texture coordinates of the vertex
+    TexCoord,
+
+    /// The color of the vertex
+    Color,
+}
+
+impl VertexSemantic {
+    /// Get the byte the semantic is serialized as
+    pub fn to_u8(self) -> u8 {
+        match self {
+            VertexSemantic::Position => 0,
+            VertexSemantic::TexCoord => 1,
+            VertexSemantic::Color => 2,
+        }
+    }
+
+    /// Get the semantic from its serialized byte
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[Self]`)` - The semantic
+    /// * `None` - The byte isn't a known semantic
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(VertexSemantic::Position),
+            1 => Some(VertexSemantic::TexCoord),
+            2 => Some(VertexSemantic::Color),
+            _ => None,
+        }
+    }
+}
+
+/// The format of a single vertex attribute
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum VertexFormat {
+    /// A single 32-bit float
+    Float32,
+
+    /// Two 32-bit floats
+    Float32x2,
+
+    /// Three 32-bit floats
+    Float32x3,
+
+    /// Four 32-bit floats
+    Float32x4,
+
+    /// A single 32-bit unsigned integer
+    Uint32,
+
+    /// Four 8-bit unsigned integers mapped to the range 0 to 1
+    Unorm8x4,
+}
+
+impl VertexFormat {
+    /// Get the number of components of the format
+    pub fn components(self) -> usize {
+        match self {
+            VertexFormat::Float32 | VertexFormat::Uint32 => 1,
+            VertexFormat::Float32x2 => 2,
+            VertexFormat::Float32x3 => 3,
+            VertexFormat::Float32x4 | VertexFormat::Unorm8x4 => 4,
+        }
+    }
+
+    /// Get the size of the format in bytes
+    pub fn size(self) -> usize {
+        match self {
+            VertexFormat::Unorm8x4 => 4,
+            _ => self.components() * std::mem::size_of::<f32>(),
+        }
+    }
+
+    /// Get the byte the format is serialized as
+    pub fn to_u8(self) -> u8 {
+        match self {
+            VertexFormat::Float32 => 0,
+            VertexFormat::Float32x2 => 1,
+            VertexFormat::Float32x3 => 2,
+            VertexFormat::Float32x4 => 3,
+            VertexFormat::Uint32 => 4,
+            VertexFormat::Unorm8x4 => 5,
+        }
+    }
+
+    /// Get the format from its serialized byte
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[Self]`)` - The format
+    /// * `None` - The byte isn't a known format
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(VertexFormat::Float32),
+            1 => Some(VertexFormat::Float32x2),
+            2 => Some(VertexFormat::Float32x3),
+            3 => Some(VertexFormat::Float32x4),
+            4 => Some(VertexFormat::Uint32),
+            5 => Some(VertexFormat::Unorm8x4),
+            _ => None,
+        }
+    }
+}
+
+/// A single attribute inside a packed vertex
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct VertexAttribute {
+    /// What the attribute is used for
+    pub semantic: VertexSemantic,
+
+    /// The format of the attribute
+    pub format: VertexFormat,
+
+    /// The offset of the attribute inside the vertex in bytes
+    pub offset: usize,
+}
+
+/// Describes how the vertices packed by [Mesh::as_vertex_bytes] are laid
+/// out, renderers can build their vertex attribute descriptions from this
+/// instead of hardcoding the layout
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct VertexLayout {
+    /// The size of a single vertex in bytes
+    pub stride: usize,
+
+    /// The attributes of the vertex ordered by offset
+    pub attributes: Vec<VertexAttribute>,
+}
+
+impl VertexLayout {
+    /// Get the attribute with a semantic
+    ///
+    /// # Arguments
+    ///
+    /// * `semantic` - The semantic to search for
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[VertexAttribute]`)` - The attribute with the semantic
+    /// * `None` - The layout doesn't have an attribute with the semantic
+    pub fn attribute(&self, semantic: VertexSemantic)
+        -> Option<&VertexAttribute>
+    {
+        self.attributes.iter()
+            .find(|attribute| attribute.semantic == semantic)
+    }
+}
+
+/// The vertex attributes of a mesh as separate arrays, see
+/// [Mesh::planar_attributes]
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct PlanarAttributes {
+    /// The position of every vertex (x, y, z)
+    pub positions: Vec<[f32; 3]>,
+
+    /// The texture coordinates of every vertex (u, v)
+    pub uvs: Vec<[f32; 2]>,
+
+    /// The color of every vertex (r, g, b, a)
+    pub colors: Vec<[f32; 4]>,
+}
+
+impl Vertex {
+    /// The size of a single packed vertex in bytes
+    pub const STRIDE: usize = 9 * std::mem::size_of::<f32>();
+
+    /// The offset of the position (3 x f32) inside a packed vertex
+    pub const POSITION_OFFSET: usize = 0;
+
+    /// The offset of the texture coordinates (2 x f32) inside a packed
+    /// vertex
+    pub const UV_OFFSET: usize = 3 * std::mem::size_of::<f32>();
+
+    /// The offset of the color (4 x f32) inside a packed vertex
+    pub const COLOR_OFFSET: usize = 5 * std::mem::size_of::<f32>();
+}
+
+impl Mesh {
+    /// The size of a single packed index in bytes, the indices are packed
+    /// as u32
+    pub const INDEX_STRIDE: usize = std::mem::size_of::<u32>();
+
+    /// Get the layout of the vertices packed by [Mesh::as_vertex_bytes]
+    pub fn vertex_layout(&self) -> VertexLayout {
+        VertexLayout {
+            stride: Vertex::STRIDE,
+            attributes: vec![
+                VertexAttribute {
+                    semantic: VertexSemantic::Position,
+                    format: VertexFormat::Float32x3,
+                    offset: Vertex::POSITION_OFFSET,
+                },
+                VertexAttribute {
+                    semantic: VertexSemantic::TexCoord,
+                    format: VertexFormat::Float32x2,
+                    offset: Vertex::UV_OFFSET,
+                },
+                VertexAttribute {
+                    semantic: VertexSemantic::Color,
+                    format: VertexFormat::Float32x4,
+                    offset: Vertex::COLOR_OFFSET,
+                },
+            ],
+        }
+    }
+
+    /// Pack the vertices into an interleaved byte buffer
+    ///
+    /// Every vertex takes [Vertex::STRIDE] bytes, the position, texture
+    /// coordinates and color are found at [Vertex::POSITION_OFFSET],
+    /// [Vertex::UV_OFFSET] and [Vertex::COLOR_OFFSET]. The values use the
+    /// native byte order so the buffer can be uploaded as it is.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<u8>` - The packed vertices
+    pub fn as_vertex_bytes(&self) -> Vec<u8> {
+        let mut bytes =
+            Vec::with_capacity(self.vertex_buffer.len() * Vertex::STRIDE);
+
+        for vertex in &self.vertex_buffer {
+            let values = vertex.pos.iter()
+                .chain(&vertex.uv)
+                .chain(&vertex.color);
+            for value in values {
+                bytes.extend_from_slice(&value.to_ne_bytes());
+            }
+        }
+
+        bytes
+    }
+
+    /// Split the vertices into separate arrays for every attribute, for
+    /// renderers using de-interleaved vertex streams
+    ///
+    /// # Returns
+    ///
+    /// * [PlanarAttributes] - The attributes of the vertices in the same
+    ///   order as the vertex buffer
+    pub fn planar_attributes(&self) -> PlanarAttributes {
+        let count = self.vertex_buffer.len();
+        let mut attributes = PlanarAttributes {
+            positions: Vec::with_capacity(count),
+            uvs: Vec::with_capacity(count),
+            colors: Vec::with_capacity(count),
+        };
+
+        for vertex in &self.vertex_buffer {
+            attributes.positions.push(vertex.pos);
+            attributes.uvs.push(vertex.uv);
+            attributes.colors.push(vertex.color);
+        }
+
+        attributes
+    }
+
+    /// Pack the indices into a byte buffer, every index takes
+    /// [Mesh::INDEX_STRIDE] bytes in the native byte order
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<u8>` - The packed indices
+    pub fn as_index_bytes(&self) -> Vec<u8> {
+        let mut bytes =
+            Vec::with_capacity(self.index_buffer.len() * Self::INDEX_STRIDE);
+
+        for index in &self.index_buffer {
+            bytes.extend_from_slice(&index.to_ne_bytes());
+        }
+
+        bytes
+    }
+}
@@ -0,0 +1,193 @@
+//! Named groups of sectors used as layers
+
+use crate::*;
+use crate::buffer::{ Reader, write_string, write_usize, string_size };
+
+/// A named set of sectors, like "basement" or "secret areas", editors use
+/// groups as layers and engines can toggle whole groups at once
+///
+/// The sectors are referenced by their [SectorId] so the group stays valid
+/// when other sectors are added or removed, a sector can be part of
+/// multiple groups.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SectorGroup {
+    /// The name of the group
+    pub name: String,
+
+    /// The sectors inside the group
+    pub sectors: Vec<SectorId>,
+
+    /// The sectors of the group are shown when the map is loaded
+    pub visible: bool,
+}
+
+impl SectorGroup {
+    /// Creates a new empty and visible group
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the group
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new group
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            sectors: Vec::new(),
+            visible: true,
+        }
+    }
+
+    /// Check if a sector is part of the group
+    pub fn contains(&self, id: SectorId) -> bool {
+        self.sectors.contains(&id)
+    }
+
+    /// Get the number of bytes [SectorGroup::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        // The name, visible flag and the sector IDs
+        string_size(&self.name) + 1 + 8 + self.sectors.len() * 8
+    }
+
+    /// Serialize the group to a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - Successfully serialized the group
+    /// * `Err(`[Error]`)` - Failed to serialize the group
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        write_string(buffer, &self.name)?;
+        buffer.push(self.visible as u8);
+
+        write_usize(buffer, self.sectors.len())?;
+        for id in &self.sectors {
+            buffer.extend_from_slice(&id.0.to_le_bytes());
+        }
+
+        Ok(())
+    }
+
+    /// Deserialize the group from a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the group
+    /// * `Err(`[Error]`)` - Failed to deserialize the group
+    pub fn deserialize(buffer: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallChunk);
+
+        let name = reader.string()?;
+        let visible = reader.u8()? != 0;
+
+        let count = reader.usize()?;
+
+        let mut sectors = Vec::new();
+        for _i in 0..count {
+            sectors.push(SectorId(reader.u64()?));
+        }
+
+        Ok(Self {
+            name,
+            sectors,
+            visible,
+        })
+    }
+}
+
+impl Map {
+    /// Get a group
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the group
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[SectorGroup]`)` - The group with the name
+    /// * `None` - The map doesn't have a group with the name
+    pub fn group(&self, name: &str) -> Option<&SectorGroup> {
+        self.groups.iter().find(|group| group.name == name)
+    }
+
+    /// Get a group to modify it, the group is created when it doesn't exist
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the group
+    ///
+    /// # Returns
+    ///
+    /// * [SectorGroup] - The group with the name
+    pub fn group_mut(&mut self, name: &str) -> &mut SectorGroup {
+        let index = match self.groups.iter().position(|g| g.name == name) {
+            Some(index) => index,
+            None => {
+                self.groups.push(SectorGroup::new(name));
+                self.groups.len() - 1
+            }
+        };
+
+        &mut self.groups[index]
+    }
+
+    /// Get the sectors of a group
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the group
+    ///
+    /// # Returns
+    ///
+    /// * An iterator over the index and the sector of every sector inside
+    ///   the group, removed sectors are skipped and the iterator is empty
+    ///   when the map doesn't have the group
+    pub fn group_sectors(&self, name: &str)
+        -> impl Iterator<Item = (usize, &Sector)>
+    {
+        let ids = self.group(name)
+            .map(|group| group.sectors.as_slice())
+            .unwrap_or_default();
+
+        ids.iter()
+            .filter_map(|&id| {
+                let index = self.sector_index(id)?;
+                Some((index, &self.sectors[index]))
+            })
+    }
+
+    /// Get the groups a sector is part of
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the sector
+    ///
+    /// # Returns
+    ///
+    /// * An iterator over the groups containing the sector
+    pub fn sector_groups(&self, id: SectorId)
+        -> impl Iterator<Item = &SectorGroup>
+    {
+        self.groups.iter().filter(move |group| group.contains(id))
+    }
+
+    /// Check if a sector should be shown, sectors are hidden when they are
+    /// part of a hidden group
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the sector
+    pub fn is_sector_visible(&self, id: SectorId) -> bool {
+        self.sector_groups(id).all(|group| group.visible)
+    }
+}
@@ -0,0 +1,155 @@
+//! Terrain built from grayscale heightmaps
+//!
+//! Every sample of the heightmap becomes a vertex of a grid, the grid is
+//! split into square sectors with only a floor mesh so large outdoor areas
+//! can be streamed and culled like the rest of the map. The height is
+//! along the Y axis, matching the default [AxisConvention].
+
+use crate::*;
+
+/// Options for [Map::from_heightmap]
+#[derive(Clone, PartialEq, Debug)]
+pub struct HeightmapOptions {
+    /// The distance between two samples along the X and Z axes
+    pub cell_size: f32,
+
+    /// The height of a single step of the samples, a sample of 255 with a
+    /// scale of 0.1 is 25.5 units high
+    pub height_scale: f32,
+
+    /// The number of cells along each side of a sector
+    pub cells_per_sector: usize,
+
+    /// The vertex colors by height, stops of (height, color) where the
+    /// height goes from 0.0 at the lowest sample to 1.0 at the highest,
+    /// sorted by height, the colors between two stops are blended and an
+    /// empty ramp gives white
+    pub color_ramp: Vec<(f32, [f32; 4])>,
+
+    /// The texture ID of the floor meshes
+    pub texture_id: u64,
+}
+
+impl Default for HeightmapOptions {
+    fn default() -> Self {
+        Self {
+            cell_size: 1.0,
+            height_scale: 1.0 / 255.0,
+            cells_per_sector: 32,
+            color_ramp: Vec::new(),
+            texture_id: 0,
+        }
+    }
+}
+
+impl HeightmapOptions {
+    /// Get the color of the ramp at a height
+    ///
+    /// # Arguments
+    ///
+    /// * `height` - The height between 0.0 and 1.0
+    fn color(&self, height: f32) -> [f32; 4] {
+        let ramp = &self.color_ramp;
+
+        let next = ramp.partition_point(|&(stop, _)| stop < height);
+        match (next.checked_sub(1).map(|i| ramp[i]), ramp.get(next)) {
+            (Some((start, a)), Some(&(end, b))) if end > start => {
+                let t = (height - start) / (end - start);
+                std::array::from_fn(|i| a[i] + (b[i] - a[i]) * t)
+            }
+
+            (_, Some(&(_, color))) | (Some((_, color)), None) => color,
+
+            (None, None) => [1.0; 4],
+        }
+    }
+}
+
+impl Map {
+    /// Create a map with terrain from a grayscale heightmap
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - The samples of the heightmap row by row, any 8 or 16
+    ///   bit grayscale image
+    /// * `width`   - The number of samples of every row, along the X axis
+    /// * `height`  - The number of rows, along the Z axis
+    /// * `options` - The size of the cells, the height scale, the sector
+    ///   size and the colors
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Map]`)` - The map with one sector for every square of
+    ///   [HeightmapOptions::cells_per_sector] cells, a heightmap with less
+    ///   than two rows or columns doesn't have any cells
+    /// * `Err(`[Error]`)` - [Error::InvalidHeightmapSize] if the number of
+    ///   samples isn't `width * height`
+    pub fn from_heightmap<T>(samples: &[T],
+                             width: usize,
+                             height: usize,
+                             options: &HeightmapOptions)
+        -> Result<Map>
+        where T: Copy + Into<f32>
+    {
+        if width.checked_mul(height) != Some(samples.len()) {
+            return Err(Error::InvalidHeightmapSize(samples.len()));
+        }
+
+        let sample = |x: usize, z: usize| samples[z * width + x].into();
+
+        let (lowest, highest) = samples.iter()
+            .map(|&sample| sample.into())
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(low, high), s| {
+                (low.min(s), high.max(s))
+            });
+        let range = (highest - lowest).max(f32::EPSILON);
+
+        let cells_x = width.saturating_sub(1);
+        let cells_z = height.saturating_sub(1);
+        let step = options.cells_per_sector.max(1);
+
+        let mut sectors = Vec::new();
+        for start_z in (0..cells_z).step_by(step) {
+            for start_x in (0..cells_x).step_by(step) {
+                let end_x = (start_x + step).min(cells_x);
+                let end_z = (start_z + step).min(cells_z);
+
+                let mut vertices = Vec::new();
+                for z in start_z..=end_z {
+                    for x in start_x..=end_x {
+                        let value = sample(x, z);
+                        let position = [
+                            x as f32 * options.cell_size,
+                            value * options.height_scale,
+                            z as f32 * options.cell_size,
+                        ];
+                        let uv = [x as f32 / cells_x as f32,
+                                  z as f32 / cells_z as f32];
+                        let color = options.color((value - lowest) / range);
+
+                        vertices.push(Vertex::new(position, uv, color));
+                    }
+                }
+
+                // NOTE(patrik): (x, z), (x, z + 1), (x + 1, z) is counter
+                // clockwise seen from above so the floor faces up
+                let row = (end_x - start_x + 1) as u32;
+                let mut indices = Vec::new();
+                for z in 0..(end_z - start_z) as u32 {
+                    for x in 0..(end_x - start_x) as u32 {
+                        let a = z * row + x;
+                        let b = a + row;
+                        indices.extend_from_slice(&[a, b, a + 1,
+                                                    a + 1, b, b + 1]);
+                    }
+                }
+
+                let floor = Mesh::new(vertices, indices, options.texture_id);
+                let empty = || Mesh::new(Vec::new(), Vec::new(), 0);
+                sectors.push(Sector::new(floor, empty(), empty()));
+            }
+        }
+
+        Ok(Map::new(sectors))
+    }
+}
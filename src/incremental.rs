@@ -0,0 +1,300 @@
+//! Saving only the sectors that changed since the map was last saved
+//!
+//! [Map::save_to_file] leaves unused space after every sector and remembers
+//! where the sectors were written. [Map::save_incremental] then only
+//! rewrites the sectors marked with [Map::mark_sector_dirty] in place, as
+//! long as they still fit inside the space reserved for them.
+
+use crate::*;
+use crate::buffer::Reader;
+use crate::limits::Budget;
+use crate::map::{ CURRENT_VERSION, HEADER_MAGIC, HEADER_SIZE, MapLayout,
+                  read_magic_and_version };
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use crate::compression::sector_block;
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use crate::map::{ MAP_HEADER_SIZE, SECTOR_ENTRY_SIZE, SectorSlot };
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::path::Path;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::fs::{ File, OpenOptions };
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::io::{ Write, Seek, SeekFrom };
+
+/// The unused space left after every sector by [Map::save_to_file] in
+/// percent of the size of the sector
+const SECTOR_SLACK: usize = 25;
+
+/// The offset of the map size inside a file with a single map (header, map
+/// count)
+const MAP_SIZE_OFFSET: u64 = HEADER_SIZE as u64 + 8;
+
+/// The offset of the map inside a file with a single map (header, map
+/// count, map size)
+pub(crate) const MAP_OFFSET: u64 = MAP_SIZE_OFFSET + 8;
+
+/// How [Map::save_incremental] saved the map
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SaveKind {
+    /// Only the changed sectors and the chunks were rewritten
+    Incremental {
+        /// The number of sectors rewritten
+        sectors_written: usize,
+    },
+
+    /// The whole file was rewritten
+    Full,
+}
+
+impl Map {
+    /// Mark a sector as changed so the next [Map::save_incremental] writes
+    /// it to the file
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index of the sector that changed
+    pub fn mark_sector_dirty(&mut self, index: usize) {
+        self.dirty_sectors.insert(index);
+    }
+
+    /// Get the indices of the sectors changed since the map was last saved
+    /// or loaded, in ascending order
+    pub fn dirty_sectors(&self) -> impl Iterator<Item = usize> + '_ {
+        self.dirty_sectors.iter().copied()
+    }
+
+    /// Serialize the map as a mime file with a single map and write it to a
+    /// file, unused space is left after every sector so later saves can use
+    /// [Map::save_incremental]
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - Filename of the file we should create to write the
+    ///   serialized data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - Successfully serialized the map and wrote the data to
+    ///   the file
+    /// * `Err(`[Error]`)` - Failed to serialize the map or write the data
+    ///   to the file
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn save_to_file<P>(&mut self, filename: P) -> Result<()>
+        where P: AsRef<Path>
+    {
+        let (buffer, layout) = self.serialize_file()?;
+
+        let mut file = File::create(filename)
+            .map_err(Error::FileCreationFailed)?;
+        file.write_all(&buffer[..])
+            .map_err(Error::FileWriteFailed)?;
+
+        self.layout = Some(layout);
+        self.dirty_sectors.clear();
+
+        Ok(())
+    }
+
+    /// Serialize the map as a mime file with a single map, the same data
+    /// [Map::save_to_file] writes
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<u8>)` - The content of the file
+    /// * `Err(`[Error]`)` - Failed to serialize the map
+    pub fn save_to_bytes(&self) -> Result<Vec<u8>> {
+        self.serialize_file().map(|(buffer, _)| buffer)
+    }
+
+    /// Serialize the map as a mime file with a single map, the layout is
+    /// recorded by the caller once the file has been written
+    pub(crate) fn serialize_file(&self) -> Result<(Vec<u8>, MapLayout)> {
+        let mut buffer = Vec::with_capacity(MAP_OFFSET as usize +
+                                            self.max_serialized_size());
+        buffer.extend_from_slice(HEADER_MAGIC);
+        buffer.extend_from_slice(&CURRENT_VERSION.to_le_bytes());
+        buffer.extend_from_slice(&1u64.to_le_bytes());
+
+        // The map size is patched when the map has been written
+        buffer.extend_from_slice(&0u64.to_le_bytes());
+        let layout = self.serialize_with_slack(&mut buffer, SECTOR_SLACK)?;
+
+        let map_size = buffer.len() as u64 - MAP_OFFSET;
+        buffer[MAP_SIZE_OFFSET as usize..MAP_OFFSET as usize]
+            .copy_from_slice(&map_size.to_le_bytes());
+
+        Ok((buffer, layout))
+    }
+
+    /// Load a map from a mime file containing a single map
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - Filename of the file to read
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Map]`)` - Successfully read and deserialized the map
+    /// * `Err(`[Error]`)` - Failed to read the file, deserialize the map or
+    ///   the file doesn't contain exactly one map
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn load_from_file<P>(filename: P) -> Result<Self>
+        where P: AsRef<Path>
+    {
+        let buffer = std::fs::read(filename)
+            .map_err(Error::FileReadFailed)?;
+
+        Self::load_from_bytes(&buffer)
+    }
+
+    /// Load a map from a mime file containing a single map that is already
+    /// in memory, like a file fetched over HTTP
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The content of the file
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Map]`)` - Successfully deserialized the map
+    /// * `Err(`[Error]`)` - Failed to deserialize the map or the file
+    ///   doesn't contain exactly one map
+    pub fn load_from_bytes(buffer: &[u8]) -> Result<Self> {
+        Self::load_from_bytes_with_options(buffer,
+                                           &DeserializeOptions::default())
+    }
+
+    /// Load a map from a mime file containing a single map that is already
+    /// in memory, the counts inside the file are checked against the
+    /// limits before allocating, see [Map::deserialize_with_options]
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer`  - The content of the file
+    /// * `options` - The limits for the sectors, vertices, indices and the
+    ///   allocated memory
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Map]`)` - Successfully deserialized the map
+    /// * `Err(`[Error]`)` - Failed to deserialize the map, the file doesn't
+    ///   contain exactly one map or the map exceeds one of the limits
+    pub fn load_from_bytes_with_options(buffer: &[u8],
+                                        options: &DeserializeOptions)
+        -> Result<Self>
+    {
+        if buffer.len() < MAP_OFFSET as usize {
+            let size = SizeMismatch::new(MAP_OFFSET as usize, buffer.len());
+            return Err(Error::BufferToSmallHeader(size));
+        }
+
+        let mut reader = Reader::new(buffer, Error::BufferToSmallMap);
+        read_magic_and_version(&mut reader, HEADER_MAGIC)?;
+
+        if reader.u64()? != 1 {
+            return Err(Error::NotSingleMap);
+        }
+
+        let budget = Budget::new(*options);
+        let (mut map, layout) = reader.block(|map| {
+            Map::deserialize_with_layout(map, &budget)
+        })?;
+        map.layout = Some(layout);
+
+        Ok(map)
+    }
+
+    /// Write the sectors marked with [Map::mark_sector_dirty] and the
+    /// chunks to a file written by [Map::save_to_file] or read by
+    /// [Map::load_from_file]
+    ///
+    /// The whole file is rewritten instead when the map wasn't saved or
+    /// loaded before, the number of sectors or [Map::compression] changed
+    /// or a sector grew past the space reserved for it.
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - The file the map was last saved to or loaded from
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[SaveKind]`)` - Successfully saved the map
+    /// * `Err(`[Error]`)` - Failed to serialize the map or write the file
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn save_incremental<P>(&mut self, filename: P) -> Result<SaveKind>
+        where P: AsRef<Path>
+    {
+        let filename = filename.as_ref();
+
+        let mut layout = match &self.layout {
+            Some(layout) if layout.slots.len() == self.sectors.len() &&
+                layout.compression == self.compression =>
+            {
+                layout.clone()
+            }
+
+            _ => {
+                self.save_to_file(filename)?;
+                return Ok(SaveKind::Full);
+            }
+        };
+
+        let mut sectors = Vec::with_capacity(self.dirty_sectors.len());
+        for &index in &self.dirty_sectors {
+            // NOTE(patrik): Sectors removed after being marked are skipped
+            let Some(sector) = self.sectors.get(index) else {
+                continue;
+            };
+
+            let buffer = sector_block(sector, self.compression)?;
+
+            if buffer.len() as u64 > layout.slots[index].capacity {
+                self.save_to_file(filename)?;
+                return Ok(SaveKind::Full);
+            }
+
+            sectors.push((index, buffer));
+        }
+
+        let mut chunks = Vec::new();
+        self.serialize_chunks(&mut chunks)?;
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .open(filename)
+            .map_err(Error::FileWriteFailed)?;
+
+        let mut write_at = |offset: u64, data: &[u8]| {
+            file.seek(SeekFrom::Start(offset))
+                .and_then(|_| file.write_all(data))
+                .map_err(Error::FileWriteFailed)
+        };
+
+        for (index, buffer) in &sectors {
+            let slot = &mut layout.slots[*index];
+            *slot = SectorSlot::new(slot.offset, buffer, slot.capacity);
+
+            let entry = MAP_HEADER_SIZE + index * SECTOR_ENTRY_SIZE;
+            write_at(MAP_OFFSET + slot.offset, buffer)?;
+            write_at(MAP_OFFSET + entry as u64, &slot.to_bytes())?;
+        }
+
+        // NOTE(patrik): The chunks are always rewritten, they are small
+        // compared to the sectors
+        let map_size = layout.chunks_offset + chunks.len() as u64;
+        write_at(MAP_OFFSET + layout.chunks_offset, &chunks)?;
+        write_at(MAP_SIZE_OFFSET, &map_size.to_le_bytes())?;
+
+        file.set_len(MAP_OFFSET + map_size)
+            .map_err(Error::FileWriteFailed)?;
+
+        layout.size = map_size;
+        self.layout = Some(layout);
+        self.dirty_sectors.clear();
+
+        Ok(SaveKind::Incremental {
+            sectors_written: sectors.len(),
+        })
+    }
+}
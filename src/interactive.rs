@@ -0,0 +1,276 @@
+//! Switches, buttons and other objects the player uses to activate sectors
+//! and entities
+//!
+//! The level logic like "the switch opens the door with tag 5" is stored
+//! with the map in [Map::interactives] so it works the same in every engine.
+//! The targets reference the sectors by tag, see [Sector::tag], and the
+//! entities by their index inside [Map::entities].
+
+use crate::*;
+use crate::buffer::{ Reader, write_f32s, write_string, write_usize,
+                     string_size };
+
+/// The size of a serialized target (kind, tag or entity index)
+const TARGET_SIZE: usize = 1 + std::mem::size_of::<u32>();
+
+/// How an interactive object is activated
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub enum InteractiveKind {
+    /// Flips between on and off every time it's used
+    #[default]
+    Switch,
+
+    /// Activates the targets every time it's pressed
+    Button,
+
+    /// Activates the targets when used, like a door or a terminal
+    UseTarget,
+}
+
+impl InteractiveKind {
+    /// Get the byte the kind is serialized as
+    pub fn to_u8(self) -> u8 {
+        match self {
+            InteractiveKind::Switch => 0,
+            InteractiveKind::Button => 1,
+            InteractiveKind::UseTarget => 2,
+        }
+    }
+
+    /// Get the kind from its serialized byte
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The serialized byte
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - The kind
+    /// * `Err(`[Error]`)` - [Error::UnknownInteractiveKind] if the byte
+    ///   isn't a known kind
+    pub fn from_u8(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(InteractiveKind::Switch),
+            1 => Ok(InteractiveKind::Button),
+            2 => Ok(InteractiveKind::UseTarget),
+            value => Err(Error::UnknownInteractiveKind(value)),
+        }
+    }
+}
+
+/// What an interactive object activates
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum InteractionTarget {
+    /// Every sector with the tag
+    Tag(u32),
+
+    /// The entity with the index inside [Map::entities]
+    Entity(u32),
+}
+
+impl InteractionTarget {
+    /// Serialize the target to a buffer
+    fn serialize<O>(&self, buffer: &mut O)
+        where O: Output + ?Sized
+    {
+        let (kind, value): (u8, u32) = match *self {
+            InteractionTarget::Tag(tag) => (0, tag),
+            InteractionTarget::Entity(entity) => (1, entity),
+        };
+
+        buffer.push(kind);
+        buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Read a target written by [InteractionTarget::serialize]
+    fn read(reader: &mut Reader) -> Result<Self> {
+        let kind = reader.u8()?;
+        let value = reader.u32()?;
+
+        match kind {
+            0 => Ok(InteractionTarget::Tag(value)),
+            1 => Ok(InteractionTarget::Entity(value)),
+            kind => Err(Error::UnknownInteractionTarget(kind)),
+        }
+    }
+}
+
+/// An object inside the map the player can use
+#[derive(Clone, PartialEq, Debug)]
+pub struct Interactive {
+    /// How the object is activated
+    pub kind: InteractiveKind,
+
+    /// The position of the object
+    pub position: [f32; 3],
+
+    /// The distance the object can be used from
+    pub radius: f32,
+
+    /// What happens to the targets, like `open` or `lower`, the meaning is
+    /// decided by the engine
+    pub action: String,
+
+    /// The sectors and entities the object activates
+    pub targets: Vec<InteractionTarget>,
+}
+
+impl Interactive {
+    /// Creates a new interactive object without any targets
+    ///
+    /// # Arguments
+    ///
+    /// * `kind`     - How the object is activated
+    /// * `position` - The position of the object
+    /// * `action`   - What happens to the targets
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new object, usable from 1 unit away
+    pub fn new(kind: InteractiveKind, position: [f32; 3], action: &str)
+        -> Self
+    {
+        Self {
+            kind,
+            position,
+            radius: 1.0,
+            action: action.to_string(),
+            targets: Vec::new(),
+        }
+    }
+
+    /// Add a target and return the object, to chain while building it
+    pub fn with_target(mut self, target: InteractionTarget) -> Self {
+        self.targets.push(target);
+        self
+    }
+
+    /// Get the number of bytes [Interactive::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        // Kind, position and radius
+        1 + 4 * std::mem::size_of::<f32>() + string_size(&self.action) +
+            8 + self.targets.len() * TARGET_SIZE
+    }
+
+    /// Serialize the object to a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - Successfully serialized the object
+    /// * `Err(`[Error]`)` - Failed to serialize the object
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        buffer.push(self.kind.to_u8());
+        write_f32s(buffer, &self.position);
+        buffer.extend_from_slice(&self.radius.to_le_bytes());
+        write_string(buffer, &self.action)?;
+
+        write_usize(buffer, self.targets.len())?;
+        for target in &self.targets {
+            target.serialize(buffer);
+        }
+
+        Ok(())
+    }
+
+    /// Deserialize the object from a buffer, the entity targets are checked
+    /// when the map is loaded
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the object
+    /// * `Err(`[Error]`)` - Failed to deserialize the object
+    pub fn deserialize(buffer: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallChunk);
+
+        let kind = InteractiveKind::from_u8(reader.u8()?)?;
+        let position = reader.vec3()?;
+        let radius = reader.f32()?;
+        let action = reader.string()?;
+
+        let count = reader.usize()?;
+        let mut targets = Vec::new();
+        for _i in 0..count {
+            targets.push(InteractionTarget::read(&mut reader)?);
+        }
+
+        Ok(Self {
+            kind,
+            position,
+            radius,
+            action,
+            targets,
+        })
+    }
+}
+
+impl Map {
+    /// Get the sectors activated by an interactive object
+    ///
+    /// # Arguments
+    ///
+    /// * `interactive` - The object
+    ///
+    /// # Returns
+    ///
+    /// * The indices of the sectors with one of the target tags in
+    ///   ascending order
+    pub fn interactive_sectors(&self, interactive: &Interactive)
+        -> Vec<usize>
+    {
+        self.sectors.iter()
+            .enumerate()
+            .filter(|(_, sector)| {
+                sector.tag != 0 &&
+                    interactive.targets
+                        .contains(&InteractionTarget::Tag(sector.tag))
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Get the entities activated by an interactive object
+    ///
+    /// # Arguments
+    ///
+    /// * `interactive` - The object
+    ///
+    /// # Returns
+    ///
+    /// * An iterator over the entities in the order of the targets
+    pub fn interactive_entities<'a>(&'a self, interactive: &'a Interactive)
+        -> impl Iterator<Item = &'a Entity> + 'a
+    {
+        interactive.targets.iter().filter_map(|target| match target {
+            InteractionTarget::Entity(index) => {
+                self.entities.get(*index as usize)
+            }
+
+            InteractionTarget::Tag(_) => None,
+        })
+    }
+
+    /// Check that every entity targeted by the interactive objects exists
+    pub(crate) fn check_interactives(&self) -> Result<()> {
+        let targets = self.interactives.iter()
+            .flat_map(|interactive| &interactive.targets);
+        for target in targets {
+            if let InteractionTarget::Entity(index) = *target {
+                if index as usize >= self.entities.len() {
+                    return Err(Error::InvalidTargetEntity(index));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,440 @@
+//! Loading the sectors of a map on demand
+//!
+//! [LazyMap] only reads the header and the sector table of a mime file
+//! containing a single map. The sectors are deserialized the first time
+//! they are accessed and can be unloaded again when they are not needed.
+
+use crate::*;
+use crate::buffer::Reader;
+use crate::incremental::MAP_OFFSET;
+use crate::bounds::BOUNDS_SIZE;
+use crate::limits::Budget;
+use crate::compression::{ SECTOR_BLOCK_HEADER_SIZE, read_sector_block,
+                          read_block_bounds };
+use crate::map::{ HEADER_MAGIC, MAP_HEADER_SIZE, SECTOR_ENTRY_SIZE,
+                  THUMBNAIL_CHUNK, SectorSlot, clamp_next_sector_id,
+                  read_header_compression, read_magic_and_version };
+
+use std::borrow::Cow;
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::path::Path;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::fs::File;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::io::{ Read, Seek, SeekFrom };
+
+/// Where a [LazyMap] reads the sectors from
+enum Source {
+    /// The whole file is in memory
+    Buffer(Vec<u8>),
+
+    /// The sectors are read from the file when they are accessed
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    File {
+        /// The opened file
+        file: File,
+
+        /// The size of the file
+        len: usize,
+    },
+}
+
+impl Source {
+    /// Read a range of the file
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset of the first byte inside the file
+    /// * `size`   - The number of bytes to read
+    /// * `error`  - Creates the error returned when the range is past the
+    ///   end of the file
+    fn read(&mut self,
+            offset: usize,
+            size: usize,
+            error: fn(SizeMismatch) -> Error)
+        -> Result<Cow<'_, [u8]>>
+    {
+        let end = offset.saturating_add(size);
+
+        match self {
+            Source::Buffer(buffer) => {
+                buffer.get(offset..end)
+                    .map(Cow::Borrowed)
+                    .ok_or_else(|| error(SizeMismatch::new(end, buffer.len())))
+            }
+
+            #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+            Source::File { file, len } => {
+                // NOTE(patrik): Check the size before allocating, the sizes
+                // come from the file
+                if end > *len {
+                    return Err(error(SizeMismatch::new(end, *len)));
+                }
+
+                let mut data = vec![0; size];
+                file.seek(SeekFrom::Start(offset as u64))
+                    .and_then(|_| file.read_exact(&mut data))
+                    .map_err(Error::FileReadFailed)?;
+
+                Ok(Cow::Owned(data))
+            }
+        }
+    }
+}
+
+/// A map where the sectors are only deserialized when they are accessed
+pub struct LazyMap {
+    /// Where the sectors are read from
+    source: Source,
+
+    /// The location of every sector relative to the start of the map
+    slots: Vec<SectorSlot>,
+
+    /// The sectors deserialized so far
+    sectors: Vec<Option<Sector>>,
+
+    /// The size of the map
+    map_size: u64,
+
+    /// The offset of the chunk list relative to the start of the map
+    chunks_offset: u64,
+
+    /// The ID given to the next sector added to the map
+    next_sector_id: u64,
+
+    /// The compression recorded in the header
+    compression: SectorCompression,
+}
+
+impl LazyMap {
+    /// Open a mime file containing a single map, only the header and the
+    /// sector table are read
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - Filename of the file to open
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[LazyMap]`)` - Successfully read the sector table
+    /// * `Err(`[Error]`)` - Failed to read the file, the header is invalid
+    ///   or the file doesn't contain exactly one map
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn open<P>(filename: P) -> Result<Self>
+        where P: AsRef<Path>
+    {
+        let file = File::open(filename)
+            .map_err(Error::FileReadFailed)?;
+        let len = file.metadata()
+            .map_err(Error::FileReadFailed)?
+            .len();
+        let len = usize::try_from(len)
+            .map_err(Error::IntegerConvertionError)?;
+
+        Self::new(Source::File { file, len })
+    }
+
+    /// Create a lazy map from a mime file containing a single map that is
+    /// already in memory, only the header and the sector table are parsed
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The content of the file
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[LazyMap]`)` - Successfully parsed the sector table
+    /// * `Err(`[Error]`)` - The header is invalid or the file doesn't
+    ///   contain exactly one map
+    pub fn from_bytes(buffer: Vec<u8>) -> Result<Self> {
+        Self::new(Source::Buffer(buffer))
+    }
+
+    /// Read the header and the sector table from the source
+    fn new(mut source: Source) -> Result<Self> {
+        let header = source.read(0, FileHeader::SIZE,
+                                 Error::BufferToSmallHeader)?;
+        let header = FileHeader::parse(&header)?;
+
+        let table = source.read(FileHeader::SIZE, header.table_size(),
+                                Error::BufferToSmallMap)?;
+        let slots = parse_slots(&table);
+
+        Ok(Self {
+            source,
+            sectors: slots.iter().map(|_| None).collect(),
+            slots,
+            map_size: header.map_size,
+            chunks_offset: header.chunks_offset,
+            next_sector_id: header.next_sector_id,
+            compression: header.compression,
+        })
+    }
+
+    /// Get the number of sectors in the map
+    pub fn sector_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Check if a sector has been deserialized
+    pub fn is_loaded(&self, index: usize) -> bool {
+        matches!(self.sectors.get(index), Some(Some(_)))
+    }
+
+    /// Get a sector, the sector is deserialized the first time it is
+    /// accessed
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index of the sector
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Sector]`)` - The sector
+    /// * `Err(`[Error]`)` - The map doesn't have a sector with the index or
+    ///   the sector couldn't be read or deserialized
+    pub fn sector(&mut self, index: usize) -> Result<&Sector> {
+        let slot = *self.slots.get(index)
+            .ok_or(Error::SectorNotFound)?;
+
+        match &mut self.sectors[index] {
+            Some(sector) => Ok(sector),
+
+            cached @ None => {
+                let sector = Self::read_sector(&mut self.source, index,
+                                               slot)?;
+                Ok(cached.insert(sector))
+            }
+        }
+    }
+
+    /// Get the bounding box of a sector, only the bounds stored at the
+    /// start of the sector are read
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index of the sector
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Bounds]`)` - The bounding box of the sector
+    /// * `Err(`[Error]`)` - The map doesn't have a sector with the index or
+    ///   the bounds couldn't be read
+    pub fn sector_bounds(&mut self, index: usize) -> Result<Bounds> {
+        if let Some(Some(sector)) = self.sectors.get(index) {
+            return Ok(sector.bounds());
+        }
+
+        let slot = *self.slots.get(index)
+            .ok_or(Error::SectorNotFound)?;
+
+        let offset = to_file_offset(slot.offset);
+        let size = usize::try_from(slot.size).unwrap_or(usize::MAX);
+        let data = self.source.read(offset,
+                                    size.min(SECTOR_BLOCK_HEADER_SIZE +
+                                             BOUNDS_SIZE),
+                                    Error::BufferToSmallMap)?;
+
+        read_block_bounds(&data)
+            .map_err(|error| error.at_offset(offset))
+    }
+
+    /// Find the sectors that don't match the checksums of the sector table,
+    /// the sectors are read but not deserialized
+    ///
+    /// A patcher can download only the corrupt sectors again instead of the
+    /// whole map.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<usize>)` - The indices of the corrupt sectors
+    /// * `Err(`[Error]`)` - A sector couldn't be read
+    pub fn corrupt_sectors(&mut self) -> Result<Vec<usize>> {
+        let mut corrupt = Vec::new();
+        for (index, slot) in self.slots.iter().enumerate() {
+            let offset = to_file_offset(slot.offset);
+            let size = usize::try_from(slot.size).unwrap_or(usize::MAX);
+            let data = self.source.read(offset, size,
+                                        Error::BufferToSmallMap)?;
+
+            if slot.verify(&data).is_err() {
+                corrupt.push(index);
+            }
+        }
+
+        Ok(corrupt)
+    }
+
+    /// Drop a deserialized sector to free its memory, the sector is
+    /// deserialized again on the next access
+    pub fn unload(&mut self, index: usize) {
+        if let Some(sector) = self.sectors.get_mut(index) {
+            *sector = None;
+        }
+    }
+
+    /// Read the preview image of the map, only the chunk list is read and
+    /// none of the sectors
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(`[Thumbnail]`))` - The preview image
+    /// * `Ok(None)` - The map doesn't have a preview image
+    /// * `Err(`[Error]`)` - Failed to read or deserialize the chunk list
+    pub fn thumbnail(&mut self) -> Result<Option<Thumbnail>> {
+        let (offset, chunks) = self.read_chunks()?;
+
+        let thumbnail = Map::find_chunk(&chunks, THUMBNAIL_CHUNK)
+            .and_then(|chunk| chunk.map(Thumbnail::deserialize).transpose());
+
+        thumbnail.map_err(|error| error.at_offset(offset))
+    }
+
+    /// Deserialize all the sectors that are not loaded yet and the chunks
+    /// of the map
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Map]`)` - The whole map
+    /// * `Err(`[Error]`)` - Failed to read or deserialize the map
+    pub fn into_map(mut self) -> Result<Map> {
+        let mut sectors = Vec::with_capacity(self.slots.len());
+        for (index, slot) in self.slots.iter().enumerate() {
+            let sector = match self.sectors[index].take() {
+                Some(sector) => sector,
+                None => Self::read_sector(&mut self.source, index, *slot)?,
+            };
+            sectors.push(sector);
+        }
+
+        // NOTE(patrik): Map::new would give the sectors new IDs
+        let mut map = Map::new(Vec::new());
+        map.next_sector_id =
+            clamp_next_sector_id(self.next_sector_id, &sectors);
+        map.sectors = sectors;
+        map.compression = self.compression;
+
+        let (offset, chunks) = self.read_chunks()?;
+        map.deserialize_chunks(&chunks)
+            .map_err(|error| error.at_offset(offset))?;
+
+        Ok(map)
+    }
+
+    /// Read the chunk list of the map
+    ///
+    /// # Returns
+    ///
+    /// * `Ok((usize, Cow<[u8]>))` - The offset of the chunk list inside the
+    ///   file and its content
+    /// * `Err(`[Error]`)` - The chunk list is past the end of the file
+    fn read_chunks(&mut self) -> Result<(usize, Cow<'_, [u8]>)> {
+        let offset = to_file_offset(self.chunks_offset);
+        let size = self.map_size.saturating_sub(self.chunks_offset);
+        let size = usize::try_from(size).unwrap_or(usize::MAX);
+        let chunks = self.source.read(offset, size, Error::BufferToSmallMap)?;
+
+        Ok((offset, chunks))
+    }
+
+    /// Read and deserialize the sector stored in a slot, the errors get the
+    /// index of the sector and offsets relative to the start of the file
+    fn read_sector(source: &mut Source,
+                   index: usize,
+                   slot: SectorSlot)
+        -> Result<Sector>
+    {
+        let offset = to_file_offset(slot.offset);
+        let size = usize::try_from(slot.size).unwrap_or(usize::MAX);
+
+        let sector = match source.read(offset, size, Error::BufferToSmallMap) {
+            Ok(data) => {
+                slot.verify(&data)
+                    .and_then(|_| {
+                        read_sector_block(&data, &Budget::unlimited())
+                    })
+                    .map_err(|error| error.at_offset(offset))
+            }
+
+            Err(error) => {
+                let entry = FileHeader::SIZE + index * SECTOR_ENTRY_SIZE;
+                Err(error.at_offset(entry))
+            }
+        };
+
+        sector.map_err(|error| {
+            error.with_context(|context| context.sector = Some(index))
+        })
+    }
+}
+
+/// The header of a mime file containing a single map followed by the
+/// header of the map
+pub(crate) struct FileHeader {
+    /// The size of the map
+    pub(crate) map_size: u64,
+
+    /// The number of sectors in the map
+    pub(crate) sector_count: usize,
+
+    /// The offset of the chunk list relative to the start of the map
+    pub(crate) chunks_offset: u64,
+
+    /// The ID given to the next sector added to the map
+    pub(crate) next_sector_id: u64,
+
+    /// The compression of the sectors
+    pub(crate) compression: SectorCompression,
+}
+
+impl FileHeader {
+    /// The size of the headers, the sector table follows them
+    pub(crate) const SIZE: usize = MAP_OFFSET as usize + MAP_HEADER_SIZE;
+
+    /// Parse the headers
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[FileHeader]`)` - The parsed headers
+    /// * `Err(`[Error]`)` - The headers are invalid or the file doesn't
+    ///   contain exactly one map
+    pub(crate) fn parse(buffer: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallHeader);
+
+        read_magic_and_version(&mut reader, HEADER_MAGIC)?;
+
+        if reader.u64()? != 1 {
+            return Err(Error::NotSingleMap);
+        }
+
+        Ok(Self {
+            map_size: reader.u64()?,
+            sector_count: reader.usize()?,
+            chunks_offset: reader.u64()?,
+            next_sector_id: reader.u64()?,
+            compression: read_header_compression(&mut reader)?,
+        })
+    }
+
+    /// Get the size of the sector table, saturates so a corrupt count ends
+    /// up past the end of the file
+    pub(crate) fn table_size(&self) -> usize {
+        self.sector_count.saturating_mul(SECTOR_ENTRY_SIZE)
+    }
+}
+
+/// Parse the slots of a sector table
+pub(crate) fn parse_slots(table: &[u8]) -> Vec<SectorSlot> {
+    table.chunks_exact(SECTOR_ENTRY_SIZE)
+        .map(|entry| SectorSlot::from_bytes(entry.try_into().unwrap()))
+        .collect()
+}
+
+/// Convert an offset relative to the start of the map to an offset inside
+/// the file, offsets that don't fit are clamped past the end of the file
+pub(crate) fn to_file_offset(offset: u64) -> usize {
+    usize::try_from(offset)
+        .unwrap_or(usize::MAX)
+        .saturating_add(MAP_OFFSET as usize)
+}
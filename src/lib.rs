@@ -1,15 +1,154 @@
 //! Mime is a library for a simple Map format used primarily for my 3D engines
 #![warn(missing_docs)]
 
-pub use map::{ Mime, Map, Sector, Mesh, Vertex };
+pub use map::{ Mime, Map, Sector, SectorId, Lod, Mesh, MeshRole, Vertex };
+pub use lightmap::{ Lightmap, LightmapUvOptions };
+pub use light::{ Light, LightKind };
+pub use bake::BakeOptions;
+pub use sky::Sky;
+pub use sound::{ SoundEmitter, Acoustics, ReverbPreset };
+pub use trigger::{ Trigger, TriggerShape };
+pub use wall::Wall;
+pub use archive::{ MimeArchive, ArchiveIndex, IndexEntry, EntryKind };
+pub use asset::{ EmbeddedAsset, AssetDependency, AssetKind };
+pub use diff::{ MapDiff, SectorDiff, MeshDiff };
+pub use transform::{ Mat4, ToMat4 };
+pub use incremental::SaveKind;
+pub use buffer::{ Output, MAX_STRING_SIZE };
+pub use lazy::LazyMap;
+pub use optimize::NonFinitePolicy;
+pub use color::ColorSpace;
+pub use axes::{ AxisConvention, UpAxis, Handedness };
+pub use material::{ Material, BlendMode };
+pub use attribute::{ VertexDescriptor, UvSet, CustomAttribute };
+pub use submesh::Submesh;
+pub use topology::Topology;
+pub use property::{ PropertyValue, PropertyType };
+pub use entity::Entity;
+pub use entity_def::{ EntityDef, EntityDefs, PropertyDef, EntityIssue };
+pub use spawn::SpawnPoint;
+pub use waypoint::{ WaypointGraph, Waypoint, WaypointLink };
+pub use connectivity::SectorGraph;
+pub use heightmap::HeightmapOptions;
+pub use csg::{ Plane, Brush, Solid, CsgOptions };
+pub use snap::snap_to_grid;
+pub use prop::PropInstance;
+pub use occluder::Occluder;
+pub use decal::Decal;
+pub use liquid::{ Liquid, LiquidKind };
+pub use environment::{ Environment, EnvironmentOverride };
+pub use sector_flags::SectorFlags;
+pub use interactive::{ Interactive, InteractiveKind, InteractionTarget };
+pub use script::{ Scripts, ScriptHook, ScriptTarget };
+pub use recovery::{ DamageReport, SectorDamage, ChunkDamage };
+pub use unknown_chunk::UnknownChunk;
+pub use schema::{ schema, Schema, StructSchema, ChunkSchema, Field,
+                  FieldKind };
+pub use animation::{ SectorAnimation, SectorPlane, Keyframe };
+pub use group::SectorGroup;
+pub use pvs::{ Pvs, Portal };
+pub use thumbnail::{ Thumbnail, ThumbnailFormat };
+pub use minimap::{ Minimap, MinimapLine, MinimapOptions };
+pub use bounds::Bounds;
+pub use compression::SectorCompression;
+pub use limits::{ DeserializeOptions, DeserializeLimit };
+pub use editor::{ MapEditor, Edit };
+pub use gpu::{ VertexLayout, VertexAttribute, VertexSemantic, VertexFormat,
+               PlanarAttributes };
 
 pub mod map;
+pub mod lightmap;
+pub mod light;
+pub mod bake;
+pub mod sky;
+pub mod sound;
+pub mod trigger;
+pub mod wall;
+pub mod archive;
+pub mod asset;
+pub mod diff;
+pub mod transform;
+pub mod merge;
+pub mod incremental;
+pub mod editor;
+pub mod gpu;
+pub mod lazy;
+pub mod stream;
+pub mod optimize;
+pub mod winding;
+pub mod color;
+pub mod material;
+pub mod attribute;
+pub mod animation;
+pub mod slope;
+pub mod group;
+pub mod pvs;
+pub mod thumbnail;
+pub mod minimap;
+pub mod bounds;
+pub mod canonical;
+pub mod signature;
+pub mod compression;
+pub mod patch;
+pub mod limits;
+pub mod triangle;
+pub mod vertices;
+pub mod axes;
+pub mod units;
+pub mod append;
+pub mod submesh;
+pub mod topology;
+pub mod edges;
+pub mod schema;
+pub mod property;
+pub mod entity;
+pub mod entity_def;
+pub mod spawn;
+pub mod waypoint;
+pub mod connectivity;
+pub mod heightmap;
+pub mod csg;
+pub mod primitive;
+pub mod snap;
+pub mod prop;
+pub mod mesh_library;
+pub mod occluder;
+pub mod decal;
+pub mod liquid;
+pub mod environment;
+pub mod sector_flags;
+pub mod interactive;
+pub mod script;
+pub mod recovery;
+pub mod unknown_chunk;
+pub mod concat;
+#[cfg(feature = "wgpu")]
+pub mod wgpu_util;
+#[cfg(feature = "bevy")]
+pub mod bevy_util;
+#[cfg(all(feature = "async",
+          not(all(target_arch = "wasm32", target_os = "unknown"))))]
+pub mod async_fs;
+#[cfg(feature = "testgen")]
+pub mod testgen;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "python")]
+pub mod python;
+mod buffer;
+mod math;
 
 #[cfg(test)]
 mod tests;
 
 /// Error enum for all the errors for this library
+///
+/// New variants can be added when the format changes, so matches need a
+/// wildcard arm.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// Failed to convert a slice to an array
     SliceConvertionError(std::array::TryFromSliceError),
@@ -17,29 +156,587 @@ pub enum Error {
     /// Failed to convert a integer
     IntegerConvertionError(std::num::TryFromIntError),
 
+    /// Failed to convert bytes to a UTF-8 string
+    Utf8ConvertionError(std::str::Utf8Error),
+
+    /// A string is longer than [MAX_STRING_SIZE] bytes
+    StringTooLong(usize),
+
     /// Failed to create file
     FileCreationFailed(std::io::Error),
 
     /// Failed write to file
     FileWriteFailed(std::io::Error),
 
+    /// Failed to read from file
+    FileReadFailed(std::io::Error),
+
     /// Deserialization failed with incorrect magic
     IncorrectMagic,
 
-    /// Deserialization failed with incorrect version
-    IncorrectVersion,
+    /// Deserialization failed with incorrect version, the major version is
+    /// different from [map::MAJOR_VERSION], contains the version found in
+    /// the buffer
+    IncorrectVersion(u32),
+
+    /// Deserialization of the file header failed, the buffer is too small
+    /// to parse data from
+    BufferToSmallHeader(SizeMismatch),
 
     /// Deserialization of vertex failed, the buffer is too small to
     /// parse data from
-    BufferToSmallVertex,
+    BufferToSmallVertex(SizeMismatch),
+
+    /// Deserialization of mesh failed, the buffer is too small to parse
+    /// data from
+    BufferToSmallMesh(SizeMismatch),
 
     /// Deserialization of sector failed, the buffer is too small to
     /// parse data from
-    BufferToSmallSector,
+    BufferToSmallSector(SizeMismatch),
 
     /// Deserialization of map failed, the buffer is too small to
     /// parse data from
-    BufferToSmallMap,
+    BufferToSmallMap(SizeMismatch),
+
+    /// Deserialization of a chunk of the map (lightmap, lights, sky, ...)
+    /// failed, the buffer is too small to parse data from
+    BufferToSmallChunk(SizeMismatch),
+
+    /// Deserialization of mesh failed, the mesh has lightmap texture
+    /// coordinates but not one for every vertex
+    LightmapUvCountMismatch,
+
+    /// Deserialization of mesh failed, an extra texture coordinate set
+    /// doesn't have one coordinate for every vertex
+    UvSetCountMismatch,
+
+    /// Deserialization of mesh failed, the data of a custom attribute
+    /// doesn't have a value for every vertex
+    CustomAttributeSizeMismatch,
+
+    /// Deserialization of lightmap failed, the size of the texel data
+    /// doesn't match the width and height of the lightmap
+    LightmapSizeMismatch,
+
+    /// Deserialization of light failed, the kind of the light is unknown
+    UnknownLightKind(u8),
+
+    /// Deserialization of sky failed, the kind of the sky is unknown
+    UnknownSkyKind(u8),
+
+    /// Deserialization of trigger failed, the shape of the trigger is
+    /// unknown
+    UnknownTriggerShape(u8),
+
+    /// Deserialization of the color space failed, the color space is
+    /// unknown
+    UnknownColorSpace(u8),
+
+    /// Deserialization failed, the topology of a mesh is unknown
+    UnknownTopology(u8),
+
+    /// Deserialization of a property failed, the type of the value is
+    /// unknown
+    UnknownPropertyType(u8),
+
+    /// A waypoint link leads to a node that doesn't exist
+    InvalidWaypointLink(u32),
+
+    /// A prop places a mesh that isn't inside the mesh library
+    InvalidPropMesh(u32),
+
+    /// Deserialization of the mesh library failed, an entry references a
+    /// mesh that isn't before it
+    InvalidMeshAlias(u32),
+
+    /// Deserialization of the mesh library failed, the kind of an entry is
+    /// unknown
+    UnknownLibraryEntry(u8),
+
+    /// Deserialization failed, the kind of a liquid is unknown
+    UnknownLiquidKind(u8),
+
+    /// Deserialization failed, the reverb preset of a sector is unknown
+    UnknownReverbPreset(u8),
+
+    /// Deserialization failed, the kind of an interactive object is unknown
+    UnknownInteractiveKind(u8),
+
+    /// Deserialization failed, the kind of the target of an interactive
+    /// object is unknown
+    UnknownInteractionTarget(u8),
+
+    /// An interactive object targets an entity that doesn't exist
+    InvalidTargetEntity(u32),
+
+    /// Deserialization failed, the kind of the target of a script hook is
+    /// unknown
+    UnknownScriptTarget(u8),
+
+    /// A script hook is bound to a trigger or entity that doesn't exist
+    InvalidScriptTarget(ScriptTarget),
+
+    /// The map uses a compression codec whose feature isn't enabled
+    UnsupportedCompression(SectorCompression),
+
+    /// A sector doesn't match the checksum stored in the sector table, the
+    /// context has the index of the sector
+    SectorChecksumMismatch,
+
+    /// Deserialization of mesh failed, an index points past the end of the
+    /// vertex buffer, only checked when
+    /// [DeserializeOptions::check_indices] is set
+    IndexOutOfRange {
+        /// The mesh of the sector, `None` for meshes deserialized on their
+        /// own
+        mesh: Option<MeshRole>,
+
+        /// The value of the index
+        index: u32,
+
+        /// The number of vertices, every index has to be below it
+        max: usize,
+    },
+
+    /// Deserialization failed, the buffer has bytes left after the map,
+    /// contains the number of bytes left
+    TrailingBytes(usize),
+
+    /// The number of samples of a heightmap doesn't match its width and
+    /// height
+    InvalidHeightmapSize(usize),
+
+    /// Deserialization failed, the axis convention is unknown
+    UnknownAxisConvention(u8),
+
+    /// The units per meter aren't finite and positive
+    InvalidUnitScale(f32),
+
+    /// Deserialization of material failed, the blend mode is unknown
+    UnknownBlendMode(u8),
+
+    /// Deserialization of sector animation failed, the animated plane is
+    /// unknown
+    UnknownSectorPlane(u8),
+
+    /// Deserialization of thumbnail failed, the image format is unknown
+    UnknownThumbnailFormat(u8),
+
+    /// The ed25519 public key used to verify a map isn't a valid key
+    InvalidPublicKey,
+
+    /// Deserialization of sector failed, the compression of the sector is
+    /// unknown
+    UnknownSectorCompression(u8),
+
+    /// Decompression of sector failed, the compressed data is corrupt
+    DecompressionFailed,
+
+    /// Deserialization failed, a size or offset read from the buffer
+    /// doesn't fit inside a usize
+    OffsetOverflow,
+
+    /// Deserialization failed, the data exceeds one of the limits of the
+    /// [DeserializeOptions]
+    LimitExceeded(DeserializeLimit),
+
+    /// Deserialization of mesh failed, the vertex descriptor uses an
+    /// unknown vertex format
+    UnknownVertexFormat(u8),
+
+    /// The vertex descriptor of a mesh doesn't store the position, stores
+    /// an attribute twice or in a format the attribute can't use
+    InvalidVertexDescriptor,
+
+    /// Deserialization of archive failed, the buffer is too small to
+    /// parse data from
+    BufferToSmallArchive(SizeMismatch),
+
+    /// Deserialization of archive failed, the kind of an entry is unknown
+    UnknownEntryKind(u8),
+
+    /// The archive doesn't have an entry with the name
+    EntryNotFound,
+
+    /// Deserialization of asset dependency failed, the kind of the asset
+    /// is unknown
+    UnknownAssetKind(u8),
+
+    /// Loading a single map failed, the mime file doesn't contain exactly
+    /// one map
+    NotSingleMap,
+
+    /// Deserialization of edit failed, the buffer is too small to parse
+    /// data from
+    BufferToSmallEdit(SizeMismatch),
+
+    /// Deserialization of edit failed, the kind of the edit is unknown
+    UnknownEditKind(u8),
+
+    /// Applying an edit failed, the map doesn't match the state the edit
+    /// was recorded in
+    EditMismatch,
+
+    /// Deserialization of patch failed, the buffer is too small to parse
+    /// data from
+    BufferToSmallPatch(SizeMismatch),
+
+    /// Applying a patch failed, the map isn't the revision the patch was
+    /// created from or the patch didn't produce the expected revision
+    PatchMismatch,
+
+    /// The map doesn't have a sector with the index
+    SectorNotFound,
+
+    /// Streaming the sectors failed, a sector is stored before the end of
+    /// the sector preceding it in the sector table
+    UnorderedSectors,
+
+    /// Serialization failed, the output buffer is too small for the
+    /// serialized data
+    BufferToSmallOutput(SizeMismatch),
+
+    /// Validation of mesh failed, a vertex or texture coordinate has a NaN
+    /// or infinite value, the context tells which one
+    NonFiniteValue,
+
+    /// Merging maps failed, offsetting the tags of the appended map past the
+    /// tags of the map overflows a u32
+    TagOverflow,
+
+    /// Deserialization failed, the context tells where inside the buffer
+    /// the error happened
+    WithContext {
+        /// The error that happened
+        error: Box<Error>,
+
+        /// Where the error happened
+        context: ErrorContext,
+    },
+}
+
+impl Error {
+    /// Get the error without the context, see [Error::context]
+    pub fn kind(&self) -> &Error {
+        match self {
+            Error::WithContext { error, .. } => error,
+            error => error,
+        }
+    }
+
+    /// Get where inside the buffer the error happened
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[ErrorContext]`)` - Where the error happened
+    /// * `None` - The error doesn't have a location, like errors returned
+    ///   by serialization
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Error::WithContext { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// Update the context of the error, errors without a context get one
+    /// at offset 0
+    pub(crate) fn with_context<F>(self, f: F) -> Self
+        where F: FnOnce(&mut ErrorContext)
+    {
+        let (error, mut context) = match self {
+            Error::WithContext { error, context } => (error, context),
+            error => (Box::new(error), ErrorContext::default()),
+        };
+
+        f(&mut context);
+
+        Error::WithContext { error, context }
+    }
+
+    /// Move the offset of the error forward, used when the error happened
+    /// inside a block starting at `offset` of the outer buffer
+    pub(crate) fn at_offset(self, offset: usize) -> Self {
+        self.with_context(|context| context.offset += offset)
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::SliceConvertionError(error) => error.fmt(f),
+            Error::IntegerConvertionError(error) => error.fmt(f),
+            Error::Utf8ConvertionError(error) => error.fmt(f),
+            Error::StringTooLong(size) => {
+                write!(f, "string of {} bytes is longer than {} bytes", size,
+                       MAX_STRING_SIZE)
+            }
+            Error::FileCreationFailed(error) => {
+                write!(f, "failed to create file: {}", error)
+            }
+            Error::FileWriteFailed(error) => {
+                write!(f, "failed to write file: {}", error)
+            }
+            Error::FileReadFailed(error) => {
+                write!(f, "failed to read file: {}", error)
+            }
+            Error::IncorrectMagic => write!(f, "incorrect magic"),
+            Error::IncorrectVersion(version) => {
+                write!(f, "incorrect version {}", version)
+            }
+            Error::BufferToSmallHeader(size) => size.fmt("header", f),
+            Error::BufferToSmallVertex(size) => size.fmt("vertex", f),
+            Error::BufferToSmallMesh(size) => size.fmt("mesh", f),
+            Error::BufferToSmallSector(size) => size.fmt("sector", f),
+            Error::BufferToSmallMap(size) => size.fmt("map", f),
+            Error::BufferToSmallChunk(size) => size.fmt("chunk", f),
+            Error::BufferToSmallArchive(size) => size.fmt("archive", f),
+            Error::BufferToSmallEdit(size) => size.fmt("edit", f),
+            Error::BufferToSmallPatch(size) => size.fmt("patch", f),
+            Error::BufferToSmallOutput(size) => size.fmt("output", f),
+            Error::LightmapUvCountMismatch => {
+                write!(f, "lightmap uv count doesn't match the vertex count")
+            }
+            Error::UvSetCountMismatch => {
+                write!(f, "uv set count doesn't match the vertex count")
+            }
+            Error::CustomAttributeSizeMismatch => {
+                write!(f, "custom attribute doesn't have a value per vertex")
+            }
+            Error::LightmapSizeMismatch => {
+                write!(f, "lightmap texel count doesn't match its size")
+            }
+            Error::UnknownLightKind(kind) => {
+                write!(f, "unknown light kind {}", kind)
+            }
+            Error::UnknownSkyKind(kind) => {
+                write!(f, "unknown sky kind {}", kind)
+            }
+            Error::UnknownTriggerShape(shape) => {
+                write!(f, "unknown trigger shape {}", shape)
+            }
+            Error::UnknownColorSpace(value) => {
+                write!(f, "unknown color space {}", value)
+            }
+            Error::UnknownTopology(value) => {
+                write!(f, "unknown topology {}", value)
+            }
+            Error::UnknownPropertyType(value) => {
+                write!(f, "unknown property type {}", value)
+            }
+            Error::InvalidWaypointLink(node) => {
+                write!(f, "waypoint link to missing node {}", node)
+            }
+            Error::InvalidPropMesh(mesh) => {
+                write!(f, "prop places missing library mesh {}", mesh)
+            }
+            Error::InvalidMeshAlias(mesh) => {
+                write!(f, "library entry references missing mesh {}", mesh)
+            }
+            Error::UnknownLibraryEntry(kind) => {
+                write!(f, "unknown library entry kind {}", kind)
+            }
+            Error::UnknownLiquidKind(kind) => {
+                write!(f, "unknown liquid kind {}", kind)
+            }
+            Error::UnknownReverbPreset(preset) => {
+                write!(f, "unknown reverb preset {}", preset)
+            }
+            Error::UnknownInteractiveKind(kind) => {
+                write!(f, "unknown interactive object kind {}", kind)
+            }
+            Error::UnknownInteractionTarget(kind) => {
+                write!(f, "unknown interaction target kind {}", kind)
+            }
+            Error::InvalidTargetEntity(entity) => {
+                write!(f, "interactive object targets missing entity {}",
+                       entity)
+            }
+            Error::UnknownScriptTarget(kind) => {
+                write!(f, "unknown script target kind {}", kind)
+            }
+            Error::InvalidScriptTarget(target) => {
+                write!(f, "script hook bound to missing {:?}", target)
+            }
+            Error::UnsupportedCompression(compression) => {
+                write!(f, "unsupported sector compression {:?}", compression)
+            }
+            Error::SectorChecksumMismatch => {
+                write!(f, "sector checksum mismatch")
+            }
+            Error::IndexOutOfRange { mesh, index, max } => {
+                write!(f, "index {} is out of range for {} vertices", index,
+                       max)?;
+                if let Some(mesh) = mesh {
+                    write!(f, " of the {:?} mesh", mesh)?;
+                }
+
+                Ok(())
+            }
+            Error::TrailingBytes(size) => {
+                write!(f, "{} bytes left after the map", size)
+            }
+            Error::InvalidHeightmapSize(samples) => {
+                write!(f, "heightmap with {} samples doesn't match its size",
+                       samples)
+            }
+            Error::UnknownAxisConvention(value) => {
+                write!(f, "unknown axis convention {}", value)
+            }
+            Error::InvalidUnitScale(value) => {
+                write!(f, "invalid units per meter {}", value)
+            }
+            Error::UnknownBlendMode(mode) => {
+                write!(f, "unknown blend mode {}", mode)
+            }
+            Error::UnknownSectorPlane(plane) => {
+                write!(f, "unknown sector plane {}", plane)
+            }
+            Error::UnknownThumbnailFormat(format) => {
+                write!(f, "unknown thumbnail format {}", format)
+            }
+            Error::InvalidPublicKey => write!(f, "invalid public key"),
+            Error::UnknownSectorCompression(compression) => {
+                write!(f, "unknown sector compression {}", compression)
+            }
+            Error::DecompressionFailed => write!(f, "decompression failed"),
+            Error::OffsetOverflow => write!(f, "offset overflow"),
+            Error::LimitExceeded(limit) => {
+                write!(f, "deserialize limit exceeded: {:?}", limit)
+            }
+            Error::UnknownVertexFormat(format) => {
+                write!(f, "unknown vertex format {}", format)
+            }
+            Error::InvalidVertexDescriptor => {
+                write!(f, "invalid vertex descriptor")
+            }
+            Error::UnknownEntryKind(kind) => {
+                write!(f, "unknown archive entry kind {}", kind)
+            }
+            Error::EntryNotFound => write!(f, "archive entry not found"),
+            Error::UnknownAssetKind(kind) => {
+                write!(f, "unknown asset kind {}", kind)
+            }
+            Error::NotSingleMap => {
+                write!(f, "the file doesn't contain exactly one map")
+            }
+            Error::UnknownEditKind(kind) => {
+                write!(f, "unknown edit kind {}", kind)
+            }
+            Error::EditMismatch => {
+                write!(f, "the edit doesn't match the map")
+            }
+            Error::PatchMismatch => {
+                write!(f, "the patch doesn't match the map")
+            }
+            Error::SectorNotFound => write!(f, "sector not found"),
+            Error::UnorderedSectors => {
+                write!(f, "the sectors aren't stored in the table order")
+            }
+            Error::NonFiniteValue => {
+                write!(f, "the mesh has a NaN or infinite value")
+            }
+            Error::TagOverflow => write!(f, "the merged tags overflow"),
+            Error::WithContext { error, context } => {
+                write!(f, "{} at offset {}", error, context.offset)?;
+                if let Some(sector) = context.sector {
+                    write!(f, ", sector {}", sector)?;
+                }
+                if let Some(lod) = context.lod {
+                    write!(f, ", lod {}", lod)?;
+                }
+                if let Some(mesh) = context.mesh {
+                    write!(f, ", {:?} mesh", mesh)?;
+                }
+                if let Some(element) = context.element {
+                    write!(f, ", {:?}", element)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::SliceConvertionError(error) => Some(error),
+            Error::IntegerConvertionError(error) => Some(error),
+            Error::Utf8ConvertionError(error) => Some(error),
+            Error::FileCreationFailed(error) |
+            Error::FileWriteFailed(error) |
+            Error::FileReadFailed(error) => Some(error),
+            Error::WithContext { error, .. } => error.source(),
+            _ => None,
+        }
+    }
+}
+
+/// The sizes of a buffer that was too small
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct SizeMismatch {
+    /// The number of bytes the buffer needed
+    pub expected: usize,
+
+    /// The number of bytes the buffer had
+    pub actual: usize,
+}
+
+impl SizeMismatch {
+    /// Creates a new size mismatch
+    pub fn new(expected: usize, actual: usize) -> Self {
+        Self {
+            expected,
+            actual,
+        }
+    }
+
+    /// Format the buffer too small error of `what`
+    fn fmt(&self, what: &str, f: &mut std::fmt::Formatter<'_>)
+        -> std::fmt::Result
+    {
+        write!(f, "buffer too small for {}, expected {} bytes but got {}",
+               what, self.expected, self.actual)
+    }
+}
+
+/// Where inside the deserialized buffer an error happened
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+pub struct ErrorContext {
+    /// The byte offset from the start of the buffer passed to the
+    /// deserialize function
+    pub offset: usize,
+
+    /// The index of the sector
+    pub sector: Option<usize>,
+
+    /// The level of detail of the sector, `None` for the full detail
+    /// meshes
+    pub lod: Option<usize>,
+
+    /// The mesh of the sector
+    pub mesh: Option<MeshRole>,
+
+    /// The element inside the mesh
+    pub element: Option<ErrorElement>,
+}
+
+/// An element of a mesh an error happened at
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ErrorElement {
+    /// The vertex with the index
+    Vertex(usize),
+
+    /// The index with the position inside the index buffer
+    Index(usize),
+
+    /// The lightmap texture coordinate of the vertex with the index
+    LightmapUv(usize),
+
+    /// The texture coordinate inside an extra set, the index of the set
+    /// and the index of the vertex
+    UvSet(usize, usize),
 }
 
 /// A Result type for the library
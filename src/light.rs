@@ -0,0 +1,249 @@
+//! Lights placed inside the map
+
+use crate::*;
+use crate::buffer::Reader;
+
+/// The different kinds of lights
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum LightKind {
+    /// Light emitted in all directions from the position of the light
+    Point,
+
+    /// Light emitted in a cone from the position of the light
+    Spot {
+        /// The direction the cone is pointing
+        direction: [f32; 3],
+
+        /// The angle in radians from the direction where the light starts
+        /// to fade out
+        inner_angle: f32,
+
+        /// The angle in radians from the direction where the light is
+        /// completely faded out
+        outer_angle: f32,
+    },
+
+    /// Light coming from infinitely far away in a single direction, like
+    /// the sun
+    Directional {
+        /// The direction the light is traveling
+        direction: [f32; 3],
+    },
+}
+
+/// A light inside the map
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Light {
+    /// The kind of the light
+    pub kind: LightKind,
+
+    /// The position of the light, not used by directional lights
+    pub position: [f32; 3],
+
+    /// The color of the light (r, g, b)
+    pub color: [f32; 3],
+
+    /// The intensity the color is scaled by
+    pub intensity: f32,
+
+    /// The distance where the light has faded out completely, not used by
+    /// directional lights
+    pub radius: f32,
+}
+
+impl Light {
+    /// Creates a new point light
+    ///
+    /// # Arguments
+    ///
+    /// * `position`  - The position of the light
+    /// * `color`     - The color of the light (r, g, b)
+    /// * `intensity` - The intensity the color is scaled by
+    /// * `radius`    - The distance where the light has faded out
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new light
+    pub fn point(position: [f32; 3],
+                 color: [f32; 3],
+                 intensity: f32,
+                 radius: f32)
+        -> Self
+    {
+        Self {
+            kind: LightKind::Point,
+            position,
+            color,
+            intensity,
+            radius,
+        }
+    }
+
+    /// Creates a new spot light
+    ///
+    /// # Arguments
+    ///
+    /// * `position`    - The position of the light
+    /// * `direction`   - The direction the cone is pointing
+    /// * `inner_angle` - The angle in radians where the light starts to
+    ///   fade out
+    /// * `outer_angle` - The angle in radians where the light has faded out
+    /// * `color`       - The color of the light (r, g, b)
+    /// * `intensity`   - The intensity the color is scaled by
+    /// * `radius`      - The distance where the light has faded out
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new light
+    pub fn spot(position: [f32; 3],
+                direction: [f32; 3],
+                inner_angle: f32,
+                outer_angle: f32,
+                color: [f32; 3],
+                intensity: f32,
+                radius: f32)
+        -> Self
+    {
+        Self {
+            kind: LightKind::Spot {
+                direction,
+                inner_angle,
+                outer_angle,
+            },
+            position,
+            color,
+            intensity,
+            radius,
+        }
+    }
+
+    /// Creates a new directional light
+    ///
+    /// # Arguments
+    ///
+    /// * `direction` - The direction the light is traveling
+    /// * `color`     - The color of the light (r, g, b)
+    /// * `intensity` - The intensity the color is scaled by
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new light
+    pub fn directional(direction: [f32; 3], color: [f32; 3], intensity: f32)
+        -> Self
+    {
+        Self {
+            kind: LightKind::Directional {
+                direction,
+            },
+            position: [0.0; 3],
+            color,
+            intensity,
+            radius: 0.0,
+        }
+    }
+
+    /// Get the number of bytes [Light::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        // Kind, position, color, intensity and radius
+        let size = 1 + 8 * std::mem::size_of::<f32>();
+
+        size + match self.kind {
+            LightKind::Point => 0,
+            LightKind::Spot { .. } => 5 * std::mem::size_of::<f32>(),
+            LightKind::Directional { .. } => 3 * std::mem::size_of::<f32>(),
+        }
+    }
+
+    /// Serialize the light to a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - Successfully serialized the light
+    /// * `Err(`[Error]`)` - Failed to serialize the light
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        let kind: u8 = match self.kind {
+            LightKind::Point => 0,
+            LightKind::Spot { .. } => 1,
+            LightKind::Directional { .. } => 2,
+        };
+        buffer.push(kind);
+
+        let values = self.position.iter()
+            .chain(&self.color)
+            .chain([&self.intensity, &self.radius]);
+        for value in values {
+            buffer.extend_from_slice(&value.to_le_bytes());
+        }
+
+        match self.kind {
+            LightKind::Point => {}
+
+            LightKind::Spot { direction, inner_angle, outer_angle } => {
+                for value in direction.iter().chain([&inner_angle,
+                                                     &outer_angle])
+                {
+                    buffer.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+
+            LightKind::Directional { direction } => {
+                for value in &direction {
+                    buffer.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deserialize the light from a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the light
+    /// * `Err(`[Error]`)` - Failed to deserialize the light
+    pub fn deserialize(buffer: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallChunk);
+
+        let kind = reader.u8()?;
+
+        let position = [reader.f32()?, reader.f32()?, reader.f32()?];
+        let color = [reader.f32()?, reader.f32()?, reader.f32()?];
+        let intensity = reader.f32()?;
+        let radius = reader.f32()?;
+
+        let kind = match kind {
+            0 => LightKind::Point,
+
+            1 => LightKind::Spot {
+                direction: [reader.f32()?, reader.f32()?, reader.f32()?],
+                inner_angle: reader.f32()?,
+                outer_angle: reader.f32()?,
+            },
+
+            2 => LightKind::Directional {
+                direction: [reader.f32()?, reader.f32()?, reader.f32()?],
+            },
+
+            _ => return Err(Error::UnknownLightKind(kind)),
+        };
+
+        Ok(Self {
+            kind,
+            position,
+            color,
+            intensity,
+            radius,
+        })
+    }
+}
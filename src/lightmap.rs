@@ -0,0 +1,500 @@
+//! Lightmap support for maps
+
+use crate::*;
+use crate::buffer::Reader;
+
+use std::collections::HashMap;
+
+/// The size of a single lightmap texel (r, g, b, a)
+const TEXEL_SIZE: usize = 4;
+
+/// A baked lightmap atlas with RGBA8 texels, the meshes reference it with
+/// their lightmap texture coordinates
+#[derive(Clone, PartialEq, Debug)]
+pub struct Lightmap {
+    /// The width of the lightmap in texels
+    width: u32,
+
+    /// The height of the lightmap in texels
+    height: u32,
+
+    /// The texel data, row by row with 4 bytes (r, g, b, a) per texel
+    texels: Vec<u8>,
+}
+
+impl Lightmap {
+    /// Creates a new black lightmap
+    ///
+    /// # Arguments
+    ///
+    /// * `width`  - The width of the lightmap in texels
+    /// * `height` - The height of the lightmap in texels
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new lightmap
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the texel data overflows a `usize`
+    pub fn new(width: u32, height: u32) -> Self {
+        let size = texels_size(width, height)
+            .expect("lightmap size overflows");
+
+        Self {
+            width,
+            height,
+            texels: vec![0; size],
+        }
+    }
+
+    /// Creates a lightmap from existing texel data
+    ///
+    /// # Arguments
+    ///
+    /// * `width`  - The width of the lightmap in texels
+    /// * `height` - The height of the lightmap in texels
+    /// * `texels` - The texel data, 4 bytes (r, g, b, a) per texel
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - The new lightmap
+    /// * `Err(`[Error]`)` - The texel data doesn't match the size or the
+    ///   size overflows
+    pub fn from_texels(width: u32, height: u32, texels: Vec<u8>)
+        -> Result<Self>
+    {
+        if texels_size(width, height) != Some(texels.len()) {
+            return Err(Error::LightmapSizeMismatch);
+        }
+
+        Ok(Self {
+            width,
+            height,
+            texels,
+        })
+    }
+
+    /// Get the width of the lightmap in texels
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Get the height of the lightmap in texels
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Get the raw texel data, row by row with 4 bytes per texel
+    pub fn texels(&self) -> &[u8] {
+        &self.texels
+    }
+
+    /// Get a single texel (r, g, b, a)
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is outside the lightmap
+    pub fn texel(&self, x: u32, y: u32) -> [u8; 4] {
+        let start = self.texel_offset(x, y);
+        self.texels[start..start + TEXEL_SIZE].try_into().unwrap()
+    }
+
+    /// Set a single texel (r, g, b, a)
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is outside the lightmap
+    pub fn set_texel(&mut self, x: u32, y: u32, texel: [u8; 4]) {
+        let start = self.texel_offset(x, y);
+        self.texels[start..start + TEXEL_SIZE].copy_from_slice(&texel);
+    }
+
+    /// Get the offset of a texel inside the texel data
+    fn texel_offset(&self, x: u32, y: u32) -> usize {
+        assert!(x < self.width && y < self.height,
+                "texel ({}, {}) is outside the lightmap", x, y);
+
+        (y as usize * self.width as usize + x as usize) * TEXEL_SIZE
+    }
+
+    /// Get the number of bytes [Lightmap::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        2 * std::mem::size_of::<u32>() + self.texels.len()
+    }
+
+    /// Serialize the lightmap to a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - Successfully serialized the lightmap
+    /// * `Err(`[Error]`)` - Failed to serialize the lightmap
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        buffer.extend_from_slice(&self.width.to_le_bytes());
+        buffer.extend_from_slice(&self.height.to_le_bytes());
+        buffer.extend_from_slice(&self.texels);
+
+        Ok(())
+    }
+
+    /// Deserialize the lightmap from a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the lightmap
+    /// * `Err(`[Error]`)` - Failed to deserialize the lightmap
+    pub fn deserialize(buffer: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallChunk);
+
+        let width = reader.u32()?;
+        let height = reader.u32()?;
+
+        Self::from_texels(width, height, reader.remaining().to_vec())
+    }
+}
+
+/// Get the number of bytes of the texel data of a lightmap
+///
+/// # Arguments
+///
+/// * `width`  - The width of the lightmap in texels
+/// * `height` - The height of the lightmap in texels
+///
+/// # Returns
+///
+/// * `Some(usize)` - The number of bytes
+/// * `None` - The size overflows a `usize`
+fn texels_size(width: u32, height: u32) -> Option<usize> {
+    (width as usize).checked_mul(height as usize)?.checked_mul(TEXEL_SIZE)
+}
+
+/// Options for the lightmap texture coordinate generation
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct LightmapUvOptions {
+    /// How many lightmap texels one unit of world space covers
+    pub texels_per_unit: f32,
+
+    /// The number of empty texels around every chart, prevents light from
+    /// bleeding between charts when the lightmap is filtered
+    pub padding: u32,
+}
+
+impl Default for LightmapUvOptions {
+    fn default() -> Self {
+        Self {
+            texels_per_unit: 16.0,
+            padding: 2,
+        }
+    }
+}
+
+/// A group of connected triangles facing the same direction that gets
+/// unwrapped as one piece inside the lightmap
+struct Chart {
+    /// The size of the chart in texels, including the padding
+    size: [u32; 2],
+
+    /// The position of the chart inside the atlas in texels
+    position: [u32; 2],
+}
+
+/// Find the root of a triangle inside the union find structure
+fn find(parents: &mut [usize], mut index: usize) -> usize {
+    while parents[index] != index {
+        parents[index] = parents[parents[index]];
+        index = parents[index];
+    }
+
+    index
+}
+
+/// Get the projection key of a triangle, the key is the dominant axis of the
+/// face normal together with the direction the face is facing
+fn projection_key(a: &Vertex, b: &Vertex, c: &Vertex) -> usize {
+    let e0 = [b.x() - a.x(), b.y() - a.y(), b.z() - a.z()];
+    let e1 = [c.x() - a.x(), c.y() - a.y(), c.z() - a.z()];
+    let normal = [
+        e0[1] * e1[2] - e0[2] * e1[1],
+        e0[2] * e1[0] - e0[0] * e1[2],
+        e0[0] * e1[1] - e0[1] * e1[0],
+    ];
+
+    let mut axis = 0;
+    for i in 1..3 {
+        if normal[i].abs() > normal[axis].abs() {
+            axis = i;
+        }
+    }
+
+    axis * 2 + usize::from(normal[axis] >= 0.0)
+}
+
+/// Project a position onto the plane perpendicular to the axis of a
+/// projection key
+fn project(key: usize, pos: [f32; 3]) -> [f32; 2] {
+    match key / 2 {
+        0 => [pos[2], pos[1]],
+        1 => [pos[0], pos[2]],
+        _ => [pos[0], pos[1]],
+    }
+}
+
+/// Copy a vertex with its values from the other per-vertex buffers to the
+/// end of the mesh
+///
+/// # Returns
+///
+/// * The index of the copy
+fn duplicate_vertex(mesh: &mut Mesh, index: usize) -> u32 {
+    let vertex = mesh.vertex_buffer[index];
+    mesh.vertex_buffer.push(vertex);
+
+    for set in &mut mesh.uv_sets {
+        if let Some(&uv) = set.uvs.get(index) {
+            set.uvs.push(uv);
+        }
+    }
+
+    for attribute in &mut mesh.custom_attributes {
+        let size = attribute.format.size();
+        let range = index * size..(index + 1) * size;
+        if range.end <= attribute.data.len() {
+            attribute.data.extend_from_within(range);
+        }
+    }
+
+    (mesh.vertex_buffer.len() - 1) as u32
+}
+
+/// Split the mesh into charts, vertices shared between charts are
+/// duplicated so every vertex belongs to a single chart
+///
+/// The lightmap texture coordinates of the mesh are set to the texel
+/// position of the vertex relative to its chart, the returned list contains
+/// the chart of every vertex. Triangles referencing vertices outside the
+/// vertex buffer are skipped and keep their indices. Meshes that aren't a
+/// [Topology::TriangleList] are left untouched and the list is empty.
+fn unwrap_mesh(mesh: &mut Mesh,
+               options: &LightmapUvOptions,
+               charts: &mut Vec<Chart>)
+    -> Vec<usize>
+{
+    // NOTE(patrik): The triangles of a strip share their indices so the
+    // vertices of a seam can't be duplicated
+    if mesh.topology != Topology::TriangleList {
+        return Vec::new();
+    }
+
+    // NOTE(patrik): The triangles are stored as the offset of their first
+    // index
+    let vertex_count = mesh.vertex_buffer.len();
+    let triangles = mesh.index_buffer.chunks_exact(3)
+        .enumerate()
+        .filter(|(_, triangle)| {
+            triangle.iter().all(|&index| (index as usize) < vertex_count)
+        })
+        .map(|(t, _)| t * 3)
+        .collect::<Vec<_>>();
+
+    let keys = triangles.iter()
+        .map(|&start| {
+            let i = &mesh.index_buffer[start..start + 3];
+            projection_key(&mesh.vertex_buffer[i[0] as usize],
+                           &mesh.vertex_buffer[i[1] as usize],
+                           &mesh.vertex_buffer[i[2] as usize])
+        })
+        .collect::<Vec<_>>();
+
+    // Join triangles sharing a vertex and facing the same direction
+    let mut parents = (0..triangles.len()).collect::<Vec<_>>();
+    let mut first = HashMap::new();
+    for (t, &key) in keys.iter().enumerate() {
+        let start = triangles[t];
+        for &index in &mesh.index_buffer[start..start + 3] {
+            let other = *first.entry((index, key)).or_insert(t);
+            let a = find(&mut parents, t);
+            let b = find(&mut parents, other);
+            parents[a] = b;
+        }
+    }
+
+    // Assign the chart of the vertices and duplicate the shared ones
+    let mut chart_ids = HashMap::new();
+    let mut vertex_charts = vec![None; mesh.vertex_buffer.len()];
+    let mut remap = HashMap::new();
+    for (t, &key) in keys.iter().enumerate() {
+        let root = find(&mut parents, t);
+        let next_id = charts.len() + chart_ids.len();
+        let chart = *chart_ids.entry(root).or_insert(next_id);
+
+        for i in triangles[t]..triangles[t] + 3 {
+            let index = mesh.index_buffer[i];
+            let new_index = match vertex_charts[index as usize] {
+                None => {
+                    vertex_charts[index as usize] = Some((chart, key));
+                    index
+                }
+
+                Some((owner, _)) if owner == chart => index,
+
+                Some(_) => *remap.entry((index, chart)).or_insert_with(|| {
+                    vertex_charts.push(Some((chart, key)));
+                    duplicate_vertex(mesh, index as usize)
+                }),
+            };
+
+            mesh.index_buffer[i] = new_index;
+        }
+    }
+
+    // Compute the bounds of every chart in the projected space
+    let mut bounds = vec![([f32::MAX; 2], [f32::MIN; 2]); chart_ids.len()];
+    for (vertex, owner) in mesh.vertex_buffer.iter().zip(&vertex_charts) {
+        if let Some((chart, key)) = owner {
+            let p = project(*key, vertex.pos);
+            let (min, max) = &mut bounds[chart - charts.len()];
+            for i in 0..2 {
+                min[i] = min[i].min(p[i]);
+                max[i] = max[i].max(p[i]);
+            }
+        }
+    }
+
+    let padding = options.padding as f32;
+    mesh.lightmap_uvs = mesh.vertex_buffer.iter().zip(&vertex_charts)
+        .map(|(vertex, owner)| match owner {
+            Some((chart, key)) => {
+                let p = project(*key, vertex.pos);
+                let (min, _) = bounds[chart - charts.len()];
+                [(p[0] - min[0]) * options.texels_per_unit + padding,
+                 (p[1] - min[1]) * options.texels_per_unit + padding]
+            }
+
+            None => [0.0, 0.0],
+        })
+        .collect();
+
+    for (min, max) in bounds {
+        let size = [
+            ((max[0] - min[0]) * options.texels_per_unit).ceil().max(1.0),
+            ((max[1] - min[1]) * options.texels_per_unit).ceil().max(1.0),
+        ];
+
+        charts.push(Chart {
+            size: [size[0] as u32 + options.padding * 2,
+                   size[1] as u32 + options.padding * 2],
+            position: [0, 0],
+        });
+    }
+
+    vertex_charts.into_iter()
+        .map(|owner| owner.map(|(chart, _)| chart).unwrap_or(usize::MAX))
+        .collect()
+}
+
+/// Pack the charts into a square atlas using rows of charts
+///
+/// # Returns
+///
+/// * `true` - All the charts fit inside the atlas
+/// * `false` - The atlas is too small
+fn pack_charts(charts: &mut [Chart], order: &[usize], atlas_size: u32)
+    -> bool
+{
+    let mut x = 0;
+    let mut y = 0;
+    let mut row_height = 0;
+
+    for &index in order {
+        let chart = &mut charts[index];
+        if chart.size[0] > atlas_size {
+            return false;
+        }
+
+        if x + chart.size[0] > atlas_size {
+            x = 0;
+            y += row_height;
+            row_height = 0;
+        }
+
+        chart.position = [x, y];
+        x += chart.size[0];
+        row_height = row_height.max(chart.size[1]);
+    }
+
+    y + row_height <= atlas_size
+}
+
+impl Map {
+    /// Generate a second, non-overlapping set of texture coordinates for the
+    /// floor, ceiling and wall meshes of all sectors, suitable for
+    /// lightmapping
+    ///
+    /// All meshes share a single square atlas. Vertices shared between
+    /// triangles facing different directions are duplicated so every chart
+    /// gets its own texture coordinates, the levels of detail and the meshes
+    /// that aren't a [Topology::TriangleList] are left untouched.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - Controls the texel density and the padding
+    ///
+    /// # Returns
+    ///
+    /// * [u32] - The width and height of the atlas in texels
+    pub fn generate_lightmap_uvs(&mut self, options: &LightmapUvOptions)
+        -> u32
+    {
+        let mut charts = Vec::new();
+
+        let vertex_charts = self.sectors.iter_mut()
+            .flat_map(|sector| [&mut sector.floor_mesh,
+                                &mut sector.ceiling_mesh,
+                                &mut sector.wall_mesh])
+            .map(|mesh| unwrap_mesh(mesh, options, &mut charts))
+            .collect::<Vec<_>>();
+
+        // Place the tallest charts first to keep the rows tight
+        let mut order = (0..charts.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&index| std::cmp::Reverse(charts[index].size[1]));
+
+        let area = charts.iter()
+            .map(|chart| chart.size[0] as u64 * chart.size[1] as u64)
+            .sum::<u64>();
+        let mut atlas_size = ((area as f64).sqrt().ceil() as u32)
+            .max(1)
+            .next_power_of_two();
+        while !pack_charts(&mut charts, &order, atlas_size) {
+            atlas_size *= 2;
+        }
+
+        let meshes = self.sectors.iter_mut()
+            .flat_map(|sector| [&mut sector.floor_mesh,
+                                &mut sector.ceiling_mesh,
+                                &mut sector.wall_mesh]);
+        for (mesh, vertex_charts) in meshes.zip(vertex_charts) {
+            for (uv, chart) in mesh.lightmap_uvs.iter_mut()
+                .zip(vertex_charts)
+            {
+                let offset = charts.get(chart)
+                    .map(|chart| chart.position)
+                    .unwrap_or([0, 0]);
+
+                uv[0] = (uv[0] + offset[0] as f32) / atlas_size as f32;
+                uv[1] = (uv[1] + offset[1] as f32) / atlas_size as f32;
+            }
+        }
+
+        atlas_size
+    }
+}
@@ -0,0 +1,162 @@
+//! Resource limits for deserializing untrusted files
+//!
+//! The counts inside a file decide how much memory the deserializer
+//! allocates, so a small hostile file can claim billions of vertices.
+//! [DeserializeOptions] caps the counts and the memory allocated for them,
+//! the caps are checked before anything is allocated.
+
+use crate::*;
+
+use std::sync::atomic::{ AtomicUsize, Ordering };
+
+/// The limits checked while deserializing, see [Map::deserialize_with_options]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct DeserializeOptions {
+    /// The largest number of sectors of a map
+    pub max_sectors: usize,
+
+    /// The largest number of vertices of all the meshes together
+    pub max_vertices: usize,
+
+    /// The largest number of indices of all the meshes together
+    pub max_indices: usize,
+
+    /// The largest number of bytes allocated for the maps, sectors,
+    /// vertices, indices and decompressed sectors together
+    pub max_allocated_bytes: usize,
+
+    /// Check that every index of the meshes is inside the vertex buffer,
+    /// the indices aren't checked by default so loading stays fast, see
+    /// [Error::IndexOutOfRange]
+    pub check_indices: bool,
+}
+
+impl Default for DeserializeOptions {
+    /// No limits and no index checks, the counts are still checked
+    /// against the size of the buffer
+    fn default() -> Self {
+        Self {
+            max_sectors: usize::MAX,
+            max_vertices: usize::MAX,
+            max_indices: usize::MAX,
+            max_allocated_bytes: usize::MAX,
+            check_indices: false,
+        }
+    }
+}
+
+/// The limit of [DeserializeOptions] a file exceeded
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DeserializeLimit {
+    /// [DeserializeOptions::max_sectors]
+    Sectors,
+
+    /// [DeserializeOptions::max_vertices]
+    Vertices,
+
+    /// [DeserializeOptions::max_indices]
+    Indices,
+
+    /// [DeserializeOptions::max_allocated_bytes]
+    AllocatedBytes,
+}
+
+/// What is left of the limits while deserializing, shared by the sectors
+/// deserialized in parallel
+pub(crate) struct Budget {
+    /// The limits
+    options: DeserializeOptions,
+
+    /// The vertices deserialized so far
+    vertices: AtomicUsize,
+
+    /// The indices deserialized so far
+    indices: AtomicUsize,
+
+    /// The bytes allocated so far
+    allocated_bytes: AtomicUsize,
+}
+
+impl Budget {
+    /// Create a budget for the limits
+    pub(crate) fn new(options: DeserializeOptions) -> Self {
+        Self {
+            options,
+            vertices: AtomicUsize::new(0),
+            indices: AtomicUsize::new(0),
+            allocated_bytes: AtomicUsize::new(0),
+        }
+    }
+
+    /// Create a budget without any limits
+    pub(crate) fn unlimited() -> Self {
+        Self::new(DeserializeOptions::default())
+    }
+
+    /// Add to a counter and check it against its limit, the counter
+    /// saturates so huge counts can't wrap around
+    fn take(counter: &AtomicUsize,
+            amount: usize,
+            max: usize,
+            limit: DeserializeLimit)
+        -> Result<()>
+    {
+        let previous = counter.fetch_update(Ordering::Relaxed,
+                                            Ordering::Relaxed,
+                                            |total| {
+            Some(total.saturating_add(amount))
+        });
+
+        // NOTE(patrik): The closure always returns Some
+        let total = previous.unwrap_or_else(|total| total)
+            .saturating_add(amount);
+        if total > max {
+            return Err(Error::LimitExceeded(limit));
+        }
+
+        Ok(())
+    }
+
+    /// Check the number of sectors of a map before they are allocated
+    pub(crate) fn sectors(&self, count: usize) -> Result<()> {
+        if count > self.options.max_sectors {
+            return Err(Error::LimitExceeded(DeserializeLimit::Sectors));
+        }
+
+        self.allocate::<Sector>(count)
+    }
+
+    /// Take vertices of a mesh from the budget before they are allocated
+    pub(crate) fn vertices(&self, count: usize) -> Result<()> {
+        Self::take(&self.vertices, count, self.options.max_vertices,
+                   DeserializeLimit::Vertices)?;
+
+        self.allocate::<Vertex>(count)
+    }
+
+    /// Take indices of a mesh from the budget before they are allocated
+    pub(crate) fn indices(&self, count: usize) -> Result<()> {
+        Self::take(&self.indices, count, self.options.max_indices,
+                   DeserializeLimit::Indices)?;
+
+        self.allocate::<u32>(count)
+    }
+
+    /// Check if the indices of the meshes should be checked
+    pub(crate) fn check_indices(&self) -> bool {
+        self.options.check_indices
+    }
+
+    /// Take the memory of `count` items of a type from the budget before
+    /// they are allocated
+    pub(crate) fn allocate<T>(&self, count: usize) -> Result<()> {
+        self.allocate_bytes(count.saturating_mul(std::mem::size_of::<T>()))
+    }
+
+    /// Take bytes from the budget before they are allocated
+    pub(crate) fn allocate_bytes(&self, bytes: usize) -> Result<()> {
+        Self::take(&self.allocated_bytes, bytes,
+                   self.options.max_allocated_bytes,
+                   DeserializeLimit::AllocatedBytes)
+    }
+}
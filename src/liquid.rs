@@ -0,0 +1,137 @@
+//! Liquid filling the lower part of a sector
+//!
+//! A sector with [Sector::liquid] is filled with water, lava or slime up to
+//! a flat surface. The height is along the Y axis, matching the default
+//! [AxisConvention].
+
+use crate::*;
+use crate::buffer::{ Reader, write_f32s };
+
+/// The size of a serialized liquid (kind, surface height, flow, color and
+/// fog density)
+pub(crate) const LIQUID_SIZE: usize = 1 + 9 * std::mem::size_of::<f32>();
+
+/// The different kinds of liquids, the engine decides how they look and
+/// hurt
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub enum LiquidKind {
+    /// Water the player can swim in
+    #[default]
+    Water,
+
+    /// Lava that burns whatever touches it
+    Lava,
+
+    /// Toxic slime
+    Slime,
+}
+
+impl LiquidKind {
+    /// Get the byte the kind is serialized as
+    pub fn to_u8(self) -> u8 {
+        match self {
+            LiquidKind::Water => 0,
+            LiquidKind::Lava => 1,
+            LiquidKind::Slime => 2,
+        }
+    }
+
+    /// Get the kind from its serialized byte
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The serialized byte
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - The kind
+    /// * `Err(`[Error]`)` - [Error::UnknownLiquidKind] if the byte isn't a
+    ///   known kind
+    pub fn from_u8(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(LiquidKind::Water),
+            1 => Ok(LiquidKind::Lava),
+            2 => Ok(LiquidKind::Slime),
+            value => Err(Error::UnknownLiquidKind(value)),
+        }
+    }
+}
+
+/// The liquid inside a sector
+#[derive(Copy, Clone, PartialEq, Default, Debug)]
+pub struct Liquid {
+    /// The kind of the liquid
+    pub kind: LiquidKind,
+
+    /// The height of the surface, everything in the sector below it is
+    /// inside the liquid
+    pub surface_height: f32,
+
+    /// The direction and speed in units per second the liquid flows, zero
+    /// for still liquid
+    pub flow: [f32; 3],
+
+    /// The color (r, g, b, a) of the surface, the alpha is the opacity
+    pub color: [f32; 4],
+
+    /// How quickly the view fades to the color below the surface, 0.0 for
+    /// clear liquid
+    pub fog_density: f32,
+}
+
+impl Liquid {
+    /// Creates a new still liquid
+    ///
+    /// # Arguments
+    ///
+    /// * `kind`           - The kind of the liquid
+    /// * `surface_height` - The height of the surface
+    /// * `color`          - The color of the surface
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new liquid without fog
+    pub fn new(kind: LiquidKind, surface_height: f32, color: [f32; 4])
+        -> Self
+    {
+        Self {
+            kind,
+            surface_height,
+            flow: [0.0; 3],
+            color,
+            fog_density: 0.0,
+        }
+    }
+
+    /// Get how deep a position is below the surface
+    ///
+    /// # Returns
+    ///
+    /// * The distance to the surface, negative above the surface
+    pub fn depth(&self, position: [f32; 3]) -> f32 {
+        self.surface_height - position[1]
+    }
+
+    /// Serialize the liquid to a buffer
+    pub(crate) fn serialize<O>(&self, buffer: &mut O)
+        where O: Output + ?Sized
+    {
+        buffer.push(self.kind.to_u8());
+        buffer.extend_from_slice(&self.surface_height.to_le_bytes());
+        write_f32s(buffer, &self.flow);
+        write_f32s(buffer, &self.color);
+        buffer.extend_from_slice(&self.fog_density.to_le_bytes());
+    }
+
+    /// Read a liquid written by [Liquid::serialize]
+    pub(crate) fn read(reader: &mut Reader) -> Result<Self> {
+        Ok(Self {
+            kind: LiquidKind::from_u8(reader.u8()?)?,
+            surface_height: reader.f32()?,
+            flow: reader.vec3()?,
+            color: [reader.f32()?, reader.f32()?,
+                    reader.f32()?, reader.f32()?],
+            fog_density: reader.f32()?,
+        })
+    }
+}
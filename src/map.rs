@@ -2,22 +2,59 @@
 
 // TODO(patrik): Should we do this?
 use crate::*;
-
+use crate::buffer::{ Reader, ChunkWriter, SliceOutput, CHUNK_HEADER_SIZE,
+                     write_sized, write_usize, write_list, write_zeros,
+                     write_f32s, write_optional_string, list_size,
+                     optional_string_size, checked_size };
+use crate::bounds::BOUNDS_SIZE;
+use crate::liquid::LIQUID_SIZE;
+use crate::environment::{ ENVIRONMENT_SIZE, ENVIRONMENT_OVERRIDE_SIZE };
+use crate::signature::SIGNATURE_SIZE;
+use crate::limits::Budget;
+use crate::units::{ DEFAULT_UNITS_PER_METER, check_units_per_meter };
+use crate::compression::{ sector_block, sector_block_size,
+                          read_sector_block };
+use crate::lightmap::Lightmap;
+use crate::light::Light;
+use crate::sky::Sky;
+use crate::sound::{ SoundEmitter, ACOUSTICS_SIZE };
+use crate::trigger::Trigger;
+use crate::wall::Wall;
+use crate::asset::{ EmbeddedAsset, AssetDependency };
+use crate::mesh_library::{ library_size, write_library, read_library };
+use crate::attribute::{ VertexDecoder, uv_sets_size, write_uv_sets,
+                        read_uv_sets, custom_attributes_size,
+                        write_custom_attributes, read_custom_attributes };
+
+use std::collections::BTreeSet;
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
 use std::path::Path;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
 use std::fs::File;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
 use std::io::Write;
 
-// TODO(patrik): Make a better verison
-/// The current version of the file format
-pub const CURRENT_VERSION: u32 = 1;
+/// The major version of the file format, bumped by changes older readers
+/// can't skip, like a new field inside the sectors
+pub const MAJOR_VERSION: u16 = 47;
+
+/// The minor version of the file format, bumped by changes older readers
+/// can skip, like a new chunk, see [crate::unknown_chunk]
+pub const MINOR_VERSION: u16 = 0;
+
+/// The version of the file format written by this library, the major
+/// version in the upper 16 bits and the minor version in the lower 16 bits
+pub const CURRENT_VERSION: u32 =
+    ((MAJOR_VERSION as u32) << 16) | MINOR_VERSION as u32;
 
 type Index = u32;
 
 /// The size of the mime header
-const HEADER_SIZE: usize = 4 + std::mem::size_of::<u32>();
+pub(crate) const HEADER_SIZE: usize = 4 + std::mem::size_of::<u32>();
 
 /// The header magic
-const HEADER_MAGIC: &[u8] = b"MIME";
+pub(crate) const HEADER_MAGIC: &[u8] = b"MIME";
 
 /// The size of a single vertex (x, y, z, u, v, r, g, b, a)
 const VERTEX_SIZE: usize = 9 * std::mem::size_of::<f32>();
@@ -25,8 +62,137 @@ const VERTEX_SIZE: usize = 9 * std::mem::size_of::<f32>();
 /// The size of a single index
 const INDEX_SIZE: usize = std::mem::size_of::<u32>();
 
+/// The material index serialized for meshes without a material
+pub(crate) const NO_MATERIAL: u32 = u32::MAX;
+
+/// Check if a file written with a version of the format can be read, the
+/// files with the same major version can be read even when they were
+/// written by a newer minor version
+///
+/// # Arguments
+///
+/// * `version` - The version stored in the file
+///
+/// # Returns
+///
+/// * `true` - The file can be read, the chunks added by newer minor
+///   versions are kept in [Map::unknown_chunks]
+/// * `false` - The file uses another major version
+pub fn is_readable_version(version: u32) -> bool {
+    version >> 16 == MAJOR_VERSION as u32
+}
+
+/// Read the magic and the version at the start of a file and check that
+/// the version can be read, see [is_readable_version]
+///
+/// # Returns
+///
+/// * `Ok(u32)` - The version of the file
+/// * `Err(`[Error]`)` - [Error::IncorrectMagic] or
+///   [Error::IncorrectVersion] if the file can't be read
+pub(crate) fn read_magic_and_version(reader: &mut Reader, magic: &[u8])
+    -> Result<u32>
+{
+    if reader.bytes(magic.len())? != magic {
+        return Err(Error::IncorrectMagic);
+    }
+
+    let version = reader.u32()?;
+    if !is_readable_version(version) {
+        return Err(Error::IncorrectVersion(version));
+    }
+
+    Ok(version)
+}
+
+/// The size of the map header (sector count, chunk offset, next sector ID,
+/// compression)
+pub(crate) const MAP_HEADER_SIZE: usize = 8 + 8 + 8 + 1;
+
+/// The size of a single entry inside the sector table (offset, size,
+/// capacity, checksum)
+pub(crate) const SECTOR_ENTRY_SIZE: usize = 8 * 4;
+
+/// The chunk tag of the lightmap
+pub(crate) const LIGHTMAP_CHUNK: &[u8; 4] = b"LMAP";
+
+/// The chunk tag of the light list
+pub(crate) const LIGHTS_CHUNK: &[u8; 4] = b"LGHT";
+
+/// The chunk tag of the sky
+pub(crate) const SKY_CHUNK: &[u8; 4] = b"SKY ";
+
+/// The chunk tag of the sound emitter list
+pub(crate) const SOUND_EMITTERS_CHUNK: &[u8; 4] = b"SNDE";
+
+/// The chunk tag of the trigger list
+pub(crate) const TRIGGERS_CHUNK: &[u8; 4] = b"TRIG";
+
+/// The chunk tag of the entity list
+pub(crate) const ENTITIES_CHUNK: &[u8; 4] = b"ENTS";
+
+/// The chunk tag of the embedded asset list
+pub(crate) const EMBEDDED_ASSETS_CHUNK: &[u8; 4] = b"ASET";
+
+/// The chunk tag of the external asset dependency manifest
+pub(crate) const DEPENDENCIES_CHUNK: &[u8; 4] = b"DEPS";
+
+/// The chunk tag of the color space of the vertex colors
+pub(crate) const COLOR_SPACE_CHUNK: &[u8; 4] = b"CSPC";
+
+/// The chunk tag of the axis convention
+pub(crate) const AXES_CHUNK: &[u8; 4] = b"AXES";
+
+/// The chunk tag of the units per meter
+pub(crate) const UNITS_CHUNK: &[u8; 4] = b"UNIT";
+
+/// The chunk tag of the material table
+pub(crate) const MATERIALS_CHUNK: &[u8; 4] = b"MATL";
+
+/// The chunk tag of the sector animation list
+pub(crate) const ANIMATIONS_CHUNK: &[u8; 4] = b"ANIM";
+
+/// The chunk tag of the sector group list
+pub(crate) const GROUPS_CHUNK: &[u8; 4] = b"GRPS";
+
+/// The chunk tag of the potentially visible sets
+pub(crate) const PVS_CHUNK: &[u8; 4] = b"PVS ";
+
+/// The chunk tag of the waypoint graph
+pub(crate) const WAYPOINTS_CHUNK: &[u8; 4] = b"WAYP";
+
+/// The chunk tag of the mesh library
+pub(crate) const MESH_LIBRARY_CHUNK: &[u8; 4] = b"MLIB";
+
+/// The chunk tag of the prop list
+pub(crate) const PROPS_CHUNK: &[u8; 4] = b"PROP";
+
+/// The chunk tag of the decal list
+pub(crate) const DECALS_CHUNK: &[u8; 4] = b"DECL";
+
+/// The chunk tag of the default environment
+pub(crate) const ENVIRONMENT_CHUNK: &[u8; 4] = b"ENVR";
+
+/// The chunk tag of the interactive object list
+pub(crate) const INTERACTIVES_CHUNK: &[u8; 4] = b"INTR";
+
+/// The chunk tag of the script hooks and the embedded script source
+pub(crate) const SCRIPTS_CHUNK: &[u8; 4] = b"SCRP";
+
+/// The chunk tag of the preview image
+pub(crate) const THUMBNAIL_CHUNK: &[u8; 4] = b"THMB";
+
+/// The chunk tag of the ed25519 signature
+pub(crate) const SIGNATURE_CHUNK: &[u8; 4] = b"SIGN";
+
 /// A single vertex in 3D space
+///
+/// The vertex is `#[repr(C)]` and matches the layout described by
+/// [Vertex::STRIDE], with the `bytemuck` feature a slice of vertices can be
+/// cast to bytes without copying.
 #[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[repr(C)]
 pub struct Vertex {
     /// Vertex Position (x, y, z)
     pub pos: [f32; 3],
@@ -58,18 +224,47 @@ impl Vertex {
         }
     }
 
+    /// Get the vertex position as any vector type that can be created from
+    /// `[f32; 3]`, like `glam::Vec3`, `mint::Vector3<f32>` or
+    /// `nalgebra::Vector3<f32>`
+    pub fn position<T>(&self) -> T
+        where T: From<[f32; 3]>
+    {
+        T::from(self.pos)
+    }
+
+    /// Set the vertex position from any vector type that can be converted
+    /// to `[f32; 3]`
+    ///
+    /// # Arguments
+    ///
+    /// * `pos` - The new position
+    pub fn set_position<T>(&mut self, pos: T)
+        where T: Into<[f32; 3]>
+    {
+        self.pos = pos.into();
+    }
+
+    /// Get the x component of the vertex position
     pub fn x(&self) -> f32 {
         self.pos[0]
     }
 
+    /// Get the y component of the vertex position
     pub fn y(&self) -> f32 {
         self.pos[1]
     }
 
+    /// Get the z component of the vertex position
     pub fn z(&self) -> f32 {
         self.pos[2]
     }
 
+    /// Get the number of bytes [Vertex::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        VERTEX_SIZE
+    }
+
     /// Serialize a vertex the to a buffer
     ///
     /// # Arguments
@@ -80,7 +275,9 @@ impl Vertex {
     ///
     /// * `Ok()` - Successfully serialized the vertex
     /// * `Err(`[Error]`)` - Failed to serialize the vertex
-    pub fn serialize(&self, buffer: &mut Vec<u8>) -> Result<()> {
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
         // Vertex Position (x, y)
         buffer.extend_from_slice(&self.pos[0].to_le_bytes());
         buffer.extend_from_slice(&self.pos[1].to_le_bytes());
@@ -111,7 +308,8 @@ impl Vertex {
     /// * `Err(`[Error]`)` - Failed to deserialize the vertex
     pub fn deserialize(buffer: &[u8]) -> Result<Self> {
         if buffer.len() < VERTEX_SIZE {
-            return Err(Error::BufferToSmallVertex);
+            let size = SizeMismatch::new(VERTEX_SIZE, buffer.len());
+            return Err(Error::BufferToSmallVertex(size));
         }
 
         let x = f32::from_le_bytes(
@@ -148,6 +346,8 @@ impl Vertex {
     }
 }
 
+/// A mesh with a vertex buffer and an index buffer
+#[derive(Clone, PartialEq, Default, Debug)]
 pub struct Mesh {
     /// The vertex buffer of the mesh
     pub vertex_buffer: Vec<Vertex>,
@@ -157,9 +357,47 @@ pub struct Mesh {
 
     /// The Texture ID inside the Texture Table
     pub texture_id: u64,
+
+    /// The lightmap texture coordinates (u, v) of every vertex, empty if
+    /// the mesh doesn't have a lightmap UV set
+    pub lightmap_uvs: Vec<[f32; 2]>,
+
+    /// The index of the material inside the material table of the map,
+    /// `None` if the mesh doesn't use a material
+    pub material: Option<u32>,
+
+    /// Extra texture coordinate sets like detail UVs, every set has a
+    /// coordinate for every vertex
+    pub uv_sets: Vec<UvSet>,
+
+    /// Named per-vertex attributes the library doesn't interpret, see
+    /// [Mesh::set_custom_attribute]
+    pub custom_attributes: Vec<CustomAttribute>,
+
+    /// The named ranges of the index buffer drawn with their own material,
+    /// see [crate::submesh]
+    pub submeshes: Vec<Submesh>,
+
+    /// How the indices form primitives
+    pub topology: Topology,
+
+    /// How the vertices are encoded when the mesh is serialized, set to the
+    /// descriptor found when the mesh is deserialized
+    pub vertex_descriptor: VertexDescriptor,
 }
 
 impl Mesh {
+    /// Creates a new mesh
+    ///
+    /// # Arguments
+    ///
+    /// * `vertex_buffer` - The vertices of the mesh
+    /// * `index_buffer`  - The indices into the vertex buffer
+    /// * `texture_id`    - The Texture ID inside the Texture Table
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new mesh
     pub fn new(vertex_buffer: Vec<Vertex>,
                index_buffer: Vec<u32>,
                texture_id: u64)
@@ -168,10 +406,30 @@ impl Mesh {
         Self {
             vertex_buffer,
             index_buffer,
-            texture_id
+            texture_id,
+            lightmap_uvs: Vec::new(),
+            material: None,
+            uv_sets: Vec::new(),
+            custom_attributes: Vec::new(),
+            submeshes: Vec::new(),
+            topology: Topology::default(),
+            vertex_descriptor: VertexDescriptor::default(),
         }
     }
 
+    /// Get the number of bytes [Mesh::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        // Vertex count, index count, lightmap texture coordinate count,
+        // material index and topology
+        self.vertex_descriptor.serialized_size() + 3 * 8 + 4 + 1 +
+            self.vertex_buffer.len() * self.vertex_descriptor.stride() +
+            self.index_buffer.len() * INDEX_SIZE +
+            self.lightmap_uvs.len() * 2 * std::mem::size_of::<f32>() +
+            uv_sets_size(&self.uv_sets) +
+            custom_attributes_size(&self.custom_attributes) +
+            list_size(&self.submeshes, Submesh::serialized_size)
+    }
+
     /// Serialize the mesh to a buffer
     ///
     /// # Arguments
@@ -182,7 +440,11 @@ impl Mesh {
     ///
     /// * `Ok()` - Successfully serialized the mesh
     /// * `Err(`[Error]`)` - Failed to serialize the mesh
-    pub fn serialize(&self, buffer: &mut Vec<u8>) -> Result<()> {
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        self.vertex_descriptor.serialize(buffer)?;
+
         // Vertex buffer count
         let count: u64 =
             self.vertex_buffer.len().try_into()
@@ -196,7 +458,7 @@ impl Mesh {
 
         // Serialize the vertex buffer
         for vertex in &self.vertex_buffer {
-            vertex.serialize(buffer)?;
+            self.vertex_descriptor.encode(vertex, buffer);
         }
 
         // Serialize the index buffer
@@ -204,6 +466,21 @@ impl Mesh {
             buffer.extend_from_slice(&index.to_le_bytes());
         }
 
+        // Serialize the lightmap texture coordinates
+        write_usize(buffer, self.lightmap_uvs.len())?;
+        for uv in &self.lightmap_uvs {
+            buffer.extend_from_slice(&uv[0].to_le_bytes());
+            buffer.extend_from_slice(&uv[1].to_le_bytes());
+        }
+
+        let material = self.material.unwrap_or(NO_MATERIAL);
+        buffer.extend_from_slice(&material.to_le_bytes());
+        buffer.push(self.topology.to_u8());
+
+        write_uv_sets(buffer, &self.uv_sets)?;
+        write_custom_attributes(buffer, &self.custom_attributes)?;
+        write_list(buffer, &self.submeshes, Submesh::serialize)?;
+
         Ok(())
     }
 
@@ -218,9 +495,48 @@ impl Mesh {
     /// * `Ok(`[Self]`)` - Successfully deserialized the mesh
     /// * `Err(`[Error]`)` - Failed to deserialize the mesh
     pub fn deserialize(buffer: &[u8]) -> Result<Self> {
+        Self::deserialize_with_budget(buffer, &Budget::unlimited())
+    }
+
+    /// Deserialize the mesh with limits and the optional index check
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer`  - The buffer we should deserialize
+    /// * `options` - The limits for the vertices, indices and the allocated
+    ///   memory, and if the indices should be checked
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the mesh
+    /// * `Err(`[Error]`)` - Failed to deserialize the mesh, it exceeds one
+    ///   of the limits or has an index out of range
+    pub fn deserialize_with_options(buffer: &[u8],
+                                    options: &DeserializeOptions)
+        -> Result<Self>
+    {
+        Self::deserialize_with_budget(buffer, &Budget::new(*options))
+    }
+
+    /// Deserialize the mesh and take the vertices and indices from the
+    /// budget before allocating them
+    pub(crate) fn deserialize_with_budget(buffer: &[u8], budget: &Budget)
+        -> Result<Self>
+    {
+        let mesh_buffer = buffer;
+
+        // NOTE(patrik): The mesh starts with the vertex descriptor telling
+        // how the vertices are encoded
+        let mut reader = Reader::new(buffer, Error::BufferToSmallMesh);
+        let (descriptor, decoder) = VertexDecoder::read(&mut reader)?;
+        let stride = decoder.stride;
+        let start = reader.offset();
+
+        let buffer = &buffer[start..];
         if buffer.len() < std::mem::size_of::<u64>() * 2 {
-            // TODO(patrik): Change this error
-            return Err(Error::BufferToSmallSector);
+            let size = SizeMismatch::new(start + 16, mesh_buffer.len());
+            return Err(Error::BufferToSmallMesh(size)
+                .at_offset(mesh_buffer.len()));
         }
 
         let vertex_count = u64::from_le_bytes(
@@ -235,72 +551,186 @@ impl Mesh {
         let index_count: usize = index_count.try_into()
             .map_err(Error::IntegerConvertionError)?;
 
-        let buffer = &buffer[16..];
+        budget.vertices(vertex_count)
+            .map_err(|error| error.at_offset(start))?;
+        budget.indices(index_count)
+            .map_err(|error| error.at_offset(start + 8))?;
 
-        if buffer.len() < VERTEX_SIZE * vertex_count {
-            return Err(Error::BufferToSmallSector);
+        // NOTE(patrik): The counts come from the buffer, the sizes are
+        // checked so crafted counts can't wrap around and pass the size
+        // checks below
+        let vertices_size = checked_size(vertex_count, stride)
+            .map_err(|error| error.at_offset(start))?;
+        let indices_size = checked_size(index_count, INDEX_SIZE)
+            .map_err(|error| error.at_offset(start + 8))?;
+
+        let buffer = &buffer[16..];
+        let vertices_offset = start + 16;
+
+        // NOTE(patrik): The errors point at the first element that doesn't
+        // fit inside the buffer
+        if buffer.len() < vertices_size {
+            let vertex = buffer.len() / stride;
+            let end = vertices_offset.saturating_add(vertices_size);
+            let size = SizeMismatch::new(end, mesh_buffer.len());
+            return Err(Error::BufferToSmallMesh(size).with_context(|context| {
+                context.offset = vertices_offset + vertex * stride;
+                context.element = Some(ErrorElement::Vertex(vertex));
+            }));
         }
 
-        let mut vertex_buffer = Vec::with_capacity(vertex_count);
+        let (vertices, buffer) = buffer.split_at(vertices_size);
+        let vertex_buffer = vertices.chunks_exact(stride)
+            .map(|vertex| decoder.decode(vertex))
+            .collect::<Vec<_>>();
+
+        // NOTE(patrik): The vertices are inside the buffer so the offsets
+        // up to the end of them can't overflow
+        let indices_offset = vertices_offset + vertices_size;
+        if buffer.len() < indices_size {
+            let index = buffer.len() / INDEX_SIZE;
+            let end = indices_offset.saturating_add(indices_size);
+            let size = SizeMismatch::new(end, mesh_buffer.len());
+            return Err(Error::BufferToSmallMesh(size).with_context(|context| {
+                context.offset = indices_offset + index * INDEX_SIZE;
+                context.element = Some(ErrorElement::Index(index));
+            }));
+        }
 
-        for i in 0..vertex_count {
-            let start = i * VERTEX_SIZE;
-            let buffer = &buffer[start..start + VERTEX_SIZE];
-            let vertex = Vertex::deserialize(buffer)?;
-            vertex_buffer.push(vertex);
+        let index_buffer = buffer[..indices_size].chunks_exact(INDEX_SIZE)
+            .map(|index| u32::from_le_bytes(index.try_into().unwrap()))
+            .collect::<Vec<_>>();
+
+        // NOTE(patrik): An index past the vertices would only fail once the
+        // mesh is drawn, far away from the load
+        if budget.check_indices() {
+            let max = vertex_buffer.len();
+            let invalid = index_buffer.iter()
+                .position(|index| *index as usize >= max);
+            if let Some(position) = invalid {
+                let index = index_buffer[position];
+                let error = Error::IndexOutOfRange { mesh: None, index, max };
+                return Err(error.with_context(|context| {
+                    context.offset = indices_offset + position * INDEX_SIZE;
+                    context.element = Some(ErrorElement::Index(position));
+                }));
+            }
         }
 
-        let buffer = &buffer[(vertex_count * VERTEX_SIZE)..];
+        // NOTE(patrik): The reader starts at the beginning of the mesh so
+        // the offsets of the errors are relative to the mesh
+        let uvs_offset = indices_offset + indices_size;
+        reader.bytes(uvs_offset - start)?;
 
-        let mut index_buffer = Vec::with_capacity(index_count);
+        let mut mesh = Self::new(vertex_buffer, index_buffer, 0);
+        mesh.vertex_descriptor = descriptor;
 
-        if buffer.len() < INDEX_SIZE * index_count {
-            return Err(Error::BufferToSmallSector);
+        let uv_count = reader.usize()?;
+        if uv_count != 0 && uv_count != mesh.vertex_buffer.len() {
+            return Err(Error::LightmapUvCountMismatch.at_offset(uvs_offset));
         }
 
-        for i in 0..index_count {
-            let start = i * INDEX_SIZE;
-            let buffer = &buffer[start..start + INDEX_SIZE];
-            let index = u32::from_le_bytes(
-                buffer.try_into()
-                    .map_err(Error::SliceConvertionError)?);
-            index_buffer.push(index);
+        mesh.lightmap_uvs.reserve(uv_count);
+        for i in 0..uv_count {
+            let element = |error: Error| {
+                error.with_context(|context| {
+                    context.element = Some(ErrorElement::LightmapUv(i));
+                })
+            };
+
+            let uv = [reader.f32().map_err(element)?,
+                      reader.f32().map_err(element)?];
+            mesh.lightmap_uvs.push(uv);
         }
 
-        Ok(Self::new(vertex_buffer, index_buffer, 0))
+        mesh.material = match reader.u32()? {
+            NO_MATERIAL => None,
+            material => Some(material),
+        };
+        mesh.topology = Topology::from_u8(reader.u8()?)?;
+
+        let vertex_count = mesh.vertex_buffer.len();
+        mesh.uv_sets = read_uv_sets(&mut reader, vertex_count)?;
+        mesh.custom_attributes =
+            read_custom_attributes(&mut reader, vertex_count)?;
+        mesh.submeshes = reader.list(Submesh::deserialize)?;
+
+        Ok(mesh)
     }
 }
 
-/// A sector of the map contains the mesh
-pub struct Sector {
+/// Read a size prefixed mesh of a sector or a level of detail, the role of
+/// the mesh is added to the errors
+fn read_mesh(reader: &mut Reader, role: MeshRole, budget: &Budget)
+    -> Result<Mesh>
+{
+    reader.block(|mesh| Mesh::deserialize_with_budget(mesh, budget))
+        .map_err(|error| {
+            let mut error =
+                error.with_context(|context| context.mesh = Some(role));
+            if let Error::WithContext { error, .. } = &mut error {
+                if let Error::IndexOutOfRange { mesh, .. } = error.as_mut() {
+                    *mesh = Some(role);
+                }
+            }
+
+            error
+        })
+}
+
+/// A lower level of detail version of the sector meshes
+#[derive(Clone, PartialEq, Debug)]
+pub struct Lod {
+    /// The distance from the viewer where the engine should switch to
+    /// this level of detail
+    pub switch_distance: f32,
+
+    /// The mesh of the floor
     pub floor_mesh: Mesh,
+
+    /// The mesh of the ceiling
     pub ceiling_mesh: Mesh,
+
+    /// The mesh of the walls
     pub wall_mesh: Mesh,
 }
 
-impl Sector {
-    /// Creates a new sector
+impl Lod {
+    /// Creates a new level of detail
     ///
     /// # Arguments
     ///
-    /// * `floor_mesh`   - The mesh of the floor for this sector
-    /// * `ceiling_mesh` - The mesh of the ceiling for this sector
-    /// * `wall_mesh`    - The mesh of the walls for this sector
+    /// * `switch_distance` - The distance where this level should be used
+    /// * `floor_mesh`      - The mesh of the floor
+    /// * `ceiling_mesh`    - The mesh of the ceiling
+    /// * `wall_mesh`       - The mesh of the walls
     ///
     /// # Returns
     ///
-    /// * [`Self`] - The new sector
-    pub fn new(floor_mesh: Mesh, ceiling_mesh: Mesh, wall_mesh: Mesh)
-        -> Sector
+    /// * [Self] - The new level of detail
+    pub fn new(switch_distance: f32,
+               floor_mesh: Mesh,
+               ceiling_mesh: Mesh,
+               wall_mesh: Mesh)
+        -> Self
     {
         Self {
+            switch_distance,
             floor_mesh,
             ceiling_mesh,
             wall_mesh,
         }
     }
 
-    /// Serialize the sector to a buffer
+    /// Get the number of bytes [Lod::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        std::mem::size_of::<f32>() +
+            8 + self.floor_mesh.serialized_size() +
+            8 + self.ceiling_mesh.serialized_size() +
+            8 + self.wall_mesh.serialized_size()
+    }
+
+    /// Serialize the level of detail to a buffer
     ///
     /// # Arguments
     ///
@@ -308,41 +738,21 @@ impl Sector {
     ///
     /// # Returns
     ///
-    /// * `Ok()` - Successfully serialized the sector
-    /// * `Err(`[Error]`)` - Failed to serialize the sector
-    pub fn serialize(&self, buffer: &mut Vec<u8>) -> Result<()> {
-        let mut temp_buffer = Vec::new();
-        self.floor_mesh.serialize(&mut temp_buffer)?;
-
-        let size: u64 = temp_buffer.len().try_into()
-            .map_err(Error::IntegerConvertionError)?;
-
-        buffer.extend_from_slice(&size.to_le_bytes());
-        buffer.extend_from_slice(&temp_buffer);
-
-        let mut temp_buffer = Vec::new();
-        self.ceiling_mesh.serialize(&mut temp_buffer)?;
-
-        let size: u64 = temp_buffer.len().try_into()
-            .map_err(Error::IntegerConvertionError)?;
-
-        buffer.extend_from_slice(&size.to_le_bytes());
-        buffer.extend_from_slice(&temp_buffer);
-
-        let mut temp_buffer = Vec::new();
-        self.wall_mesh.serialize(&mut temp_buffer)?;
-
-        let size: u64 = temp_buffer.len().try_into()
-            .map_err(Error::IntegerConvertionError)?;
+    /// * `Ok()` - Successfully serialized the level of detail
+    /// * `Err(`[Error]`)` - Failed to serialize the level of detail
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        buffer.extend_from_slice(&self.switch_distance.to_le_bytes());
 
-        buffer.extend_from_slice(&size.to_le_bytes());
-        buffer.extend_from_slice(&temp_buffer);
+        write_sized(buffer, |buffer| self.floor_mesh.serialize(buffer))?;
+        write_sized(buffer, |buffer| self.ceiling_mesh.serialize(buffer))?;
+        write_sized(buffer, |buffer| self.wall_mesh.serialize(buffer))?;
 
         Ok(())
     }
 
-    // TODO(patrik): Change this comment
-    /// Deserialize the sector to a buffer
+    /// Deserialize the level of detail from a buffer
     ///
     /// # Arguments
     ///
@@ -350,61 +760,256 @@ impl Sector {
     ///
     /// # Returns
     ///
-    /// * `Ok(`[Self]`)` - Successfully deserialized the mesh
-    /// * `Err(`[Error]`)` - Failed to deserialize the mesh
+    /// * `Ok(`[Self]`)` - Successfully deserialized the level of detail
+    /// * `Err(`[Error]`)` - Failed to deserialize the level of detail
     pub fn deserialize(buffer: &[u8]) -> Result<Self> {
-        let floor_mesh_size = u64::from_le_bytes(
-            buffer[0..8].try_into()
-                .map_err(Error::SliceConvertionError)?);
-        let floor_mesh_size: usize = floor_mesh_size.try_into()
-            .map_err(Error::IntegerConvertionError)?;
-        let buffer = &buffer[8..];
+        Self::deserialize_with_budget(buffer, &Budget::unlimited())
+    }
 
-        let floor_mesh = Mesh::deserialize(&buffer[0..floor_mesh_size])?;
-        let buffer = &buffer[floor_mesh_size..];
+    /// Deserialize the level of detail and take the meshes from the budget
+    pub(crate) fn deserialize_with_budget(buffer: &[u8], budget: &Budget)
+        -> Result<Self>
+    {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallSector);
 
-        let ceiling_mesh_size = u64::from_le_bytes(buffer[0..8].try_into().map_err(Error::SliceConvertionError)?);
-        let ceiling_mesh_size: usize = ceiling_mesh_size.try_into()
-            .map_err(Error::IntegerConvertionError)?;
-        let buffer = &buffer[8..];
+        let switch_distance = reader.f32()?;
+        let floor_mesh = read_mesh(&mut reader, MeshRole::Floor, budget)?;
+        let ceiling_mesh = read_mesh(&mut reader, MeshRole::Ceiling, budget)?;
+        let wall_mesh = read_mesh(&mut reader, MeshRole::Wall, budget)?;
 
-        let ceiling_mesh = Mesh::deserialize(&buffer[0..ceiling_mesh_size])?;
-        let buffer = &buffer[ceiling_mesh_size..];
+        Ok(Self::new(switch_distance, floor_mesh, ceiling_mesh, wall_mesh))
+    }
+}
 
-        let wall_mesh_size = u64::from_le_bytes(buffer[0..8].try_into().map_err(Error::SliceConvertionError)?);
-        let wall_mesh_size: usize = wall_mesh_size.try_into()
-            .map_err(Error::IntegerConvertionError)?;
-        let buffer = &buffer[8..];
+/// The role of a mesh inside a sector
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum MeshRole {
+    /// The mesh of the floor
+    Floor,
 
-        let wall_mesh = Mesh::deserialize(&buffer[0..wall_mesh_size])?;
+    /// The mesh of the ceiling
+    Ceiling,
 
-        Ok(Sector::new(floor_mesh, ceiling_mesh, wall_mesh))
-    }
+    /// The mesh of the walls
+    Wall,
 }
 
-/// The map structure containing infomation about the map
-pub struct Map {
-    /// The sectors of the map
-    pub sectors: Vec<Sector>,
+impl MeshRole {
+    /// All the mesh roles in the order the meshes are serialized
+    pub const ALL: [MeshRole; 3] =
+        [MeshRole::Floor, MeshRole::Ceiling, MeshRole::Wall];
 }
 
-impl Map {
-    /// Create a new map structure
+/// The persistent ID of a sector inside a map, unlike the index of the
+/// sector it doesn't change when other sectors are removed
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct SectorId(pub u64);
+
+/// A sector of the map contains the mesh
+#[derive(Clone, PartialEq, Debug)]
+pub struct Sector {
+    /// The mesh of the floor
+    pub floor_mesh: Mesh,
+
+    /// The mesh of the ceiling
+    pub ceiling_mesh: Mesh,
+
+    /// The mesh of the walls
+    pub wall_mesh: Mesh,
+
+    /// The lower levels of detail for the sector, ordered from the most
+    /// detailed to the least detailed
+    pub lods: Vec<Lod>,
+
+    /// The tag used by triggers and scripts to reference a group of
+    /// sectors, 0 means the sector isn't tagged
+    pub tag: u32,
+
+    /// The wall segments of the sector, each one references a range of the
+    /// wall mesh
+    pub walls: Vec<Wall>,
+
+    /// The plane (a, b, c, d) of the floor, where `a * x + b * y + c * z = d`
+    /// is on the floor and the normal points up into the sector, for exact
+    /// height queries, see [Sector::compute_planes]
+    pub floor_plane: Option<[f32; 4]>,
+
+    /// The plane (a, b, c, d) of the ceiling with the normal pointing down
+    /// into the sector
+    pub ceiling_plane: Option<[f32; 4]>,
+
+    /// The human-readable name used by scripts and editors, like "lobby",
+    /// see [Map::sector_by_name]
+    pub name: Option<String>,
+
+    /// The props that belong to the sector, loaded and culled with it, see
+    /// [Map::mesh_library]
+    pub props: Vec<PropInstance>,
+
+    /// The simplified geometry hiding what is behind it, for occlusion
+    /// culling only and never drawn
+    pub occluders: Vec<Occluder>,
+
+    /// The liquid filling the sector up to its surface
+    pub liquid: Option<Liquid>,
+
+    /// The fog and ambient light of the sector replacing the defaults of
+    /// [Map::environment], see [Map::sector_environment]
+    pub environment: EnvironmentOverride,
+
+    /// The reverb and occlusion the sound engine uses inside the sector
+    pub acoustics: Acoustics,
+
+    /// The gameplay flags of the sector
+    pub flags: SectorFlags,
+
+    /// The ID of the sector inside the map, given by [Map::new] and
+    /// [Map::add_sector]
+    pub(crate) id: SectorId,
+}
+
+impl Default for Sector {
+    /// A sector with empty meshes
+    fn default() -> Self {
+        Self::new(Mesh::default(), Mesh::default(), Mesh::default())
+    }
+}
+
+impl Sector {
+    /// Creates a new sector
     ///
     /// # Arguments
     ///
-    /// * `sectors` - Map sectors
+    /// * `floor_mesh`   - The mesh of the floor for this sector
+    /// * `ceiling_mesh` - The mesh of the ceiling for this sector
+    /// * `wall_mesh`    - The mesh of the walls for this sector
     ///
     /// # Returns
     ///
-    /// * [Self] - Returns the created map structure
-    pub fn new(sectors: Vec<Sector>) -> Self {
+    /// * [`Self`] - The new sector
+    pub fn new(floor_mesh: Mesh, ceiling_mesh: Mesh, wall_mesh: Mesh)
+        -> Sector
+    {
         Self {
-            sectors
+            floor_mesh,
+            ceiling_mesh,
+            wall_mesh,
+            lods: Vec::new(),
+            tag: 0,
+            walls: Vec::new(),
+            floor_plane: None,
+            ceiling_plane: None,
+            name: None,
+            props: Vec::new(),
+            occluders: Vec::new(),
+            liquid: None,
+            environment: EnvironmentOverride::default(),
+            acoustics: Acoustics::default(),
+            flags: SectorFlags::empty(),
+            id: SectorId(0),
         }
     }
 
-    /// Serialize the map to a buffer
+    /// Get the ID of the sector, stays the same when other sectors are
+    /// added to or removed from the map
+    pub fn id(&self) -> SectorId {
+        self.id
+    }
+
+    /// Get the mesh with a role
+    pub fn mesh(&self, role: MeshRole) -> &Mesh {
+        match role {
+            MeshRole::Floor => &self.floor_mesh,
+            MeshRole::Ceiling => &self.ceiling_mesh,
+            MeshRole::Wall => &self.wall_mesh,
+        }
+    }
+
+    /// Get the mesh with a role as mutable
+    pub fn mesh_mut(&mut self, role: MeshRole) -> &mut Mesh {
+        match role {
+            MeshRole::Floor => &mut self.floor_mesh,
+            MeshRole::Ceiling => &mut self.ceiling_mesh,
+            MeshRole::Wall => &mut self.wall_mesh,
+        }
+    }
+
+    /// Add a level of detail to the sector, the levels are kept sorted by
+    /// the switch distance
+    ///
+    /// # Arguments
+    ///
+    /// * `lod` - The level of detail to add
+    pub fn add_lod(&mut self, lod: Lod) {
+        let index = self.lods.iter()
+            .position(|l| l.switch_distance > lod.switch_distance)
+            .unwrap_or(self.lods.len());
+        self.lods.insert(index, lod);
+    }
+
+    /// Get the number of lower levels of detail the sector has
+    pub fn lod_count(&self) -> usize {
+        self.lods.len()
+    }
+
+    /// Get a lower level of detail of the sector
+    ///
+    /// # Arguments
+    ///
+    /// * `level` - The level to get, level 0 is the first level below the
+    ///   full detail meshes of the sector
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[Lod]`)` - The level of detail
+    /// * `None` - The sector doesn't have that many levels
+    pub fn lod(&self, level: usize) -> Option<&Lod> {
+        self.lods.get(level)
+    }
+
+    /// Get the level of detail that should be used at a distance
+    ///
+    /// # Arguments
+    ///
+    /// * `distance` - The distance from the viewer to the sector
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[Lod]`)` - The level of detail to use
+    /// * `None` - The full detail meshes of the sector should be used
+    pub fn lod_for_distance(&self, distance: f32) -> Option<&Lod> {
+        self.lods.iter()
+            .filter(|lod| lod.switch_distance <= distance)
+            .max_by(|a, b| a.switch_distance.total_cmp(&b.switch_distance))
+    }
+
+    /// Get the number of bytes [Sector::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        let meshes = 8 + self.floor_mesh.serialized_size() +
+            8 + self.ceiling_mesh.serialized_size() +
+            8 + self.wall_mesh.serialized_size();
+        let lods = 8 + self.lods.iter()
+            .map(|lod| 8 + lod.serialized_size())
+            .sum::<usize>();
+
+        // Tag and ID
+        let ids = std::mem::size_of::<u32>() + 8;
+
+        // A byte telling if the plane is used followed by the plane
+        let planes = 2 * (1 + 4 * std::mem::size_of::<f32>());
+
+        // A byte telling if the sector has a name followed by the name
+        let name = optional_string_size(self.name.as_deref());
+
+        BOUNDS_SIZE + meshes + lods + ids +
+            list_size(&self.walls, Wall::serialized_size) + planes + name +
+            list_size(&self.props, PropInstance::serialized_size) +
+            list_size(&self.occluders, Occluder::serialized_size) +
+            1 + LIQUID_SIZE + ENVIRONMENT_OVERRIDE_SIZE + ACOUSTICS_SIZE +
+            std::mem::size_of::<u32>()
+    }
+
+    /// Serialize the sector to a buffer
     ///
     /// # Arguments
     ///
@@ -412,31 +1017,50 @@ impl Map {
     ///
     /// # Returns
     ///
-    /// * `Ok(())` - Successfully serialized the map
-    /// * `Err(`[Error]`)` - Failed to serialize the map
-    pub fn serialize(&self, buffer: &mut Vec<u8>) -> Result<()> {
-        // Serialize the sector count
-        let count: u64 =
-            self.sectors.len().try_into()
-                .map_err(Error::IntegerConvertionError)?;
-        buffer.extend_from_slice(&count.to_le_bytes());
+    /// * `Ok()` - Successfully serialized the sector
+    /// * `Err(`[Error]`)` - Failed to serialize the sector
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        // NOTE(patrik): The bounds go first so they can be read without
+        // decoding the meshes
+        self.bounds().serialize(buffer);
+
+        write_sized(buffer, |buffer| self.floor_mesh.serialize(buffer))?;
+        write_sized(buffer, |buffer| self.ceiling_mesh.serialize(buffer))?;
+        write_sized(buffer, |buffer| self.wall_mesh.serialize(buffer))?;
+
+        // Levels of detail
+        write_usize(buffer, self.lods.len())?;
+        for lod in &self.lods {
+            write_sized(buffer, |buffer| lod.serialize(buffer))?;
+        }
 
-        // Serialize all the sectors
-        for sector in &self.sectors {
-            let mut sector_buffer = Vec::new();
-            sector.serialize(&mut sector_buffer)?;
+        buffer.extend_from_slice(&self.tag.to_le_bytes());
+        buffer.extend_from_slice(&self.id.0.to_le_bytes());
 
-            let sector_size: u64 =
-                sector_buffer.len().try_into()
-                    .map_err(Error::IntegerConvertionError)?;
-            buffer.extend_from_slice(&sector_size.to_le_bytes());
-            buffer.extend_from_slice(&sector_buffer);
+        write_list(buffer, &self.walls, Wall::serialize)?;
+
+        for plane in [self.floor_plane, self.ceiling_plane] {
+            buffer.push(plane.is_some() as u8);
+            write_f32s(buffer, &plane.unwrap_or_default());
         }
 
+        write_optional_string(buffer, self.name.as_deref())?;
+        write_list(buffer, &self.props, PropInstance::serialize)?;
+        write_list(buffer, &self.occluders, Occluder::serialize)?;
+
+        buffer.push(self.liquid.is_some() as u8);
+        self.liquid.unwrap_or_default().serialize(buffer);
+
+        self.environment.serialize(buffer);
+        self.acoustics.serialize(buffer);
+        buffer.extend_from_slice(&self.flags.bits().to_le_bytes());
+
         Ok(())
     }
 
-    /// Deserialize the buffer and create a map structure
+    /// Deserialize the sector from a buffer
     ///
     /// # Arguments
     ///
@@ -444,65 +1068,1487 @@ impl Map {
     ///
     /// # Returns
     ///
-    /// * `Ok(`[Map]`)` - Successfully derserialized the data and created a
-    ///                   map structure
-    /// * `Err(`[Error]`)` - Failed to deserialize the data
+    /// * `Ok(`[Self]`)` - Successfully deserialized the sector
+    /// * `Err(`[Error]`)` - Failed to deserialize the sector
     pub fn deserialize(buffer: &[u8]) -> Result<Self> {
-        if buffer.len() < std::mem::size_of::<u64>() {
-            return Err(Error::BufferToSmallMap);
-        }
+        Self::deserialize_with_budget(buffer, &Budget::unlimited())
+    }
 
-        let sector_count = u64::from_le_bytes(
-            buffer[0..8].try_into()
-                .map_err(Error::SliceConvertionError)?);
-        let sector_count: usize = sector_count.try_into()
-            .map_err(Error::IntegerConvertionError)?;
+    /// Deserialize the sector and take the meshes from the budget
+    pub(crate) fn deserialize_with_budget(buffer: &[u8], budget: &Budget)
+        -> Result<Self>
+    {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallSector);
 
-        let buffer = &buffer[8..];
+        // The stored bounds are computed from the meshes
+        Bounds::read(&mut reader)?;
 
-        let mut sectors = Vec::with_capacity(sector_count);
+        let floor_mesh = read_mesh(&mut reader, MeshRole::Floor, budget)?;
+        let ceiling_mesh = read_mesh(&mut reader, MeshRole::Ceiling, budget)?;
+        let wall_mesh = read_mesh(&mut reader, MeshRole::Wall, budget)?;
 
-        let mut offset = 0;
+        let mut sector = Sector::new(floor_mesh, ceiling_mesh, wall_mesh);
 
-        for _i in 0..sector_count {
-            let start = offset;
-            let sector_size = u64::from_le_bytes(
-                buffer[start..start + 8].try_into()
-                    .map_err(Error::SliceConvertionError)?);
-            let sector_size: usize =
-                sector_size.try_into()
-                    .map_err(Error::IntegerConvertionError)?;
-            let start = start + 8;
+        let lod_count = reader.usize()?;
+        for i in 0..lod_count {
+            let lod = reader
+                .block(|lod| Lod::deserialize_with_budget(lod, budget))
+                .map_err(|error| {
+                    error.with_context(|context| context.lod = Some(i))
+                })?;
+            sector.lods.push(lod);
+        }
 
-            let sector =
-                Sector::deserialize(&buffer[start..start + sector_size])?;
-            sectors.push(sector);
+        sector.tag = reader.u32()?;
+        sector.id = SectorId(reader.u64()?);
+        sector.walls = reader.list(Wall::deserialize)?;
 
-            offset += sector_size + 8;
-        }
+        let mut plane = || -> Result<Option<[f32; 4]>> {
+            let used = reader.u8()? != 0;
+            let plane = [reader.f32()?, reader.f32()?,
+                         reader.f32()?, reader.f32()?];
+            Ok(used.then_some(plane))
+        };
+        sector.floor_plane = plane()?;
+        sector.ceiling_plane = plane()?;
 
-        Ok(Self::new(sectors))
-    }
+        sector.name = reader.optional_string()?;
+        sector.props = reader.list(PropInstance::deserialize)?;
+        sector.occluders = reader.list(Occluder::deserialize)?;
 
-}
+        let has_liquid = reader.u8()? != 0;
+        let liquid = Liquid::read(&mut reader)?;
+        sector.liquid = has_liquid.then_some(liquid);
 
-pub struct Mime {
-    maps: Vec<Map>,
-    // textures: Vec<Texture>,
-}
+        sector.environment = EnvironmentOverride::read(&mut reader)?;
+        sector.acoustics = Acoustics::read(&mut reader)?;
+        sector.flags = SectorFlags::from_bits_retain(reader.u32()?);
 
-impl Mime {
-    pub fn new() -> Self {
-        Self {
-            maps: Vec::new(),
-        }
+        Ok(sector)
     }
+}
+
+/// The map structure containing infomation about the map
+#[derive(Clone, Debug)]
+pub struct Map {
+    /// The sectors of the map
+    pub sectors: Vec<Sector>,
+
+    /// The baked lightmap referenced by the lightmap texture coordinates of
+    /// the meshes
+    pub lightmap: Option<Lightmap>,
+
+    /// The lights placed inside the map
+    pub lights: Vec<Light>,
+
+    /// The sky of the map, `None` lets the engine decide
+    pub sky: Option<Sky>,
+
+    /// The ambient sound emitters placed inside the map
+    pub sound_emitters: Vec<SoundEmitter>,
+
+    /// The trigger volumes placed inside the map
+    pub triggers: Vec<Trigger>,
+
+    /// The entities placed inside the map
+    pub entities: Vec<Entity>,
+
+    /// The binary assets embedded inside the map
+    pub embedded_assets: Vec<EmbeddedAsset>,
+
+    /// The manifest of external assets the map depends on, see
+    /// [Map::update_dependencies]
+    pub dependencies: Vec<AssetDependency>,
+
+    /// The color space of the vertex colors of every mesh, see
+    /// [Map::convert_color_space] to change it without changing how the
+    /// colors look
+    pub color_space: ColorSpace,
+
+    /// The axis convention of the coordinates, see [Map::convert_axes] to
+    /// change it without changing how the map looks
+    pub axes: AxisConvention,
+
+    /// The number of units that make up a meter, see [Map::rescale]
+    pub(crate) units_per_meter: f32,
+
+    /// The materials referenced by the meshes, see [Mesh::material]
+    pub materials: Vec<Material>,
+
+    /// How the sectors are stored when the map is saved, set to the
+    /// compression recorded in the header when the map is loaded
+    pub compression: SectorCompression,
+
+    /// The animations moving the floors and ceilings of tagged sectors
+    pub animations: Vec<SectorAnimation>,
+
+    /// The named groups of sectors used as layers, see [Map::group_mut]
+    pub groups: Vec<SectorGroup>,
+
+    /// The sectors that can possibly be seen from every sector, see
+    /// [Map::build_pvs]
+    pub pvs: Option<Pvs>,
+
+    /// The hand placed waypoints used by the AI
+    pub waypoints: Option<WaypointGraph>,
+
+    /// The meshes placed by the props of the map and the sectors, stored
+    /// once no matter how many props use them, see [Map::add_library_mesh]
+    /// and [crate::mesh_library]
+    pub mesh_library: Vec<Mesh>,
+
+    /// The static props placing the meshes of the mesh library
+    pub props: Vec<PropInstance>,
+
+    /// The decals projected onto the sectors
+    pub decals: Vec<Decal>,
+
+    /// The fog and ambient light of the sectors that don't override them
+    pub environment: Environment,
+
+    /// The switches, buttons and other objects activating sectors and
+    /// entities
+    pub interactives: Vec<Interactive>,
+
+    /// The script functions bound to the sectors, triggers and entities
+    pub scripts: Scripts,
+
+    /// The chunks this version doesn't know, written again when the map is
+    /// saved, see [crate::unknown_chunk]
+    pub unknown_chunks: Vec<UnknownChunk>,
+
+    /// The preview image of the map, it is stored as the first chunk so
+    /// browsers can read it without the rest of the map
+    pub thumbnail: Option<Thumbnail>,
+
+    /// The ed25519 signature of the map content, see [crate::signature]
+    pub(crate) signature: Option<[u8; SIGNATURE_SIZE]>,
+
+    /// Where the sectors were stored when the map was last saved or loaded,
+    /// used by the incremental save
+    pub(crate) layout: Option<MapLayout>,
+
+    /// The sectors changed since the map was last saved or loaded
+    pub(crate) dirty_sectors: BTreeSet<usize>,
+
+    /// The ID given to the next sector added to the map, IDs are never
+    /// reused
+    pub(crate) next_sector_id: u64,
+}
+
+impl Default for Map {
+    /// A map without any sectors
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl PartialEq for Map {
+    /// Compare the content of the maps, where the maps were last saved and
+    /// the sectors changed since then are ignored
+    fn eq(&self, other: &Self) -> bool {
+        // NOTE(patrik): The fields are listed without `..` so new fields
+        // can't be forgotten here
+        let Map {
+            sectors,
+            lightmap,
+            lights,
+            sky,
+            sound_emitters,
+            triggers,
+            entities,
+            embedded_assets,
+            dependencies,
+            color_space,
+            axes,
+            units_per_meter,
+            materials,
+            compression,
+            animations,
+            groups,
+            pvs,
+            waypoints,
+            mesh_library,
+            props,
+            decals,
+            environment,
+            interactives,
+            scripts,
+            unknown_chunks,
+            thumbnail,
+            signature,
+            layout: _,
+            dirty_sectors: _,
+            next_sector_id,
+        } = self;
+
+        *sectors == other.sectors &&
+            *lightmap == other.lightmap &&
+            *lights == other.lights &&
+            *sky == other.sky &&
+            *sound_emitters == other.sound_emitters &&
+            *triggers == other.triggers &&
+            *entities == other.entities &&
+            *embedded_assets == other.embedded_assets &&
+            *dependencies == other.dependencies &&
+            *color_space == other.color_space &&
+            *axes == other.axes &&
+            *units_per_meter == other.units_per_meter &&
+            *materials == other.materials &&
+            *compression == other.compression &&
+            *animations == other.animations &&
+            *groups == other.groups &&
+            *pvs == other.pvs &&
+            *waypoints == other.waypoints &&
+            *mesh_library == other.mesh_library &&
+            *props == other.props &&
+            *decals == other.decals &&
+            *environment == other.environment &&
+            *interactives == other.interactives &&
+            *scripts == other.scripts &&
+            *unknown_chunks == other.unknown_chunks &&
+            *thumbnail == other.thumbnail &&
+            *signature == other.signature &&
+            *next_sector_id == other.next_sector_id
+    }
+}
+
+impl Map {
+    /// Create a new map structure
+    ///
+    /// # Arguments
+    ///
+    /// * `sectors` - Map sectors
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - Returns the created map structure
+    pub fn new(mut sectors: Vec<Sector>) -> Self {
+        for (id, sector) in sectors.iter_mut().enumerate() {
+            sector.id = SectorId(id as u64);
+        }
+
+        Self {
+            next_sector_id: sectors.len() as u64,
+            sectors,
+            lightmap: None,
+            lights: Vec::new(),
+            sky: None,
+            sound_emitters: Vec::new(),
+            triggers: Vec::new(),
+            entities: Vec::new(),
+            embedded_assets: Vec::new(),
+            dependencies: Vec::new(),
+            color_space: ColorSpace::default(),
+            axes: AxisConvention::default(),
+            units_per_meter: DEFAULT_UNITS_PER_METER,
+            materials: Vec::new(),
+            compression: SectorCompression::None,
+            animations: Vec::new(),
+            groups: Vec::new(),
+            pvs: None,
+            waypoints: None,
+            mesh_library: Vec::new(),
+            props: Vec::new(),
+            decals: Vec::new(),
+            environment: Environment::default(),
+            interactives: Vec::new(),
+            scripts: Scripts::default(),
+            unknown_chunks: Vec::new(),
+            thumbnail: None,
+            signature: None,
+            layout: None,
+            dirty_sectors: BTreeSet::new(),
+        }
+    }
+
+    /// Get the first sector with a name
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the sector, see [Sector::name]
+    ///
+    /// # Returns
+    ///
+    /// * `Some((usize, `[Sector]`))` - The index and the sector
+    /// * `None` - The map doesn't have a sector with the name
+    pub fn sector_by_name(&self, name: &str) -> Option<(usize, &Sector)> {
+        self.sectors.iter()
+            .enumerate()
+            .find(|(_, sector)| sector.name.as_deref() == Some(name))
+    }
+
+    /// Get all the sectors with a tag
+    ///
+    /// # Arguments
+    ///
+    /// * `tag` - The tag to search for
+    ///
+    /// # Returns
+    ///
+    /// * An iterator over the index and the sector of every sector with
+    ///   the tag
+    pub fn sectors_with_tag(&self, tag: u32)
+        -> impl Iterator<Item = (usize, &Sector)>
+    {
+        self.sectors.iter()
+            .enumerate()
+            .filter(move |(_, sector)| sector.tag == tag)
+    }
+
+    /// Add a sector to the map and give it a new ID
+    ///
+    /// # Arguments
+    ///
+    /// * `sector` - The sector to add
+    ///
+    /// # Returns
+    ///
+    /// * [SectorId] - The ID of the added sector
+    pub fn add_sector(&mut self, mut sector: Sector) -> SectorId {
+        let id = SectorId(self.next_sector_id);
+        self.next_sector_id += 1;
+
+        sector.id = id;
+        self.sectors.push(sector);
+
+        id
+    }
+
+    /// Remove a sector from the map, the indices of the sectors after it
+    /// are shifted down but their IDs stay the same
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the sector to remove
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[Sector]`)` - The removed sector
+    /// * `None` - The map doesn't have a sector with the ID
+    pub fn remove_sector(&mut self, id: SectorId) -> Option<Sector> {
+        let index = self.sector_index(id)?;
+
+        // NOTE(patrik): The sectors after the removed one moved so the next
+        // save can't be incremental
+        self.layout = None;
+        self.dirty_sectors.clear();
+
+        Some(self.sectors.remove(index))
+    }
+
+    /// Insert a sector at an index and keep the ID of the sector, used to
+    /// restore removed sectors
+    pub(crate) fn insert_sector(&mut self, index: usize, sector: Sector) {
+        self.next_sector_id =
+            self.next_sector_id.max(sector.id.0.saturating_add(1));
+
+        // NOTE(patrik): The sectors after the inserted one moved so the next
+        // save can't be incremental
+        self.layout = None;
+        self.dirty_sectors.clear();
+
+        self.sectors.insert(index, sector);
+    }
+
+    /// Get the index of the sector with an ID
+    pub fn sector_index(&self, id: SectorId) -> Option<usize> {
+        self.sectors.iter().position(|sector| sector.id == id)
+    }
+
+    /// Get the sector with an ID
+    pub fn sector(&self, id: SectorId) -> Option<&Sector> {
+        self.sectors.iter().find(|sector| sector.id == id)
+    }
+
+    /// Get the sector with an ID to modify it, the sector is marked as
+    /// changed for [Map::save_incremental]
+    pub fn sector_mut(&mut self, id: SectorId) -> Option<&mut Sector> {
+        let index = self.sector_index(id)?;
+        self.dirty_sectors.insert(index);
+
+        Some(&mut self.sectors[index])
+    }
+
+    /// Get the number of bytes [Map::serialize] writes
+    ///
+    /// The sectors are compressed to get their size when the map uses
+    /// [Map::compression].
+    pub fn serialized_size(&self) -> usize {
+        let sectors = self.sectors.iter()
+            .map(|sector| {
+                SECTOR_ENTRY_SIZE + sector_block_size(sector, self.compression)
+            })
+            .sum::<usize>();
+
+        MAP_HEADER_SIZE + sectors + self.chunks_size()
+    }
+
+    /// Get the number of bytes [Map::serialize] writes when no sector is
+    /// compressed, used to reserve buffers without compressing the sectors
+    ///
+    /// Compressed sectors are only stored when they are smaller, so the
+    /// size is never less than what [Map::serialize] writes.
+    pub(crate) fn max_serialized_size(&self) -> usize {
+        let sectors = self.sectors.iter()
+            .map(|sector| {
+                SECTOR_ENTRY_SIZE +
+                    sector_block_size(sector, SectorCompression::None)
+            })
+            .sum::<usize>();
+
+        MAP_HEADER_SIZE + sectors + self.chunks_size()
+    }
+
+    /// Get the number of bytes [Map::serialize_chunks] writes
+    fn chunks_size(&self) -> usize {
+        // NOTE(patrik): The color space, the axis convention and the units
+        // per meter are always written so readers don't have to know the
+        // defaults
+        let mut size = 8 + (CHUNK_HEADER_SIZE + 1) * 2 + CHUNK_HEADER_SIZE + 4;
+
+        if let Some(thumbnail) = &self.thumbnail {
+            size += CHUNK_HEADER_SIZE + thumbnail.serialized_size();
+        }
+
+        if let Some(lightmap) = &self.lightmap {
+            size += CHUNK_HEADER_SIZE + lightmap.serialized_size();
+        }
+
+        if !self.lights.is_empty() {
+            size += CHUNK_HEADER_SIZE +
+                list_size(&self.lights, Light::serialized_size);
+        }
+
+        if let Some(sky) = &self.sky {
+            size += CHUNK_HEADER_SIZE + sky.serialized_size();
+        }
+
+        if !self.sound_emitters.is_empty() {
+            size += CHUNK_HEADER_SIZE +
+                list_size(&self.sound_emitters, SoundEmitter::serialized_size);
+        }
+
+        if !self.triggers.is_empty() {
+            size += CHUNK_HEADER_SIZE +
+                list_size(&self.triggers, Trigger::serialized_size);
+        }
+
+        if !self.entities.is_empty() {
+            size += CHUNK_HEADER_SIZE +
+                list_size(&self.entities, Entity::serialized_size);
+        }
+
+        if !self.embedded_assets.is_empty() {
+            size += CHUNK_HEADER_SIZE +
+                list_size(&self.embedded_assets,
+                          EmbeddedAsset::serialized_size);
+        }
+
+        if !self.dependencies.is_empty() {
+            size += CHUNK_HEADER_SIZE +
+                list_size(&self.dependencies,
+                          AssetDependency::serialized_size);
+        }
+
+        if !self.materials.is_empty() {
+            size += CHUNK_HEADER_SIZE +
+                list_size(&self.materials, Material::serialized_size);
+        }
+
+        if !self.animations.is_empty() {
+            size += CHUNK_HEADER_SIZE +
+                list_size(&self.animations, SectorAnimation::serialized_size);
+        }
+
+        if !self.groups.is_empty() {
+            size += CHUNK_HEADER_SIZE +
+                list_size(&self.groups, SectorGroup::serialized_size);
+        }
+
+        if let Some(pvs) = &self.pvs {
+            size += CHUNK_HEADER_SIZE + pvs.serialized_size();
+        }
+
+        if let Some(waypoints) = &self.waypoints {
+            size += CHUNK_HEADER_SIZE + waypoints.serialized_size();
+        }
+
+        if !self.mesh_library.is_empty() {
+            size += CHUNK_HEADER_SIZE + library_size(&self.mesh_library);
+        }
+
+        if !self.props.is_empty() {
+            size += CHUNK_HEADER_SIZE +
+                list_size(&self.props, PropInstance::serialized_size);
+        }
+
+        if !self.decals.is_empty() {
+            size += CHUNK_HEADER_SIZE +
+                list_size(&self.decals, Decal::serialized_size);
+        }
+
+        if self.environment != Environment::default() {
+            size += CHUNK_HEADER_SIZE + ENVIRONMENT_SIZE;
+        }
+
+        if !self.interactives.is_empty() {
+            size += CHUNK_HEADER_SIZE +
+                list_size(&self.interactives, Interactive::serialized_size);
+        }
+
+        if !self.scripts.is_empty() {
+            size += CHUNK_HEADER_SIZE + self.scripts.serialized_size();
+        }
+
+        for chunk in &self.unknown_chunks {
+            size += CHUNK_HEADER_SIZE + chunk.data.len();
+        }
+
+        if self.signature.is_some() {
+            size += CHUNK_HEADER_SIZE + SIGNATURE_SIZE;
+        }
+
+        size
+    }
+
+    /// Serialize the map to a buffer
+    ///
+    /// The output only depends on the content of the map, see
+    /// [crate::canonical] for the guarantees.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - Successfully serialized the map
+    /// * `Err(`[Error]`)` - Failed to serialize the map
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        self.serialize_with_slack(buffer, 0)?;
+
+        Ok(())
+    }
+
+    /// Serialize the map into a slice without allocating
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - The slice to write to, [Map::serialized_size] tells how
+    ///   large it needs to be
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(usize)` - The number of bytes written
+    /// * `Err(`[Error]`)` - Failed to serialize the map or the slice is too
+    ///   small
+    pub fn serialize_into(&self, out: &mut [u8]) -> Result<usize> {
+        let mut output = SliceOutput::new(out);
+        self.serialize(&mut output)?;
+
+        output.finish()
+    }
+
+    /// Serialize the map to a buffer and leave unused space after every
+    /// sector so the sectors can grow without moving the other sectors
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    /// * `slack`  - The unused space after every sector in percent of the
+    ///   size of the sector
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[MapLayout]`)` - Where the sectors and chunks were written
+    /// * `Err(`[Error]`)` - Failed to serialize the map
+    pub(crate) fn serialize_with_slack<O>(&self, buffer: &mut O, slack: usize)
+        -> Result<MapLayout>
+        where O: Output + ?Sized
+    {
+        // NOTE(patrik): The sector table has the size and the checksum of
+        // every sector, compressed sectors are serialized up front to get
+        // their size. With rayon all the sectors are serialized up front in
+        // parallel, otherwise the size of uncompressed sectors is known and
+        // their checksums are filled in as they are written.
+        let blocks = if cfg!(feature = "rayon") ||
+            self.compression != SectorCompression::None
+        {
+            Some(self.sector_blocks(self.compression)?)
+        } else {
+            None
+        };
+
+        let mut offset = MAP_HEADER_SIZE + SECTOR_ENTRY_SIZE * self.sectors.len();
+        let mut slots = Vec::with_capacity(self.sectors.len());
+        for (index, sector) in self.sectors.iter().enumerate() {
+            let slot = match &blocks {
+                Some(blocks) => {
+                    let size = blocks[index].len();
+                    let capacity = size + size * slack / 100;
+                    SectorSlot::new(offset as u64, &blocks[index],
+                                    capacity as u64)
+                }
+
+                None => {
+                    let size = sector_block_size(sector,
+                                                 SectorCompression::None);
+                    let capacity = size + size * slack / 100;
+                    SectorSlot {
+                        offset: offset as u64,
+                        size: size as u64,
+                        capacity: capacity as u64,
+                        checksum: 0,
+                    }
+                }
+            };
+
+            offset += slot.capacity as usize;
+            slots.push(slot);
+        }
+
+        let chunks_offset = offset as u64;
+        buffer.reserve(offset + self.chunks_size());
+
+        // Serialize the header and the sector table
+        write_usize(buffer, self.sectors.len())?;
+        buffer.extend_from_slice(&chunks_offset.to_le_bytes());
+        buffer.extend_from_slice(&self.next_sector_id.to_le_bytes());
+        buffer.push(self.compression.to_u8());
+        let table = buffer.position();
+        for slot in &slots {
+            buffer.extend_from_slice(&slot.to_bytes());
+        }
+
+        // Serialize all the sectors followed by the unused space of their
+        // slots
+        match &blocks {
+            Some(blocks) => {
+                for (block, slot) in blocks.iter().zip(&slots) {
+                    buffer.extend_from_slice(block);
+                    write_zeros(buffer, (slot.capacity - slot.size) as usize);
+                }
+            }
+
+            // NOTE(patrik): Only one sector is kept in memory at a time,
+            // the checksum is patched into the table once it is known
+            None => {
+                let mut block = Vec::new();
+                let sectors = self.sectors.iter().zip(&mut slots);
+                for (index, (sector, slot)) in sectors.enumerate() {
+                    block.clear();
+                    block.push(SectorCompression::None.to_u8());
+                    sector.serialize(&mut block)?;
+                    debug_assert_eq!(block.len() as u64, slot.size);
+
+                    *slot = SectorSlot::new(slot.offset, &block,
+                                            slot.capacity);
+                    buffer.patch(table + index * SECTOR_ENTRY_SIZE,
+                                 &slot.to_bytes());
+
+                    buffer.extend_from_slice(&block);
+                    write_zeros(buffer, (slot.capacity - slot.size) as usize);
+                }
+            }
+        }
+
+        self.serialize_chunks(buffer)?;
+
+        Ok(MapLayout {
+            slots,
+            chunks_offset,
+            compression: self.compression,
+            size: chunks_offset + self.chunks_size() as u64,
+        })
+    }
+
+    /// Serialize every sector into its own block
+    #[cfg(not(feature = "rayon"))]
+    fn sector_blocks(&self, compression: SectorCompression)
+        -> Result<Vec<Vec<u8>>>
+    {
+        self.sectors.iter()
+            .map(|sector| sector_block(sector, compression))
+            .collect()
+    }
+
+    /// Serialize every sector into its own block in parallel
+    #[cfg(feature = "rayon")]
+    fn sector_blocks(&self, compression: SectorCompression)
+        -> Result<Vec<Vec<u8>>>
+    {
+        use rayon::prelude::*;
+
+        self.sectors.par_iter()
+            .map(|sector| sector_block(sector, compression))
+            .collect()
+    }
+
+    /// Serialize the optional chunks of the map
+    pub(crate) fn serialize_chunks<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        let mut chunks = ChunkWriter::new(buffer);
+
+        // NOTE(patrik): The thumbnail goes first so finding it only needs
+        // to read the first chunk header
+        if let Some(thumbnail) = &self.thumbnail {
+            chunks.chunk(THUMBNAIL_CHUNK, |buffer| {
+                thumbnail.serialize(buffer)
+            })?;
+        }
+
+        chunks.chunk(COLOR_SPACE_CHUNK, |buffer| {
+            buffer.push(self.color_space.to_u8());
+            Ok(())
+        })?;
+
+        chunks.chunk(AXES_CHUNK, |buffer| {
+            buffer.push(self.axes.to_u8());
+            Ok(())
+        })?;
+
+        chunks.chunk(UNITS_CHUNK, |buffer| {
+            buffer.extend_from_slice(&self.units_per_meter.to_le_bytes());
+            Ok(())
+        })?;
+
+        if let Some(lightmap) = &self.lightmap {
+            chunks.chunk(LIGHTMAP_CHUNK, |buffer| lightmap.serialize(buffer))?;
+        }
+
+        if !self.lights.is_empty() {
+            chunks.chunk(LIGHTS_CHUNK, |buffer| {
+                write_list(buffer, &self.lights, Light::serialize)
+            })?;
+        }
+
+        if let Some(sky) = &self.sky {
+            chunks.chunk(SKY_CHUNK, |buffer| sky.serialize(buffer))?;
+        }
+
+        if !self.sound_emitters.is_empty() {
+            chunks.chunk(SOUND_EMITTERS_CHUNK, |buffer| {
+                write_list(buffer, &self.sound_emitters,
+                           SoundEmitter::serialize)
+            })?;
+        }
+
+        if !self.triggers.is_empty() {
+            chunks.chunk(TRIGGERS_CHUNK, |buffer| {
+                write_list(buffer, &self.triggers, Trigger::serialize)
+            })?;
+        }
+
+        if !self.entities.is_empty() {
+            chunks.chunk(ENTITIES_CHUNK, |buffer| {
+                write_list(buffer, &self.entities, Entity::serialize)
+            })?;
+        }
+
+        if !self.embedded_assets.is_empty() {
+            chunks.chunk(EMBEDDED_ASSETS_CHUNK, |buffer| {
+                write_list(buffer, &self.embedded_assets,
+                           EmbeddedAsset::serialize)
+            })?;
+        }
+
+        if !self.dependencies.is_empty() {
+            chunks.chunk(DEPENDENCIES_CHUNK, |buffer| {
+                write_list(buffer, &self.dependencies,
+                           AssetDependency::serialize)
+            })?;
+        }
+
+        if !self.materials.is_empty() {
+            chunks.chunk(MATERIALS_CHUNK, |buffer| {
+                write_list(buffer, &self.materials, Material::serialize)
+            })?;
+        }
+
+        if !self.animations.is_empty() {
+            chunks.chunk(ANIMATIONS_CHUNK, |buffer| {
+                write_list(buffer, &self.animations,
+                           SectorAnimation::serialize)
+            })?;
+        }
+
+        if !self.groups.is_empty() {
+            chunks.chunk(GROUPS_CHUNK, |buffer| {
+                write_list(buffer, &self.groups, SectorGroup::serialize)
+            })?;
+        }
+
+        if let Some(pvs) = &self.pvs {
+            chunks.chunk(PVS_CHUNK, |buffer| pvs.serialize(buffer))?;
+        }
+
+        if let Some(waypoints) = &self.waypoints {
+            chunks.chunk(WAYPOINTS_CHUNK, |buffer| {
+                waypoints.serialize(buffer)
+            })?;
+        }
+
+        if !self.mesh_library.is_empty() {
+            chunks.chunk(MESH_LIBRARY_CHUNK, |buffer| {
+                write_library(buffer, &self.mesh_library)
+            })?;
+        }
+
+        if !self.props.is_empty() {
+            chunks.chunk(PROPS_CHUNK, |buffer| {
+                write_list(buffer, &self.props, PropInstance::serialize)
+            })?;
+        }
+
+        if !self.decals.is_empty() {
+            chunks.chunk(DECALS_CHUNK, |buffer| {
+                write_list(buffer, &self.decals, Decal::serialize)
+            })?;
+        }
+
+        if self.environment != Environment::default() {
+            chunks.chunk(ENVIRONMENT_CHUNK, |buffer| {
+                self.environment.serialize(buffer);
+                Ok(())
+            })?;
+        }
+
+        if !self.interactives.is_empty() {
+            chunks.chunk(INTERACTIVES_CHUNK, |buffer| {
+                write_list(buffer, &self.interactives, Interactive::serialize)
+            })?;
+        }
 
+        if !self.scripts.is_empty() {
+            chunks.chunk(SCRIPTS_CHUNK, |buffer| {
+                self.scripts.serialize(buffer)
+            })?;
+        }
+
+        for chunk in &self.unknown_chunks {
+            chunks.chunk(&chunk.tag, |buffer| {
+                buffer.extend_from_slice(&chunk.data);
+                Ok(())
+            })?;
+        }
+
+        // NOTE(patrik): The signature is the last chunk so it trails the
+        // content it signs
+        if let Some(signature) = &self.signature {
+            chunks.chunk(SIGNATURE_CHUNK, |buffer| {
+                buffer.extend_from_slice(signature);
+                Ok(())
+            })?;
+        }
+
+        chunks.finish();
+
+        Ok(())
+    }
+
+    /// Deserialize the buffer and create a map structure
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Map]`)` - Successfully derserialized the data and created a
+    ///   map structure
+    /// * `Err(`[Error]`)` - Failed to deserialize the data
+    pub fn deserialize(buffer: &[u8]) -> Result<Self> {
+        Self::deserialize_with_options(buffer, &DeserializeOptions::default())
+    }
+
+    /// Deserialize the buffer and create a map structure, the counts inside
+    /// the buffer are checked against the limits before allocating
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer`  - The buffer we should deserialize
+    /// * `options` - The limits for the sectors, vertices, indices and the
+    ///   allocated memory, and if the indices should be checked
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Map]`)` - Successfully derserialized the data and created a
+    ///   map structure
+    /// * `Err(`[Error]`)` - Failed to deserialize the data or the map
+    ///   exceeds one of the limits
+    pub fn deserialize_with_options(buffer: &[u8],
+                                    options: &DeserializeOptions)
+        -> Result<Self>
+    {
+        Self::deserialize_with_layout(buffer, &Budget::new(*options))
+            .map(|(map, _)| map)
+    }
+
+    /// Deserialize a map stored at the start of the buffer, like a map
+    /// embedded inside a larger file, the bytes after the map are ignored
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer starting with the map
+    ///
+    /// # Returns
+    ///
+    /// * `Ok((`[Map]`, usize))` - The map and the number of bytes it used,
+    ///   the rest of the buffer starts there
+    /// * `Err(`[Error]`)` - Failed to deserialize the map
+    pub fn deserialize_prefix(buffer: &[u8]) -> Result<(Self, usize)> {
+        Self::deserialize_prefix_with_options(buffer,
+                                              &DeserializeOptions::default())
+    }
+
+    /// Deserialize a map stored at the start of the buffer with limits, see
+    /// [Map::deserialize_prefix] and [Map::deserialize_with_options]
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer`  - The buffer starting with the map
+    /// * `options` - The limits for the sectors, vertices, indices and the
+    ///   allocated memory, and if the indices should be checked
+    ///
+    /// # Returns
+    ///
+    /// * `Ok((`[Map]`, usize))` - The map and the number of bytes it used,
+    ///   the rest of the buffer starts there
+    /// * `Err(`[Error]`)` - Failed to deserialize the map or the map
+    ///   exceeds one of the limits
+    pub fn deserialize_prefix_with_options(buffer: &[u8],
+                                           options: &DeserializeOptions)
+        -> Result<(Self, usize)>
+    {
+        let (map, layout) =
+            Self::deserialize_with_layout(buffer, &Budget::new(*options))?;
+
+        Ok((map, layout.size as usize))
+    }
+
+    /// Deserialize a map that has to fill the whole buffer, see
+    /// [Map::deserialize_prefix]
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Map]`)` - Successfully deserialized the map
+    /// * `Err(`[Error]`)` - Failed to deserialize the map or
+    ///   [Error::TrailingBytes] if the map ends before the buffer
+    pub fn deserialize_strict(buffer: &[u8]) -> Result<Self> {
+        Self::deserialize_strict_with_options(buffer,
+                                              &DeserializeOptions::default())
+    }
+
+    /// Deserialize a map that has to fill the whole buffer with limits, see
+    /// [Map::deserialize_strict] and [Map::deserialize_with_options]
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer`  - The buffer we should deserialize
+    /// * `options` - The limits for the sectors, vertices, indices and the
+    ///   allocated memory, and if the indices should be checked
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Map]`)` - Successfully deserialized the map
+    /// * `Err(`[Error]`)` - Failed to deserialize the map, the map exceeds
+    ///   one of the limits or [Error::TrailingBytes] if the map ends before
+    ///   the buffer
+    pub fn deserialize_strict_with_options(buffer: &[u8],
+                                           options: &DeserializeOptions)
+        -> Result<Self>
+    {
+        let (map, size) =
+            Self::deserialize_prefix_with_options(buffer, options)?;
+        if size != buffer.len() {
+            let error = Error::TrailingBytes(buffer.len() - size);
+            return Err(error.at_offset(size));
+        }
+
+        Ok(map)
+    }
+
+    /// Deserialize the map and return where the sectors and chunks were
+    /// stored inside the buffer
+    pub(crate) fn deserialize_with_layout(buffer: &[u8], budget: &Budget)
+        -> Result<(Self, MapLayout)>
+    {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallMap);
+
+        let sector_count = reader.usize()?;
+        let chunks_offset = reader.u64()?;
+        let next_sector_id = reader.u64()?;
+        let compression = read_header_compression(&mut reader)?;
+
+        budget.sectors(sector_count)?;
+
+        let mut slots = Vec::new();
+        for _i in 0..sector_count {
+            slots.push(SectorSlot::from_bytes(&reader.array()?));
+        }
+
+        let sectors = Self::deserialize_sectors(buffer, &slots, budget)?;
+
+        // NOTE(patrik): Map::new would give the sectors new IDs
+        let mut map = Self::new(Vec::new());
+        map.next_sector_id = clamp_next_sector_id(next_sector_id, &sectors);
+        map.sectors = sectors;
+        map.compression = compression;
+
+        let offset = usize::try_from(chunks_offset).unwrap_or(usize::MAX);
+        let Some(chunks) = buffer.get(offset..) else {
+            // NOTE(patrik): Point at the chunks offset inside the header
+            let size = SizeMismatch::new(offset, buffer.len());
+            return Err(Error::BufferToSmallMap(size).at_offset(8));
+        };
+        let chunks_size = map.deserialize_chunks(chunks)
+            .map_err(|error| error.at_offset(offset))?;
+
+        Ok((map, MapLayout {
+            slots,
+            chunks_offset,
+            compression,
+            size: (offset + chunks_size) as u64,
+        }))
+    }
+
+    /// Deserialize the sectors stored in the slots of the sector table
+    #[cfg(not(feature = "rayon"))]
+    fn deserialize_sectors(buffer: &[u8],
+                           slots: &[SectorSlot],
+                           budget: &Budget)
+        -> Result<Vec<Sector>>
+    {
+        slots.iter()
+            .enumerate()
+            .map(|(index, slot)| {
+                Self::deserialize_sector(buffer, index, *slot, budget)
+            })
+            .collect()
+    }
+
+    /// Deserialize the sectors stored in the slots of the sector table, the
+    /// sectors are deserialized in parallel
+    #[cfg(feature = "rayon")]
+    fn deserialize_sectors(buffer: &[u8],
+                           slots: &[SectorSlot],
+                           budget: &Budget)
+        -> Result<Vec<Sector>>
+    {
+        use rayon::prelude::*;
+
+        slots.par_iter()
+            .enumerate()
+            .map(|(index, slot)| {
+                Self::deserialize_sector(buffer, index, *slot, budget)
+            })
+            .collect()
+    }
+
+    /// Deserialize the sector stored in a slot of the sector table, the
+    /// index of the sector is added to the errors
+    pub(crate) fn deserialize_sector(buffer: &[u8],
+                                     index: usize,
+                                     slot: SectorSlot,
+                                     budget: &Budget)
+        -> Result<Sector>
+    {
+        let sector = match slot.data(buffer) {
+            // NOTE(patrik): The offset fits inside a usize, the data was
+            // found inside the buffer
+            Ok(data) => slot.verify(data)
+                .and_then(|_| read_sector_block(data, budget))
+                .map_err(|error| error.at_offset(slot.offset as usize)),
+
+            Err(error) => {
+                let entry = MAP_HEADER_SIZE + index * SECTOR_ENTRY_SIZE;
+                Err(error.at_offset(entry))
+            }
+        };
+
+        sector.map_err(|error| {
+            error.with_context(|context| context.sector = Some(index))
+        })
+    }
+
+    /// Deserialize the optional chunks of the map, unknown chunks are kept
+    /// in [Map::unknown_chunks]
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(usize)` - The size of the chunk list, the bytes after it
+    ///   aren't read
+    /// * `Err(`[Error]`)` - Failed to deserialize a chunk
+    pub(crate) fn deserialize_chunks(&mut self, buffer: &[u8])
+        -> Result<usize>
+    {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallMap);
+
+        let chunk_count = reader.usize()?;
+        for _i in 0..chunk_count {
+            let tag = reader.array::<4>()?;
+            reader.block(|chunk| self.deserialize_chunk(&tag, chunk))?;
+        }
+
+        self.check_props()?;
+        self.check_interactives()?;
+        self.check_scripts()?;
+
+        Ok(reader.offset())
+    }
+
+    /// Find the content of a chunk inside the chunk list without
+    /// deserializing the other chunks
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(&[u8]))` - The content of the first chunk with the tag
+    /// * `Ok(None)` - The chunk list doesn't have a chunk with the tag
+    /// * `Err(`[Error]`)` - The chunk list is truncated
+    pub(crate) fn find_chunk<'a>(buffer: &'a [u8], tag: &[u8; 4])
+        -> Result<Option<&'a [u8]>>
+    {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallMap);
+
+        let chunk_count = reader.usize()?;
+        for _i in 0..chunk_count {
+            let chunk_tag = reader.array::<4>()?;
+            let chunk = reader.sized()?;
+            if &chunk_tag == tag {
+                return Ok(Some(chunk));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Deserialize the content of a single chunk, unknown chunks are kept
+    /// in [Map::unknown_chunks]
+    pub(crate) fn deserialize_chunk(&mut self,
+                                    tag: &[u8; 4],
+                                    chunk: &[u8])
+        -> Result<()>
+    {
+        let reader = || Reader::new(chunk, Error::BufferToSmallChunk);
+
+        match tag {
+            LIGHTMAP_CHUNK => {
+                self.lightmap = Some(Lightmap::deserialize(chunk)?);
+            }
+
+            LIGHTS_CHUNK => self.lights = reader().list(Light::deserialize)?,
+
+            SKY_CHUNK => self.sky = Some(Sky::deserialize(chunk)?),
+
+            SOUND_EMITTERS_CHUNK => {
+                self.sound_emitters = reader().list(SoundEmitter::deserialize)?;
+            }
+
+            TRIGGERS_CHUNK => {
+                self.triggers = reader().list(Trigger::deserialize)?;
+            }
+
+            ENTITIES_CHUNK => {
+                self.entities = reader().list(Entity::deserialize)?;
+            }
+
+            EMBEDDED_ASSETS_CHUNK => {
+                self.embedded_assets =
+                    reader().list(EmbeddedAsset::deserialize)?;
+            }
+
+            DEPENDENCIES_CHUNK => {
+                self.dependencies =
+                    reader().list(AssetDependency::deserialize)?;
+            }
+
+            MATERIALS_CHUNK => {
+                self.materials = reader().list(Material::deserialize)?;
+            }
+
+            ANIMATIONS_CHUNK => {
+                self.animations =
+                    reader().list(SectorAnimation::deserialize)?;
+            }
+
+            GROUPS_CHUNK => {
+                self.groups = reader().list(SectorGroup::deserialize)?;
+            }
+
+            PVS_CHUNK => {
+                self.pvs = Some(Pvs::deserialize(chunk)?);
+            }
+
+            WAYPOINTS_CHUNK => {
+                self.waypoints = Some(WaypointGraph::deserialize(chunk)?);
+            }
+
+            MESH_LIBRARY_CHUNK => {
+                self.mesh_library = read_library(chunk)?;
+            }
+
+            PROPS_CHUNK => {
+                self.props = reader().list(PropInstance::deserialize)?;
+            }
+
+            DECALS_CHUNK => {
+                self.decals = reader().list(Decal::deserialize)?;
+            }
+
+            ENVIRONMENT_CHUNK => {
+                self.environment = Environment::read(&mut reader())?;
+            }
+
+            INTERACTIVES_CHUNK => {
+                self.interactives = reader().list(Interactive::deserialize)?;
+            }
+
+            SCRIPTS_CHUNK => {
+                self.scripts = Scripts::read(&mut reader())?;
+            }
+
+            THUMBNAIL_CHUNK => {
+                self.thumbnail = Some(Thumbnail::deserialize(chunk)?);
+            }
+
+            SIGNATURE_CHUNK => {
+                self.signature = Some(reader().array()?);
+            }
+
+            COLOR_SPACE_CHUNK => {
+                self.color_space = ColorSpace::from_u8(reader().u8()?)?;
+            }
+
+            AXES_CHUNK => {
+                self.axes = AxisConvention::from_u8(reader().u8()?)?;
+            }
+
+            UNITS_CHUNK => {
+                self.units_per_meter =
+                    check_units_per_meter(reader().f32()?)?;
+            }
+
+            tag => self.unknown_chunks.push(UnknownChunk {
+                tag: *tag,
+                data: chunk.to_vec(),
+            }),
+        }
+
+        Ok(())
+    }
+}
+
+/// The location of a serialized sector relative to the start of the map
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) struct SectorSlot {
+    /// The offset of the sector
+    pub(crate) offset: u64,
+
+    /// The size of the serialized sector
+    pub(crate) size: u64,
+
+    /// The space reserved for the sector, at least the size
+    pub(crate) capacity: u64,
+
+    /// The xxHash64 of the serialized sector, the unused space of the slot
+    /// isn't included
+    pub(crate) checksum: u64,
+}
+
+impl SectorSlot {
+    /// Create the slot of a serialized sector
+    ///
+    /// # Arguments
+    ///
+    /// * `offset`   - The offset of the sector
+    /// * `block`    - The serialized sector
+    /// * `capacity` - The space reserved for the sector
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The slot with the size and checksum of the sector
+    pub(crate) fn new(offset: u64, block: &[u8], capacity: u64) -> Self {
+        Self {
+            offset,
+            size: block.len() as u64,
+            capacity,
+            checksum: xxhash_rust::xxh64::xxh64(block, 0),
+        }
+    }
+
+    /// Check the serialized sector against the checksum of the slot
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The sector is intact
+    /// * `Err(`[Error]`)` - [Error::SectorChecksumMismatch] if the sector
+    ///   was corrupted
+    pub(crate) fn verify(self, block: &[u8]) -> Result<()> {
+        if xxhash_rust::xxh64::xxh64(block, 0) != self.checksum {
+            return Err(Error::SectorChecksumMismatch);
+        }
+
+        Ok(())
+    }
+
+    /// Convert the slot to its entry inside the sector table
+    pub(crate) fn to_bytes(self) -> [u8; SECTOR_ENTRY_SIZE] {
+        let mut bytes = [0; SECTOR_ENTRY_SIZE];
+        bytes[0..8].copy_from_slice(&self.offset.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.size.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.capacity.to_le_bytes());
+        bytes[24..32].copy_from_slice(&self.checksum.to_le_bytes());
+
+        bytes
+    }
+
+    /// Create the slot from its entry inside the sector table
+    pub(crate) fn from_bytes(bytes: &[u8; SECTOR_ENTRY_SIZE]) -> Self {
+        let value = |i: usize| {
+            u64::from_le_bytes(bytes[i * 8..i * 8 + 8].try_into().unwrap())
+        };
+
+        Self {
+            offset: value(0),
+            size: value(1),
+            capacity: value(2),
+            checksum: value(3),
+        }
+    }
+
+    /// Get the serialized sector from the buffer of the map
+    pub(crate) fn data(self, buffer: &[u8]) -> Result<&[u8]> {
+        // NOTE(patrik): Offsets that don't fit are clamped so they always
+        // end up past the end of the buffer
+        let offset = usize::try_from(self.offset).unwrap_or(usize::MAX);
+        let size = usize::try_from(self.size).unwrap_or(usize::MAX);
+        let end = offset.saturating_add(size);
+
+        buffer.get(offset..end)
+            .ok_or_else(|| {
+                Error::BufferToSmallMap(SizeMismatch::new(end, buffer.len()))
+            })
+    }
+}
+
+/// Where the sectors and chunks of a map were serialized
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) struct MapLayout {
+    /// The location of every sector
+    pub(crate) slots: Vec<SectorSlot>,
+
+    /// The offset of the chunk list relative to the start of the map
+    pub(crate) chunks_offset: u64,
+
+    /// The compression recorded in the header
+    pub(crate) compression: SectorCompression,
+
+    /// The number of bytes of the map, the chunk list is always stored
+    /// last so the map ends with it
+    pub(crate) size: u64,
+}
+
+/// Read the compression from the map header, the codec has to be supported
+/// to read the sectors
+pub(crate) fn read_header_compression(reader: &mut Reader)
+    -> Result<SectorCompression>
+{
+    let start = reader.offset();
+    SectorCompression::from_u8(reader.u8()?)
+        .and_then(|compression| {
+            compression.check_supported()?;
+            Ok(compression)
+        })
+        .map_err(|error| error.at_offset(start))
+}
+
+/// Get the ID given to the next added sector from the one stored in a
+/// header, a header value at or below the ID of one of the sectors would
+/// hand out that ID again so it's raised past the highest ID
+///
+/// # Arguments
+///
+/// * `next_sector_id` - The next sector ID stored in the header
+/// * `sectors`        - The sectors loaded from the map
+///
+/// # Returns
+///
+/// * `u64` - The next sector ID to use
+pub(crate) fn clamp_next_sector_id<'a, I>(next_sector_id: u64, sectors: I)
+    -> u64
+    where I: IntoIterator<Item = &'a Sector>
+{
+    sectors.into_iter()
+        .map(|sector| sector.id.0.saturating_add(1))
+        .fold(next_sector_id, u64::max)
+}
+
+/// The top level mime file containing all the maps
+#[derive(Default)]
+pub struct Mime {
+    maps: Vec<Map>,
+    // textures: Vec<Texture>,
+}
+
+impl Mime {
+    /// Create a new empty mime file
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new mime file without any maps
+    pub fn new() -> Self {
+        Self {
+            maps: Vec::new(),
+        }
+    }
+
+    /// Add a map to the mime file
+    ///
+    /// # Arguments
+    ///
+    /// * `map` - The map to add
     pub fn add_map(&mut self, map: Map) {
         self.maps.push(map);
     }
 
-    pub fn serialize(&self, buffer: &mut Vec<u8>) -> Result<()> {
+    /// Get the maps inside the mime file
+    pub fn maps(&self) -> &[Map] {
+        &self.maps
+    }
+
+    /// Take the maps out of the mime file
+    pub fn into_maps(self) -> Vec<Map> {
+        self.maps
+    }
+
+    /// Get the number of bytes [Mime::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        HEADER_SIZE + 8 + self.maps.iter()
+            .map(|map| 8 + map.serialized_size())
+            .sum::<usize>()
+    }
+
+    /// Serialize the mime file with the header and all the maps to a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - Successfully serialized the mime file
+    /// * `Err(`[Error]`)` - Failed to serialize the mime file
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
         // Magic
         buffer.extend_from_slice(b"MIME");
 
@@ -515,37 +2561,75 @@ impl Mime {
         buffer.extend_from_slice(&map_count.to_le_bytes());
 
         for map in &self.maps {
-            let mut map_buffer = Vec::new();
-            map.serialize(&mut map_buffer)?;
-
-            let map_size: u64 =
-                map_buffer.len().try_into()
-                    .map_err(Error::IntegerConvertionError)?;
-            buffer.extend_from_slice(&map_size.to_le_bytes());
-            buffer.extend_from_slice(&map_buffer);
+            write_sized(buffer, |buffer| map.serialize(buffer))?;
         }
 
-        return Ok(());
+        Ok(())
+    }
+
+    /// Serialize the mime file into a slice without allocating
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - The slice to write to, [Mime::serialized_size] tells how
+    ///   large it needs to be
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(usize)` - The number of bytes written
+    /// * `Err(`[Error]`)` - Failed to serialize the mime file or the slice
+    ///   is too small
+    pub fn serialize_into(&self, out: &mut [u8]) -> Result<usize> {
+        let mut output = SliceOutput::new(out);
+        self.serialize(&mut output)?;
+
+        output.finish()
     }
 
+    /// Deserialize the buffer and create a mime file
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the mime file
+    /// * `Err(`[Error]`)` - Failed to deserialize the mime file
     pub fn deserialize(buffer: &[u8]) -> Result<Self> {
-        if buffer.len() < HEADER_SIZE {
-            return Err(Error::BufferToSmallMap);
-        }
+        Self::deserialize_with_options(buffer, &DeserializeOptions::default())
+    }
 
-        let magic = &buffer[0..4];
-        if magic != HEADER_MAGIC {
-            return Err(Error::IncorrectMagic);
-        }
+    /// Deserialize the buffer and create a mime file, the limits are shared
+    /// by all the maps
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer`  - The buffer we should deserialize
+    /// * `options` - The limits for the sectors, vertices, indices and the
+    ///   allocated memory, and if the indices should be checked
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the mime file
+    /// * `Err(`[Error]`)` - Failed to deserialize the mime file or it
+    ///   exceeds one of the limits
+    pub fn deserialize_with_options(buffer: &[u8],
+                                    options: &DeserializeOptions)
+        -> Result<Self>
+    {
+        let budget = Budget::new(*options);
 
-        let version = u32::from_le_bytes(
-            buffer[4..8].try_into()
-                .map_err(Error::SliceConvertionError)?);
-        if version != CURRENT_VERSION {
-            return Err(Error::IncorrectVersion);
+        // Header and map count
+        if buffer.len() < HEADER_SIZE + 8 {
+            let size = SizeMismatch::new(HEADER_SIZE + 8, buffer.len());
+            return Err(Error::BufferToSmallHeader(size));
         }
 
-        let buffer = &buffer[8..];
+        let mut reader = Reader::new(buffer, Error::BufferToSmallHeader);
+        read_magic_and_version(&mut reader, HEADER_MAGIC)?;
+
+        let buffer = &buffer[HEADER_SIZE..];
 
         let map_count = u64::from_le_bytes(
             buffer[0..8].try_into()
@@ -555,30 +2639,27 @@ impl Mime {
 
         let buffer = &buffer[8..];
 
-        let mut maps = Vec::with_capacity(map_count);
+        // NOTE(patrik): Every map is prefixed with its size, so the buffer
+        // can't hold more maps than that
+        budget.allocate::<Map>(map_count)?;
+        let mut maps = Vec::with_capacity(map_count.min(buffer.len() / 8));
 
-        let mut offset = 0;
+        // NOTE(patrik): The reader checks the map sizes against the buffer
+        // without adding them to the offset first, so crafted sizes can't
+        // overflow
+        let mut reader = Reader::new(buffer, Error::BufferToSmallMap);
         for _i in 0..map_count {
-            let start = offset;
-
-            let map_size = u64::from_le_bytes(
-                buffer[start..start + 8].try_into()
-                    .map_err(Error::SliceConvertionError)?);
-            let map_size: usize = map_size.try_into()
-                .map_err(Error::IntegerConvertionError)?;
-
-            let start = start + std::mem::size_of::<u64>();
-
-            let map =
-                Map::deserialize(&buffer[start..start + map_size])?;
-            maps.push(map);
-
-            offset += map_size + std::mem::size_of::<u64>();
+            // NOTE(patrik): The offsets of the errors are relative to the
+            // start of the file
+            let map = reader
+                .block(|map| Map::deserialize_with_layout(map, &budget))
+                .map_err(|error| error.at_offset(HEADER_SIZE + 8))?;
+            maps.push(map.0);
         }
 
-        return Ok(Self {
+        Ok(Self {
             maps
-        });
+        })
     }
 
     // TODO(patrik): Fix comment
@@ -587,19 +2668,23 @@ impl Mime {
     /// # Arguments
     ///
     /// * `filename` - Filename of the file we should create to write
-    ///                the serialized data to
+    ///   the serialized data to
     ///
     /// # Returns
     ///
     /// * `Ok(())` - Successfully serialized the map and wrote the date
-    ///              to the file
+    ///   to the file
     /// * `Err(`[Error]`)` - Failed to serialize the map or write the data
-    ///                      to the file
+    ///   to the file
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
     pub fn save_to_file<P>(&self, filename: P) -> Result<()>
         where P: AsRef<Path>
     {
         // Create the buffer holding the serialized data
-        let mut buffer = Vec::new();
+        let max_size = HEADER_SIZE + 8 + self.maps.iter()
+            .map(|map| 8 + map.max_serialized_size())
+            .sum::<usize>();
+        let mut buffer = Vec::with_capacity(max_size);
 
         // Serialize the map
         self.serialize(&mut buffer)?;
@@ -0,0 +1,247 @@
+//! Materials describing how the surfaces of the map look
+
+use crate::*;
+use crate::buffer::{ Reader, write_f32s, write_string, string_size };
+
+/// How the surface is combined with what is behind it
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum BlendMode {
+    /// The surface hides everything behind it, the alpha is ignored
+    #[default]
+    Opaque,
+
+    /// The surface is either fully opaque or fully transparent
+    Masked {
+        /// The pixels with a lower alpha than the cutoff are discarded
+        cutoff: f32,
+    },
+
+    /// The surface is blended with what is behind it using the alpha
+    Blend,
+
+    /// The surface is added on top of what is behind it
+    Additive,
+}
+
+/// A physically based material stored inside the material table of the
+/// map, see [Mesh::material]
+#[derive(Clone, PartialEq, Debug)]
+pub struct Material {
+    /// The name used to reference the material
+    pub name: String,
+
+    /// The base color multiplied with the base color texture (r, g, b, a)
+    pub base_color: [f32; 4],
+
+    /// How metallic the surface is, 0 for dielectrics and 1 for metals
+    pub metallic: f32,
+
+    /// How rough the surface is, 0 for a mirror and 1 for fully diffuse
+    pub roughness: f32,
+
+    /// The light emitted by the surface (r, g, b)
+    pub emissive: [f32; 3],
+
+    /// The name of the base color texture
+    pub base_color_texture: Option<String>,
+
+    /// The name of the texture with the metallic value in the blue channel
+    /// and the roughness in the green channel
+    pub metallic_roughness_texture: Option<String>,
+
+    /// The name of the tangent space normal map
+    pub normal_texture: Option<String>,
+
+    /// How the surface is combined with what is behind it
+    pub blend_mode: BlendMode,
+}
+
+impl Material {
+    /// Creates a new opaque, white and fully rough material without
+    /// textures
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name used to reference the material
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new material
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            base_color: [1.0; 4],
+            metallic: 0.0,
+            roughness: 1.0,
+            emissive: [0.0; 3],
+            base_color_texture: None,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            blend_mode: BlendMode::Opaque,
+        }
+    }
+
+    /// Get the textures of the material in the order they are serialized
+    fn textures(&self) -> [&Option<String>; 3] {
+        [
+            &self.base_color_texture,
+            &self.metallic_roughness_texture,
+            &self.normal_texture,
+        ]
+    }
+
+    /// Get the number of bytes [Material::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        // Base color, metallic, roughness, emissive and the blend cutoff
+        let values = 10 * std::mem::size_of::<f32>();
+
+        // A byte telling if the texture is used followed by its name
+        let textures = self.textures().iter()
+            .map(|texture| 1 + texture.as_deref().map_or(0, string_size))
+            .sum::<usize>();
+
+        // The name and the blend mode
+        string_size(&self.name) + values + textures + 1
+    }
+
+    /// Serialize the material to a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - Successfully serialized the material
+    /// * `Err(`[Error]`)` - Failed to serialize the material
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        write_string(buffer, &self.name)?;
+        write_f32s(buffer, &self.base_color);
+        write_f32s(buffer, &[self.metallic, self.roughness]);
+        write_f32s(buffer, &self.emissive);
+
+        for texture in self.textures() {
+            match texture {
+                Some(texture) => {
+                    buffer.push(1);
+                    write_string(buffer, texture)?;
+                }
+
+                None => buffer.push(0),
+            }
+        }
+
+        let (mode, cutoff) = match self.blend_mode {
+            BlendMode::Opaque => (0, 0.0),
+            BlendMode::Masked { cutoff } => (1, cutoff),
+            BlendMode::Blend => (2, 0.0),
+            BlendMode::Additive => (3, 0.0),
+        };
+        buffer.push(mode);
+        buffer.extend_from_slice(&f32::to_le_bytes(cutoff));
+
+        Ok(())
+    }
+
+    /// Deserialize the material from a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the material
+    /// * `Err(`[Error]`)` - Failed to deserialize the material
+    pub fn deserialize(buffer: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallChunk);
+
+        let name = reader.string()?;
+        let base_color = [reader.f32()?, reader.f32()?,
+                          reader.f32()?, reader.f32()?];
+        let metallic = reader.f32()?;
+        let roughness = reader.f32()?;
+        let emissive = reader.vec3()?;
+
+        let mut texture = || -> Result<Option<String>> {
+            match reader.u8()? {
+                0 => Ok(None),
+                _ => reader.string().map(Some),
+            }
+        };
+        let base_color_texture = texture()?;
+        let metallic_roughness_texture = texture()?;
+        let normal_texture = texture()?;
+
+        let mode = reader.u8()?;
+        let cutoff = reader.f32()?;
+        let blend_mode = match mode {
+            0 => BlendMode::Opaque,
+            1 => BlendMode::Masked { cutoff },
+            2 => BlendMode::Blend,
+            3 => BlendMode::Additive,
+            mode => return Err(Error::UnknownBlendMode(mode)),
+        };
+
+        Ok(Self {
+            name,
+            base_color,
+            metallic,
+            roughness,
+            emissive,
+            base_color_texture,
+            metallic_roughness_texture,
+            normal_texture,
+            blend_mode,
+        })
+    }
+}
+
+impl Map {
+    /// Add a material to the material table of the map
+    ///
+    /// # Arguments
+    ///
+    /// * `material` - The material to add
+    ///
+    /// # Returns
+    ///
+    /// * The index meshes use to reference the material
+    pub fn add_material(&mut self, material: Material) -> u32 {
+        self.materials.push(material);
+        (self.materials.len() - 1) as u32
+    }
+
+    /// Get the index of the material with a name
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the material
+    ///
+    /// # Returns
+    ///
+    /// * `Some(u32)` - The index of the first material with the name
+    /// * `None` - The map doesn't have a material with the name
+    pub fn find_material(&self, name: &str) -> Option<u32> {
+        self.materials.iter()
+            .position(|material| material.name == name)
+            .map(|index| index as u32)
+    }
+
+    /// Get the material a mesh uses
+    ///
+    /// # Arguments
+    ///
+    /// * `mesh` - The mesh, it should belong to this map
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[Material]`)` - The material of the mesh
+    /// * `None` - The mesh doesn't use a material or the index is outside
+    ///   the material table
+    pub fn mesh_material(&self, mesh: &Mesh) -> Option<&Material> {
+        self.materials.get(mesh.material? as usize)
+    }
+}
@@ -0,0 +1,301 @@
+//! Merging multiple maps into one
+
+use crate::*;
+use crate::transform::*;
+
+use std::ops::Range;
+use std::collections::HashMap;
+
+impl Map {
+    /// Append the content of another map to this map
+    ///
+    /// The sectors, lights, sound emitters, triggers, entities, waypoints,
+    /// props, decals and interactive objects of `other` are appended,
+    /// optionally transformed, followed by its animations and library
+    /// meshes. The interactive objects and script hooks are retargeted to
+    /// the appended sectors, triggers and entities, the embedded script
+    /// sources are joined so the function names must be unique. The groups
+    /// of `other` are added to the groups with the same name and the
+    /// potentially visible sets are cleared, rebuild them with
+    /// [Map::build_pvs]. The non-zero tags of `other` are offset past the
+    /// highest tag used by this map so the tags of the two maps don't
+    /// collide, embedded assets and dependencies are added when missing. The
+    /// sky, environment, lightmap and unknown chunks of this map are kept,
+    /// the sectors of `other` keep their environment. Regenerate the
+    /// lightmap texture coordinates after merging maps with lightmaps. The
+    /// vertex colors of `other` are converted to the color space of this map
+    /// and its materials are appended to the material table. `other` is
+    /// rescaled to the units per meter of this map before the transform is
+    /// applied.
+    ///
+    /// # Arguments
+    ///
+    /// * `other`     - The map to append
+    /// * `transform` - The transform applied to the content of `other`, any
+    ///   matrix implementing [ToMat4]
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Range]`)` - The indices of the appended sectors
+    /// * `Err(`[Error]`)` - [Error::TagOverflow] if the offset tags of
+    ///   `other` don't fit a `u32`, this map is left unchanged
+    pub fn merge(&mut self, mut other: Map, transform: Option<&dyn ToMat4>)
+        -> Result<Range<usize>>
+    {
+        let tag_offset = max_tag(self);
+        if max_tag(&other).checked_add(tag_offset).is_none() {
+            return Err(Error::TagOverflow);
+        }
+
+        other.rescale(self.units_per_meter)?;
+
+        let transform = transform.map(|m| m.to_mat4());
+        let transform = transform.as_ref();
+
+        // NOTE(patrik): The highest tag of `other` was checked above so
+        // offsetting the tags can't overflow
+        let remap_tag = |tag: u32| {
+            if tag == 0 {
+                0
+            } else {
+                tag + tag_offset
+            }
+        };
+
+        let start = self.sectors.len();
+
+        // The appended sectors get new IDs
+        let mut ids = HashMap::new();
+
+        let material_offset = self.materials.len() as u32;
+        let remap_material = |mesh: &mut Mesh| {
+            let remap = |material: Option<u32>| {
+                material.map(|index| index + material_offset)
+            };
+
+            mesh.material = remap(mesh.material);
+            for submesh in &mut mesh.submeshes {
+                submesh.material = remap(submesh.material);
+            }
+        };
+
+        let mesh_offset = self.mesh_library.len() as u32;
+
+        for mut sector in other.sectors {
+            if let Some(m) = transform {
+                transform_sector(&mut sector, m);
+            }
+
+            sector.convert_color_space(other.color_space, self.color_space);
+
+            for role in MeshRole::ALL {
+                remap_material(sector.mesh_mut(role));
+            }
+
+            for lod in &mut sector.lods {
+                remap_material(&mut lod.floor_mesh);
+                remap_material(&mut lod.ceiling_mesh);
+                remap_material(&mut lod.wall_mesh);
+            }
+
+            // NOTE(patrik): The sectors keep the environment they had inside
+            // `other` when the default environments differ
+            if other.environment != self.environment {
+                sector.environment =
+                    sector.environment.resolve(&other.environment).into();
+            }
+
+            sector.tag = remap_tag(sector.tag);
+            for prop in &mut sector.props {
+                prop.mesh += mesh_offset;
+            }
+
+            let old_id = sector.id;
+            ids.insert(old_id, self.add_sector(sector));
+        }
+
+        for mut light in other.lights {
+            if let Some(m) = transform {
+                transform_light(&mut light, m);
+            }
+
+            self.lights.push(light);
+        }
+
+        for mut emitter in other.sound_emitters {
+            if let Some(m) = transform {
+                emitter.position = transform_point(m, emitter.position);
+            }
+
+            self.sound_emitters.push(emitter);
+        }
+
+        let trigger_offset = self.triggers.len() as u32;
+        for mut trigger in other.triggers {
+            if let Some(m) = transform {
+                transform_trigger(&mut trigger, m);
+            }
+
+            trigger.tag = remap_tag(trigger.tag);
+            self.triggers.push(trigger);
+        }
+
+        let entity_offset = self.entities.len() as u32;
+        for mut entity in other.entities {
+            if let Some(m) = transform {
+                entity.position = transform_point(m, entity.position);
+            }
+
+            self.entities.push(entity);
+        }
+
+        if let Some(other_waypoints) = other.waypoints {
+            let waypoints = self.waypoints.get_or_insert_with(Default::default);
+            let offset = waypoints.nodes.len() as u32;
+
+            for mut node in other_waypoints.nodes {
+                if let Some(m) = transform {
+                    node.position = transform_point(m, node.position);
+                }
+
+                for link in &mut node.links {
+                    link.target += offset;
+                }
+
+                waypoints.nodes.push(node);
+            }
+        }
+
+        for mut prop in other.props {
+            if let Some(m) = transform {
+                prop.transform = multiply(m, &prop.transform);
+            }
+
+            prop.mesh += mesh_offset;
+            self.props.push(prop);
+        }
+
+        for mut mesh in other.mesh_library {
+            mesh.convert_color_space(other.color_space, self.color_space);
+            remap_material(&mut mesh);
+            self.mesh_library.push(mesh);
+        }
+
+        for mut decal in other.decals {
+            let Some(&sector) = ids.get(&decal.sector) else {
+                continue;
+            };
+
+            if let Some(m) = transform {
+                transform_decal(&mut decal, m);
+            }
+
+            decal.sector = sector;
+            decal.material += material_offset;
+            self.decals.push(decal);
+        }
+
+        for mut interactive in other.interactives {
+            if let Some(m) = transform {
+                interactive.position =
+                    transform_point(m, interactive.position);
+            }
+
+            for target in &mut interactive.targets {
+                *target = match *target {
+                    InteractionTarget::Tag(tag) => {
+                        InteractionTarget::Tag(remap_tag(tag))
+                    }
+
+                    InteractionTarget::Entity(entity) => {
+                        InteractionTarget::Entity(entity + entity_offset)
+                    }
+                };
+            }
+
+            self.interactives.push(interactive);
+        }
+
+        if let Some(source) = other.scripts.source {
+            match &mut self.scripts.source {
+                Some(existing) => {
+                    existing.push('\n');
+                    existing.push_str(&source);
+                }
+
+                None => self.scripts.source = Some(source),
+            }
+        }
+
+        for mut hook in other.scripts.hooks {
+            hook.target = match hook.target {
+                ScriptTarget::Sector(id) => {
+                    let Some(&id) = ids.get(&id) else {
+                        continue;
+                    };
+
+                    ScriptTarget::Sector(id)
+                }
+
+                ScriptTarget::Trigger(index) => {
+                    ScriptTarget::Trigger(index + trigger_offset)
+                }
+
+                ScriptTarget::Entity(index) => {
+                    ScriptTarget::Entity(index + entity_offset)
+                }
+            };
+
+            self.scripts.hooks.push(hook);
+        }
+
+        for mut animation in other.animations {
+            animation.tag = remap_tag(animation.tag);
+            self.animations.push(animation);
+        }
+
+        for group in other.groups {
+            let visible = group.visible;
+            let target = self.group_mut(&group.name);
+            target.visible &= visible;
+            target.sectors.extend(
+                group.sectors.iter().filter_map(|id| ids.get(id).copied()));
+        }
+
+        self.materials.extend(other.materials);
+        self.pvs = None;
+        self.signature = None;
+
+        for asset in other.embedded_assets {
+            if self.embedded_asset(&asset.name).is_none() {
+                self.embedded_assets.push(asset);
+            }
+        }
+
+        for dependency in other.dependencies {
+            if !self.dependencies.contains(&dependency) {
+                self.dependencies.push(dependency);
+            }
+        }
+
+        Ok(start..self.sectors.len())
+    }
+}
+
+/// Get the highest tag used by the sectors, triggers and animations of a map
+///
+/// # Arguments
+///
+/// * `map` - The map to search
+///
+/// # Returns
+///
+/// * `u32` - The highest tag or 0 if the map doesn't use any tags
+fn max_tag(map: &Map) -> u32 {
+    map.sectors.iter()
+        .map(|sector| sector.tag)
+        .chain(map.triggers.iter().map(|trigger| trigger.tag))
+        .chain(map.animations.iter().map(|animation| animation.tag))
+        .max()
+        .unwrap_or(0)
+}
@@ -0,0 +1,189 @@
+//! The map-level library of shared meshes
+//!
+//! The props of the map and of every sector reference the meshes of
+//! [Map::mesh_library] by index. When the map is saved a mesh identical to
+//! an earlier mesh of the library is written as a reference to that mesh,
+//! so tiled and repetitive maps store every mesh once while the indices
+//! used by the props stay the same.
+
+use crate::*;
+use crate::buffer::{ Reader, write_list, list_size };
+
+use std::collections::HashMap;
+
+/// The library entry holds the data of the mesh
+const ENTRY_MESH: u8 = 0;
+
+/// The library entry references an earlier identical mesh
+const ENTRY_ALIAS: u8 = 1;
+
+/// Find the meshes identical to an earlier mesh of a library
+///
+/// # Returns
+///
+/// * One entry for every mesh, the index of the first identical mesh or
+///   `None` for the first mesh with its content
+pub(crate) fn library_aliases(meshes: &[Mesh]) -> Vec<Option<u32>> {
+    // NOTE(patrik): Only meshes with the same sizes and texture can be
+    // equal, comparing inside those buckets keeps large libraries fast
+    let mut buckets: HashMap<_, Vec<u32>> = HashMap::new();
+
+    meshes.iter()
+        .enumerate()
+        .map(|(index, mesh)| {
+            let key = (mesh.vertex_buffer.len(), mesh.index_buffer.len(),
+                       mesh.texture_id);
+            let bucket = buckets.entry(key).or_default();
+
+            let first = bucket.iter()
+                .copied()
+                .find(|&other| meshes[other as usize] == *mesh);
+            if first.is_none() {
+                bucket.push(index as u32);
+            }
+
+            first
+        })
+        .collect()
+}
+
+/// Get the number of bytes [write_library] writes
+pub(crate) fn library_size(meshes: &[Mesh]) -> usize {
+    let aliases = library_aliases(meshes);
+    let entries = meshes.iter().zip(aliases).collect::<Vec<_>>();
+
+    list_size(&entries, |(mesh, alias)| {
+        1 + match alias {
+            Some(_) => std::mem::size_of::<u32>(),
+            None => mesh.serialized_size(),
+        }
+    })
+}
+
+/// Write a library, every mesh identical to an earlier mesh is written as
+/// the index of that mesh
+///
+/// # Arguments
+///
+/// * `buffer` - The buffer we use to append the data to
+/// * `meshes` - The meshes of the library
+pub(crate) fn write_library<O>(buffer: &mut O, meshes: &[Mesh]) -> Result<()>
+    where O: Output + ?Sized
+{
+    let aliases = library_aliases(meshes);
+    let entries = meshes.iter().zip(aliases).collect::<Vec<_>>();
+
+    write_list(buffer, &entries, |(mesh, alias), buffer| {
+        match alias {
+            Some(first) => {
+                buffer.push(ENTRY_ALIAS);
+                buffer.extend_from_slice(&first.to_le_bytes());
+                Ok(())
+            }
+
+            None => {
+                buffer.push(ENTRY_MESH);
+                mesh.serialize(buffer)
+            }
+        }
+    })
+}
+
+/// Read a library written by [write_library]
+///
+/// # Returns
+///
+/// * `Ok(Vec<Mesh>)` - The meshes with the references replaced by copies
+/// * `Err(`[Error]`)` - Failed to deserialize a mesh,
+///   [Error::InvalidMeshAlias] if an entry doesn't reference an earlier
+///   mesh or [Error::UnknownLibraryEntry] if the kind of an entry is
+///   unknown
+pub(crate) fn read_library(buffer: &[u8]) -> Result<Vec<Mesh>> {
+    let mut reader = Reader::new(buffer, Error::BufferToSmallChunk);
+
+    let count = reader.usize()?;
+    let mut meshes: Vec<Mesh> = Vec::new();
+    for _i in 0..count {
+        let mesh = reader.block(|entry| {
+            let mut reader = Reader::new(entry, Error::BufferToSmallChunk);
+            match reader.u8()? {
+                ENTRY_ALIAS => {
+                    let first = reader.u32()?;
+                    meshes.get(first as usize)
+                        .cloned()
+                        .ok_or(Error::InvalidMeshAlias(first))
+                }
+
+                ENTRY_MESH => {
+                    let start = reader.offset();
+                    Mesh::deserialize(reader.remaining())
+                        .map_err(|error| error.at_offset(start))
+                }
+
+                kind => Err(Error::UnknownLibraryEntry(kind)),
+            }
+        })?;
+
+        meshes.push(mesh);
+    }
+
+    Ok(meshes)
+}
+
+impl Map {
+    /// Remove the meshes of the library that are identical to an earlier
+    /// mesh and point the props using them at the earlier mesh
+    ///
+    /// Saving already stores every mesh once, this also shrinks the library
+    /// in memory. The sectors with changed props are marked for the next
+    /// [Map::save_incremental].
+    ///
+    /// # Returns
+    ///
+    /// * The number of meshes removed
+    pub fn dedup_mesh_library(&mut self) -> usize {
+        let aliases = library_aliases(&self.mesh_library);
+
+        // The new index of every mesh
+        let mut remap = Vec::with_capacity(aliases.len());
+        let mut kept = 0;
+        for alias in &aliases {
+            match alias {
+                Some(first) => remap.push(remap[*first as usize]),
+
+                None => {
+                    remap.push(kept);
+                    kept += 1;
+                }
+            }
+        }
+
+        let removed = self.mesh_library.len() - kept as usize;
+        if removed == 0 {
+            return 0;
+        }
+
+        let mut index = 0;
+        self.mesh_library.retain(|_| {
+            index += 1;
+            aliases[index - 1].is_none()
+        });
+
+        let remap_prop = |prop: &mut PropInstance| {
+            if let Some(&mesh) = remap.get(prop.mesh as usize) {
+                prop.mesh = mesh;
+            }
+        };
+
+        self.props.iter_mut().for_each(remap_prop);
+
+        for index in 0..self.sectors.len() {
+            if !self.sectors[index].props.is_empty() {
+                self.sectors[index].props.iter_mut().for_each(remap_prop);
+                self.mark_sector_dirty(index);
+            }
+        }
+
+        removed
+    }
+}
@@ -0,0 +1,216 @@
+//! Top-down minimaps of the floors and walls
+
+use crate::*;
+
+use std::fmt::Write;
+
+/// Options for the SVG minimap export
+#[derive(Clone, PartialEq, Debug)]
+pub struct MinimapOptions {
+    /// The width of the image in pixels, the height follows the aspect
+    /// ratio of the map
+    pub width: f32,
+
+    /// The empty space around the map in pixels
+    pub padding: f32,
+
+    /// The fill color of the floors, any SVG color
+    pub floor_color: String,
+
+    /// The color of the solid walls, any SVG color
+    pub wall_color: String,
+
+    /// The color of the two sided walls, `None` to leave them out
+    pub two_sided_color: Option<String>,
+
+    /// The width of the wall lines in pixels
+    pub line_width: f32,
+}
+
+impl Default for MinimapOptions {
+    fn default() -> Self {
+        Self {
+            width: 512.0,
+            padding: 8.0,
+            floor_color: "#404040".to_string(),
+            wall_color: "#e0e0e0".to_string(),
+            two_sided_color: Some("#808080".to_string()),
+            line_width: 1.5,
+        }
+    }
+}
+
+/// A wall projected to the ground plane
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct MinimapLine {
+    /// The index of the sector the wall belongs to
+    pub sector: usize,
+
+    /// The start of the wall (x, z)
+    pub start: [f32; 2],
+
+    /// The end of the wall (x, z)
+    pub end: [f32; 2],
+
+    /// The wall can be seen and passed from both sides
+    pub two_sided: bool,
+}
+
+/// The floors and walls of a map projected to the ground plane, the x axis
+/// of the map becomes the horizontal axis and the z axis the vertical axis
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Minimap {
+    /// The floor triangles of every sector (x, z)
+    pub floors: Vec<Vec<[[f32; 2]; 3]>>,
+
+    /// The walls of every sector
+    pub lines: Vec<MinimapLine>,
+}
+
+impl Minimap {
+    /// Get the bounds of the minimap
+    ///
+    /// # Returns
+    ///
+    /// * `Some(([f32; 2], [f32; 2]))` - The minimum and maximum corner
+    /// * `None` - The minimap is empty
+    pub fn bounds(&self) -> Option<([f32; 2], [f32; 2])> {
+        let points = self.floors.iter()
+            .flatten()
+            .flatten()
+            .chain(self.lines.iter().flat_map(|line| [&line.start, &line.end]));
+
+        points.fold(None, |bounds, p| {
+            let (min, max) = bounds.unwrap_or((*p, *p));
+            Some(([min[0].min(p[0]), min[1].min(p[1])],
+                  [max[0].max(p[0]), max[1].max(p[1])]))
+        })
+    }
+
+    /// Render the minimap as an SVG image
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - The size and the colors of the image
+    ///
+    /// # Returns
+    ///
+    /// * The content of the SVG file
+    pub fn to_svg(&self, options: &MinimapOptions) -> String {
+        let (min, max) = self.bounds().unwrap_or(([0.0; 2], [0.0; 2]));
+
+        let extent = (max[0] - min[0]).max(max[1] - min[1]);
+        let scale = if extent > 0.0 {
+            (options.width - 2.0 * options.padding).max(0.0) / extent
+        } else {
+            1.0
+        };
+
+        let width = options.width;
+        let height = (max[1] - min[1]) * scale + 2.0 * options.padding;
+        let project = |p: [f32; 2]| {
+            [(p[0] - min[0]) * scale + options.padding,
+             (p[1] - min[1]) * scale + options.padding]
+        };
+
+        let mut svg = String::new();
+
+        // NOTE(patrik): Writing to a String can't fail
+        let _ = writeln!(svg, "<svg xmlns=\"http://www.w3.org/2000/svg\" \
+                               width=\"{:.2}\" height=\"{:.2}\" \
+                               viewBox=\"0 0 {:.2} {:.2}\">",
+                         width, height, width, height);
+
+        // The floor is stroked with its own color to hide the seams
+        // between the triangles
+        for (sector, triangles) in self.floors.iter().enumerate() {
+            if triangles.is_empty() {
+                continue;
+            }
+
+            let mut path = String::new();
+            for triangle in triangles {
+                let [a, b, c] = triangle.map(project);
+                let _ = write!(path, "M{:.2} {:.2}L{:.2} {:.2}L{:.2} {:.2}Z",
+                               a[0], a[1], b[0], b[1], c[0], c[1]);
+            }
+
+            let _ = writeln!(svg, "<path data-sector=\"{}\" d=\"{}\" \
+                                   fill=\"{}\" stroke=\"{}\" \
+                                   stroke-width=\"0.5\"/>",
+                             sector, path, options.floor_color,
+                             options.floor_color);
+        }
+
+        for line in &self.lines {
+            let color = if line.two_sided {
+                match &options.two_sided_color {
+                    Some(color) => color,
+                    None => continue,
+                }
+            } else {
+                &options.wall_color
+            };
+
+            let a = project(line.start);
+            let b = project(line.end);
+            let _ = writeln!(svg, "<line data-sector=\"{}\" x1=\"{:.2}\" \
+                                   y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\" \
+                                   stroke=\"{}\" stroke-width=\"{:.2}\" \
+                                   stroke-linecap=\"round\"/>",
+                             line.sector, a[0], a[1], b[0], b[1], color,
+                             options.line_width);
+        }
+
+        svg.push_str("</svg>\n");
+
+        svg
+    }
+}
+
+impl Map {
+    /// Project the floor meshes and the walls of every sector to the ground
+    /// plane
+    pub fn minimap(&self) -> Minimap {
+        let flat = |p: [f32; 3]| [p[0], p[2]];
+
+        let floors = self.sectors.iter()
+            .map(|sector| {
+                sector.floor_mesh.triangle_positions()
+                    .map(|[a, b, c]| [flat(a), flat(b), flat(c)])
+                    .collect()
+            })
+            .collect();
+
+        let lines = self.sectors.iter()
+            .enumerate()
+            .flat_map(|(index, sector)| {
+                sector.walls.iter().map(move |wall| MinimapLine {
+                    sector: index,
+                    start: flat(wall.start),
+                    end: flat(wall.end),
+                    two_sided: wall.is_two_sided(),
+                })
+            })
+            .collect();
+
+        Minimap {
+            floors,
+            lines,
+        }
+    }
+
+    /// Render a top-down minimap of the floors and walls as an SVG image,
+    /// see [Map::minimap] for the projected geometry
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - The size and the colors of the image
+    ///
+    /// # Returns
+    ///
+    /// * The content of the SVG file
+    pub fn minimap_svg(&self, options: &MinimapOptions) -> String {
+        self.minimap().to_svg(options)
+    }
+}
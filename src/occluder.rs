@@ -0,0 +1,133 @@
+//! Simplified occluder geometry for occlusion culling
+//!
+//! Engines doing software occlusion culling rasterize a few large triangles
+//! instead of the render meshes. The occluders are authored next to the rest
+//! of the sector in [Sector::occluders] and only store positions, they are
+//! never drawn and aren't part of [Sector::meshes].
+
+use crate::*;
+use crate::buffer::{ Reader, write_f32s, write_usize };
+
+/// A triangle list only used to hide the geometry behind it
+#[derive(Clone, PartialEq, Default, Debug)]
+pub struct Occluder {
+    /// The positions of the corners
+    pub vertices: Vec<[f32; 3]>,
+
+    /// The indices of the corners of every triangle, 3 for every triangle
+    pub indices: Vec<u32>,
+}
+
+impl Occluder {
+    /// Creates a new occluder
+    ///
+    /// # Arguments
+    ///
+    /// * `vertices` - The positions of the corners
+    /// * `indices`  - The indices of the triangles
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new occluder
+    pub fn new(vertices: Vec<[f32; 3]>, indices: Vec<u32>) -> Self {
+        Self {
+            vertices,
+            indices,
+        }
+    }
+
+    /// Create an occluder from the triangles of a mesh, the positions are
+    /// kept and everything else is dropped
+    ///
+    /// # Arguments
+    ///
+    /// * `mesh` - The mesh, line and point meshes give an empty occluder
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The occluder with the triangles of the mesh
+    pub fn from_mesh(mesh: &Mesh) -> Self {
+        Self {
+            vertices: mesh.vertex_buffer.iter()
+                .map(|vertex| vertex.pos)
+                .collect(),
+            indices: mesh.triangle_indices().flatten().collect(),
+        }
+    }
+
+    /// Get the positions of every triangle, triangles referencing vertices
+    /// that don't exist and indices left over at the end are skipped
+    pub fn triangles(&self) -> impl Iterator<Item = [[f32; 3]; 3]> + '_ {
+        let vertex = |index: u32| self.vertices.get(index as usize).copied();
+
+        self.indices.chunks_exact(3)
+            .filter_map(move |triangle| {
+                Some([vertex(triangle[0])?, vertex(triangle[1])?,
+                      vertex(triangle[2])?])
+            })
+    }
+
+    /// Get the number of bytes [Occluder::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        // The vertex and index counts
+        8 + self.vertices.len() * 3 * std::mem::size_of::<f32>() +
+            8 + self.indices.len() * std::mem::size_of::<u32>()
+    }
+
+    /// Serialize the occluder to a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - Successfully serialized the occluder
+    /// * `Err(`[Error]`)` - Failed to serialize the occluder
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        write_usize(buffer, self.vertices.len())?;
+        for vertex in &self.vertices {
+            write_f32s(buffer, vertex);
+        }
+
+        write_usize(buffer, self.indices.len())?;
+        for index in &self.indices {
+            buffer.extend_from_slice(&index.to_le_bytes());
+        }
+
+        Ok(())
+    }
+
+    /// Deserialize the occluder from a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the occluder
+    /// * `Err(`[Error]`)` - Failed to deserialize the occluder
+    pub fn deserialize(buffer: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallChunk);
+
+        let vertex_count = reader.usize()?;
+        let mut vertices = Vec::new();
+        for _i in 0..vertex_count {
+            vertices.push(reader.vec3()?);
+        }
+
+        let index_count = reader.usize()?;
+        let mut indices = Vec::new();
+        for _i in 0..index_count {
+            indices.push(reader.u32()?);
+        }
+
+        Ok(Self {
+            vertices,
+            indices,
+        })
+    }
+}
@@ -0,0 +1,370 @@
+//! Passes cleaning up the geometry of maps
+
+use crate::*;
+use crate::math::*;
+
+/// Triangles with a smaller area than this are degenerate
+pub const MIN_TRIANGLE_AREA: f32 = 1e-8;
+
+/// What to do with NaN or infinite values inside the meshes, they survive
+/// serialization and break everything using the positions later
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum NonFinitePolicy {
+    /// Fail with [Error::NonFiniteValue] and leave the meshes unchanged
+    Error,
+
+    /// Replace NaN with 0 and clamp the infinities to the largest finite
+    /// value with the same sign
+    Clamp,
+}
+
+/// Make a value finite, see [NonFinitePolicy::Clamp]
+///
+/// # Returns
+///
+/// * `true` - The value wasn't finite and got replaced
+/// * `false` - The value was already finite
+fn clamp_non_finite(value: &mut f32) -> bool {
+    if value.is_finite() {
+        return false;
+    }
+
+    *value = if value.is_nan() { 0.0 } else { value.clamp(f32::MIN, f32::MAX) };
+    true
+}
+
+impl Mesh {
+    /// Check if a triangle is degenerate, it uses the same vertex more than
+    /// once or its area is smaller than [MIN_TRIANGLE_AREA]
+    ///
+    /// # Arguments
+    ///
+    /// * `triangle` - The index of the triangle, the indices of the
+    ///   triangle start at `triangle * 3` inside the index buffer
+    ///
+    /// # Returns
+    ///
+    /// * `true` - The triangle is degenerate
+    /// * `false` - The triangle is fine or references vertices that don't
+    ///   exist
+    pub fn is_degenerate_triangle(&self, triangle: usize) -> bool {
+        let start = triangle * 3;
+        let Some(indices) = self.index_buffer.get(start..start + 3) else {
+            return false;
+        };
+
+        let [a, b, c] = [indices[0], indices[1], indices[2]];
+        if a == b || b == c || a == c {
+            return true;
+        }
+
+        let position = |index: u32| {
+            self.vertex_buffer.get(index as usize).map(|vertex| vertex.pos)
+        };
+
+        match (position(a), position(b), position(c)) {
+            (Some(a), Some(b), Some(c)) => {
+                length(triangle_normal(a, b, c)) * 0.5 < MIN_TRIANGLE_AREA
+            }
+
+            _ => false,
+        }
+    }
+
+    /// Get the degenerate triangles of the mesh, see
+    /// [Mesh::is_degenerate_triangle]
+    ///
+    /// # Returns
+    ///
+    /// * The indices of the degenerate triangles in ascending order, empty
+    ///   if the mesh isn't a [Topology::TriangleList]
+    pub fn degenerate_triangles(&self) -> Vec<usize> {
+        if self.topology != Topology::TriangleList {
+            return Vec::new();
+        }
+
+        (0..self.index_buffer.len() / 3)
+            .filter(|&triangle| self.is_degenerate_triangle(triangle))
+            .collect()
+    }
+
+    /// Remove the degenerate triangles from the index buffer, the vertices
+    /// are kept
+    ///
+    /// Prefer [Sector::remove_degenerate_triangles] for the wall mesh of a
+    /// sector, it also updates the index ranges of the walls.
+    ///
+    /// # Returns
+    ///
+    /// * The number of triangles removed
+    pub fn remove_degenerate_triangles(&mut self) -> usize {
+        self.remove_triangles(&self.degenerate_triangles())
+    }
+
+    /// Remove triangles from the index buffer, the ranges of the submeshes
+    /// are updated
+    ///
+    /// # Arguments
+    ///
+    /// * `triangles` - The indices of the triangles to remove in ascending
+    ///   order
+    ///
+    /// # Returns
+    ///
+    /// * The number of triangles removed
+    fn remove_triangles(&mut self, triangles: &[usize]) -> usize {
+        if triangles.is_empty() {
+            return 0;
+        }
+
+        for submesh in &mut self.submeshes {
+            shift_index_range(&mut submesh.index_start,
+                              &mut submesh.index_count, triangles);
+        }
+
+        let mut removed = triangles.iter().peekable();
+        let mut triangle = 0;
+        let mut index = 0;
+        self.index_buffer.retain(|_| {
+            let keep = removed.peek() != Some(&&triangle);
+
+            index += 1;
+            if index == 3 {
+                index = 0;
+                if !keep {
+                    removed.next();
+                }
+                triangle += 1;
+            }
+
+            keep
+        });
+
+        triangles.len()
+    }
+
+    /// Check that every vertex and texture coordinate of the mesh only has
+    /// finite values
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - Every value is finite
+    /// * `Err(`[Error]`)` - [Error::NonFiniteValue] with the element that
+    ///   has the first NaN or infinite value
+    pub fn check_finite(&self) -> Result<()> {
+        let error = |element| {
+            Error::NonFiniteValue
+                .with_context(|context| context.element = Some(element))
+        };
+
+        for (index, vertex) in self.vertex_buffer.iter().enumerate() {
+            let mut values =
+                vertex.pos.iter().chain(&vertex.uv).chain(&vertex.color);
+            if !values.all(|value| value.is_finite()) {
+                return Err(error(ErrorElement::Vertex(index)));
+            }
+        }
+
+        for (index, uv) in self.lightmap_uvs.iter().enumerate() {
+            if !uv.iter().all(|value| value.is_finite()) {
+                return Err(error(ErrorElement::LightmapUv(index)));
+            }
+        }
+
+        for (set, uvs) in self.uv_sets.iter().enumerate() {
+            for (index, uv) in uvs.uvs.iter().enumerate() {
+                if !uv.iter().all(|value| value.is_finite()) {
+                    return Err(error(ErrorElement::UvSet(set, index)));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handle the NaN and infinite values of the vertices and the texture
+    /// coordinates
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - Fail or clamp when a value isn't finite
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(usize)` - The number of values replaced
+    /// * `Err(`[Error]`)` - A value wasn't finite with
+    ///   [NonFinitePolicy::Error]
+    pub fn scrub_non_finite(&mut self, policy: NonFinitePolicy)
+        -> Result<usize>
+    {
+        if policy == NonFinitePolicy::Error {
+            return self.check_finite().map(|_| 0);
+        }
+
+        let vertices = self.vertex_buffer.iter_mut()
+            .flat_map(|vertex| {
+                vertex.pos.iter_mut()
+                    .chain(&mut vertex.uv)
+                    .chain(&mut vertex.color)
+            });
+        let uvs = self.lightmap_uvs.iter_mut()
+            .chain(self.uv_sets.iter_mut().flat_map(|set| &mut set.uvs))
+            .flatten();
+
+        let replaced = vertices.chain(uvs)
+            .map(clamp_non_finite)
+            .filter(|&replaced| replaced)
+            .count();
+
+        Ok(replaced)
+    }
+}
+
+impl Sector {
+    /// Remove the degenerate triangles from all the meshes of the sector
+    /// and its levels of detail, the index ranges of the walls are updated
+    /// to match the new wall mesh
+    ///
+    /// # Returns
+    ///
+    /// * The number of triangles removed
+    pub fn remove_degenerate_triangles(&mut self) -> usize {
+        let wall_triangles = self.wall_mesh.degenerate_triangles();
+        for wall in &mut self.walls {
+            shift_index_range(&mut wall.index_start, &mut wall.index_count,
+                              &wall_triangles);
+        }
+
+        let mut removed = self.wall_mesh.remove_triangles(&wall_triangles);
+        removed += self.floor_mesh.remove_degenerate_triangles();
+        removed += self.ceiling_mesh.remove_degenerate_triangles();
+
+        for lod in &mut self.lods {
+            removed += lod.floor_mesh.remove_degenerate_triangles();
+            removed += lod.ceiling_mesh.remove_degenerate_triangles();
+            removed += lod.wall_mesh.remove_degenerate_triangles();
+        }
+
+        removed
+    }
+
+    /// Handle the NaN and infinite values of all the meshes of the sector
+    /// and its levels of detail, see [Mesh::scrub_non_finite]
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - Fail or clamp when a value isn't finite
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(usize)` - The number of values replaced
+    /// * `Err(`[Error]`)` - A value wasn't finite with
+    ///   [NonFinitePolicy::Error], the context has the mesh and level of
+    ///   detail
+    pub fn scrub_non_finite(&mut self, policy: NonFinitePolicy)
+        -> Result<usize>
+    {
+        let mut replaced = 0;
+        for role in MeshRole::ALL {
+            replaced += self.mesh_mut(role).scrub_non_finite(policy)
+                .map_err(|error| {
+                    error.with_context(|context| context.mesh = Some(role))
+                })?;
+        }
+
+        for (index, lod) in self.lods.iter_mut().enumerate() {
+            let meshes = [
+                (MeshRole::Floor, &mut lod.floor_mesh),
+                (MeshRole::Ceiling, &mut lod.ceiling_mesh),
+                (MeshRole::Wall, &mut lod.wall_mesh),
+            ];
+
+            for (role, mesh) in meshes {
+                replaced += mesh.scrub_non_finite(policy)
+                    .map_err(|error| {
+                        error.with_context(|context| {
+                            context.lod = Some(index);
+                            context.mesh = Some(role);
+                        })
+                    })?;
+            }
+        }
+
+        Ok(replaced)
+    }
+}
+
+impl Map {
+    /// Remove the degenerate triangles from every sector, see
+    /// [Sector::remove_degenerate_triangles]
+    ///
+    /// The changed sectors are marked for the next [Map::save_incremental].
+    ///
+    /// # Returns
+    ///
+    /// * The number of triangles removed
+    pub fn remove_degenerate_triangles(&mut self) -> usize {
+        let mut removed = 0;
+        for index in 0..self.sectors.len() {
+            let count = self.sectors[index].remove_degenerate_triangles();
+            if count > 0 {
+                self.mark_sector_dirty(index);
+            }
+
+            removed += count;
+        }
+
+        removed
+    }
+
+    /// Handle the NaN and infinite values of every sector, see
+    /// [Sector::scrub_non_finite]
+    ///
+    /// The changed sectors are marked for the next [Map::save_incremental].
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - Fail or clamp when a value isn't finite
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(usize)` - The number of values replaced
+    /// * `Err(`[Error]`)` - A value wasn't finite with
+    ///   [NonFinitePolicy::Error], the context has the sector
+    pub fn scrub_non_finite(&mut self, policy: NonFinitePolicy)
+        -> Result<usize>
+    {
+        let mut replaced = 0;
+        for index in 0..self.sectors.len() {
+            let count = self.sectors[index].scrub_non_finite(policy)
+                .map_err(|error| {
+                    error.with_context(|context| context.sector = Some(index))
+                })?;
+            if count > 0 {
+                self.mark_sector_dirty(index);
+            }
+
+            replaced += count;
+        }
+
+        Ok(replaced)
+    }
+}
+
+/// Update a range of an index buffer for the removal of triangles
+///
+/// # Arguments
+///
+/// * `start`     - The first index of the range
+/// * `count`     - The number of indices of the range
+/// * `triangles` - The indices of the removed triangles in ascending order
+fn shift_index_range(start: &mut u32, count: &mut u32, triangles: &[usize]) {
+    let end = start.saturating_add(*count) as usize / 3;
+    let first = *start as usize / 3;
+
+    let before = triangles.partition_point(|&t| t < first);
+    let inside = triangles.partition_point(|&t| t < end) - before;
+
+    *start -= before as u32 * 3;
+    *count = count.saturating_sub(inside as u32 * 3);
+}
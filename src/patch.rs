@@ -0,0 +1,214 @@
+//! Binary patches between two revisions of a map
+//!
+//! A patch stores the sectors that were added or changed, the order of the
+//! sectors of the new revision and the chunk list when it changed. Sectors
+//! are matched by their ID so moving sectors around doesn't resend them.
+//! The patch also stores the [Map::content_hash] of both revisions, so a
+//! patch is only applied to the revision it was created from and applying
+//! it is checked to give the new revision.
+
+use crate::*;
+use crate::buffer::{ Reader, write_usize, write_list, write_sized };
+use crate::compression::{ sector_block, read_sector_block };
+use crate::map::{ CURRENT_VERSION, clamp_next_sector_id,
+                  read_magic_and_version };
+use crate::limits::Budget;
+
+use std::collections::HashMap;
+
+/// The magic at the start of a patch
+const PATCH_MAGIC: &[u8; 4] = b"MPCH";
+
+/// How the changed sectors are stored, LZ4 when the codec is enabled
+const PATCH_COMPRESSION: SectorCompression = if cfg!(feature = "lz4") {
+    SectorCompression::Lz4
+} else {
+    SectorCompression::None
+};
+
+impl Map {
+    /// Create a patch that turns a map into a newer revision of the map
+    ///
+    /// # Arguments
+    ///
+    /// * `old` - The revision the patch is applied to
+    /// * `new` - The revision the patch produces
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<u8>)` - The patch, see [Map::apply_patch]
+    /// * `Err(`[Error]`)` - Failed to serialize the maps
+    pub fn create_patch(old: &Map, new: &Map) -> Result<Vec<u8>> {
+        let serialize_sector = |sector: &Sector| {
+            let mut buffer = Vec::with_capacity(sector.serialized_size());
+            sector.serialize(&mut buffer)?;
+
+            Ok(buffer)
+        };
+
+        let old_sectors = old.sectors.iter()
+            .map(|sector| (sector.id, sector))
+            .collect::<HashMap<_, _>>();
+
+        let mut changed = Vec::new();
+        for sector in &new.sectors {
+            let unchanged = match old_sectors.get(&sector.id) {
+                Some(old_sector) => {
+                    serialize_sector(old_sector)? == serialize_sector(sector)?
+                }
+
+                None => false,
+            };
+
+            if !unchanged {
+                changed.push(sector);
+            }
+        }
+
+        let mut old_chunks = Vec::new();
+        old.serialize_chunks(&mut old_chunks)?;
+        let mut new_chunks = Vec::new();
+        new.serialize_chunks(&mut new_chunks)?;
+
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(PATCH_MAGIC);
+        buffer.extend_from_slice(&CURRENT_VERSION.to_le_bytes());
+        buffer.extend_from_slice(&old.content_hash()?.to_le_bytes());
+        buffer.extend_from_slice(&new.content_hash()?.to_le_bytes());
+        buffer.extend_from_slice(&new.next_sector_id.to_le_bytes());
+
+        write_usize(&mut buffer, new.sectors.len())?;
+        for sector in &new.sectors {
+            buffer.extend_from_slice(&sector.id.0.to_le_bytes());
+        }
+
+        write_list(&mut buffer, &changed, |sector, buffer| {
+            buffer.extend_from_slice(
+                &sector_block(sector, PATCH_COMPRESSION)?);
+            Ok(())
+        })?;
+
+        if old_chunks == new_chunks {
+            buffer.push(0);
+        } else {
+            buffer.push(1);
+            write_sized(&mut buffer, |buffer| {
+                buffer.extend_from_slice(&new_chunks);
+                Ok(())
+            })?;
+        }
+
+        Ok(buffer)
+    }
+
+    /// Apply a patch created by [Map::create_patch], the map is left
+    /// untouched when the patch fails
+    ///
+    /// The added, changed and moved sectors are marked for the next
+    /// [Map::save_incremental].
+    ///
+    /// # Arguments
+    ///
+    /// * `patch` - The patch to apply
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - Successfully applied the patch
+    /// * `Err(`[Error]`)` - The patch is invalid or was created from
+    ///   another revision of the map
+    pub fn apply_patch(&mut self, patch: &[u8]) -> Result<()> {
+        let mut reader = Reader::new(patch, Error::BufferToSmallPatch);
+
+        read_magic_and_version(&mut reader, PATCH_MAGIC)?;
+
+        let base_hash = reader.u64()?;
+        let result_hash = reader.u64()?;
+        let next_sector_id = reader.u64()?;
+
+        if self.content_hash()? != base_hash {
+            return Err(Error::PatchMismatch);
+        }
+
+        let sector_count = reader.usize()?;
+        let mut order = Vec::new();
+        for _i in 0..sector_count {
+            order.push(SectorId(reader.u64()?));
+        }
+
+        let budget = Budget::unlimited();
+        let mut changed = reader
+            .list(|block| read_sector_block(block, &budget))?
+            .into_iter()
+            .map(|sector| (sector.id, sector))
+            .collect::<HashMap<_, _>>();
+
+        let chunks = match reader.u8()? {
+            0 => None,
+            _ => Some(reader.sized()?),
+        };
+
+        let old_indices = self.sectors.iter()
+            .enumerate()
+            .map(|(index, sector)| (sector.id, index))
+            .collect::<HashMap<_, _>>();
+
+        let mut sectors = Vec::with_capacity(order.len());
+        let mut dirty = Vec::new();
+        for (index, id) in order.iter().enumerate() {
+            let sector = match changed.remove(id) {
+                Some(sector) => {
+                    dirty.push(index);
+                    sector
+                }
+
+                None => {
+                    let &old_index = old_indices.get(id)
+                        .ok_or(Error::PatchMismatch)?;
+                    if old_index != index {
+                        dirty.push(index);
+                    }
+
+                    self.sectors[old_index].clone()
+                }
+            };
+
+            sectors.push(sector);
+        }
+
+        // NOTE(patrik): The chunks are deserialized into a new map so the
+        // chunks removed by the patch don't stay behind
+        let mut map = match chunks {
+            Some(chunks) => {
+                let mut map = Map::new(Vec::new());
+                map.deserialize_chunks(chunks)?;
+                map.compression = self.compression;
+                map.layout = self.layout.clone();
+                map.dirty_sectors = self.dirty_sectors.clone();
+
+                map
+            }
+
+            None => {
+                let old_sectors = std::mem::take(&mut self.sectors);
+                let map = self.clone();
+                self.sectors = old_sectors;
+
+                map
+            }
+        };
+
+        map.next_sector_id = clamp_next_sector_id(next_sector_id, &sectors);
+        map.sectors = sectors;
+        for index in dirty {
+            map.mark_sector_dirty(index);
+        }
+
+        if map.content_hash()? != result_hash {
+            return Err(Error::PatchMismatch);
+        }
+
+        *self = map;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+//! Parameterized brushes for procedural levels
+//!
+//! Generators build the brushes from a few numbers instead of computing the
+//! planes, combine them with [Solid] and compile the result into a sector.
+
+use crate::*;
+
+use std::f32::consts::PI;
+
+impl Brush {
+    /// Creates an axis aligned box
+    ///
+    /// # Arguments
+    ///
+    /// * `min` - The minimum corner of the box
+    /// * `max` - The maximum corner of the box
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new brush
+    pub fn cuboid(min: [f32; 3], max: [f32; 3]) -> Self {
+        Self::new(vec![
+            Plane::new([1.0, 0.0, 0.0], max[0]),
+            Plane::new([-1.0, 0.0, 0.0], -min[0]),
+            Plane::new([0.0, 1.0, 0.0], max[1]),
+            Plane::new([0.0, -1.0, 0.0], -min[1]),
+            Plane::new([0.0, 0.0, 1.0], max[2]),
+            Plane::new([0.0, 0.0, -1.0], -min[2]),
+        ])
+    }
+
+    /// Creates a ramp inside an axis aligned box, the slope rises along the
+    /// X axis from the bottom at the minimum X to the top at the maximum X
+    ///
+    /// # Arguments
+    ///
+    /// * `min` - The minimum corner of the box
+    /// * `max` - The maximum corner of the box
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new brush, rotate it with [Map::transform] after
+    ///   compiling for the other directions
+    pub fn wedge(min: [f32; 3], max: [f32; 3]) -> Self {
+        let width = max[0] - min[0];
+        let height = max[1] - min[1];
+
+        // NOTE(patrik): The slope goes through (min x, min y) and
+        // (max x, max y), the normal points up and back along X
+        let slope = [-height, width, 0.0];
+        let distance = slope[0] * min[0] + slope[1] * min[1];
+
+        Self::new(vec![
+            Plane::new(slope, distance),
+            Plane::new([1.0, 0.0, 0.0], max[0]),
+            Plane::new([0.0, -1.0, 0.0], -min[1]),
+            Plane::new([0.0, 0.0, 1.0], max[2]),
+            Plane::new([0.0, 0.0, -1.0], -min[2]),
+        ])
+    }
+
+    /// Creates an upright prism approximating a cylinder
+    ///
+    /// # Arguments
+    ///
+    /// * `base`   - The center of the bottom of the cylinder
+    /// * `radius` - The distance from the center to the corners of the sides
+    /// * `height` - The height of the cylinder along the Y axis
+    /// * `sides`  - The number of sides, at least 3
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new brush
+    pub fn cylinder(base: [f32; 3], radius: f32, height: f32, sides: usize)
+        -> Self
+    {
+        let sides = sides.max(3);
+
+        // NOTE(patrik): The corners are on the circle so the sides are
+        // closer to the center than the radius
+        let apothem = radius * (PI / sides as f32).cos();
+
+        let mut planes = vec![
+            Plane::new([0.0, 1.0, 0.0], base[1] + height),
+            Plane::new([0.0, -1.0, 0.0], -base[1]),
+        ];
+
+        for side in 0..sides {
+            let angle = (side as f32 + 0.5) * 2.0 * PI / sides as f32;
+            let normal = [angle.cos(), 0.0, angle.sin()];
+            let distance = normal[0] * base[0] + normal[2] * base[2];
+            planes.push(Plane::new(normal, distance + apothem));
+        }
+
+        Self::new(planes)
+    }
+
+    /// Compile the brush into a sector, see [Solid::compile]
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - The textures of the meshes and the size of the
+    ///   textures
+    ///
+    /// # Returns
+    ///
+    /// * [Sector] - The sector with the brush as its open space
+    pub fn compile_to_sector(&self, options: &CsgOptions) -> Sector {
+        Solid::from(self.clone()).compile(options)
+    }
+}
@@ -0,0 +1,169 @@
+//! Static props placed from a shared mesh library
+//!
+//! Repeated detail like pillars, crates or lamps is stored once inside
+//! [Map::mesh_library] and every placement only stores the index of the mesh
+//! and a transform, so 500 pillars don't store 500 copies of the vertices.
+//! Props are placed by the map in [Map::props] or by a sector in
+//! [Sector::props].
+
+use crate::*;
+use crate::buffer::Reader;
+use crate::transform::Mat4;
+
+/// The size of a serialized prop (mesh, transform)
+pub(crate) const PROP_SIZE: usize = std::mem::size_of::<u32>() + 16 * 4;
+
+/// A placement of a mesh of the mesh library
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct PropInstance {
+    /// The index of the mesh inside [Map::mesh_library]
+    pub mesh: u32,
+
+    /// The transform from the space of the mesh to the map, column-major
+    /// like [Mat4]
+    pub transform: Mat4,
+}
+
+impl PropInstance {
+    /// Creates a new prop
+    ///
+    /// # Arguments
+    ///
+    /// * `mesh`      - The index of the mesh inside [Map::mesh_library]
+    /// * `transform` - The transform of the mesh, any matrix implementing
+    ///   [ToMat4]
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new prop
+    pub fn new<M>(mesh: u32, transform: &M) -> Self
+        where M: ToMat4 + ?Sized
+    {
+        Self {
+            mesh,
+            transform: transform.to_mat4(),
+        }
+    }
+
+    /// Get the number of bytes [PropInstance::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        PROP_SIZE
+    }
+
+    /// Serialize the prop to a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - Successfully serialized the prop
+    /// * `Err(`[Error]`)` - Failed to serialize the prop
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        buffer.extend_from_slice(&self.mesh.to_le_bytes());
+        for value in self.transform.iter().flatten() {
+            buffer.extend_from_slice(&value.to_le_bytes());
+        }
+
+        Ok(())
+    }
+
+    /// Deserialize the prop from a buffer, the index of the mesh is checked
+    /// when the map is loaded
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the prop
+    /// * `Err(`[Error]`)` - Failed to deserialize the prop
+    pub fn deserialize(buffer: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallChunk);
+
+        let mesh = reader.u32()?;
+        let mut transform = [[0.0; 4]; 4];
+        for value in transform.iter_mut().flatten() {
+            *value = reader.f32()?;
+        }
+
+        Ok(Self { mesh, transform })
+    }
+}
+
+impl Map {
+    /// Add a mesh to the mesh library, a mesh identical to one already
+    /// inside the library isn't added again
+    ///
+    /// # Arguments
+    ///
+    /// * `mesh` - The mesh in its own space
+    ///
+    /// # Returns
+    ///
+    /// * The index of the mesh inside [Map::mesh_library]
+    pub fn add_library_mesh(&mut self, mesh: Mesh) -> u32 {
+        if let Some(index) = self.mesh_library.iter().position(|m| *m == mesh) {
+            return index as u32;
+        }
+
+        self.mesh_library.push(mesh);
+        (self.mesh_library.len() - 1) as u32
+    }
+
+    /// Place a mesh of the mesh library
+    ///
+    /// # Arguments
+    ///
+    /// * `mesh`      - The index of the mesh inside [Map::mesh_library]
+    /// * `transform` - The transform of the mesh, any matrix implementing
+    ///   [ToMat4]
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(usize)` - The index of the prop inside [Map::props]
+    /// * `Err(`[Error]`)` - [Error::InvalidPropMesh] if the mesh isn't
+    ///   inside the library
+    pub fn add_prop<M>(&mut self, mesh: u32, transform: &M) -> Result<usize>
+        where M: ToMat4 + ?Sized
+    {
+        if mesh as usize >= self.mesh_library.len() {
+            return Err(Error::InvalidPropMesh(mesh));
+        }
+
+        self.props.push(PropInstance::new(mesh, transform));
+        Ok(self.props.len() - 1)
+    }
+
+    /// Get the mesh placed by a prop
+    ///
+    /// # Arguments
+    ///
+    /// * `prop` - The prop
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[Mesh]`)` - The mesh in its own space
+    /// * `None` - The mesh isn't inside the library
+    pub fn prop_mesh(&self, prop: &PropInstance) -> Option<&Mesh> {
+        self.mesh_library.get(prop.mesh as usize)
+    }
+
+    /// Check that every prop of the map and the loaded sectors places a
+    /// mesh of the library
+    pub(crate) fn check_props(&self) -> Result<()> {
+        let sector_props = self.sectors.iter()
+            .flat_map(|sector| &sector.props);
+        for prop in self.props.iter().chain(sector_props) {
+            if prop.mesh as usize >= self.mesh_library.len() {
+                return Err(Error::InvalidPropMesh(prop.mesh));
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,273 @@
+//! Typed property values of the entities
+//!
+//! Every value is stored with a byte telling its type, so engines read
+//! numbers, vectors and colors directly instead of parsing strings.
+
+use crate::*;
+use crate::buffer::{ Reader, write_f32s, write_string, string_size };
+
+/// The type of a property value
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum PropertyType {
+    /// [PropertyValue::Bool]
+    Bool,
+
+    /// [PropertyValue::Int]
+    Int,
+
+    /// [PropertyValue::Float]
+    Float,
+
+    /// [PropertyValue::String]
+    String,
+
+    /// [PropertyValue::Vec3]
+    Vec3,
+
+    /// [PropertyValue::Color]
+    Color,
+}
+
+impl PropertyType {
+    /// Get the byte the type is serialized as
+    pub fn to_u8(self) -> u8 {
+        match self {
+            PropertyType::Bool => 0,
+            PropertyType::Int => 1,
+            PropertyType::Float => 2,
+            PropertyType::String => 3,
+            PropertyType::Vec3 => 4,
+            PropertyType::Color => 5,
+        }
+    }
+
+    /// Get the type from its serialized byte
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The serialized byte
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - The type
+    /// * `Err(`[Error]`)` - [Error::UnknownPropertyType] if the byte isn't a
+    ///   known type
+    pub fn from_u8(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(PropertyType::Bool),
+            1 => Ok(PropertyType::Int),
+            2 => Ok(PropertyType::Float),
+            3 => Ok(PropertyType::String),
+            4 => Ok(PropertyType::Vec3),
+            5 => Ok(PropertyType::Color),
+            value => Err(Error::UnknownPropertyType(value)),
+        }
+    }
+}
+
+/// The value of a property
+#[derive(Clone, PartialEq, Debug)]
+pub enum PropertyValue {
+    /// A flag
+    Bool(bool),
+
+    /// A signed integer
+    Int(i64),
+
+    /// A double precision float
+    Float(f64),
+
+    /// A UTF-8 string
+    String(String),
+
+    /// A vector like a position or direction (x, y, z)
+    Vec3([f32; 3]),
+
+    /// A color (r, g, b, a)
+    Color([f32; 4]),
+}
+
+impl PropertyValue {
+    /// Get the type of the value
+    pub fn property_type(&self) -> PropertyType {
+        match self {
+            PropertyValue::Bool(_) => PropertyType::Bool,
+            PropertyValue::Int(_) => PropertyType::Int,
+            PropertyValue::Float(_) => PropertyType::Float,
+            PropertyValue::String(_) => PropertyType::String,
+            PropertyValue::Vec3(_) => PropertyType::Vec3,
+            PropertyValue::Color(_) => PropertyType::Color,
+        }
+    }
+
+    /// Get the value if it's a [PropertyValue::Bool]
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            PropertyValue::Bool(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Get the value if it's a [PropertyValue::Int]
+    pub fn as_int(&self) -> Option<i64> {
+        match *self {
+            PropertyValue::Int(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Get the value if it's a [PropertyValue::Float], integers are
+    /// converted
+    pub fn as_float(&self) -> Option<f64> {
+        match *self {
+            PropertyValue::Float(value) => Some(value),
+            PropertyValue::Int(value) => Some(value as f64),
+            _ => None,
+        }
+    }
+
+    /// Get the value if it's a [PropertyValue::String]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            PropertyValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Get the value if it's a [PropertyValue::Vec3]
+    pub fn as_vec3(&self) -> Option<[f32; 3]> {
+        match *self {
+            PropertyValue::Vec3(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Get the value if it's a [PropertyValue::Color]
+    pub fn as_color(&self) -> Option<[f32; 4]> {
+        match *self {
+            PropertyValue::Color(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Get the number of bytes [PropertyValue::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        // The type
+        1 + match self {
+            PropertyValue::Bool(_) => 1,
+            PropertyValue::Int(_) => std::mem::size_of::<i64>(),
+            PropertyValue::Float(_) => std::mem::size_of::<f64>(),
+            PropertyValue::String(value) => string_size(value),
+            PropertyValue::Vec3(_) => 3 * std::mem::size_of::<f32>(),
+            PropertyValue::Color(_) => 4 * std::mem::size_of::<f32>(),
+        }
+    }
+
+    /// Serialize the value to a buffer, the type followed by the value
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - Successfully serialized the value
+    /// * `Err(`[Error]`)` - Failed to serialize the value
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        buffer.push(self.property_type().to_u8());
+
+        match self {
+            PropertyValue::Bool(value) => buffer.push(*value as u8),
+            PropertyValue::Int(value) => {
+                buffer.extend_from_slice(&value.to_le_bytes());
+            }
+            PropertyValue::Float(value) => {
+                buffer.extend_from_slice(&value.to_le_bytes());
+            }
+            PropertyValue::String(value) => write_string(buffer, value)?,
+            PropertyValue::Vec3(value) => write_f32s(buffer, value),
+            PropertyValue::Color(value) => write_f32s(buffer, value),
+        }
+
+        Ok(())
+    }
+
+    /// Read a value written by [PropertyValue::serialize]
+    pub(crate) fn read(reader: &mut Reader) -> Result<Self> {
+        let value = match PropertyType::from_u8(reader.u8()?)? {
+            PropertyType::Bool => PropertyValue::Bool(reader.u8()? != 0),
+            PropertyType::Int => {
+                PropertyValue::Int(i64::from_le_bytes(reader.array()?))
+            }
+            PropertyType::Float => {
+                PropertyValue::Float(f64::from_le_bytes(reader.array()?))
+            }
+            PropertyType::String => PropertyValue::String(reader.string()?),
+            PropertyType::Vec3 => PropertyValue::Vec3(reader.vec3()?),
+            PropertyType::Color => {
+                PropertyValue::Color([reader.f32()?, reader.f32()?,
+                                      reader.f32()?, reader.f32()?])
+            }
+        };
+
+        Ok(value)
+    }
+
+    /// Deserialize the value from a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the value
+    /// * `Err(`[Error]`)` - Failed to deserialize the value
+    pub fn deserialize(buffer: &[u8]) -> Result<Self> {
+        Self::read(&mut Reader::new(buffer, Error::BufferToSmallChunk))
+    }
+}
+
+impl From<bool> for PropertyValue {
+    fn from(value: bool) -> Self {
+        PropertyValue::Bool(value)
+    }
+}
+
+impl From<i64> for PropertyValue {
+    fn from(value: i64) -> Self {
+        PropertyValue::Int(value)
+    }
+}
+
+impl From<f64> for PropertyValue {
+    fn from(value: f64) -> Self {
+        PropertyValue::Float(value)
+    }
+}
+
+impl From<&str> for PropertyValue {
+    fn from(value: &str) -> Self {
+        PropertyValue::String(value.to_string())
+    }
+}
+
+impl From<String> for PropertyValue {
+    fn from(value: String) -> Self {
+        PropertyValue::String(value)
+    }
+}
+
+impl From<[f32; 3]> for PropertyValue {
+    fn from(value: [f32; 3]) -> Self {
+        PropertyValue::Vec3(value)
+    }
+}
+
+impl From<[f32; 4]> for PropertyValue {
+    fn from(value: [f32; 4]) -> Self {
+        PropertyValue::Color(value)
+    }
+}
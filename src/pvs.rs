@@ -0,0 +1,369 @@
+//! Potentially visible sets computed from the portals between sectors
+
+use crate::*;
+use crate::buffer::{ Reader, write_usize, write_list, list_size };
+
+/// How close the ends of two walls have to be to form a portal
+const PORTAL_EPSILON: f32 = 1e-3;
+
+/// An opening between two sectors, formed by two sided walls of the
+/// sectors with the same ends
+#[derive(Clone, PartialEq, Debug)]
+pub struct Portal {
+    /// The index of the sector the portal leads from
+    pub from: usize,
+
+    /// The index of the sector the portal leads to
+    pub to: usize,
+
+    /// The start of the opening seen from above (x, z)
+    pub start: [f32; 2],
+
+    /// The end of the opening seen from above (x, z)
+    pub end: [f32; 2],
+}
+
+/// Which sectors can possibly be seen from every sector, stored as one run
+/// length compressed bitset per sector
+///
+/// The sets are built for the sector order of the map when
+/// [Map::build_pvs] was called, rebuild them after adding, removing or
+/// moving sectors.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Pvs {
+    /// The number of sectors the sets were built for
+    sector_count: usize,
+
+    /// The compressed bitset of every sector, runs of zero bytes are
+    /// stored as a zero followed by the length of the run
+    rows: Vec<Vec<u8>>,
+}
+
+impl Pvs {
+    /// Create the sets from the sectors visible from every sector
+    ///
+    /// # Arguments
+    ///
+    /// * `visible` - The indices of the sectors visible from every sector
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The compressed sets
+    pub fn new(visible: &[Vec<usize>]) -> Self {
+        let sector_count = visible.len();
+
+        let rows = visible.iter()
+            .map(|sectors| {
+                let mut bits = vec![0u8; sector_count.div_ceil(8)];
+                for &index in sectors {
+                    if let Some(byte) = bits.get_mut(index / 8) {
+                        *byte |= 1 << (index % 8);
+                    }
+                }
+
+                compress(&bits)
+            })
+            .collect();
+
+        Self {
+            sector_count,
+            rows,
+        }
+    }
+
+    /// Get the number of sectors the sets were built for
+    pub fn sector_count(&self) -> usize {
+        self.sector_count
+    }
+
+    /// Check if a sector can possibly be seen from another sector
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - The index of the sector the viewer is inside
+    /// * `to`   - The index of the sector to check
+    ///
+    /// # Returns
+    ///
+    /// * `true` - The sector might be visible, also returned for sectors
+    ///   the sets weren't built for so culling stays conservative
+    /// * `false` - The sector can't be seen
+    pub fn is_visible(&self, from: usize, to: usize) -> bool {
+        let Some(row) = self.rows.get(from) else {
+            return true;
+        };
+
+        if to >= self.sector_count {
+            return true;
+        }
+
+        decompress(row).get(to / 8)
+            .is_some_and(|byte| byte & (1 << (to % 8)) != 0)
+    }
+
+    /// Get the sectors that can possibly be seen from a sector
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - The index of the sector the viewer is inside
+    ///
+    /// # Returns
+    ///
+    /// * The indices of the visible sectors, empty if the sets weren't
+    ///   built for the sector
+    pub fn visible_sectors(&self, from: usize) -> Vec<usize> {
+        let Some(row) = self.rows.get(from) else {
+            return Vec::new();
+        };
+
+        let bits = decompress(row);
+        (0..self.sector_count)
+            .filter(|&index| {
+                bits.get(index / 8)
+                    .is_some_and(|byte| byte & (1 << (index % 8)) != 0)
+            })
+            .collect()
+    }
+
+    /// Get the number of bytes [Pvs::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        8 + list_size(&self.rows, Vec::len)
+    }
+
+    /// Serialize the sets to a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - Successfully serialized the sets
+    /// * `Err(`[Error]`)` - Failed to serialize the sets
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        write_usize(buffer, self.sector_count)?;
+        write_list(buffer, &self.rows, |row, buffer| {
+            buffer.extend_from_slice(row);
+            Ok(())
+        })
+    }
+
+    /// Deserialize the sets from a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the sets
+    /// * `Err(`[Error]`)` - Failed to deserialize the sets
+    pub fn deserialize(buffer: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallChunk);
+
+        let sector_count = reader.usize()?;
+        let rows = reader.list(|row| Ok(row.to_vec()))?;
+
+        Ok(Self {
+            sector_count,
+            rows,
+        })
+    }
+}
+
+/// Compress a bitset, runs of zero bytes are replaced by a zero followed by
+/// the length of the run
+fn compress(bits: &[u8]) -> Vec<u8> {
+    let mut result = Vec::new();
+
+    let mut i = 0;
+    while i < bits.len() {
+        if bits[i] != 0 {
+            result.push(bits[i]);
+            i += 1;
+            continue;
+        }
+
+        let run = bits[i..].iter()
+            .take(u8::MAX as usize)
+            .take_while(|&&byte| byte == 0)
+            .count();
+        result.push(0);
+        result.push(run as u8);
+        i += run;
+    }
+
+    result
+}
+
+/// Decompress a bitset written by [compress]
+fn decompress(row: &[u8]) -> Vec<u8> {
+    let mut result = Vec::new();
+
+    let mut bytes = row.iter();
+    while let Some(&byte) = bytes.next() {
+        if byte != 0 {
+            result.push(byte);
+        } else {
+            let run = bytes.next().copied().unwrap_or(0);
+            result.resize(result.len() + run as usize, 0);
+        }
+    }
+
+    result
+}
+
+/// Check if a single line passes through every opening, seen from above
+///
+/// If such a line exists there is also one passing through two of the ends
+/// of the openings, so only those lines are tested.
+fn stabbing_line_exists(openings: &[([f32; 2], [f32; 2])]) -> bool {
+    let points = openings.iter()
+        .flat_map(|&(start, end)| [start, end])
+        .collect::<Vec<_>>();
+
+    let side = |p: [f32; 2], q: [f32; 2], r: [f32; 2]| {
+        (q[0] - p[0]) * (r[1] - p[1]) - (q[1] - p[1]) * (r[0] - p[0])
+    };
+
+    for (i, &p) in points.iter().enumerate() {
+        for &q in &points[i + 1..] {
+            if p == q {
+                continue;
+            }
+
+            let stabs = openings.iter().all(|&(start, end)| {
+                let a = side(p, q, start);
+                let b = side(p, q, end);
+                a * b <= 0.0 ||
+                    a.abs() <= PORTAL_EPSILON ||
+                    b.abs() <= PORTAL_EPSILON
+            });
+
+            if stabs {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+impl Map {
+    /// Find the portals between the sectors, every opening is returned
+    /// once for each direction
+    pub fn portals(&self) -> Vec<Portal> {
+        let flat = |p: [f32; 3]| [p[0], p[2]];
+        let same = |a: [f32; 2], b: [f32; 2]| {
+            (a[0] - b[0]).abs() <= PORTAL_EPSILON &&
+                (a[1] - b[1]).abs() <= PORTAL_EPSILON
+        };
+
+        let mut portals = Vec::new();
+        for (from, sector) in self.sectors.iter().enumerate() {
+            for wall in sector.walls.iter().filter(|w| w.is_two_sided()) {
+                let start = flat(wall.start);
+                let end = flat(wall.end);
+
+                for (to, other) in self.sectors.iter().enumerate() {
+                    if to == from {
+                        continue;
+                    }
+
+                    let connected = other.walls.iter()
+                        .filter(|w| w.is_two_sided())
+                        .any(|w| {
+                            let (a, b) = (flat(w.start), flat(w.end));
+                            (same(a, start) && same(b, end)) ||
+                                (same(a, end) && same(b, start))
+                        });
+
+                    if connected {
+                        portals.push(Portal { from, to, start, end });
+                    }
+                }
+            }
+        }
+
+        portals
+    }
+
+    /// Compute the potentially visible sets from the portals between the
+    /// sectors and store them in [Map::pvs]
+    ///
+    /// A sector is visible from another sector when a line seen from above
+    /// passes through every portal on a path between the two sectors. The
+    /// test is conservative, the sets can contain sectors that are hidden
+    /// but never miss a visible sector.
+    pub fn build_pvs(&mut self) {
+        let portals = self.portals();
+
+        let visible = (0..self.sectors.len())
+            .map(|index| {
+                let mut visible = vec![false; self.sectors.len()];
+                visible[index] = true;
+
+                let mut path = vec![index];
+                let mut openings = Vec::new();
+                flood_portals(&portals, &mut path, &mut openings,
+                              &mut visible);
+
+                visible.iter()
+                    .enumerate()
+                    .filter(|(_, &visible)| visible)
+                    .map(|(index, _)| index)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        self.pvs = Some(Pvs::new(&visible));
+    }
+
+    /// Check if a sector can possibly be seen from another sector
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - The index of the sector the viewer is inside
+    /// * `to`   - The index of the sector to check
+    ///
+    /// # Returns
+    ///
+    /// * `true` - The sector might be visible, also returned when the map
+    ///   doesn't have potentially visible sets
+    /// * `false` - The sector can't be seen
+    pub fn is_sector_potentially_visible(&self, from: usize, to: usize)
+        -> bool
+    {
+        self.pvs.as_ref().is_none_or(|pvs| pvs.is_visible(from, to))
+    }
+}
+
+/// Walk through the portals leaving the last sector of the path and mark
+/// the sectors that can be seen through all the openings of the path
+fn flood_portals(portals: &[Portal],
+                 path: &mut Vec<usize>,
+                 openings: &mut Vec<([f32; 2], [f32; 2])>,
+                 visible: &mut [bool])
+{
+    let current = *path.last().unwrap();
+
+    for portal in portals.iter().filter(|portal| portal.from == current) {
+        if path.contains(&portal.to) {
+            continue;
+        }
+
+        openings.push((portal.start, portal.end));
+
+        if stabbing_line_exists(openings) {
+            visible[portal.to] = true;
+
+            path.push(portal.to);
+            flood_portals(portals, path, openings, visible);
+            path.pop();
+        }
+
+        openings.pop();
+    }
+}
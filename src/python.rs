@@ -0,0 +1,302 @@
+//! Python bindings, enabled by the `python` feature
+//!
+//! The module is called `mime` and has the classes `Map`, `Sector` and
+//! `Mesh`. The vertices of a mesh are a numpy array with the shape (n, 9),
+//! every row is `x, y, z, u, v, r, g, b, a`, and the indices are a flat
+//! numpy array. The classes own copies of the data, a sector read from a
+//! map has to be added to the map again to store changes.
+//!
+//! The crate is only built as a Rust library, `maturin build --features
+//! python` builds the extension module as a cdylib.
+
+use crate::*;
+
+use std::path::PathBuf;
+
+use numpy::{ PyArray1, PyArray2, PyArrayMethods, PyReadonlyArray1,
+             PyReadonlyArray2 };
+use numpy::ndarray::{ ArrayView1, ArrayView2 };
+use pyo3::exceptions::{ PyIOError, PyIndexError, PyValueError };
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+/// The number of floats in a row of the vertex array
+const VERTEX_COMPONENTS: usize = 9;
+
+impl From<Error> for PyErr {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::FileCreationFailed(error) |
+            Error::FileWriteFailed(error) |
+            Error::FileReadFailed(error) => PyIOError::new_err(error),
+
+            error => PyValueError::new_err(format!("{:?}", error)),
+        }
+    }
+}
+
+/// Create a mesh from the arrays given to `Mesh()`
+///
+/// # Arguments
+///
+/// * `vertices`   - The (n, 9) vertex array
+/// * `indices`    - The flat index array
+/// * `texture_id` - The Texture ID inside the Texture Table
+///
+/// # Returns
+///
+/// * `Ok(`[Mesh]`)` - The mesh
+/// * `Err(String)` - The message of the `ValueError`, the vertex array has
+///   the wrong shape, the index count isn't a multiple of 3 or an index
+///   doesn't reference a vertex, it isn't a PyErr so the tests can call
+///   this without linking libpython
+pub(crate) fn mesh_from_arrays(vertices: ArrayView2<f32>,
+                               indices: ArrayView1<u32>,
+                               texture_id: u64)
+    -> std::result::Result<Mesh, String>
+{
+    if vertices.ncols() != VERTEX_COMPONENTS {
+        return Err(format!("vertices need {} columns", VERTEX_COMPONENTS));
+    }
+
+    let vertex_buffer = vertices.rows()
+        .into_iter()
+        .map(|row| {
+            Vertex::new([row[0], row[1], row[2]],
+                        [row[3], row[4]],
+                        [row[5], row[6], row[7], row[8]])
+        })
+        .collect::<Vec<_>>();
+
+    // NOTE(patrik): usize::is_multiple_of is newer than the rust-version
+    // of the crate
+    let index_buffer = indices.to_vec();
+    if index_buffer.len() % 3 != 0 {
+        return Err("index count isn't a multiple of 3".to_string());
+    }
+
+    let vertex_count = vertex_buffer.len();
+    if index_buffer.iter().any(|&index| index as usize >= vertex_count) {
+        return Err("index out of range".to_string());
+    }
+
+    Ok(Mesh::new(vertex_buffer, index_buffer, texture_id))
+}
+
+/// A mesh with a vertex buffer and an index buffer
+#[pyclass(name = "Mesh", module = "mime")]
+pub struct PyMesh {
+    mesh: Mesh,
+}
+
+#[pymethods]
+impl PyMesh {
+    /// Create a mesh, the index count has to be a multiple of 3 and every
+    /// index has to reference a vertex
+    #[new]
+    #[pyo3(signature = (vertices, indices, texture_id = 0))]
+    fn new(vertices: PyReadonlyArray2<f32>,
+           indices: PyReadonlyArray1<u32>,
+           texture_id: u64)
+        -> PyResult<Self>
+    {
+        let mesh = mesh_from_arrays(vertices.as_array(), indices.as_array(),
+                                    texture_id)
+            .map_err(PyValueError::new_err)?;
+
+        Ok(Self { mesh })
+    }
+
+    /// The vertices as a (n, 9) float32 array
+    #[getter]
+    fn vertices<'py>(&self, py: Python<'py>)
+        -> PyResult<Bound<'py, PyArray2<f32>>>
+    {
+        let data = self.mesh.vertex_buffer.iter()
+            .flat_map(|vertex| {
+                vertex.pos.iter()
+                    .chain(&vertex.uv)
+                    .chain(&vertex.color)
+                    .copied()
+            })
+            .collect::<Vec<_>>();
+
+        PyArray1::from_vec(py, data)
+            .reshape([self.mesh.vertex_buffer.len(), VERTEX_COMPONENTS])
+    }
+
+    /// The positions of the vertices as a (n, 3) float32 array
+    #[getter]
+    fn positions<'py>(&self, py: Python<'py>)
+        -> PyResult<Bound<'py, PyArray2<f32>>>
+    {
+        let data = self.mesh.vertex_buffer.iter()
+            .flat_map(|vertex| vertex.pos)
+            .collect::<Vec<_>>();
+
+        PyArray1::from_vec(py, data)
+            .reshape([self.mesh.vertex_buffer.len(), 3])
+    }
+
+    /// The indices as a uint32 array
+    #[getter]
+    fn indices<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<u32>> {
+        PyArray1::from_slice(py, &self.mesh.index_buffer)
+    }
+
+    /// The Texture ID inside the Texture Table
+    #[getter]
+    fn texture_id(&self) -> u64 {
+        self.mesh.texture_id
+    }
+
+    #[setter]
+    fn set_texture_id(&mut self, texture_id: u64) {
+        self.mesh.texture_id = texture_id;
+    }
+}
+
+/// A sector of the map
+#[pyclass(name = "Sector", module = "mime")]
+pub struct PySector {
+    sector: Sector,
+}
+
+#[pymethods]
+impl PySector {
+    /// Create a sector from the floor, ceiling and wall meshes
+    #[new]
+    #[pyo3(signature = (floor, ceiling, wall, tag = 0))]
+    fn new(floor: PyRef<PyMesh>,
+           ceiling: PyRef<PyMesh>,
+           wall: PyRef<PyMesh>,
+           tag: u32)
+        -> Self
+    {
+        let mut sector = Sector::new(floor.mesh.clone(),
+                                     ceiling.mesh.clone(),
+                                     wall.mesh.clone());
+        sector.tag = tag;
+
+        Self { sector }
+    }
+
+    /// The ID of the sector inside the map
+    #[getter]
+    fn id(&self) -> u64 {
+        self.sector.id().0
+    }
+
+    /// The tag used by triggers and scripts
+    #[getter]
+    fn tag(&self) -> u32 {
+        self.sector.tag
+    }
+
+    #[setter]
+    fn set_tag(&mut self, tag: u32) {
+        self.sector.tag = tag;
+    }
+
+    /// The bits of the gameplay flags
+    #[getter]
+    fn flags(&self) -> u32 {
+        self.sector.flags.bits()
+    }
+
+    #[setter]
+    fn set_flags(&mut self, flags: u32) {
+        self.sector.flags = SectorFlags::from_bits_retain(flags);
+    }
+
+    /// A copy of the floor mesh
+    #[getter]
+    fn floor(&self) -> PyMesh {
+        PyMesh { mesh: self.sector.floor_mesh.clone() }
+    }
+
+    /// A copy of the ceiling mesh
+    #[getter]
+    fn ceiling(&self) -> PyMesh {
+        PyMesh { mesh: self.sector.ceiling_mesh.clone() }
+    }
+
+    /// A copy of the wall mesh
+    #[getter]
+    fn wall(&self) -> PyMesh {
+        PyMesh { mesh: self.sector.wall_mesh.clone() }
+    }
+}
+
+/// A map loaded from or saved to a mime file with a single map
+#[pyclass(name = "Map", module = "mime")]
+pub struct PyMap {
+    map: Map,
+}
+
+#[pymethods]
+impl PyMap {
+    /// Create an empty map
+    #[new]
+    fn new() -> Self {
+        Self { map: Map::new(Vec::new()) }
+    }
+
+    /// Load a map from a file, see [Map::load_from_file]
+    #[staticmethod]
+    fn load(path: PathBuf) -> PyResult<Self> {
+        Ok(Self { map: Map::load_from_file(path)? })
+    }
+
+    /// Load a map from the content of a file, see [Map::load_from_bytes]
+    #[staticmethod]
+    fn from_bytes(data: &[u8]) -> PyResult<Self> {
+        Ok(Self { map: Map::load_from_bytes(data)? })
+    }
+
+    /// Save the map to a file, see [Map::save_to_file]
+    fn save(&mut self, path: PathBuf) -> PyResult<()> {
+        Ok(self.map.save_to_file(path)?)
+    }
+
+    /// Serialize the map to the content of a file, see [Map::save_to_bytes]
+    fn to_bytes<'py>(&self, py: Python<'py>)
+        -> PyResult<Bound<'py, PyBytes>>
+    {
+        Ok(PyBytes::new(py, &self.map.save_to_bytes()?))
+    }
+
+    fn __len__(&self) -> usize {
+        self.map.sectors.len()
+    }
+
+    /// A copy of the sector at an index
+    fn sector(&self, index: usize) -> PyResult<PySector> {
+        self.map.sectors.get(index)
+            .map(|sector| PySector { sector: sector.clone() })
+            .ok_or_else(|| {
+                PyIndexError::new_err("sector index out of range")
+            })
+    }
+
+    /// Add a copy of a sector to the map and return the ID it was given
+    fn add_sector(&mut self, sector: PyRef<PySector>) -> u64 {
+        self.map.add_sector(sector.sector.clone()).0
+    }
+
+    /// Remove the sector with an ID, returns False if there is no sector
+    /// with the ID
+    fn remove_sector(&mut self, id: u64) -> bool {
+        self.map.remove_sector(SectorId(id)).is_some()
+    }
+}
+
+/// The `mime` Python module
+#[pymodule]
+fn mime(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyMap>()?;
+    module.add_class::<PySector>()?;
+    module.add_class::<PyMesh>()?;
+
+    Ok(())
+}
@@ -0,0 +1,219 @@
+//! Salvaging the intact parts of a damaged map
+//!
+//! [Map::recover] deserializes a map like [Map::deserialize] but skips the
+//! sectors and chunks that fail to deserialize or don't match their
+//! checksum instead of failing. Everything that was skipped is listed in the
+//! [DamageReport] returned with the partial map. Only damage to the headers
+//! and the sector table stops the recovery.
+//!
+//! The skipped sectors are replaced by empty sectors so the other sectors
+//! keep their indices, the [Pvs], the portals and everything else
+//! referencing sectors by index stay valid.
+
+use crate::*;
+use crate::buffer::Reader;
+use crate::incremental::MAP_OFFSET;
+use crate::lazy::parse_slots;
+use crate::limits::Budget;
+use crate::map::{ HEADER_MAGIC, SECTOR_ENTRY_SIZE, clamp_next_sector_id,
+                  read_header_compression, read_magic_and_version };
+
+/// A sector skipped by [Map::recover]
+#[derive(Debug)]
+pub struct SectorDamage {
+    /// The index of the sector inside the sector table, the sector at this
+    /// index of the recovered map is an empty placeholder with a new ID
+    pub index: usize,
+
+    /// Why the sector was skipped
+    pub error: Error,
+}
+
+/// A chunk skipped by [Map::recover]
+#[derive(Debug)]
+pub struct ChunkDamage {
+    /// The tag of the chunk, `None` when the chunk list itself is damaged
+    /// and the chunks after it couldn't be found
+    pub tag: Option<[u8; 4]>,
+
+    /// Why the chunk was skipped
+    pub error: Error,
+}
+
+/// Everything [Map::recover] skipped while salvaging a map
+#[derive(Default, Debug)]
+pub struct DamageReport {
+    /// The sectors that were skipped, in the order of the sector table
+    pub sectors: Vec<SectorDamage>,
+
+    /// The chunks that were skipped, the chunks keep their defaults
+    pub chunks: Vec<ChunkDamage>,
+
+    /// The broken references found by the checks done when a map is
+    /// loaded, like a prop using a library mesh that wasn't recovered, the
+    /// map is returned as is and fails to load again until they are fixed
+    pub references: Vec<Error>,
+}
+
+impl DamageReport {
+    /// Check if the whole map was recovered
+    pub fn is_empty(&self) -> bool {
+        self.sectors.is_empty() &&
+            self.chunks.is_empty() &&
+            self.references.is_empty()
+    }
+}
+
+impl Map {
+    /// Deserialize as much of a damaged map as possible
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize, written by
+    ///   [Map::serialize]
+    ///
+    /// # Returns
+    ///
+    /// * `Ok((`[Map]`, `[DamageReport]`))` - The intact sectors and chunks
+    ///   and what was skipped, the skipped sectors are empty
+    /// * `Err(`[Error]`)` - The header or the sector table is damaged
+    pub fn recover(buffer: &[u8]) -> Result<(Self, DamageReport)> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallMap);
+
+        let sector_count = reader.usize()?;
+        let chunks_offset = reader.u64()?;
+        let next_sector_id = reader.u64()?;
+        let compression = read_header_compression(&mut reader)?;
+
+        let table_size = sector_count.saturating_mul(SECTOR_ENTRY_SIZE);
+        let slots = parse_slots(reader.bytes(table_size)?);
+
+        let budget = Budget::unlimited();
+        let sectors = slots.into_iter()
+            .enumerate()
+            .map(|(index, slot)| {
+                Self::deserialize_sector(buffer, index, slot, &budget)
+            })
+            .collect::<Vec<_>>();
+
+        // NOTE(patrik): Map::new would give the sectors new IDs, the
+        // placeholders get IDs past the intact sectors
+        let mut map = Self::new(Vec::new());
+        map.next_sector_id =
+            clamp_next_sector_id(next_sector_id, sectors.iter().flatten());
+        map.compression = compression;
+
+        let mut report = DamageReport::default();
+        for (index, sector) in sectors.into_iter().enumerate() {
+            match sector {
+                Ok(sector) => map.sectors.push(sector),
+                // NOTE(patrik): The ID of a damaged sector can't be trusted,
+                // the placeholder gets a new one
+                Err(error) => {
+                    map.add_sector(Sector::default());
+                    report.sectors.push(SectorDamage { index, error });
+                }
+            }
+        }
+
+        let offset = usize::try_from(chunks_offset).unwrap_or(usize::MAX);
+        match buffer.get(offset..) {
+            Some(chunks) => map.recover_chunks(chunks, offset, &mut report),
+
+            None => {
+                // NOTE(patrik): Point at the chunks offset inside the header
+                let size = SizeMismatch::new(offset, buffer.len());
+                report.chunks.push(ChunkDamage {
+                    tag: None,
+                    error: Error::BufferToSmallMap(size).at_offset(8),
+                });
+            }
+        }
+
+        let checks = [Self::check_props, Self::check_interactives,
+                      Self::check_scripts];
+        report.references = checks.iter()
+            .filter_map(|check| check(&map).err())
+            .collect();
+
+        Ok((map, report))
+    }
+
+    /// Deserialize as much of a damaged mime file containing a single map
+    /// as possible, see [Map::recover]
+    ///
+    /// A map size past the end of the file is ignored and the rest of the
+    /// file is used, so truncated downloads still give the sectors before
+    /// the cut.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The content of the file
+    ///
+    /// # Returns
+    ///
+    /// * `Ok((`[Map]`, `[DamageReport]`))` - The intact sectors and chunks
+    ///   and what was skipped
+    /// * `Err(`[Error]`)` - The headers or the sector table are damaged
+    pub fn recover_from_bytes(buffer: &[u8]) -> Result<(Self, DamageReport)> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallHeader);
+        read_magic_and_version(&mut reader, HEADER_MAGIC)?;
+
+        if reader.u64()? != 1 {
+            return Err(Error::NotSingleMap);
+        }
+
+        let size = usize::try_from(reader.u64()?).unwrap_or(usize::MAX);
+        let map = reader.remaining();
+        let map = &map[..size.min(map.len())];
+
+        Self::recover(map)
+            .map_err(|error| error.at_offset(MAP_OFFSET as usize))
+    }
+
+    /// Deserialize the chunks one at a time and record the chunks that fail
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The chunk list and everything after it
+    /// * `offset` - The offset of the chunk list inside the map, added to
+    ///   the errors
+    /// * `report` - Gets the skipped chunks
+    fn recover_chunks(&mut self,
+                      buffer: &[u8],
+                      offset: usize,
+                      report: &mut DamageReport)
+    {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallMap);
+        let mut damage = |tag, error: Error| {
+            report.chunks.push(ChunkDamage {
+                tag,
+                error: error.at_offset(offset),
+            });
+        };
+
+        let chunk_count = match reader.usize() {
+            Ok(count) => count,
+            Err(error) => return damage(None, error),
+        };
+
+        for _i in 0..chunk_count {
+            let tag = match reader.array::<4>() {
+                Ok(tag) => tag,
+                Err(error) => return damage(None, error),
+            };
+
+            // NOTE(patrik): A chunk with a broken size hides where the next
+            // chunk starts
+            let chunk = match reader.sized() {
+                Ok(chunk) => chunk,
+                Err(error) => return damage(Some(tag), error),
+            };
+
+            let start = reader.offset() - chunk.len();
+            if let Err(error) = self.deserialize_chunk(&tag, chunk) {
+                damage(Some(tag), error.at_offset(start));
+            }
+        }
+    }
+}
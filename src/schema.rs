@@ -0,0 +1,811 @@
+//! A description of the binary format that can be walked by programs
+//!
+//! [schema] describes every structure and chunk of the current format
+//! version, so tools like hex viewer plugins or validators in other
+//! languages can be generated from it instead of being kept in sync with
+//! the serialization code by hand. All the numbers are little endian.
+
+use crate::map::{ CURRENT_VERSION, HEADER_MAGIC, THUMBNAIL_CHUNK,
+                  COLOR_SPACE_CHUNK, AXES_CHUNK, UNITS_CHUNK, LIGHTMAP_CHUNK,
+                  LIGHTS_CHUNK, SKY_CHUNK, SOUND_EMITTERS_CHUNK,
+                  TRIGGERS_CHUNK, ENTITIES_CHUNK, EMBEDDED_ASSETS_CHUNK,
+                  DEPENDENCIES_CHUNK, MATERIALS_CHUNK, ANIMATIONS_CHUNK,
+                  GROUPS_CHUNK, PVS_CHUNK, WAYPOINTS_CHUNK,
+                  MESH_LIBRARY_CHUNK, PROPS_CHUNK, DECALS_CHUNK,
+                  ENVIRONMENT_CHUNK, INTERACTIVES_CHUNK, SCRIPTS_CHUNK,
+                  SIGNATURE_CHUNK };
+use crate::signature::SIGNATURE_SIZE;
+
+/// How a single field is encoded
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FieldKind {
+    /// An unsigned byte
+    U8,
+
+    /// An unsigned 32 bit integer
+    U32,
+
+    /// An unsigned 64 bit integer, also used for every count and size
+    U64,
+
+    /// A signed 64 bit integer
+    I64,
+
+    /// A 32 bit float
+    F32,
+
+    /// A 64 bit float
+    F64,
+
+    /// A fixed number of 32 bit floats
+    F32s(usize),
+
+    /// A fixed number of raw bytes
+    Bytes(usize),
+
+    /// A UTF-8 string prefixed with its length in bytes as a u64
+    String,
+
+    /// A byte telling if the value is used, the value only follows when
+    /// the byte isn't 0
+    Optional(&'static FieldKind),
+
+    /// A structure of the schema stored inline
+    Struct(&'static str),
+
+    /// A structure of the schema prefixed with its size as a u64
+    Sized(&'static str),
+
+    /// A u64 count followed by every item as a [FieldKind::Sized] structure
+    List(&'static str),
+
+    /// The item repeated the number of times stored in an earlier field,
+    /// a path into a repeated structure (`uv_sets.uv_count`) means the sum
+    /// of the field over every repetition
+    Repeat {
+        /// The name of the field with the count
+        count: &'static str,
+
+        /// How every item is encoded
+        item: &'static FieldKind,
+    },
+
+    /// The structure picked by the value of an earlier [FieldKind::U8]
+    /// field, a value without a case is an error
+    Variant {
+        /// The name of the field with the value
+        tag: &'static str,
+
+        /// The value and the name of the structure for every case
+        cases: &'static [(u8, &'static str)],
+    },
+
+    /// The item stored at the offset inside the map held by another field
+    At {
+        /// The name of the field with the offset
+        offset: &'static str,
+
+        /// How the item is encoded
+        item: &'static FieldKind,
+    },
+
+    /// A vertex encoded with the attributes of the vertex descriptor of
+    /// the mesh, in the order of the descriptor
+    Vertex,
+
+    /// The bytes up to the end of the enclosing block
+    Rest,
+}
+
+impl FieldKind {
+    /// Get the number of bytes the field takes up
+    ///
+    /// # Returns
+    ///
+    /// * `Some(usize)` - The size of the field
+    /// * `None` - The size depends on the content of the field
+    pub fn size(self) -> Option<usize> {
+        match self {
+            FieldKind::U8 => Some(1),
+            FieldKind::U32 | FieldKind::F32 => Some(4),
+            FieldKind::U64 | FieldKind::I64 | FieldKind::F64 => Some(8),
+            FieldKind::F32s(count) => Some(count * 4),
+            FieldKind::Bytes(count) => Some(count),
+            _ => None,
+        }
+    }
+}
+
+/// A single field of a structure
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Field {
+    /// The name of the field
+    pub name: &'static str,
+
+    /// How the field is encoded
+    pub kind: FieldKind,
+
+    /// The offset of the field from the start of the structure, `None` if
+    /// a field before it doesn't have a fixed size
+    pub offset: Option<usize>,
+
+    /// What the field holds
+    pub description: &'static str,
+}
+
+/// A structure of the format, a list of fields stored back to back
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct StructSchema {
+    /// The name other fields use to reference the structure
+    pub name: &'static str,
+
+    /// The fields in the order they are stored
+    pub fields: Vec<Field>,
+
+    /// The size of the structure, `None` if a field doesn't have a fixed
+    /// size
+    pub size: Option<usize>,
+}
+
+impl StructSchema {
+    /// Get a field of the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the field
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[Field]`)` - The field
+    /// * `None` - The structure doesn't have a field with the name
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+}
+
+/// A chunk of the map, stored inside the `Chunk` structure
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ChunkSchema {
+    /// The tag identifying the chunk
+    pub tag: [u8; 4],
+
+    /// How the content of the chunk is encoded
+    pub content: FieldKind,
+
+    /// `true` if every map has the chunk, the other chunks are only
+    /// written when they have content
+    pub required: bool,
+
+    /// What the chunk holds
+    pub description: &'static str,
+}
+
+/// The description of the whole format, see [schema]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Schema {
+    /// The version of the format described, files with another major
+    /// version (the upper 16 bits) are rejected
+    pub version: u32,
+
+    /// The magic at the start of every file
+    pub magic: [u8; 4],
+
+    /// The name of the structure at the start of every file
+    pub root: &'static str,
+
+    /// Every structure of the format
+    pub structs: Vec<StructSchema>,
+
+    /// Every chunk of the format in the order they are written
+    pub chunks: Vec<ChunkSchema>,
+}
+
+impl Schema {
+    /// Get a structure of the schema
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the structure
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[StructSchema]`)` - The structure
+    /// * `None` - The schema doesn't have a structure with the name
+    pub fn structure(&self, name: &str) -> Option<&StructSchema> {
+        self.structs.iter().find(|structure| structure.name == name)
+    }
+
+    /// Get a chunk of the schema
+    ///
+    /// # Arguments
+    ///
+    /// * `tag` - The tag of the chunk
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[ChunkSchema]`)` - The chunk
+    /// * `None` - The tag isn't a known chunk
+    pub fn chunk(&self, tag: &[u8; 4]) -> Option<&ChunkSchema> {
+        self.chunks.iter().find(|chunk| &chunk.tag == tag)
+    }
+}
+
+/// A field before the offsets are known (name, kind, description)
+type FieldDef = (&'static str, FieldKind, &'static str);
+
+use FieldKind::*;
+
+const MIME: &[FieldDef] = &[
+    ("magic", Bytes(4), "Always \"MIME\""),
+    ("version", U32,
+     "The major version in the upper 16 bits, the minor in the lower"),
+    ("map_count", U64, "The number of maps inside the file"),
+    ("maps", Repeat { count: "map_count", item: &Sized("Map") },
+     "The maps"),
+];
+
+const MAP: &[FieldDef] = &[
+    ("sector_count", U64, "The number of sectors"),
+    ("chunks_offset", U64, "The offset of the chunk list inside the map"),
+    ("next_sector_id", U64, "The ID given to the next sector added"),
+    ("compression", U8, "The codec of the compressed sectors, 0 for none, \
+                         1 for LZ4, 2 for Zstandard and 3 for deflate"),
+    ("sector_table", Repeat { count: "sector_count",
+                              item: &Struct("SectorEntry") },
+     "Where every sector is stored"),
+    ("sectors", At { offset: "sector_table.offset",
+                     item: &Struct("SectorBlock") },
+     "The sectors, the slot of a sector can be larger than the sector"),
+    ("chunks", At { offset: "chunks_offset", item: &Struct("ChunkList") },
+     "The chunks"),
+];
+
+const SECTOR_ENTRY: &[FieldDef] = &[
+    ("offset", U64, "The offset of the sector block inside the map"),
+    ("size", U64, "The size of the sector block"),
+    ("capacity", U64, "The size of the slot reserved for the sector block"),
+    ("checksum", U64, "The xxHash64 with seed 0 of the sector block"),
+];
+
+const SECTOR_BLOCK: &[FieldDef] = &[
+    ("compression", U8, "How the sector is compressed, 0 for none or the \
+                         compression of the map"),
+    ("sector", Rest, "The Sector structure, compressed if the compression \
+                      isn't 0"),
+];
+
+const CHUNK_LIST: &[FieldDef] = &[
+    ("chunk_count", U64, "The number of chunks"),
+    ("chunks", Repeat { count: "chunk_count", item: &Struct("Chunk") },
+     "The chunks"),
+];
+
+const CHUNK: &[FieldDef] = &[
+    ("tag", Bytes(4), "The tag identifying the chunk"),
+    ("size", U64, "The size of the content"),
+    ("content", Repeat { count: "size", item: &U8 },
+     "The content, described by the chunk with the tag"),
+];
+
+const SECTOR: &[FieldDef] = &[
+    ("bounds_min", F32s(3), "The smallest corner of the bounding box"),
+    ("bounds_max", F32s(3), "The largest corner of the bounding box"),
+    ("floor_mesh", Sized("Mesh"), "The mesh of the floor"),
+    ("ceiling_mesh", Sized("Mesh"), "The mesh of the ceiling"),
+    ("wall_mesh", Sized("Mesh"), "The mesh of the walls"),
+    ("lod_count", U64, "The number of levels of detail"),
+    ("lods", Repeat { count: "lod_count", item: &Sized("Lod") },
+     "The levels of detail"),
+    ("tag", U32, "The tag used by the game"),
+    ("id", U64, "The persistent ID of the sector"),
+    ("walls", List("Wall"), "The walls"),
+    ("has_floor_plane", U8, "1 if the floor plane is used"),
+    ("floor_plane", F32s(4), "The plane of a sloped floor"),
+    ("has_ceiling_plane", U8, "1 if the ceiling plane is used"),
+    ("ceiling_plane", F32s(4), "The plane of a sloped ceiling"),
+    ("has_name", U8, "1 if the sector has a name"),
+    ("name", String, "The name of the sector, empty without a name"),
+    ("props", List("Prop"), "The props of the sector"),
+    ("occluders", List("Occluder"), "The occluders of the sector"),
+    ("has_liquid", U8, "1 if the sector is filled with liquid"),
+    ("liquid", Struct("Liquid"), "The liquid of the sector"),
+    ("environment_flags", U8, "Bit 0 set for the fog color, bit 1 for the \
+                               fog density and bit 2 for the ambient color"),
+    ("environment", Struct("Environment"),
+     "The fog and ambient light replacing the defaults of the map, only the \
+      values with their bit set are used"),
+    ("acoustics", Struct("Acoustics"), "The acoustics of the sector"),
+    ("flags", U32, "The gameplay flags, the low 16 bits are reserved for \
+                    the format and the high 16 bits are left to the game"),
+];
+
+const LOD: &[FieldDef] = &[
+    ("switch_distance", F32, "The distance the level of detail is used from"),
+    ("floor_mesh", Sized("Mesh"), "The mesh of the floor"),
+    ("ceiling_mesh", Sized("Mesh"), "The mesh of the ceiling"),
+    ("wall_mesh", Sized("Mesh"), "The mesh of the walls"),
+];
+
+const MESH: &[FieldDef] = &[
+    ("vertex_descriptor", Struct("VertexDescriptor"),
+     "How the vertices are encoded"),
+    ("vertex_count", U64, "The number of vertices"),
+    ("index_count", U64, "The number of indices"),
+    ("vertices", Repeat { count: "vertex_count", item: &Vertex },
+     "The vertices"),
+    ("indices", Repeat { count: "index_count", item: &U32 }, "The indices"),
+    ("lightmap_uv_count", U64, "The number of lightmap coordinates"),
+    ("lightmap_uvs", Repeat { count: "lightmap_uv_count", item: &F32s(2) },
+     "The lightmap texture coordinates"),
+    ("material", U32, "The index of the material, 0xffffffff without one"),
+    ("topology", U8, "0 triangle list, 1 triangle strip, 2 line list and 3 \
+                      point list"),
+    ("uv_set_count", U64, "The number of extra texture coordinate sets"),
+    ("uv_sets", Repeat { count: "uv_set_count", item: &Struct("UvSet") },
+     "The extra texture coordinate sets"),
+    ("uvs", Repeat { count: "uv_sets.uv_count", item: &F32s(2) },
+     "The texture coordinates of every set, one set after another"),
+    ("custom_attributes", List("CustomAttribute"),
+     "The custom per-vertex attributes"),
+    ("submeshes", List("Submesh"), "The submeshes"),
+];
+
+const VERTEX_DESCRIPTOR: &[FieldDef] = &[
+    ("attribute_count", U8, "The number of attributes"),
+    ("attributes", Repeat { count: "attribute_count",
+                            item: &Struct("VertexAttribute") },
+     "The attributes in the order they are stored"),
+];
+
+const VERTEX_ATTRIBUTE: &[FieldDef] = &[
+    ("semantic", U8, "What the attribute holds"),
+    ("format", U8, "How the attribute is encoded"),
+];
+
+const UV_SET: &[FieldDef] = &[
+    ("name", String, "The name of the set"),
+    ("uv_count", U64, "The number of texture coordinates of the set"),
+];
+
+const CUSTOM_ATTRIBUTE: &[FieldDef] = &[
+    ("name", String, "The name of the attribute"),
+    ("format", U8, "How the values are encoded"),
+    ("data", Rest, "The value of every vertex"),
+];
+
+const SUBMESH: &[FieldDef] = &[
+    ("name", String, "The name of the submesh"),
+    ("index_start", U32, "The first index of the submesh"),
+    ("index_count", U32, "The number of indices of the submesh"),
+    ("material", U32, "The index of the material, 0xffffffff without one"),
+];
+
+const WALL: &[FieldDef] = &[
+    ("start", F32s(3), "The start of the wall"),
+    ("end", F32s(3), "The end of the wall"),
+    ("texture", String, "The name of the texture"),
+    ("uv_offset", F32s(2), "The offset of the texture coordinates"),
+    ("flags", U32, "The flags of the wall"),
+    ("index_start", U32, "The first index of the wall inside the wall mesh"),
+    ("index_count", U32, "The number of indices of the wall"),
+];
+
+const LIGHTMAP: &[FieldDef] = &[
+    ("width", U32, "The width in texels"),
+    ("height", U32, "The height in texels"),
+    ("texels", Rest, "The RGBA texels row by row"),
+];
+
+const LIGHT: &[FieldDef] = &[
+    ("kind", U8, "0 point, 1 spot and 2 directional"),
+    ("position", F32s(3), "The position"),
+    ("color", F32s(3), "The color"),
+    ("intensity", F32, "The intensity"),
+    ("radius", F32, "How far the light reaches"),
+    ("shape", Variant { tag: "kind",
+                        cases: &[(0, "PointLight"), (1, "SpotLight"),
+                                 (2, "DirectionalLight")] },
+     "The values of the kind of light"),
+];
+
+const SPOT_LIGHT: &[FieldDef] = &[
+    ("direction", F32s(3), "The direction the light points"),
+    ("inner_angle", F32, "The angle of the full intensity cone"),
+    ("outer_angle", F32, "The angle the light fades out at"),
+];
+
+const DIRECTIONAL_LIGHT: &[FieldDef] = &[
+    ("direction", F32s(3), "The direction the light points"),
+];
+
+const SKY: &[FieldDef] = &[
+    ("kind", U8, "0 skybox and 1 procedural"),
+    ("sky", Variant { tag: "kind",
+                      cases: &[(0, "Skybox"), (1, "ProceduralSky")] },
+     "The values of the kind of sky"),
+];
+
+const SKYBOX: &[FieldDef] = &[
+    ("texture", String, "The name of the cubemap texture"),
+];
+
+const PROCEDURAL_SKY: &[FieldDef] = &[
+    ("zenith_color", F32s(3), "The color straight up"),
+    ("horizon_color", F32s(3), "The color at the horizon"),
+    ("sun_direction", F32s(3), "The direction towards the sun"),
+    ("sun_color", F32s(3), "The color of the sun"),
+    ("sun_size", F32, "The size of the sun"),
+];
+
+const SOUND_EMITTER: &[FieldDef] = &[
+    ("position", F32s(3), "The position"),
+    ("sound", String, "The name of the sound"),
+    ("radius", F32, "How far the sound is heard"),
+    ("volume", F32, "The volume"),
+    ("flags", U32, "The flags of the emitter"),
+];
+
+const TRIGGER: &[FieldDef] = &[
+    ("shape_kind", U8, "0 box and 1 convex"),
+    ("shape", Variant { tag: "shape_kind",
+                        cases: &[(0, "AabbShape"), (1, "ConvexShape")] },
+     "The volume of the trigger"),
+    ("kind", U32, "What the trigger does"),
+    ("target", String, "The name of what the trigger acts on"),
+    ("tag", U32, "The tag used by the game"),
+];
+
+const AABB_SHAPE: &[FieldDef] = &[
+    ("min", F32s(3), "The smallest corner"),
+    ("max", F32s(3), "The largest corner"),
+];
+
+const CONVEX_SHAPE: &[FieldDef] = &[
+    ("plane_count", U64, "The number of planes"),
+    ("planes", Repeat { count: "plane_count", item: &F32s(4) },
+     "The planes bounding the volume"),
+];
+
+const ENTITY: &[FieldDef] = &[
+    ("class", String, "The class of the entity"),
+    ("position", F32s(3), "The position"),
+    ("properties", List("Property"), "The properties sorted by name"),
+];
+
+const PROPERTY: &[FieldDef] = &[
+    ("name", String, "The name of the property"),
+    ("type", U8, "0 bool, 1 int, 2 float, 3 string, 4 vec3 and 5 color"),
+    ("value", Variant { tag: "type",
+                        cases: &[(0, "BoolProperty"), (1, "IntProperty"),
+                                 (2, "FloatProperty"), (3, "StringProperty"),
+                                 (4, "Vec3Property"), (5, "ColorProperty")] },
+     "The value"),
+];
+
+const BOOL_PROPERTY: &[FieldDef] = &[("value", U8, "0 false and 1 true")];
+const INT_PROPERTY: &[FieldDef] = &[("value", I64, "The integer")];
+const FLOAT_PROPERTY: &[FieldDef] = &[("value", F64, "The float")];
+const STRING_PROPERTY: &[FieldDef] = &[("value", String, "The string")];
+const VEC3_PROPERTY: &[FieldDef] = &[("value", F32s(3), "The vector")];
+const COLOR_PROPERTY: &[FieldDef] = &[("value", F32s(4), "The RGBA color")];
+
+const EMBEDDED_ASSET: &[FieldDef] = &[
+    ("name", String, "The name of the asset"),
+    ("data", Rest, "The content of the asset"),
+];
+
+const ASSET_DEPENDENCY: &[FieldDef] = &[
+    ("kind", U8, "0 texture, 1 sound and 2 other"),
+    ("name", String, "The name of the asset"),
+];
+
+const MATERIAL: &[FieldDef] = &[
+    ("name", String, "The name of the material"),
+    ("base_color", F32s(4), "The base color"),
+    ("metallic", F32, "How metallic the surface is"),
+    ("roughness", F32, "How rough the surface is"),
+    ("emissive", F32s(3), "The emitted color"),
+    ("base_color_texture", Optional(&String), "The base color texture"),
+    ("metallic_roughness_texture", Optional(&String),
+     "The metallic and roughness texture"),
+    ("normal_texture", Optional(&String), "The normal map"),
+    ("blend_mode", U8, "0 opaque, 1 masked, 2 blend and 3 additive"),
+    ("cutoff", F32, "The alpha cutoff of masked materials"),
+];
+
+const SECTOR_ANIMATION: &[FieldDef] = &[
+    ("name", String, "The name of the animation"),
+    ("tag", U32, "The tag of the animated sectors"),
+    ("plane", U8, "0 floor and 1 ceiling"),
+    ("looping", U8, "1 if the animation loops"),
+    ("keyframe_count", U64, "The number of keyframes"),
+    ("keyframes", Repeat { count: "keyframe_count",
+                           item: &Struct("Keyframe") },
+     "The keyframes"),
+];
+
+const KEYFRAME: &[FieldDef] = &[
+    ("state", String, "The name of the state"),
+    ("time", F32, "The time in seconds"),
+    ("offset", F32, "The offset of the plane"),
+];
+
+const SECTOR_GROUP: &[FieldDef] = &[
+    ("name", String, "The name of the group"),
+    ("visible", U8, "1 if the group is visible"),
+    ("sector_count", U64, "The number of sectors"),
+    ("sectors", Repeat { count: "sector_count", item: &U64 },
+     "The IDs of the sectors"),
+];
+
+const PVS: &[FieldDef] = &[
+    ("sector_count", U64, "The number of sectors the sets were built for"),
+    ("rows", List("PvsRow"), "The set of every sector"),
+];
+
+const PVS_ROW: &[FieldDef] = &[
+    ("bits", Rest, "The run length compressed bitset"),
+];
+
+const WAYPOINT_GRAPH: &[FieldDef] = &[
+    ("nodes", List("Waypoint"), "The nodes, the links reference them by \
+                                 index"),
+];
+
+const WAYPOINT: &[FieldDef] = &[
+    ("position", F32s(3), "The position"),
+    ("flags", U32, "The flags of the node"),
+    ("link_count", U64, "The number of links leaving the node"),
+    ("links", Repeat { count: "link_count", item: &Struct("WaypointLink") },
+     "The links leaving the node"),
+];
+
+const WAYPOINT_LINK: &[FieldDef] = &[
+    ("target", U32, "The index of the node the link leads to"),
+    ("cost", F32, "The cost of taking the link"),
+    ("flags", U32, "The flags of the link"),
+];
+
+const LIBRARY_MESH: &[FieldDef] = &[
+    ("kind", U8, "0 for a mesh and 1 for a copy of an earlier mesh"),
+    ("entry", Variant { tag: "kind",
+                        cases: &[(0, "Mesh"), (1, "LibraryAlias")] },
+     "The mesh"),
+];
+
+const LIBRARY_ALIAS: &[FieldDef] = &[
+    ("mesh", U32, "The index of the earlier identical mesh"),
+];
+
+const PROP: &[FieldDef] = &[
+    ("mesh", U32, "The index of the mesh inside the mesh library"),
+    ("transform", F32s(16), "The column-major transform of the mesh"),
+];
+
+const ACOUSTICS: &[FieldDef] = &[
+    ("reverb", U8, "0 generic, 1 room, 2 hall, 3 corridor, 4 cave, 5 outdoor \
+                    and 6 underwater"),
+    ("occlusion", F32, "How much the sounds from outside are muffled"),
+];
+
+const ENVIRONMENT: &[FieldDef] = &[
+    ("fog_color", F32s(3), "The color distant geometry fades to"),
+    ("fog_density", F32, "How quickly the geometry fades"),
+    ("ambient_color", F32s(3), "The light reaching every surface"),
+];
+
+const LIQUID: &[FieldDef] = &[
+    ("kind", U8, "0 water, 1 lava and 2 slime"),
+    ("surface_height", F32, "The height of the surface"),
+    ("flow", F32s(3), "The direction and speed of the flow"),
+    ("color", F32s(4), "The color of the surface"),
+    ("fog_density", F32, "How quickly the view fades below the surface"),
+];
+
+const DECAL: &[FieldDef] = &[
+    ("position", F32s(3), "The center of the decal"),
+    ("normal", F32s(3), "The direction pointing out of the surface"),
+    ("up", F32s(3), "The direction of the top edge of the material"),
+    ("size", F32s(3), "The width, height and projection depth"),
+    ("material", U32, "The index of the material"),
+    ("sector", U64, "The ID of the sector the decal is projected onto"),
+];
+
+const OCCLUDER: &[FieldDef] = &[
+    ("vertex_count", U64, "The number of corners"),
+    ("vertices", Repeat { count: "vertex_count", item: &F32s(3) },
+     "The positions of the corners"),
+    ("index_count", U64, "The number of indices"),
+    ("indices", Repeat { count: "index_count", item: &U32 },
+     "The corners of the triangles, 3 for every triangle"),
+];
+
+const INTERACTIVE: &[FieldDef] = &[
+    ("kind", U8, "0 switch, 1 button and 2 use target"),
+    ("position", F32s(3), "The position of the object"),
+    ("radius", F32, "The distance the object can be used from"),
+    ("action", String, "What happens to the targets"),
+    ("target_count", U64, "The number of targets"),
+    ("targets", Repeat { count: "target_count",
+                         item: &Struct("InteractionTarget") },
+     "The sectors and entities the object activates"),
+];
+
+const INTERACTION_TARGET: &[FieldDef] = &[
+    ("kind", U8, "0 sector tag and 1 entity index"),
+    ("value", U32, "The tag or the index of the entity"),
+];
+
+const SCRIPTS: &[FieldDef] = &[
+    ("has_source", U8, "1 if the map has an embedded script source"),
+    ("source_size", U64, "The size of the source in bytes"),
+    ("source", Repeat { count: "source_size", item: &U8 },
+     "The UTF-8 script source, empty without a source"),
+    ("hooks", List("ScriptHook"), "The bound script functions"),
+];
+
+const SCRIPT_HOOK: &[FieldDef] = &[
+    ("target_kind", U8, "0 sector, 1 trigger and 2 entity"),
+    ("target", U64, "The ID of the sector or the index of the trigger or \
+                     entity"),
+    ("event", String, "The name of the event"),
+    ("function", String, "The name of the function to call"),
+];
+
+const THUMBNAIL: &[FieldDef] = &[
+    ("format", U8, "0 raw RGBA and 1 PNG"),
+    ("width", U32, "The width in pixels"),
+    ("height", U32, "The height in pixels"),
+    ("data", Rest, "The image"),
+];
+
+/// Every structure of the format with its name
+const STRUCTS: &[(&str, &[FieldDef])] = &[
+    ("Mime", MIME),
+    ("Map", MAP),
+    ("SectorEntry", SECTOR_ENTRY),
+    ("SectorBlock", SECTOR_BLOCK),
+    ("ChunkList", CHUNK_LIST),
+    ("Chunk", CHUNK),
+    ("Sector", SECTOR),
+    ("Lod", LOD),
+    ("Mesh", MESH),
+    ("VertexDescriptor", VERTEX_DESCRIPTOR),
+    ("VertexAttribute", VERTEX_ATTRIBUTE),
+    ("UvSet", UV_SET),
+    ("CustomAttribute", CUSTOM_ATTRIBUTE),
+    ("Submesh", SUBMESH),
+    ("Wall", WALL),
+    ("Lightmap", LIGHTMAP),
+    ("Light", LIGHT),
+    ("PointLight", &[]),
+    ("SpotLight", SPOT_LIGHT),
+    ("DirectionalLight", DIRECTIONAL_LIGHT),
+    ("Sky", SKY),
+    ("Skybox", SKYBOX),
+    ("ProceduralSky", PROCEDURAL_SKY),
+    ("SoundEmitter", SOUND_EMITTER),
+    ("Trigger", TRIGGER),
+    ("AabbShape", AABB_SHAPE),
+    ("ConvexShape", CONVEX_SHAPE),
+    ("Entity", ENTITY),
+    ("Property", PROPERTY),
+    ("BoolProperty", BOOL_PROPERTY),
+    ("IntProperty", INT_PROPERTY),
+    ("FloatProperty", FLOAT_PROPERTY),
+    ("StringProperty", STRING_PROPERTY),
+    ("Vec3Property", VEC3_PROPERTY),
+    ("ColorProperty", COLOR_PROPERTY),
+    ("EmbeddedAsset", EMBEDDED_ASSET),
+    ("AssetDependency", ASSET_DEPENDENCY),
+    ("Material", MATERIAL),
+    ("SectorAnimation", SECTOR_ANIMATION),
+    ("Keyframe", KEYFRAME),
+    ("SectorGroup", SECTOR_GROUP),
+    ("Pvs", PVS),
+    ("PvsRow", PVS_ROW),
+    ("WaypointGraph", WAYPOINT_GRAPH),
+    ("Waypoint", WAYPOINT),
+    ("WaypointLink", WAYPOINT_LINK),
+    ("LibraryMesh", LIBRARY_MESH),
+    ("LibraryAlias", LIBRARY_ALIAS),
+    ("Prop", PROP),
+    ("Occluder", OCCLUDER),
+    ("Liquid", LIQUID),
+    ("Environment", ENVIRONMENT),
+    ("Acoustics", ACOUSTICS),
+    ("Decal", DECAL),
+    ("Interactive", INTERACTIVE),
+    ("InteractionTarget", INTERACTION_TARGET),
+    ("Scripts", SCRIPTS),
+    ("ScriptHook", SCRIPT_HOOK),
+    ("Thumbnail", THUMBNAIL),
+];
+
+/// Every chunk in the order they are written (tag, content, required,
+/// description)
+const CHUNKS: &[(&[u8; 4], FieldKind, bool, &str)] = &[
+    (THUMBNAIL_CHUNK, Struct("Thumbnail"), false, "The preview image"),
+    (COLOR_SPACE_CHUNK, U8, true,
+     "The color space of the vertex colors, 0 sRGB and 1 linear"),
+    (AXES_CHUNK, U8, true,
+     "The axis convention, bit 0 set for Z up and bit 1 set for left \
+      handed"),
+    (UNITS_CHUNK, F32, true, "The units per meter"),
+    (LIGHTMAP_CHUNK, Struct("Lightmap"), false, "The baked lightmap"),
+    (LIGHTS_CHUNK, List("Light"), false, "The lights"),
+    (SKY_CHUNK, Struct("Sky"), false, "The sky"),
+    (SOUND_EMITTERS_CHUNK, List("SoundEmitter"), false,
+     "The sound emitters"),
+    (TRIGGERS_CHUNK, List("Trigger"), false, "The triggers"),
+    (ENTITIES_CHUNK, List("Entity"), false, "The entities"),
+    (EMBEDDED_ASSETS_CHUNK, List("EmbeddedAsset"), false,
+     "The assets stored inside the map"),
+    (DEPENDENCIES_CHUNK, List("AssetDependency"), false,
+     "The external assets the map uses"),
+    (MATERIALS_CHUNK, List("Material"), false, "The material table"),
+    (ANIMATIONS_CHUNK, List("SectorAnimation"), false,
+     "The sector animations"),
+    (GROUPS_CHUNK, List("SectorGroup"), false, "The sector groups"),
+    (PVS_CHUNK, Struct("Pvs"), false, "The potentially visible sets"),
+    (WAYPOINTS_CHUNK, Struct("WaypointGraph"), false,
+     "The waypoints used by the AI"),
+    (MESH_LIBRARY_CHUNK, List("LibraryMesh"), false,
+     "The meshes placed by the props"),
+    (PROPS_CHUNK, List("Prop"), false, "The static props"),
+    (DECALS_CHUNK, List("Decal"), false, "The decals"),
+    (ENVIRONMENT_CHUNK, Struct("Environment"), false,
+     "The default fog and ambient light"),
+    (INTERACTIVES_CHUNK, List("Interactive"), false,
+     "The switches, buttons and use targets"),
+    (SCRIPTS_CHUNK, Struct("Scripts"), false,
+     "The script hooks and the embedded script source"),
+    (SIGNATURE_CHUNK, Bytes(SIGNATURE_SIZE), false,
+     "The ed25519 signature of everything before the chunk"),
+];
+
+/// Compute the offsets of the fields of a structure
+fn struct_schema(name: &'static str, defs: &[FieldDef]) -> StructSchema {
+    let mut offset = Some(0);
+    let fields = defs.iter()
+        .map(|&(name, kind, description)| {
+            let field = Field { name, kind, offset, description };
+            offset = offset.zip(kind.size()).map(|(a, b)| a + b);
+
+            field
+        })
+        .collect();
+
+    StructSchema {
+        name,
+        fields,
+        size: offset,
+    }
+}
+
+/// Get the description of the current format version
+///
+/// # Returns
+///
+/// * [Schema] - Every structure and chunk of [CURRENT_VERSION], starting
+///   at the `Mime` structure
+pub fn schema() -> Schema {
+    Schema {
+        version: CURRENT_VERSION,
+        magic: HEADER_MAGIC.try_into().unwrap(),
+        root: "Mime",
+        structs: STRUCTS.iter()
+            .map(|&(name, defs)| struct_schema(name, defs))
+            .collect(),
+        chunks: CHUNKS.iter()
+            .map(|&(tag, content, required, description)| {
+                ChunkSchema {
+                    tag: *tag,
+                    content,
+                    required,
+                    description,
+                }
+            })
+            .collect(),
+    }
+}
@@ -0,0 +1,245 @@
+//! Script functions bound to the sectors, triggers and entities of a map
+//!
+//! The editor binds an event of an object, like a trigger being entered, to
+//! the name of a script function in [Map::scripts]. The functions can live
+//! in the scripts of the game or in the source embedded with the map, the
+//! language and the names of the events are decided by the engine.
+
+use crate::*;
+use crate::buffer::{ Reader, write_usize, write_string, write_list,
+                     string_size, list_size };
+
+/// The size of a serialized target (kind and ID or index)
+const TARGET_SIZE: usize = 1 + std::mem::size_of::<u64>();
+
+/// The object a script function is bound to
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ScriptTarget {
+    /// The sector with the ID
+    Sector(SectorId),
+
+    /// The trigger with the index inside [Map::triggers]
+    Trigger(u32),
+
+    /// The entity with the index inside [Map::entities]
+    Entity(u32),
+}
+
+impl ScriptTarget {
+    /// Serialize the target to a buffer
+    fn serialize<O>(&self, buffer: &mut O)
+        where O: Output + ?Sized
+    {
+        let (kind, value): (u8, u64) = match *self {
+            ScriptTarget::Sector(id) => (0, id.0),
+            ScriptTarget::Trigger(index) => (1, index as u64),
+            ScriptTarget::Entity(index) => (2, index as u64),
+        };
+
+        buffer.push(kind);
+        buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Read a target written by [ScriptTarget::serialize]
+    fn read(reader: &mut Reader) -> Result<Self> {
+        let kind = reader.u8()?;
+        let value = reader.u64()?;
+        let index = || {
+            u32::try_from(value).map_err(Error::IntegerConvertionError)
+        };
+
+        match kind {
+            0 => Ok(ScriptTarget::Sector(SectorId(value))),
+            1 => Ok(ScriptTarget::Trigger(index()?)),
+            2 => Ok(ScriptTarget::Entity(index()?)),
+            kind => Err(Error::UnknownScriptTarget(kind)),
+        }
+    }
+}
+
+/// A script function called when an event happens to an object
+#[derive(Clone, PartialEq, Debug)]
+pub struct ScriptHook {
+    /// The object the function is bound to
+    pub target: ScriptTarget,
+
+    /// The name of the event, like `enter` or `use`
+    pub event: String,
+
+    /// The name of the function to call
+    pub function: String,
+}
+
+impl ScriptHook {
+    /// Creates a new hook
+    ///
+    /// # Arguments
+    ///
+    /// * `target`   - The object the function is bound to
+    /// * `event`    - The name of the event
+    /// * `function` - The name of the function to call
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new hook
+    pub fn new(target: ScriptTarget, event: &str, function: &str) -> Self {
+        Self {
+            target,
+            event: event.to_string(),
+            function: function.to_string(),
+        }
+    }
+
+    /// Get the number of bytes [ScriptHook::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        TARGET_SIZE + string_size(&self.event) + string_size(&self.function)
+    }
+
+    /// Serialize the hook to a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - Successfully serialized the hook
+    /// * `Err(`[Error]`)` - Failed to serialize the hook
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        self.target.serialize(buffer);
+        write_string(buffer, &self.event)?;
+        write_string(buffer, &self.function)?;
+
+        Ok(())
+    }
+
+    /// Deserialize the hook from a buffer, the trigger and entity targets
+    /// are checked when the map is loaded
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the hook
+    /// * `Err(`[Error]`)` - Failed to deserialize the hook
+    pub fn deserialize(buffer: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallChunk);
+
+        Ok(Self {
+            target: ScriptTarget::read(&mut reader)?,
+            event: reader.string()?,
+            function: reader.string()?,
+        })
+    }
+}
+
+/// The script functions bound by a map
+#[derive(Clone, PartialEq, Default, Debug)]
+pub struct Scripts {
+    /// The script source stored with the map, the size isn't limited like
+    /// the other strings
+    pub source: Option<String>,
+
+    /// The functions bound to the objects of the map
+    pub hooks: Vec<ScriptHook>,
+}
+
+impl Scripts {
+    /// Check if the map doesn't have any scripts
+    pub fn is_empty(&self) -> bool {
+        self.source.is_none() && self.hooks.is_empty()
+    }
+
+    /// Get the number of bytes [Scripts::serialize] writes
+    pub(crate) fn serialized_size(&self) -> usize {
+        // The flag and the size of the source
+        1 + 8 + self.source.as_ref().map_or(0, |source| source.len()) +
+            list_size(&self.hooks, ScriptHook::serialized_size)
+    }
+
+    /// Serialize the scripts to a buffer
+    pub(crate) fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        let source = self.source.as_deref().unwrap_or_default();
+        buffer.push(self.source.is_some() as u8);
+        write_usize(buffer, source.len())?;
+        buffer.extend_from_slice(source.as_bytes());
+
+        write_list(buffer, &self.hooks, ScriptHook::serialize)
+    }
+
+    /// Read scripts written by [Scripts::serialize]
+    pub(crate) fn read(reader: &mut Reader) -> Result<Self> {
+        let used = reader.u8()? != 0;
+        let bytes = reader.sized()?;
+        let start = reader.offset() - bytes.len();
+        let source = std::str::from_utf8(bytes)
+            .map_err(|error| {
+                Error::Utf8ConvertionError(error).at_offset(start)
+            })?;
+
+        Ok(Self {
+            source: used.then(|| source.to_string()),
+            hooks: reader.list(ScriptHook::deserialize)?,
+        })
+    }
+}
+
+impl Map {
+    /// Get the script functions bound to an object
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The object
+    ///
+    /// # Returns
+    ///
+    /// * An iterator over the hooks of the object in the order they were
+    ///   added
+    pub fn script_hooks(&self, target: ScriptTarget)
+        -> impl Iterator<Item = &ScriptHook> + '_
+    {
+        self.scripts.hooks.iter().filter(move |hook| hook.target == target)
+    }
+
+    /// Get the function bound to an event of an object
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The object
+    /// * `event`  - The name of the event
+    ///
+    /// # Returns
+    ///
+    /// * `Some(&str)` - The name of the first function bound to the event
+    /// * `None` - No function is bound to the event
+    pub fn script_function(&self, target: ScriptTarget, event: &str)
+        -> Option<&str>
+    {
+        self.script_hooks(target)
+            .find(|hook| hook.event == event)
+            .map(|hook| hook.function.as_str())
+    }
+
+    /// Check that every trigger and entity with a script hook exists
+    pub(crate) fn check_scripts(&self) -> Result<()> {
+        for hook in &self.scripts.hooks {
+            let (index, count) = match hook.target {
+                ScriptTarget::Trigger(index) => (index, self.triggers.len()),
+                ScriptTarget::Entity(index) => (index, self.entities.len()),
+                ScriptTarget::Sector(_) => continue,
+            };
+
+            if index as usize >= count {
+                return Err(Error::InvalidScriptTarget(hook.target));
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+//! Typed gameplay flags of the sectors
+//!
+//! The low 16 bits of [SectorFlags] are reserved for the flags defined by
+//! the format, new flags are added there. The high 16 bits are left to the
+//! game, see [SectorFlags::game]. Unknown bits are kept when a map is
+//! loaded and saved again.
+
+use crate::*;
+
+use bitflags::bitflags;
+
+bitflags! {
+    /// The gameplay flags of a sector
+    #[derive(Copy, Clone, PartialEq, Eq, Hash, Default, Debug)]
+    pub struct SectorFlags: u32 {
+        /// Entering the sector counts as finding a secret
+        const SECRET = 1 << 0;
+
+        /// Standing on the floor hurts, like lava or acid
+        const DAMAGE_FLOOR = 1 << 1;
+
+        /// Landing on the floor doesn't cause fall damage
+        const NO_FALL_DAMAGE = 1 << 2;
+
+        /// The whole sector is below the surface of a liquid
+        const UNDERWATER = 1 << 3;
+
+        /// The sky is visible from the sector
+        const OUTDOOR = 1 << 4;
+
+        /// Monsters can't enter the sector
+        const NO_MONSTERS = 1 << 5;
+
+        /// The bits reserved for the flags of the format
+        const RESERVED = 0x0000_ffff;
+
+        /// The bits left to the game
+        const GAME = 0xffff_0000;
+    }
+}
+
+impl SectorFlags {
+    /// Get a flag from the bits left to the game
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index of the flag between 0 and 15
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[SectorFlags]`)` - The flag
+    /// * `None` - The index is past the bits left to the game
+    pub fn game(index: u32) -> Option<Self> {
+        (index < 16).then(|| Self::from_bits_retain(1 << (16 + index)))
+    }
+}
+
+impl Sector {
+    /// Check if the sector has all the flags
+    pub fn has_flags(&self, flags: SectorFlags) -> bool {
+        self.flags.contains(flags)
+    }
+
+    /// Set or clear flags of the sector
+    ///
+    /// # Arguments
+    ///
+    /// * `flags` - The flags to change
+    /// * `value` - `true` to set the flags, `false` to clear them
+    pub fn set_flags(&mut self, flags: SectorFlags, value: bool) {
+        self.flags.set(flags, value);
+    }
+}
+
+impl Map {
+    /// Get the sectors with all the flags
+    ///
+    /// # Arguments
+    ///
+    /// * `flags` - The flags the sectors need
+    ///
+    /// # Returns
+    ///
+    /// * An iterator over the index and the sector of every sector with the
+    ///   flags
+    pub fn sectors_with_flags(&self, flags: SectorFlags)
+        -> impl Iterator<Item = (usize, &Sector)> + '_
+    {
+        self.sectors.iter()
+            .enumerate()
+            .filter(move |(_, sector)| sector.has_flags(flags))
+    }
+}
@@ -0,0 +1,103 @@
+//! Ed25519 signatures of the map content
+//!
+//! The signature is stored in its own chunk and covers the canonical
+//! serialization of the map without the signature, see [Map::signed_payload].
+//! Because the payload is serialized again when verifying, a signed map can
+//! be saved with [Map::save_incremental] or merged into a mime file without
+//! breaking the signature, but any change to the content does.
+//!
+//! Signing and verifying needs the `signing` feature, reading and writing
+//! the signature chunk works without it.
+
+use crate::*;
+
+/// The size of an ed25519 signature
+pub const SIGNATURE_SIZE: usize = 64;
+
+impl Map {
+    /// Get the ed25519 signature of the map
+    pub fn signature(&self) -> Option<&[u8; SIGNATURE_SIZE]> {
+        self.signature.as_ref()
+    }
+
+    /// Set the ed25519 signature of the map, used to attach signatures made
+    /// outside of the crate, for example by a hardware key
+    ///
+    /// # Arguments
+    ///
+    /// * `signature` - The signature of [Map::signed_payload] or `None` to
+    ///   remove it
+    pub fn set_signature(&mut self, signature: Option<[u8; SIGNATURE_SIZE]>) {
+        self.signature = signature;
+    }
+
+    /// Get the bytes the signature covers, the canonical serialization of
+    /// the map without the signature and with uncompressed sectors
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<u8>)` - The signed bytes
+    /// * `Err(`[Error]`)` - Failed to serialize the map
+    pub fn signed_payload(&self) -> Result<Vec<u8>> {
+        let mut map = self.clone();
+        map.signature = None;
+        map.compression = SectorCompression::None;
+        map.canonicalize();
+
+        let mut buffer = Vec::with_capacity(map.max_serialized_size());
+        map.serialize(&mut buffer)?;
+
+        Ok(buffer)
+    }
+
+    /// Sign the content of the map, the signature is stored with the map
+    /// and has to be made again after changing the map
+    ///
+    /// # Arguments
+    ///
+    /// * `secret_key` - The ed25519 secret key of the author
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - Successfully signed the map
+    /// * `Err(`[Error]`)` - Failed to serialize the map
+    #[cfg(feature = "signing")]
+    pub fn sign(&mut self, secret_key: &[u8; 32]) -> Result<()> {
+        use ed25519_dalek::{ Signer, SigningKey };
+
+        let key = SigningKey::from_bytes(secret_key);
+        let signature = key.sign(&self.signed_payload()?);
+        self.signature = Some(signature.to_bytes());
+
+        Ok(())
+    }
+
+    /// Check if the map was signed with the secret key of a public key and
+    /// hasn't changed since
+    ///
+    /// # Arguments
+    ///
+    /// * `public_key` - The ed25519 public key of a trusted author
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - The signature is valid for the content of the map
+    /// * `Ok(false)` - The map isn't signed, was signed with another key or
+    ///   was changed after signing
+    /// * `Err(`[Error]`)` - The public key is invalid or serializing the map
+    ///   failed
+    #[cfg(feature = "signing")]
+    pub fn verify(&self, public_key: &[u8; 32]) -> Result<bool> {
+        use ed25519_dalek::{ Signature, VerifyingKey };
+
+        let key = VerifyingKey::from_bytes(public_key)
+            .map_err(|_| Error::InvalidPublicKey)?;
+
+        let Some(signature) = &self.signature else {
+            return Ok(false);
+        };
+        let signature = Signature::from_bytes(signature);
+
+        Ok(key.verify_strict(&self.signed_payload()?, &signature).is_ok())
+    }
+}
@@ -0,0 +1,114 @@
+//! The sky of the map
+
+use crate::*;
+use crate::buffer::{ Reader, write_string, string_size };
+
+/// Describes the sky the engine should render for a map
+#[derive(Clone, PartialEq, Debug)]
+pub enum Sky {
+    /// A skybox using a cube map texture
+    Skybox {
+        /// The name of the cube map texture
+        texture: String,
+    },
+
+    /// A sky the engine generates from a few parameters
+    Procedural {
+        /// The color straight up (r, g, b)
+        zenith_color: [f32; 3],
+
+        /// The color at the horizon (r, g, b)
+        horizon_color: [f32; 3],
+
+        /// The direction pointing towards the sun
+        sun_direction: [f32; 3],
+
+        /// The color of the sun (r, g, b)
+        sun_color: [f32; 3],
+
+        /// The angular size of the sun in radians
+        sun_size: f32,
+    },
+}
+
+impl Sky {
+    /// Get the number of bytes [Sky::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        match self {
+            Sky::Skybox { texture } => 1 + string_size(texture),
+            Sky::Procedural { .. } => 1 + 13 * std::mem::size_of::<f32>(),
+        }
+    }
+
+    /// Serialize the sky to a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - Successfully serialized the sky
+    /// * `Err(`[Error]`)` - Failed to serialize the sky
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        match self {
+            Sky::Skybox { texture } => {
+                buffer.push(0);
+                write_string(buffer, texture)?;
+            }
+
+            Sky::Procedural {
+                zenith_color,
+                horizon_color,
+                sun_direction,
+                sun_color,
+                sun_size,
+            } => {
+                buffer.push(1);
+
+                let values = zenith_color.iter()
+                    .chain(horizon_color)
+                    .chain(sun_direction)
+                    .chain(sun_color)
+                    .chain([sun_size]);
+                for value in values {
+                    buffer.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deserialize the sky from a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the sky
+    /// * `Err(`[Error]`)` - Failed to deserialize the sky
+    pub fn deserialize(buffer: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallChunk);
+
+        match reader.u8()? {
+            0 => Ok(Sky::Skybox {
+                texture: reader.string()?,
+            }),
+
+            1 => Ok(Sky::Procedural {
+                zenith_color: [reader.f32()?, reader.f32()?, reader.f32()?],
+                horizon_color: [reader.f32()?, reader.f32()?, reader.f32()?],
+                sun_direction: [reader.f32()?, reader.f32()?, reader.f32()?],
+                sun_color: [reader.f32()?, reader.f32()?, reader.f32()?],
+                sun_size: reader.f32()?,
+            }),
+
+            kind => Err(Error::UnknownSkyKind(kind)),
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! Exact floor and ceiling planes of sectors for height queries
+
+use crate::*;
+use crate::math::{ dot, normalize, length, triangle_normal };
+
+/// How far a vertex can be from the plane of a mesh and still count as
+/// lying on it
+const PLANE_EPSILON: f32 = 1e-4;
+
+/// Get the height of a plane (a, b, c, d), where `a * x + b * y + c * z = d`
+/// is on the plane, above a point
+///
+/// # Arguments
+///
+/// * `plane` - The plane
+/// * `x`     - The x coordinate of the point
+/// * `z`     - The z coordinate of the point
+///
+/// # Returns
+///
+/// * `Some(f32)` - The y coordinate of the plane at the point
+/// * `None` - The plane is vertical
+pub fn plane_height(plane: [f32; 4], x: f32, z: f32) -> Option<f32> {
+    let [a, b, c, d] = plane;
+    if b.abs() <= PLANE_EPSILON {
+        return None;
+    }
+
+    Some((d - a * x - c * z) / b)
+}
+
+impl Mesh {
+    /// Fit a plane through the vertices of the mesh
+    ///
+    /// # Arguments
+    ///
+    /// * `up` - Orient the normal of the plane upwards, like for a floor,
+    ///   instead of downwards, like for a ceiling
+    ///
+    /// # Returns
+    ///
+    /// * `Some([f32; 4])` - The plane (a, b, c, d) with a unit normal
+    /// * `None` - The mesh doesn't have a triangle with an area, its
+    ///   vertices don't lie on a single plane or the plane is vertical
+    pub fn fit_plane(&self, up: bool) -> Option<[f32; 4]> {
+        // Use the largest triangle for the most precise normal
+        let normal = self.triangle_positions()
+            .map(|[a, b, c]| triangle_normal(a, b, c))
+            .max_by(|a, b| length(*a).total_cmp(&length(*b)))
+            .filter(|normal| length(*normal) > 0.0)?;
+
+        let mut normal = normalize(normal);
+        if (normal[1] < 0.0) == up {
+            normal = [-normal[0], -normal[1], -normal[2]];
+        }
+
+        let d = dot(normal, self.vertex_buffer.first()?.position());
+        let coplanar = self.vertex_buffer.iter()
+            .all(|v| (dot(normal, v.position()) - d).abs() <= PLANE_EPSILON);
+
+        let plane = [normal[0], normal[1], normal[2], d];
+        if !coplanar || plane_height(plane, 0.0, 0.0).is_none() {
+            return None;
+        }
+
+        Some(plane)
+    }
+}
+
+impl Sector {
+    /// Get the height of the floor above a point, using the floor plane
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The x coordinate of the point
+    /// * `z` - The z coordinate of the point
+    ///
+    /// # Returns
+    ///
+    /// * `Some(f32)` - The height of the floor
+    /// * `None` - The sector doesn't have a floor plane
+    pub fn floor_height(&self, x: f32, z: f32) -> Option<f32> {
+        plane_height(self.floor_plane?, x, z)
+    }
+
+    /// Get the height of the ceiling above a point, using the ceiling plane
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The x coordinate of the point
+    /// * `z` - The z coordinate of the point
+    ///
+    /// # Returns
+    ///
+    /// * `Some(f32)` - The height of the ceiling
+    /// * `None` - The sector doesn't have a ceiling plane
+    pub fn ceiling_height(&self, x: f32, z: f32) -> Option<f32> {
+        plane_height(self.ceiling_plane?, x, z)
+    }
+
+    /// Set the floor and ceiling planes from the floor and ceiling meshes,
+    /// a plane is cleared when its mesh isn't flat
+    pub fn compute_planes(&mut self) {
+        self.floor_plane = self.floor_mesh.fit_plane(true);
+        self.ceiling_plane = self.ceiling_mesh.fit_plane(false);
+    }
+}
+
+impl Map {
+    /// Set the floor and ceiling planes of every sector from their meshes,
+    /// see [Sector::compute_planes]
+    ///
+    /// The changed sectors are marked for the next [Map::save_incremental].
+    pub fn compute_planes(&mut self) {
+        for index in 0..self.sectors.len() {
+            let sector = &mut self.sectors[index];
+            let before = (sector.floor_plane, sector.ceiling_plane);
+
+            sector.compute_planes();
+            if (sector.floor_plane, sector.ceiling_plane) != before {
+                self.mark_sector_dirty(index);
+            }
+        }
+    }
+}
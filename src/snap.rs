@@ -0,0 +1,203 @@
+//! Snapping the geometry to a grid
+//!
+//! Editors place the vertices on a grid so walls line up and the shared
+//! corners of the meshes are exactly equal. Snapping moves every position to
+//! the closest grid point and welds the vertices that end up identical.
+
+use crate::*;
+
+use std::collections::HashMap;
+
+/// Snap a position to the closest point of a grid
+///
+/// # Arguments
+///
+/// * `position`  - The position to snap
+/// * `grid_size` - The distance between the grid points, a size that isn't
+///   positive and finite leaves the position as it is
+///
+/// # Returns
+///
+/// * The snapped position
+pub fn snap_to_grid(position: [f32; 3], grid_size: f32) -> [f32; 3] {
+    if !(grid_size.is_finite() && grid_size > 0.0) {
+        return position;
+    }
+
+    // NOTE(patrik): Adding 0.0 turns -0.0 into 0.0 so the vertices welded
+    // afterwards compare equal
+    position.map(|value| (value / grid_size).round() * grid_size + 0.0)
+}
+
+impl Vertex {
+    /// Snap the position of the vertex to a grid, see [snap_to_grid]
+    pub fn snap_to_grid(&mut self, grid_size: f32) {
+        self.pos = snap_to_grid(self.pos, grid_size);
+    }
+}
+
+impl Mesh {
+    /// Snap every vertex of the mesh to a grid and weld the vertices that
+    /// end up identical, see [Mesh::weld_vertices]
+    ///
+    /// # Arguments
+    ///
+    /// * `grid_size` - The distance between the grid points
+    ///
+    /// # Returns
+    ///
+    /// * The number of vertices removed by the welding
+    pub fn snap_to_grid(&mut self, grid_size: f32) -> usize {
+        for vertex in &mut self.vertex_buffer {
+            vertex.snap_to_grid(grid_size);
+        }
+
+        self.weld_vertices()
+    }
+
+    /// Merge the vertices where the vertex and every attribute of the
+    /// vertex are identical, the triangles collapsed by the snapping are
+    /// kept, remove them with [Mesh::remove_degenerate_triangles]
+    ///
+    /// # Returns
+    ///
+    /// * The number of vertices removed
+    pub fn weld_vertices(&mut self) -> usize {
+        let count = self.vertex_buffer.len();
+
+        let mut first = HashMap::new();
+        let mut kept = Vec::new();
+        let mut remap = Vec::with_capacity(count);
+        for index in 0..count {
+            let next = kept.len() as u32;
+            let new_index = *first.entry(self.vertex_key(index))
+                .or_insert(next);
+            if new_index == next {
+                kept.push(index);
+            }
+
+            remap.push(new_index);
+        }
+
+        if kept.len() == count {
+            return 0;
+        }
+
+        for index in &mut self.index_buffer {
+            if let Some(&new_index) = remap.get(*index as usize) {
+                *index = new_index;
+            }
+        }
+
+        let keep = |values: &[[f32; 2]]| {
+            kept.iter().map(|&index| values[index]).collect()
+        };
+
+        self.vertex_buffer = kept.iter()
+            .map(|&index| self.vertex_buffer[index])
+            .collect();
+
+        if self.lightmap_uvs.len() == count {
+            self.lightmap_uvs = keep(&self.lightmap_uvs);
+        }
+
+        for set in &mut self.uv_sets {
+            if set.uvs.len() == count {
+                set.uvs = keep(&set.uvs);
+            }
+        }
+
+        for attribute in &mut self.custom_attributes {
+            let size = attribute.format.size();
+            if attribute.data.len() == count * size {
+                attribute.data = kept.iter()
+                    .flat_map(|&index| {
+                        &attribute.data[index * size..(index + 1) * size]
+                    })
+                    .copied()
+                    .collect();
+            }
+        }
+
+        count - kept.len()
+    }
+
+    /// Get the bytes of a vertex and all its attributes, equal for
+    /// vertices that can be welded
+    fn vertex_key(&self, index: usize) -> Vec<u8> {
+        let vertex = &self.vertex_buffer[index];
+
+        let mut key = Vec::new();
+        let floats = vertex.pos.iter()
+            .chain(&vertex.uv)
+            .chain(&vertex.color)
+            .chain(self.lightmap_uvs.get(index).into_iter().flatten())
+            .chain(self.uv_sets.iter()
+                   .flat_map(|set| set.uvs.get(index).into_iter().flatten()));
+        for value in floats {
+            key.extend_from_slice(&value.to_le_bytes());
+        }
+
+        for attribute in &self.custom_attributes {
+            let size = attribute.format.size();
+            if let Some(data) = attribute.data.get(index * size..
+                                                   (index + 1) * size)
+            {
+                key.extend_from_slice(data);
+            }
+        }
+
+        key
+    }
+}
+
+impl Sector {
+    /// Snap every mesh of the sector and its levels of detail, the walls
+    /// and the occluders to a grid, see [Mesh::snap_to_grid]
+    ///
+    /// # Arguments
+    ///
+    /// * `grid_size` - The distance between the grid points
+    ///
+    /// # Returns
+    ///
+    /// * The number of vertices removed by the welding
+    pub fn snap_to_grid(&mut self, grid_size: f32) -> usize {
+        for wall in &mut self.walls {
+            wall.start = snap_to_grid(wall.start, grid_size);
+            wall.end = snap_to_grid(wall.end, grid_size);
+        }
+
+        let occluders = self.occluders.iter_mut()
+            .flat_map(|occluder| &mut occluder.vertices);
+        for vertex in occluders {
+            *vertex = snap_to_grid(*vertex, grid_size);
+        }
+
+        self.meshes_mut().map(|mesh| mesh.snap_to_grid(grid_size)).sum()
+    }
+}
+
+impl Map {
+    /// Snap every sector to a grid, see [Sector::snap_to_grid]
+    ///
+    /// Every sector is marked for the next [Map::save_incremental] since
+    /// any of the vertices can move.
+    ///
+    /// # Arguments
+    ///
+    /// * `grid_size` - The distance between the grid points
+    ///
+    /// # Returns
+    ///
+    /// * The number of vertices removed by the welding
+    pub fn snap_to_grid(&mut self, grid_size: f32) -> usize {
+        let mut removed = 0;
+        for index in 0..self.sectors.len() {
+            removed += self.sectors[index].snap_to_grid(grid_size);
+            self.mark_sector_dirty(index);
+        }
+
+        removed
+    }
+}
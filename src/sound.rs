@@ -0,0 +1,205 @@
+//! Ambient sounds placed inside the map and the acoustics of the sectors
+
+use crate::*;
+use crate::buffer::{ Reader, write_f32s, write_string, string_size };
+
+/// The sound emitter plays the sound in a loop
+pub const SOUND_FLAG_LOOP: u32 = 1 << 0;
+
+/// The sound emitter starts playing when the map is loaded
+pub const SOUND_FLAG_AUTOPLAY: u32 = 1 << 1;
+
+/// A sound emitter playing an ambient sound
+#[derive(Clone, PartialEq, Debug)]
+pub struct SoundEmitter {
+    /// The position of the emitter
+    pub position: [f32; 3],
+
+    /// The name of the sound asset to play
+    pub sound: String,
+
+    /// The distance where the sound can't be heard anymore
+    pub radius: f32,
+
+    /// The volume of the sound, 1.0 is the volume of the asset
+    pub volume: f32,
+
+    /// The flags of the emitter, see the `SOUND_FLAG_*` constants
+    pub flags: u32,
+}
+
+impl SoundEmitter {
+    /// Creates a new looping sound emitter that plays when the map is loaded
+    ///
+    /// # Arguments
+    ///
+    /// * `position` - The position of the emitter
+    /// * `sound`    - The name of the sound asset to play
+    /// * `radius`   - The distance where the sound can't be heard anymore
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new sound emitter
+    pub fn new(position: [f32; 3], sound: &str, radius: f32) -> Self {
+        Self {
+            position,
+            sound: sound.to_string(),
+            radius,
+            volume: 1.0,
+            flags: SOUND_FLAG_LOOP | SOUND_FLAG_AUTOPLAY,
+        }
+    }
+
+    /// Check if the sound is played in a loop
+    pub fn is_looping(&self) -> bool {
+        self.flags & SOUND_FLAG_LOOP != 0
+    }
+
+    /// Get the number of bytes [SoundEmitter::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        // Position, radius, volume and flags
+        5 * std::mem::size_of::<f32>() + std::mem::size_of::<u32>() +
+            string_size(&self.sound)
+    }
+
+    /// Serialize the sound emitter to a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - Successfully serialized the sound emitter
+    /// * `Err(`[Error]`)` - Failed to serialize the sound emitter
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        write_f32s(buffer, &self.position);
+        write_string(buffer, &self.sound)?;
+        write_f32s(buffer, &[self.radius, self.volume]);
+        buffer.extend_from_slice(&self.flags.to_le_bytes());
+
+        Ok(())
+    }
+
+    /// Deserialize the sound emitter from a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the sound emitter
+    /// * `Err(`[Error]`)` - Failed to deserialize the sound emitter
+    pub fn deserialize(buffer: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallChunk);
+
+        Ok(Self {
+            position: reader.vec3()?,
+            sound: reader.string()?,
+            radius: reader.f32()?,
+            volume: reader.f32()?,
+            flags: reader.u32()?,
+        })
+    }
+}
+
+/// The size of serialized acoustics (reverb preset and occlusion)
+pub(crate) const ACOUSTICS_SIZE: usize = 1 + std::mem::size_of::<f32>();
+
+/// The reverb the sound engine applies inside a sector
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub enum ReverbPreset {
+    /// The default reverb of the engine
+    #[default]
+    Generic,
+
+    /// A small furnished room
+    Room,
+
+    /// A large hall with long echoes
+    Hall,
+
+    /// A narrow corridor
+    Corridor,
+
+    /// A cave with hard rock walls
+    Cave,
+
+    /// Open space without walls to reflect the sound
+    Outdoor,
+
+    /// Below the surface of a liquid
+    Underwater,
+}
+
+impl ReverbPreset {
+    /// Get the byte the preset is serialized as
+    pub fn to_u8(self) -> u8 {
+        match self {
+            ReverbPreset::Generic => 0,
+            ReverbPreset::Room => 1,
+            ReverbPreset::Hall => 2,
+            ReverbPreset::Corridor => 3,
+            ReverbPreset::Cave => 4,
+            ReverbPreset::Outdoor => 5,
+            ReverbPreset::Underwater => 6,
+        }
+    }
+
+    /// Get the preset from its serialized byte
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The serialized byte
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - The preset
+    /// * `Err(`[Error]`)` - [Error::UnknownReverbPreset] if the byte isn't a
+    ///   known preset
+    pub fn from_u8(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(ReverbPreset::Generic),
+            1 => Ok(ReverbPreset::Room),
+            2 => Ok(ReverbPreset::Hall),
+            3 => Ok(ReverbPreset::Corridor),
+            4 => Ok(ReverbPreset::Cave),
+            5 => Ok(ReverbPreset::Outdoor),
+            6 => Ok(ReverbPreset::Underwater),
+            value => Err(Error::UnknownReverbPreset(value)),
+        }
+    }
+}
+
+/// How sounds behave inside a sector, the sound engine switches to them when
+/// the listener enters the sector
+#[derive(Copy, Clone, PartialEq, Default, Debug)]
+pub struct Acoustics {
+    /// The reverb applied to the sounds heard inside the sector
+    pub reverb: ReverbPreset,
+
+    /// How much the sounds from outside the sector are muffled, 0.0 lets
+    /// everything through and 1.0 blocks them completely
+    pub occlusion: f32,
+}
+
+impl Acoustics {
+    /// Serialize the acoustics to a buffer
+    pub(crate) fn serialize<O>(&self, buffer: &mut O)
+        where O: Output + ?Sized
+    {
+        buffer.push(self.reverb.to_u8());
+        buffer.extend_from_slice(&self.occlusion.to_le_bytes());
+    }
+
+    /// Read acoustics written by [Acoustics::serialize]
+    pub(crate) fn read(reader: &mut Reader) -> Result<Self> {
+        Ok(Self {
+            reverb: ReverbPreset::from_u8(reader.u8()?)?,
+            occlusion: reader.f32()?,
+        })
+    }
+}
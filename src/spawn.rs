@@ -0,0 +1,139 @@
+//! Player start and spawn points, stored as entities of a dedicated class
+//!
+//! Every game needs to know where the players start, [SpawnPoint] reads
+//! and writes the properties of the class so games don't have to.
+
+use crate::*;
+
+/// The class of the spawn point entities
+pub const SPAWN_POINT_CLASS: &str = "info_player_start";
+
+/// The property with the yaw of the spawn point in degrees
+pub const SPAWN_ANGLE_PROPERTY: &str = "angle";
+
+/// The property with the team allowed to spawn at the point
+pub const SPAWN_TEAM_PROPERTY: &str = "team";
+
+/// The property with the index of the player allowed to spawn at the point
+pub const SPAWN_PLAYER_PROPERTY: &str = "player";
+
+/// A place where a player starts
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SpawnPoint {
+    /// The position of the feet of the player
+    pub position: [f32; 3],
+
+    /// The yaw the player faces in degrees, counter clockwise around the up
+    /// axis
+    pub angle: f32,
+
+    /// The team allowed to spawn at the point, `None` for every team
+    pub team: Option<u32>,
+
+    /// The index of the player allowed to spawn at the point, `None` for
+    /// every player
+    pub player: Option<u32>,
+}
+
+impl SpawnPoint {
+    /// Creates a new spawn point for every team and player
+    ///
+    /// # Arguments
+    ///
+    /// * `position` - The position of the feet of the player
+    /// * `angle`    - The yaw the player faces in degrees
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new spawn point
+    pub fn new(position: [f32; 3], angle: f32) -> Self {
+        Self {
+            position,
+            angle,
+            team: None,
+            player: None,
+        }
+    }
+
+    /// Get the definition of the spawn point class, for
+    /// [Map::validate_entities]
+    pub fn def() -> EntityDef {
+        EntityDef::new(SPAWN_POINT_CLASS)
+            .optional(SPAWN_ANGLE_PROPERTY, PropertyType::Float)
+            .optional(SPAWN_TEAM_PROPERTY, PropertyType::Int)
+            .optional(SPAWN_PLAYER_PROPERTY, PropertyType::Int)
+    }
+
+    /// Read a spawn point from an entity
+    ///
+    /// # Arguments
+    ///
+    /// * `entity` - The entity to read
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[SpawnPoint]`)` - The spawn point, properties with the
+    ///   wrong type or out of range values are ignored
+    /// * `None` - The entity isn't of [SPAWN_POINT_CLASS]
+    pub fn from_entity(entity: &Entity) -> Option<Self> {
+        if entity.class != SPAWN_POINT_CLASS {
+            return None;
+        }
+
+        let index = |name| {
+            entity.property(name)
+                .and_then(PropertyValue::as_int)
+                .and_then(|value| u32::try_from(value).ok())
+        };
+
+        Some(Self {
+            position: entity.position,
+            angle: entity.property(SPAWN_ANGLE_PROPERTY)
+                .and_then(PropertyValue::as_float)
+                .unwrap_or_default() as f32,
+            team: index(SPAWN_TEAM_PROPERTY),
+            player: index(SPAWN_PLAYER_PROPERTY),
+        })
+    }
+
+    /// Create the entity storing the spawn point
+    pub fn to_entity(&self) -> Entity {
+        let mut entity = Entity::new(SPAWN_POINT_CLASS, self.position);
+        entity.set_property(SPAWN_ANGLE_PROPERTY, self.angle as f64);
+
+        if let Some(team) = self.team {
+            entity.set_property(SPAWN_TEAM_PROPERTY, team as i64);
+        }
+
+        if let Some(player) = self.player {
+            entity.set_property(SPAWN_PLAYER_PROPERTY, player as i64);
+        }
+
+        entity
+    }
+}
+
+impl Map {
+    /// Get every spawn point of the map, see [SpawnPoint::from_entity]
+    ///
+    /// # Returns
+    ///
+    /// * The spawn points in the order of the entities
+    pub fn spawn_points(&self) -> Vec<SpawnPoint> {
+        self.entities.iter().filter_map(SpawnPoint::from_entity).collect()
+    }
+
+    /// Add a spawn point to the entities of the map
+    ///
+    /// # Arguments
+    ///
+    /// * `spawn` - The spawn point
+    ///
+    /// # Returns
+    ///
+    /// * The index of the entity
+    pub fn add_spawn_point(&mut self, spawn: SpawnPoint) -> usize {
+        self.entities.push(spawn.to_entity());
+        self.entities.len() - 1
+    }
+}
@@ -0,0 +1,172 @@
+//! Reading the sectors of a map one at a time
+//!
+//! [Map::sector_stream] reads a mime file containing a single map from any
+//! reader. Only the sector currently being deserialized is kept in memory,
+//! the reader never has to seek.
+
+use crate::*;
+use crate::lazy::{ FileHeader, parse_slots, to_file_offset };
+use crate::map::{ SECTOR_ENTRY_SIZE, SectorSlot };
+use crate::compression::read_sector_block;
+use crate::limits::Budget;
+
+use std::io::Read;
+
+/// The state of a [SectorStream]
+enum State {
+    /// The headers and the sector table haven't been read yet
+    Header,
+
+    /// Reading the sectors
+    Sectors {
+        /// The location of every sector
+        slots: Vec<SectorSlot>,
+
+        /// The index of the next sector to read
+        next: usize,
+    },
+
+    /// All the sectors were read or an error happened
+    Done,
+}
+
+/// An iterator deserializing the sectors of a map from a reader
+struct SectorStream<R>
+    where R: Read
+{
+    /// The reader we read the file from
+    reader: R,
+
+    /// The number of bytes read from the file so far
+    position: usize,
+
+    /// What we read next
+    state: State,
+}
+
+impl<R> SectorStream<R>
+    where R: Read
+{
+    /// Read `size` bytes, the buffer only grows as data arrives so a
+    /// corrupt size can't allocate more than the file contains
+    fn read(&mut self, size: usize, error: fn(SizeMismatch) -> Error)
+        -> Result<Vec<u8>>
+    {
+        let mut data = Vec::new();
+        self.reader.by_ref()
+            .take(size as u64)
+            .read_to_end(&mut data)
+            .map_err(Error::FileReadFailed)?;
+
+        let start = self.position;
+        self.position += data.len();
+
+        if data.len() < size {
+            let size = SizeMismatch::new(start.saturating_add(size),
+                                         self.position);
+            return Err(error(size).at_offset(self.position));
+        }
+
+        Ok(data)
+    }
+
+    /// Read the headers and the sector table
+    fn read_header(&mut self) -> Result<Vec<SectorSlot>> {
+        let header = self.read(FileHeader::SIZE, Error::BufferToSmallHeader)?;
+        let header = FileHeader::parse(&header)?;
+
+        let table = self.read(header.table_size(), Error::BufferToSmallMap)?;
+        Ok(parse_slots(&table))
+    }
+
+    /// Skip to the sector stored in a slot and deserialize it
+    fn read_sector(&mut self, index: usize, slot: SectorSlot)
+        -> Result<Sector>
+    {
+        let offset = to_file_offset(slot.offset);
+        let size = usize::try_from(slot.size).unwrap_or(usize::MAX);
+
+        // NOTE(patrik): The reader can't go back, the sectors have to be
+        // stored in the order of the sector table like the serializer
+        // writes them
+        let Some(skip) = offset.checked_sub(self.position) else {
+            let entry = FileHeader::SIZE + index * SECTOR_ENTRY_SIZE;
+            return Err(Error::UnorderedSectors.at_offset(entry));
+        };
+        self.read(skip, Error::BufferToSmallMap)?;
+
+        let data = self.read(size, Error::BufferToSmallMap)?;
+        slot.verify(&data)
+            .and_then(|_| read_sector_block(&data, &Budget::unlimited()))
+            .map_err(|error| error.at_offset(offset))
+    }
+}
+
+impl<R> Iterator for SectorStream<R>
+    where R: Read
+{
+    type Item = Result<Sector>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let State::Header = self.state {
+            match self.read_header() {
+                Ok(slots) => self.state = State::Sectors { slots, next: 0 },
+
+                Err(error) => {
+                    self.state = State::Done;
+                    return Some(Err(error));
+                }
+            }
+        }
+
+        let State::Sectors { slots, next } = &mut self.state else {
+            return None;
+        };
+
+        let index = *next;
+        let Some(&slot) = slots.get(index) else {
+            self.state = State::Done;
+            return None;
+        };
+        *next += 1;
+
+        let sector = self.read_sector(index, slot)
+            .map_err(|error| {
+                error.with_context(|context| context.sector = Some(index))
+            });
+
+        // NOTE(patrik): The position inside the file is unknown after an
+        // error so we stop
+        if sector.is_err() {
+            self.state = State::Done;
+        }
+
+        Some(sector)
+    }
+}
+
+impl Map {
+    /// Deserialize the sectors of a mime file containing a single map one
+    /// at a time, without reading the whole map into memory
+    ///
+    /// The iterator stops after the first error. The chunks of the map are
+    /// not read.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The reader positioned at the start of the file
+    ///
+    /// # Returns
+    ///
+    /// * An iterator over the sectors in the order they are stored in the
+    ///   map
+    pub fn sector_stream<R>(reader: R) -> impl Iterator<Item = Result<Sector>>
+        where R: Read
+    {
+        SectorStream {
+            reader,
+            position: 0,
+            state: State::Header,
+        }
+    }
+}
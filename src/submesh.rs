@@ -0,0 +1,141 @@
+//! Named ranges of the index buffer drawn with their own material
+//!
+//! A mesh with submeshes shares one vertex buffer between multiple draw
+//! calls, like a wall mesh where every wall uses another texture. The
+//! indices outside every submesh are drawn with [Mesh::material].
+
+use crate::*;
+use crate::buffer::{ Reader, write_string, string_size };
+use crate::map::NO_MATERIAL;
+
+use std::ops::Range;
+
+/// A named range of the index buffer of a mesh with its own material
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Submesh {
+    /// The name used to reference the submesh
+    pub name: String,
+
+    /// The first index inside the index buffer that belongs to the submesh
+    pub index_start: u32,
+
+    /// The number of indices inside the index buffer that belongs to the
+    /// submesh
+    pub index_count: u32,
+
+    /// The index of the material inside the material table of the map,
+    /// `None` if the submesh doesn't use a material
+    pub material: Option<u32>,
+}
+
+impl Submesh {
+    /// Creates a new submesh
+    ///
+    /// # Arguments
+    ///
+    /// * `name`     - The name used to reference the submesh
+    /// * `indices`  - The range of the index buffer of the mesh
+    /// * `material` - The index of the material inside the material table
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new submesh
+    pub fn new(name: &str, indices: Range<u32>, material: Option<u32>)
+        -> Self
+    {
+        Self {
+            name: name.to_string(),
+            index_start: indices.start,
+            index_count: indices.end - indices.start,
+            material,
+        }
+    }
+
+    /// Get the range of the index buffer of the submesh
+    pub fn range(&self) -> Range<usize> {
+        let start = self.index_start as usize;
+        start..start + self.index_count as usize
+    }
+
+    /// Get the indices of the mesh that belongs to this submesh
+    ///
+    /// # Arguments
+    ///
+    /// * `mesh` - The mesh the submesh belongs to
+    ///
+    /// # Returns
+    ///
+    /// * The indices, empty if the range is outside the index buffer
+    pub fn indices<'a>(&self, mesh: &'a Mesh) -> &'a [u32] {
+        mesh.index_buffer.get(self.range()).unwrap_or(&[])
+    }
+
+    /// Get the number of bytes [Submesh::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        // Index start, index count and material index
+        string_size(&self.name) + 3 * std::mem::size_of::<u32>()
+    }
+
+    /// Serialize the submesh to a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - Successfully serialized the submesh
+    /// * `Err(`[Error]`)` - Failed to serialize the submesh
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        write_string(buffer, &self.name)?;
+        buffer.extend_from_slice(&self.index_start.to_le_bytes());
+        buffer.extend_from_slice(&self.index_count.to_le_bytes());
+
+        let material = self.material.unwrap_or(NO_MATERIAL);
+        buffer.extend_from_slice(&material.to_le_bytes());
+
+        Ok(())
+    }
+
+    /// Deserialize the submesh from a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the submesh
+    /// * `Err(`[Error]`)` - Failed to deserialize the submesh
+    pub fn deserialize(buffer: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallMesh);
+
+        Ok(Self {
+            name: reader.string()?,
+            index_start: reader.u32()?,
+            index_count: reader.u32()?,
+            material: match reader.u32()? {
+                NO_MATERIAL => None,
+                material => Some(material),
+            },
+        })
+    }
+}
+
+impl Mesh {
+    /// Get a submesh
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the submesh
+    ///
+    /// # Returns
+    ///
+    /// * `Some(`[Submesh]`)` - The first submesh with the name
+    /// * `None` - The mesh doesn't have a submesh with the name
+    pub fn submesh(&self, name: &str) -> Option<&Submesh> {
+        self.submeshes.iter().find(|submesh| submesh.name == name)
+    }
+}
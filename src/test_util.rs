@@ -0,0 +1,108 @@
+//! proptest strategies generating valid mime data
+//!
+//! Crates reading or writing maps can property test their importers and
+//! renderers with the strategies in this module. Everything generated
+//! serializes and loads again, the positions and colors are finite and the
+//! indices are inside the vertex buffers.
+//!
+//! ```ignore
+//! proptest! {
+//!     #[test]
+//!     fn import(map in mime::test_util::map()) {
+//!         my_importer(&map);
+//!     }
+//! }
+//! ```
+
+use crate::*;
+
+use std::ops::Range;
+
+use proptest::prelude::*;
+use proptest::array::{ uniform2, uniform3, uniform4 };
+use proptest::collection::vec;
+
+/// The largest distance of the generated positions from the origin along
+/// each axis
+const MAX_COORDINATE: f32 = 1000.0;
+
+/// Generate a vertex with a finite position, texture coordinates and a
+/// color between 0.0 and 1.0
+pub fn vertex() -> impl Strategy<Value = Vertex> {
+    let position = uniform3(-MAX_COORDINATE..MAX_COORDINATE);
+    let uv = uniform2(-4.0f32..4.0);
+    let color = uniform4(0.0f32..=1.0);
+
+    (position, uv, color)
+        .prop_map(|(position, uv, color)| Vertex::new(position, uv, color))
+}
+
+/// Generate a triangle list mesh
+///
+/// # Arguments
+///
+/// * `vertices`  - The range of the number of vertices, at least 1
+/// * `triangles` - The range of the number of triangles
+///
+/// # Returns
+///
+/// * A strategy for meshes with every index inside the vertex buffer
+pub fn mesh_with(vertices: Range<usize>, triangles: Range<usize>)
+    -> impl Strategy<Value = Mesh>
+{
+    let vertices = vertices.start.max(1)..vertices.end.max(2);
+
+    vec(vertex(), vertices)
+        .prop_flat_map(move |vertex_buffer| {
+            let count = vertex_buffer.len() as u32;
+            let indices = vec(uniform3(0..count), triangles.clone());
+
+            (Just(vertex_buffer), indices)
+        })
+        .prop_map(|(vertex_buffer, triangles)| {
+            let index_buffer = triangles.into_iter().flatten().collect();
+            Mesh::new(vertex_buffer, index_buffer, 0)
+        })
+}
+
+/// Generate a small triangle list mesh, see [mesh_with]
+pub fn mesh() -> impl Strategy<Value = Mesh> {
+    mesh_with(3..16, 1..16)
+}
+
+/// Generate a sector with a tag and up to two lower levels of detail
+pub fn sector() -> impl Strategy<Value = Sector> {
+    let lod = (1.0f32..1000.0, mesh(), mesh(), mesh())
+        .prop_map(|(distance, floor, ceiling, wall)| {
+            Lod::new(distance, floor, ceiling, wall)
+        });
+
+    (mesh(), mesh(), mesh(), any::<u32>(), vec(lod, 0..3))
+        .prop_map(|(floor, ceiling, wall, tag, lods)| {
+            let mut sector = Sector::new(floor, ceiling, wall);
+            sector.tag = tag;
+            for lod in lods {
+                sector.add_lod(lod);
+            }
+
+            sector
+        })
+}
+
+/// Generate a map
+///
+/// # Arguments
+///
+/// * `sectors` - The range of the number of sectors
+///
+/// # Returns
+///
+/// * A strategy for maps with only sectors
+pub fn map_with(sectors: Range<usize>) -> impl Strategy<Value = Map> {
+    vec(sector(), sectors).prop_map(Map::new)
+}
+
+/// Generate a map with up to 8 sectors, see [map_with]
+pub fn map() -> impl Strategy<Value = Map> {
+    map_with(0..8)
+}
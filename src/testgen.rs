@@ -0,0 +1,201 @@
+//! Procedurally generated maps for benchmarks and tests
+//!
+//! [Map::generate] builds a map of any size from a few numbers so the
+//! benchmarks, stress tests and the tests of crates using mime don't need
+//! to build the meshes by hand. The same options always give the same map,
+//! a seed adds random heights and colors while staying reproducible.
+
+use crate::*;
+
+/// The distance between two sectors and the length of a mesh along the X
+/// axis
+const SECTOR_SIZE: f32 = 8.0;
+
+/// The height of the ceiling above the floor
+const SECTOR_HEIGHT: f32 = 4.0;
+
+/// Options for [Map::generate]
+#[derive(Clone, PartialEq, Debug)]
+pub struct GeneratorOptions {
+    /// The number of sectors, placed on a square grid
+    pub sectors: usize,
+
+    /// The number of vertices of every floor, ceiling and wall mesh, at
+    /// least 3
+    pub vertices_per_mesh: usize,
+
+    /// The number of levels of detail of every sector
+    pub lods: usize,
+
+    /// The number of point lights, one above each of the first sectors
+    pub lights: usize,
+
+    /// The number of entities, one inside each of the first sectors
+    pub entities: usize,
+
+    /// The seed of the random heights and colors, `None` gives flat white
+    /// meshes
+    pub seed: Option<u64>,
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> Self {
+        Self {
+            sectors: 16,
+            vertices_per_mesh: 4,
+            lods: 0,
+            lights: 0,
+            entities: 0,
+            seed: None,
+        }
+    }
+}
+
+/// A small deterministic random number generator (splitmix64), the maps
+/// don't need good randomness and the feature shouldn't pull in a crate
+struct Random {
+    state: u64,
+}
+
+impl Random {
+    /// Get the next random number
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+
+        let mut value = self.state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94d049bb133111eb);
+        value ^ (value >> 31)
+    }
+
+    /// Get a random number between 0.0 and 1.0
+    fn next_f32(&mut self) -> f32 {
+        // NOTE(patrik): The top 24 bits fit the mantissa exactly
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+/// Generate a strip of triangles
+///
+/// # Arguments
+///
+/// * `vertices` - The number of vertices, at least 3
+/// * `origin`   - The position of the first vertex
+/// * `side`     - The direction from one row of the strip to the other
+/// * `random`   - Moves the vertices along Y and colors them when used
+///
+/// # Returns
+///
+/// * [Mesh] - The strip, going along the X axis from `origin`
+fn generate_mesh(vertices: usize,
+                 origin: [f32; 3],
+                 side: [f32; 3],
+                 random: &mut Option<Random>)
+    -> Mesh
+{
+    let vertices = vertices.max(3);
+    let columns = vertices.div_ceil(2);
+    let step = SECTOR_SIZE / (columns - 1).max(1) as f32;
+
+    let mut vertex_buffer = Vec::with_capacity(vertices);
+    for index in 0..vertices {
+        let (column, row) = (index / 2, (index % 2) as f32);
+
+        let mut pos = [
+            origin[0] + column as f32 * step + side[0] * row,
+            origin[1] + side[1] * row,
+            origin[2] + side[2] * row,
+        ];
+        let mut color = [1.0; 4];
+        if let Some(random) = random {
+            pos[1] += random.next_f32() * 0.5 - 0.25;
+            for channel in &mut color[..3] {
+                *channel = random.next_f32();
+            }
+        }
+
+        let uv = [column as f32 / columns as f32, row];
+        vertex_buffer.push(Vertex::new(pos, uv, color));
+    }
+
+    // NOTE(patrik): Every other triangle of a strip is flipped to keep the
+    // winding the same
+    let mut index_buffer = Vec::with_capacity((vertices - 2) * 3);
+    for index in 0..vertices as u32 - 2 {
+        if index % 2 == 0 {
+            index_buffer.extend_from_slice(&[index, index + 1, index + 2]);
+        } else {
+            index_buffer.extend_from_slice(&[index + 1, index, index + 2]);
+        }
+    }
+
+    Mesh::new(vertex_buffer, index_buffer, 0)
+}
+
+impl Map {
+    /// Generate a map for benchmarks and tests
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - The size of the map and the seed
+    ///
+    /// # Returns
+    ///
+    /// * [Map] - The generated map, the same options give the same map
+    pub fn generate(options: &GeneratorOptions) -> Map {
+        let mut random = options.seed.map(|seed| Random { state: seed });
+        let columns = (options.sectors as f64).sqrt().ceil().max(1.0) as usize;
+        let origin = |index: usize| {
+            [(index % columns) as f32 * SECTOR_SIZE,
+             0.0,
+             (index / columns) as f32 * SECTOR_SIZE]
+        };
+
+        let mut mesh = |origin: [f32; 3], side: [f32; 3]| {
+            generate_mesh(options.vertices_per_mesh, origin, side,
+                          &mut random)
+        };
+
+        let mut sectors = Vec::with_capacity(options.sectors);
+        for index in 0..options.sectors {
+            let bottom = origin(index);
+            let top = [bottom[0], bottom[1] + SECTOR_HEIGHT, bottom[2]];
+            let across = [0.0, 0.0, SECTOR_SIZE];
+            let up = [0.0, SECTOR_HEIGHT, 0.0];
+
+            let mut sector = Sector::new(mesh(bottom, across),
+                                         mesh(top, across),
+                                         mesh(bottom, up));
+            for lod in 0..options.lods {
+                let distance = SECTOR_SIZE * 4.0 * (lod + 1) as f32;
+                sector.add_lod(Lod::new(distance,
+                                        mesh(bottom, across),
+                                        mesh(top, across),
+                                        mesh(bottom, up)));
+            }
+
+            sectors.push(sector);
+        }
+
+        let mut map = Map::new(sectors);
+
+        let center = |index: usize, height: f32| {
+            let [x, y, z] = origin(index);
+            let half = SECTOR_SIZE / 2.0;
+            [x + half, y + height, z + half]
+        };
+
+        for index in 0..options.lights {
+            let position = center(index, SECTOR_HEIGHT - 0.5);
+            map.lights.push(Light::point(position, [1.0; 3], 1.0,
+                                         SECTOR_SIZE));
+        }
+
+        for index in 0..options.entities {
+            map.entities.push(Entity::new("info_generated",
+                                          center(index, 0.0)));
+        }
+
+        map
+    }
+}
@@ -1,7 +1,20 @@
 //! Module for all the unit tests
 
+// NOTE(patrik): The tests are wrapped in their own module inside the file
+// declared by lib.rs
+#[allow(clippy::module_inception)]
 mod tests {
     use crate::map::*;
+    use crate::{ Lightmap, LightmapUvOptions, Light, BakeOptions, Sky,
+                 SoundEmitter, Trigger, TriggerShape, Wall,
+                 MimeArchive, ArchiveIndex, AssetDependency, AssetKind,
+                 MeshRole, SaveKind, SectorId, MapEditor, Edit,
+                 VertexSemantic, VertexFormat,
+                 NonFinitePolicy, ErrorElement, ColorSpace,
+                 Material, BlendMode, CustomAttribute, VertexDescriptor,
+                 SectorAnimation, SectorPlane, Keyframe, SectorGroup,
+                 Pvs, Thumbnail, LazyMap, MinimapOptions, Bounds,
+                 SectorCompression };
 
     macro_rules! parse_u32 {
         ($buf:expr, $i:expr) => {{
@@ -52,25 +65,32 @@ mod tests {
         assert_eq!(parse_f32!(buffer, index), 4.0);
         assert_eq!(parse_f32!(buffer, index), 5.0);
         assert_eq!(parse_f32!(buffer, index), 6.0);
+
+        assert_eq!(index, buffer.len());
     }
 
     #[test]
     fn mesh_serialize() {
-        let mut vertex_buffer = Vec::new();
-        vertex_buffer.push(Vertex::new([0.0, 0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]));
-        vertex_buffer.push(Vertex::new([0.0, 1.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]));
-        vertex_buffer.push(Vertex::new([1.0, 1.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]));
-        vertex_buffer.push(Vertex::new([1.0, 0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]));
+        let vertex_buffer = vec![
+            Vertex::new([0.0, 0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+            Vertex::new([0.0, 1.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+            Vertex::new([1.0, 1.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+            Vertex::new([1.0, 0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+        ];
 
         let index_buffer = vec![0, 1, 2, 2, 3, 0];
 
         let mesh = Mesh::new(vertex_buffer, index_buffer, 0);
 
         let mut buffer = Vec::new();
-        mesh.serialize(&mut buffer);
+        mesh.serialize(&mut buffer).unwrap();
 
         let mut index = 0;
 
+        // The vertex descriptor with the position, uv and color
+        assert_eq!(buffer[index], 3);
+        skip!(index, 1 + 3 * 2);
+
         assert_eq!(parse_u64!(buffer, index), 4);
         assert_eq!(parse_u64!(buffer, index), 6);
 
@@ -83,15 +103,19 @@ mod tests {
         assert_eq!(parse_u32!(buffer, index), 2);
         assert_eq!(parse_u32!(buffer, index), 3);
         assert_eq!(parse_u32!(buffer, index), 0);
+
+        // No lightmap texture coordinates
+        assert_eq!(buffer[index..index + 8], 0u64.to_le_bytes());
     }
 
     #[test]
     fn sector_serialize() {
-        let mut vertex_buffer = Vec::new();
-        vertex_buffer.push(Vertex::new([0.0, 0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]));
-        vertex_buffer.push(Vertex::new([0.0, 1.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]));
-        vertex_buffer.push(Vertex::new([1.0, 1.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]));
-        vertex_buffer.push(Vertex::new([1.0, 0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]));
+        let vertex_buffer = vec![
+            Vertex::new([0.0, 0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+            Vertex::new([0.0, 1.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+            Vertex::new([1.0, 1.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+            Vertex::new([1.0, 0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+        ];
 
         let index_buffer = vec![0, 1, 2, 2, 3, 0];
 
@@ -103,10 +127,13 @@ mod tests {
         let mut buffer = Vec::new();
         sector.serialize(&mut buffer).unwrap();
 
+        // The sector starts with its bounds
         let mut index = 0;
+        assert_eq!(parse_f32!(buffer, index), 0.0);
+        skip!(index, 4 * 5);
 
-        let expected_size = 8 + std::mem::size_of::<Vertex>() * 4 +
-            std::mem::size_of::<u32>() * 6 + 8;
+        let expected_size = 7 + 8 + std::mem::size_of::<Vertex>() * 4 +
+            std::mem::size_of::<u32>() * 6 + 8 + 8 + 4 + 1 + 8 + 8 + 8;
 
         assert_eq!(parse_u64!(buffer, index), expected_size as u64);
         skip!(index, expected_size);
@@ -115,15 +142,19 @@ mod tests {
         skip!(index, expected_size);
 
         assert_eq!(parse_u64!(buffer, index), expected_size as u64);
+
+        // The wall mesh starts with its vertex descriptor
+        assert_eq!(buffer[index], 3);
     }
 
     #[test]
     fn map_serialize() {
-        let mut vertex_buffer = Vec::new();
-        vertex_buffer.push(Vertex::new([0.0, 0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]));
-        vertex_buffer.push(Vertex::new([0.0, 1.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]));
-        vertex_buffer.push(Vertex::new([1.0, 1.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]));
-        vertex_buffer.push(Vertex::new([1.0, 0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]));
+        let vertex_buffer = vec![
+            Vertex::new([0.0, 0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+            Vertex::new([0.0, 1.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+            Vertex::new([1.0, 1.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+            Vertex::new([1.0, 0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+        ];
 
         let index_buffer = vec![0, 1, 2, 2, 3, 0];
 
@@ -131,13 +162,15 @@ mod tests {
         let ceiling_mesh = Mesh::new(vertex_buffer.clone(), index_buffer.clone(), 0);
         let wall_mesh = Mesh::new(vertex_buffer.clone(), index_buffer.clone(), 0);
 
-        let mut sectors = Vec::new();
-        sectors.push(Sector::new(floor_mesh, ceiling_mesh, wall_mesh));
+        let sectors = vec![Sector::new(floor_mesh, ceiling_mesh, wall_mesh)];
 
         let map = Map::new(sectors);
 
+        let mut mime = Mime::new();
+        mime.add_map(map);
+
         let mut buffer = Vec::new();
-        map.serialize(&mut buffer).unwrap();
+        mime.serialize(&mut buffer).unwrap();
 
         let mut index = 0;
 
@@ -148,6 +181,10 @@ mod tests {
         assert_eq!(parse_u32!(buffer, index), CURRENT_VERSION);
 
         assert_eq!(parse_u64!(buffer, index), 1);
+
+        // The map is prefixed with its size and fills the rest of the file
+        let size = parse_u64!(buffer, index) as usize;
+        assert_eq!(index + size, buffer.len());
     }
 
     #[test]
@@ -162,25 +199,14 @@ mod tests {
         assert_eq!(result, vertex);
     }
 
-    fn compare_mesh(a: &Mesh, b: &Mesh) {
-        assert_eq!(a.vertex_buffer.len(), b.vertex_buffer.len());
-        for index in 0..a.vertex_buffer.len() {
-            assert_eq!(a.vertex_buffer[index], b.vertex_buffer[index]);
-        }
-
-        assert_eq!(a.index_buffer.len(), b.index_buffer.len());
-        for index in 0..a.index_buffer.len() {
-            assert_eq!(a.index_buffer[index], a.index_buffer[index]);
-        }
-    }
-
     #[test]
     fn mesh_deserialize() {
-        let mut vertex_buffer = Vec::new();
-        vertex_buffer.push(Vertex::new([0.0, 0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]));
-        vertex_buffer.push(Vertex::new([0.0, 1.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]));
-        vertex_buffer.push(Vertex::new([1.0, 1.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]));
-        vertex_buffer.push(Vertex::new([1.0, 0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]));
+        let vertex_buffer = vec![
+            Vertex::new([0.0, 0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+            Vertex::new([0.0, 1.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+            Vertex::new([1.0, 1.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+            Vertex::new([1.0, 0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+        ];
 
         let index_buffer = vec![0, 1, 2, 2, 3, 0];
 
@@ -190,22 +216,17 @@ mod tests {
 
         let result = Mesh::deserialize(&buffer).unwrap();
 
-        compare_mesh(&result, &mesh);
-    }
-
-    fn compare_sector(a: &Sector, b: &Sector) {
-        compare_mesh(&a.floor_mesh, &b.floor_mesh);
-        compare_mesh(&a.ceiling_mesh, &b.ceiling_mesh);
-        compare_mesh(&a.wall_mesh, &b.wall_mesh);
+        assert_eq!(result, mesh);
     }
 
     #[test]
     fn sector_deserialize() {
-        let mut vertex_buffer = Vec::new();
-        vertex_buffer.push(Vertex::new([0.0, 0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]));
-        vertex_buffer.push(Vertex::new([0.0, 1.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]));
-        vertex_buffer.push(Vertex::new([1.0, 1.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]));
-        vertex_buffer.push(Vertex::new([1.0, 0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]));
+        let vertex_buffer = vec![
+            Vertex::new([0.0, 0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+            Vertex::new([0.0, 1.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+            Vertex::new([1.0, 1.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+            Vertex::new([1.0, 0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+        ];
 
         let index_buffer = vec![0, 1, 2, 2, 3, 0];
 
@@ -219,16 +240,17 @@ mod tests {
 
         let result = Sector::deserialize(&buffer).unwrap();
 
-        compare_sector(&result, &sector);
+        assert_eq!(result, sector);
     }
 
     #[test]
     fn map_deserialize() {
-        let mut vertex_buffer = Vec::new();
-        vertex_buffer.push(Vertex::new([0.0, 0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]));
-        vertex_buffer.push(Vertex::new([0.0, 1.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]));
-        vertex_buffer.push(Vertex::new([1.0, 1.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]));
-        vertex_buffer.push(Vertex::new([1.0, 0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]));
+        let vertex_buffer = vec![
+            Vertex::new([0.0, 0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+            Vertex::new([0.0, 1.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+            Vertex::new([1.0, 1.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+            Vertex::new([1.0, 0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+        ];
 
         let index_buffer = vec![0, 1, 2, 2, 3, 0];
 
@@ -236,8 +258,294 @@ mod tests {
         let ceiling_mesh = Mesh::new(vertex_buffer.clone(), index_buffer.clone(), 0);
         let wall_mesh = Mesh::new(vertex_buffer.clone(), index_buffer.clone(), 0);
 
-        let mut sectors = Vec::new();
-        sectors.push(Sector::new(floor_mesh, ceiling_mesh, wall_mesh));
+        let sectors = vec![Sector::new(floor_mesh, ceiling_mesh, wall_mesh)];
+
+        let map = Map::new(sectors);
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+
+        let result = Map::deserialize(&buffer).unwrap();
+
+        assert_eq!(result, map);
+    }
+
+    fn quad_mesh() -> Mesh {
+        let vertex_buffer = vec![
+            Vertex::new([0.0, 0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+            Vertex::new([0.0, 1.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+            Vertex::new([1.0, 1.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+            Vertex::new([1.0, 0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
+        ];
+
+        let index_buffer = vec![0, 1, 2, 2, 3, 0];
+
+        Mesh::new(vertex_buffer, index_buffer, 0)
+    }
+
+    fn quad_sector() -> Sector {
+        Sector::new(quad_mesh(), quad_mesh(), quad_mesh())
+    }
+
+    /// Recompute the checksums of the sector table of a serialized map
+    /// after corrupting a sector on purpose
+    fn update_checksums(map: &mut [u8]) {
+        let value = |map: &[u8], offset: usize| {
+            u64::from_le_bytes(map[offset..offset + 8].try_into().unwrap())
+                as usize
+        };
+
+        for index in 0..value(map, 0) {
+            let entry = MAP_HEADER_SIZE + index * SECTOR_ENTRY_SIZE;
+            let (offset, size) = (value(map, entry), value(map, entry + 8));
+            let checksum = xxhash_rust::xxh64::xxh64(
+                &map[offset..offset + size], 0);
+            map[entry + 24..entry + 32]
+                .copy_from_slice(&checksum.to_le_bytes());
+        }
+    }
+
+    #[test]
+    fn sector_lod_deserialize() {
+        let mut sector = quad_sector();
+        sector.add_lod(Lod::new(100.0, quad_mesh(), quad_mesh(), quad_mesh()));
+        sector.add_lod(Lod::new(50.0, quad_mesh(), quad_mesh(), quad_mesh()));
+
+        let mut buffer = Vec::new();
+        sector.serialize(&mut buffer).unwrap();
+
+        let result = Sector::deserialize(&buffer).unwrap();
+
+        assert_eq!(result, sector);
+        assert_eq!(result.lod_count(), 2);
+        assert_eq!(result.lod(0).unwrap().switch_distance, 50.0);
+        assert_eq!(result.lod(1).unwrap().switch_distance, 100.0);
+        assert_eq!(result.lod(1).unwrap().wall_mesh,
+                   sector.lod(1).unwrap().wall_mesh);
+
+        assert!(result.lod_for_distance(10.0).is_none());
+        assert_eq!(result.lod_for_distance(75.0).unwrap().switch_distance,
+                   50.0);
+        assert_eq!(result.lod_for_distance(500.0).unwrap().switch_distance,
+                   100.0);
+    }
+
+    #[test]
+    fn lightmap_uv_generation() {
+        // Two walls meeting in a corner, the corner vertices are shared
+        let color = [1.0, 1.0, 1.0, 1.0];
+        let wall_mesh = Mesh::new(vec![
+            Vertex::new([0.0, 0.0, 0.0], [0.0, 0.0], color),
+            Vertex::new([1.0, 0.0, 0.0], [0.0, 0.0], color),
+            Vertex::new([1.0, 1.0, 0.0], [0.0, 0.0], color),
+            Vertex::new([0.0, 1.0, 0.0], [0.0, 0.0], color),
+            Vertex::new([1.0, 0.0, 1.0], [0.0, 0.0], color),
+            Vertex::new([1.0, 1.0, 1.0], [0.0, 0.0], color),
+        ], vec![0, 1, 2, 2, 3, 0, 1, 4, 5, 5, 2, 1], 0);
+
+        let sector = Sector::new(quad_mesh(), quad_mesh(), wall_mesh);
+        let mut map = Map::new(vec![sector]);
+
+        let options = LightmapUvOptions::default();
+        let atlas_size = map.generate_lightmap_uvs(&options);
+        assert!(atlas_size.is_power_of_two());
+
+        let wall_mesh = &map.sectors[0].wall_mesh;
+        assert_eq!(wall_mesh.vertex_buffer.len(), 8);
+        assert_eq!(wall_mesh.lightmap_uvs.len(), 8);
+
+        for sector in &map.sectors {
+            for mesh in [&sector.floor_mesh, &sector.ceiling_mesh,
+                         &sector.wall_mesh]
+            {
+                assert_eq!(mesh.lightmap_uvs.len(), mesh.vertex_buffer.len());
+                for uv in &mesh.lightmap_uvs {
+                    assert!((0.0..=1.0).contains(&uv[0]));
+                    assert!((0.0..=1.0).contains(&uv[1]));
+                }
+            }
+        }
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+
+        let result = Map::deserialize(&buffer).unwrap();
+        assert_eq!(result.sectors[0].wall_mesh.lightmap_uvs,
+                   wall_mesh.lightmap_uvs);
+    }
+
+    #[test]
+    fn lightmap_deserialize() {
+        let mut lightmap = Lightmap::new(4, 2);
+        lightmap.set_texel(3, 1, [10, 20, 30, 255]);
+
+        let mut map = Map::new(vec![quad_sector()]);
+        map.lightmap = Some(lightmap.clone());
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+
+        let result = Map::deserialize(&buffer).unwrap();
+        let result_lightmap = result.lightmap.unwrap();
+        assert_eq!(result_lightmap, lightmap);
+        assert_eq!(result_lightmap.texel(3, 1), [10, 20, 30, 255]);
+        assert_eq!(result_lightmap.texel(0, 0), [0, 0, 0, 0]);
+
+        assert!(Lightmap::from_texels(2, 2, vec![0; 3]).is_err());
+    }
+
+    #[test]
+    fn bake_vertex_lighting() {
+        // The quad faces -z, the blocker sits between it and the light
+        let color = [1.0, 1.0, 1.0, 1.0];
+        let blocker = Mesh::new(vec![
+            Vertex::new([-2.0, -2.0, -0.5], [0.0, 0.0], color),
+            Vertex::new([-2.0, 3.0, -0.5], [0.0, 0.0], color),
+            Vertex::new([3.0, 3.0, -0.5], [0.0, 0.0], color),
+            Vertex::new([3.0, -2.0, -0.5], [0.0, 0.0], color),
+        ], vec![0, 1, 2, 2, 3, 0], 0);
+        let empty = || Mesh::new(Vec::new(), Vec::new(), 0);
+
+        let mut map = Map::new(vec![
+            Sector::new(quad_mesh(), empty(), empty()),
+            Sector::new(empty(), empty(), blocker),
+        ]);
+        map.color_space = ColorSpace::Linear;
+
+        let lights = [Light::point([0.5, 0.5, -2.0], [1.0, 0.5, 0.25],
+                                   1.0, 10.0)];
+
+        let mut options = BakeOptions {
+            shadows: false,
+            ..Default::default()
+        };
+        map.bake_vertex_lighting(&lights, &options);
+
+        let color = map.sectors[0].floor_mesh.vertex_buffer[0].color;
+        assert!(color[0] > options.ambient[0]);
+        assert!(color[0] > color[1] && color[1] > color[2]);
+        assert_eq!(color[3], 1.0);
+
+        options.shadows = true;
+        map.bake_vertex_lighting(&lights, &options);
+
+        for vertex in &map.sectors[0].floor_mesh.vertex_buffer {
+            assert_eq!(vertex.color, [0.1, 0.1, 0.1, 1.0]);
+        }
+
+        // A light behind the quad doesn't light it
+        let lights = [Light::point([0.5, 0.5, 2.0], [1.0; 3], 1.0, 10.0)];
+        options.shadows = false;
+        map.bake_vertex_lighting(&lights, &options);
+
+        for vertex in &map.sectors[0].floor_mesh.vertex_buffer {
+            assert_eq!(vertex.color, [0.1, 0.1, 0.1, 1.0]);
+        }
+
+        // The baked light is stored in the color space of the map
+        map.color_space = ColorSpace::Srgb;
+        map.bake_vertex_lighting(&lights, &options);
+
+        let srgb = crate::color::linear_to_srgb(0.1);
+        for vertex in &map.sectors[0].floor_mesh.vertex_buffer {
+            assert_eq!(vertex.color, [srgb, srgb, srgb, 1.0]);
+        }
+    }
+
+    #[test]
+    fn lights_deserialize() {
+        let mut map = Map::new(vec![quad_sector()]);
+        map.lights.push(Light::point([1.0, 2.0, 3.0], [1.0, 0.5, 0.0],
+                                     2.0, 8.0));
+        map.lights.push(Light::spot([0.0, 4.0, 0.0], [0.0, -1.0, 0.0],
+                                    0.3, 0.6, [1.0; 3], 1.0, 16.0));
+        map.lights.push(Light::directional([0.0, -1.0, 0.5], [1.0; 3], 0.5));
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+
+        let result = Map::deserialize(&buffer).unwrap();
+        assert_eq!(result.lights, map.lights);
+    }
+
+    #[test]
+    fn sky_deserialize() {
+        let mut map = Map::new(vec![quad_sector()]);
+        assert!(map.sky.is_none());
+
+        for sky in [
+            Sky::Skybox { texture: "sky/night".to_string() },
+            Sky::Procedural {
+                zenith_color: [0.1, 0.2, 0.8],
+                horizon_color: [0.7, 0.8, 1.0],
+                sun_direction: [0.0, 1.0, 0.5],
+                sun_color: [1.0, 0.9, 0.7],
+                sun_size: 0.05,
+            },
+        ] {
+            map.sky = Some(sky);
+
+            let mut buffer = Vec::new();
+            map.serialize(&mut buffer).unwrap();
+
+            let result = Map::deserialize(&buffer).unwrap();
+            assert_eq!(result.sky, map.sky);
+        }
+    }
+
+    #[test]
+    fn sound_emitters_deserialize() {
+        let mut map = Map::new(vec![quad_sector()]);
+
+        let mut emitter = SoundEmitter::new([1.0, 2.0, 3.0], "amb/wind", 12.0);
+        emitter.volume = 0.5;
+        map.sound_emitters.push(emitter);
+
+        let mut emitter = SoundEmitter::new([0.0; 3], "amb/drip", 4.0);
+        emitter.flags = 0;
+        map.sound_emitters.push(emitter);
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+
+        let result = Map::deserialize(&buffer).unwrap();
+        assert_eq!(result.sound_emitters, map.sound_emitters);
+        assert!(result.sound_emitters[0].is_looping());
+        assert!(!result.sound_emitters[1].is_looping());
+    }
+
+    #[test]
+    fn triggers_deserialize() {
+        let mut map = Map::new(vec![quad_sector()]);
+        map.triggers.push(Trigger::new(TriggerShape::Aabb {
+            min: [0.0, 0.0, 0.0],
+            max: [1.0, 2.0, 1.0],
+        }, 1, "exit", 0));
+        map.triggers.push(Trigger::new(TriggerShape::Convex {
+            planes: vec![
+                [1.0, 0.0, 0.0, 1.0],
+                [-1.0, 0.0, 0.0, 1.0],
+                [0.0, 1.0, 0.0, 0.0],
+            ],
+        }, 2, "", 5));
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+
+        let result = Map::deserialize(&buffer).unwrap();
+        assert_eq!(result.triggers, map.triggers);
+
+        assert!(result.triggers[0].contains([0.5, 1.5, 0.5]));
+        assert!(!result.triggers[0].contains([0.5, 2.5, 0.5]));
+        assert!(result.triggers[1].contains([0.5, -3.0, 100.0]));
+        assert!(!result.triggers[1].contains([1.5, -3.0, 0.0]));
+    }
+
+    #[test]
+    fn sectors_with_tag() {
+        let mut sectors = vec![quad_sector(), quad_sector(), quad_sector()];
+        sectors[0].tag = 5;
+        sectors[2].tag = 5;
 
         let map = Map::new(sectors);
 
@@ -245,11 +553,3794 @@ mod tests {
         map.serialize(&mut buffer).unwrap();
 
         let result = Map::deserialize(&buffer).unwrap();
+        let tagged = result.sectors_with_tag(5)
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        assert_eq!(tagged, vec![0, 2]);
+        assert_eq!(result.sectors_with_tag(1).count(), 0);
+    }
+
+    #[test]
+    fn sector_walls_deserialize() {
+        let mut sector = quad_sector();
+
+        let mut wall = Wall::new([0.0, 0.0, 0.0], [1.0, 0.0, 0.0],
+                                 "brick", 0..6);
+        wall.uv_offset = [0.5, 0.25];
+        wall.flags = crate::wall::WALL_FLAG_TWO_SIDED;
+        sector.walls.push(wall);
+
+        let mut buffer = Vec::new();
+        sector.serialize(&mut buffer).unwrap();
+
+        let result = Sector::deserialize(&buffer).unwrap();
+        assert_eq!(result.walls, sector.walls);
+        assert!(result.walls[0].is_two_sided());
+        assert_eq!(result.walls[0].indices(&result.wall_mesh),
+                   &[0, 1, 2, 2, 3, 0]);
+    }
+
+    #[test]
+    fn archive_deserialize() {
+        use crate::Error;
+
+        let mut first = Map::new(vec![quad_sector()]);
+        first.sectors[0].tag = 1;
+        let second = Map::new(vec![quad_sector(), quad_sector()]);
+
+        let mut archive = MimeArchive::new();
+        archive.add_map("e1m1", &first).unwrap();
+        archive.add_map("e1m2", &second).unwrap();
+        archive.add_resource("palette", vec![1, 2, 3, 4]);
+
+        let mut buffer = Vec::new();
+        archive.serialize(&mut buffer).unwrap();
+
+        let result = MimeArchive::deserialize(&buffer).unwrap();
+        assert_eq!(result, archive);
+        assert_eq!(result.map_names().collect::<Vec<_>>(),
+                   vec!["e1m1", "e1m2"]);
+        assert_eq!(result.resource("palette"), Some(&[1, 2, 3, 4][..]));
+        assert_eq!(result.map("e1m2").unwrap().sectors.len(), 2);
+        assert!(result.map("palette").is_err());
+
+        // Random access through the index
+        let mut cursor = std::io::Cursor::new(buffer);
+        let index = ArchiveIndex::read(&mut cursor).unwrap();
+        assert_eq!(index.entries.len(), 3);
+
+        let map = index.read_map(&mut cursor, "e1m1").unwrap();
+        assert_eq!(map.sectors[0].tag, 1);
+        assert_eq!(index.read_entry(&mut cursor, "palette").unwrap(),
+                   vec![1, 2, 3, 4]);
+
+        // A hostile size in the index fails without allocating it
+        let mut index = index;
+        index.entries[2].size = u64::MAX;
+        let error = index.read_entry(&mut cursor, "palette").unwrap_err();
+        assert!(matches!(error.kind(), Error::BufferToSmallArchive(_)));
+
+        // The maps of an archive written by another major version can't be
+        // read
+        let mut newer = cursor.into_inner();
+        let version = CURRENT_VERSION + (1 << 16);
+        newer[8..12].copy_from_slice(&version.to_le_bytes());
+        let error = MimeArchive::deserialize(&newer).unwrap_err();
+        assert!(matches!(error.kind(),
+                         Error::IncorrectVersion(v) if *v == version));
+    }
+
+    #[test]
+    fn embedded_assets_deserialize() {
+        let mut map = Map::new(vec![quad_sector()]);
+        map.embed_asset("palette", vec![0, 1, 2]);
+        map.embed_asset("tex/brick", vec![9; 16]);
+        map.embed_asset("palette", vec![3, 4, 5]);
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+
+        let mut result = Map::deserialize(&buffer).unwrap();
+        assert_eq!(result.embedded_assets, map.embedded_assets);
+        assert_eq!(result.embedded_asset("palette"), Some(&[3, 4, 5][..]));
+        assert_eq!(result.embedded_asset("missing"), None);
+
+        assert!(result.remove_embedded_asset("palette").is_some());
+        assert_eq!(result.embedded_assets.len(), 1);
+    }
+
+    #[test]
+    fn asset_dependencies() {
+        let mut sector = quad_sector();
+        sector.walls.push(Wall::new([0.0; 3], [1.0, 0.0, 0.0], "brick", 0..6));
+        sector.walls.push(Wall::new([0.0; 3], [0.0, 0.0, 1.0], "brick", 0..6));
+        sector.walls.push(Wall::new([0.0; 3], [0.0, 0.0, 1.0], "local", 0..6));
+
+        let mut map = Map::new(vec![sector]);
+        map.sky = Some(Sky::Skybox { texture: "sky/day".to_string() });
+        map.sound_emitters.push(SoundEmitter::new([0.0; 3], "wind", 1.0));
+        map.embed_asset("local", vec![1]);
+
+        map.update_dependencies();
+        assert_eq!(map.dependencies, vec![
+            AssetDependency::new(AssetKind::Texture, "brick"),
+            AssetDependency::new(AssetKind::Texture, "sky/day"),
+            AssetDependency::new(AssetKind::Sound, "wind"),
+        ]);
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+
+        let result = Map::deserialize(&buffer).unwrap();
+        assert_eq!(result.dependencies, map.dependencies);
+
+        let missing = result.missing_dependencies(|d| d.name != "wind");
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].kind, AssetKind::Sound);
+    }
+
+    #[test]
+    fn map_diff() {
+        use crate::Topology;
+
+        let old = Map::new(vec![quad_sector(), quad_sector(), quad_sector()]);
+        assert!(old.diff(&old).is_empty());
+
+        let mut new = Map::new(vec![quad_sector(), quad_sector()]);
+        new.sectors[1].tag = 3;
+        new.sectors[1].wall_mesh.vertex_buffer[2].pos[1] = 5.0;
+        new.sectors[1].wall_mesh.vertex_buffer.push(
+            Vertex::new([0.0; 3], [0.0; 2], [1.0; 4]));
+
+        let diff = old.diff(&new);
+        assert!(diff.added_sectors.is_empty());
+        assert_eq!(diff.removed_sectors, vec![SectorId(2)]);
+        assert_eq!(diff.changed_sectors.len(), 1);
+
+        let sector = &diff.changed_sectors[0];
+        assert_eq!(sector.id, SectorId(1));
+        assert!(sector.tag_changed);
+        assert!(!sector.walls_changed);
+        assert_eq!(sector.meshes.len(), 1);
+        assert_eq!(sector.meshes[0].role, MeshRole::Wall);
+        assert_eq!(sector.meshes[0].changed_vertices, vec![2]);
+        assert_eq!(sector.meshes[0].added_vertices, 1);
+        assert!(!sector.meshes[0].indices_changed);
+
+        let diff = new.diff(&old);
+        assert_eq!(diff.added_sectors, vec![SectorId(2)]);
+        assert_eq!(diff.changed_sectors[0].meshes[0].removed_vertices, 1);
+
+        // Changes to the texture, the topology or the vertex descriptor
+        // alone are reported
+        let mut new = old.clone();
+        new.sectors[0].floor_mesh.texture_id = 4;
+        new.sectors[1].floor_mesh.topology = Topology::TriangleStrip;
+        new.sectors[2].floor_mesh.vertex_descriptor.attributes.pop();
+
+        let diff = old.diff(&new);
+        assert_eq!(diff.changed_sectors.len(), 3);
+        let meshes = diff.changed_sectors.iter()
+            .map(|sector| &sector.meshes[0])
+            .collect::<Vec<_>>();
+        assert!(meshes[0].texture_changed && !meshes[0].topology_changed);
+        assert!(meshes[1].topology_changed);
+        assert!(meshes[2].vertex_descriptor_changed);
+    }
+
+    #[test]
+    fn map_merge() {
+        let mut sector = quad_sector();
+        sector.tag = 2;
+        let mut map = Map::new(vec![sector]);
+
+        let mut sector = quad_sector();
+        sector.tag = 1;
+        let mut prefab = Map::new(vec![sector, quad_sector()]);
+        prefab.triggers.push(Trigger::new(TriggerShape::Aabb {
+            min: [0.0; 3],
+            max: [1.0; 3],
+        }, 0, "door", 1));
+        prefab.lights.push(Light::point([0.0; 3], [1.0; 3], 1.0, 4.0));
+
+        // Translate by (10, 0, 0) and mirror the x axis
+        let transform = [
+            [-1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [10.0, 0.0, 0.0, 1.0],
+        ];
+
+        let range = map.merge(prefab, Some(&transform)).unwrap();
+        assert_eq!(range, 1..3);
+        assert_eq!(map.sectors.len(), 3);
+
+        assert_eq!(map.sectors[1].tag, 3);
+        assert_eq!(map.sectors[2].tag, 0);
+        assert_eq!(map.triggers[0].tag, 3);
+
+        let mesh = &map.sectors[1].floor_mesh;
+        assert_eq!(mesh.vertex_buffer[2].pos, [9.0, 1.0, 0.0]);
+        assert_eq!(mesh.index_buffer, vec![0, 2, 1, 2, 0, 3]);
+
+        assert_eq!(map.lights[0].position, [10.0, 0.0, 0.0]);
+        assert!(map.triggers[0].contains([9.5, 0.5, 0.5]));
+        assert!(!map.triggers[0].contains([0.5, 0.5, 0.5]));
+    }
+
+    #[test]
+    fn map_save_incremental() {
+        let path = std::env::temp_dir()
+            .join(format!("mime_incremental_{}.mime", std::process::id()));
+
+        let mut map = Map::new(vec![quad_sector(), quad_sector()]);
+        map.save_to_file(&path).unwrap();
+
+        // Change a sector without changing its size
+        map.sectors[1].floor_mesh.vertex_buffer[0].pos = [5.0, 6.0, 7.0];
+        map.mark_sector_dirty(1);
+        map.lights.push(Light::point([0.0; 3], [1.0; 3], 1.0, 4.0));
+        assert_eq!(map.dirty_sectors().collect::<Vec<_>>(), vec![1]);
+
+        let kind = map.save_incremental(&path).unwrap();
+        assert_eq!(kind, SaveKind::Incremental { sectors_written: 1 });
+        assert_eq!(map.dirty_sectors().count(), 0);
+
+        let mut loaded = Map::load_from_file(&path).unwrap();
+        assert_eq!(loaded.sectors[1].floor_mesh.vertex_buffer[0].pos,
+                   [5.0, 6.0, 7.0]);
+        assert_eq!(loaded.lights.len(), 1);
+
+        // Grow a sector past the space reserved for it
+        loaded.sectors[0].add_lod(Lod::new(10.0, quad_mesh(),
+                                           quad_mesh(), quad_mesh()));
+        loaded.mark_sector_dirty(0);
+        assert_eq!(loaded.save_incremental(&path).unwrap(), SaveKind::Full);
+
+        let loaded = Map::load_from_file(&path).unwrap();
+        assert_eq!(loaded.sectors[0].lod_count(), 1);
+        assert_eq!(loaded.sectors[1].floor_mesh.vertex_buffer[0].pos,
+                   [5.0, 6.0, 7.0]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn sector_ids() {
+        let mut map = Map::new(vec![quad_sector(), quad_sector()]);
+        assert_eq!(map.sectors[1].id(), SectorId(1));
+
+        let id = map.add_sector(quad_sector());
+        assert_eq!(id, SectorId(2));
+
+        let removed = map.remove_sector(SectorId(0)).unwrap();
+        assert_eq!(removed.id(), SectorId(0));
+        assert!(map.sector(SectorId(0)).is_none());
+        assert_eq!(map.sector_index(id), Some(1));
+
+        map.sector_mut(id).unwrap().tag = 7;
+        assert_eq!(map.dirty_sectors().collect::<Vec<_>>(), vec![1]);
+        assert_eq!(map.sectors[1].tag, 7);
+
+        // IDs are not reused after a sector has been removed
+        map.remove_sector(id);
+        assert_eq!(map.add_sector(quad_sector()), SectorId(3));
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+
+        let mut result = Map::deserialize(&buffer).unwrap();
+        let ids = result.sectors.iter()
+            .map(|sector| sector.id())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![SectorId(1), SectorId(3)]);
+        assert_eq!(result.add_sector(quad_sector()), SectorId(4));
+    }
+
+    #[test]
+    fn map_editor_undo_redo() {
+        let mut editor = MapEditor::new(Map::new(vec![quad_sector()]));
+
+        let id = editor.add_sector(quad_sector()).unwrap();
+        editor.modify_sector(id, |sector| sector.tag = 5).unwrap();
+        editor.add_light(Light::point([0.0; 3], [1.0; 3], 1.0, 4.0)).unwrap();
+        assert!(editor.remove_sector(SectorId(0)).unwrap());
+        assert_eq!(editor.map().sectors.len(), 1);
+
+        // Persist the edit log and replay it on the original map
+        let mut log = Vec::new();
+        Edit::serialize_log(editor.edits(), &mut log).unwrap();
+        let edits = Edit::deserialize_log(&log).unwrap();
+        assert_eq!(edits, editor.edits());
+
+        let mut newer = log.clone();
+        let version = CURRENT_VERSION + (1 << 16);
+        newer[0..4].copy_from_slice(&version.to_le_bytes());
+        assert!(Edit::deserialize_log(&newer).is_err());
+
+        let mut replayed = Map::new(vec![quad_sector()]);
+        replayed.replay(&edits).unwrap();
+        assert_eq!(replayed.sectors.len(), 1);
+        assert_eq!(replayed.sectors[0].id(), id);
+        assert_eq!(replayed.sectors[0].tag, 5);
+        assert_eq!(replayed.lights.len(), 1);
+
+        // Undo everything
+        while editor.undo().unwrap() {}
+        assert!(!editor.can_undo());
+        assert_eq!(editor.map().sectors.len(), 1);
+        assert_eq!(editor.map().sectors[0].id(), SectorId(0));
+        assert!(editor.map().lights.is_empty());
+
+        // Redo the sector changes
+        assert!(editor.redo().unwrap());
+        assert!(editor.redo().unwrap());
+        assert_eq!(editor.map().sector(id).unwrap().tag, 5);
+
+        // A new edit clears the redo history
+        editor.remove_light(0).unwrap();
+        editor.add_light(Light::point([1.0; 3], [1.0; 3], 1.0, 4.0)).unwrap();
+        assert!(!editor.can_redo());
+        assert_eq!(editor.map().lights.len(), 1);
+    }
+
+    #[test]
+    fn mesh_gpu_bytes() {
+        let mesh = quad_mesh();
+
+        let vertices = mesh.as_vertex_bytes();
+        assert_eq!(vertices.len(), 4 * Vertex::STRIDE);
+
+        let vertex = &vertices[2 * Vertex::STRIDE..3 * Vertex::STRIDE];
+        let read = |offset: usize| {
+            f32::from_ne_bytes(vertex[offset..offset + 4].try_into().unwrap())
+        };
+        assert_eq!(read(Vertex::POSITION_OFFSET), 1.0);
+        assert_eq!(read(Vertex::POSITION_OFFSET + 4), 1.0);
+        assert_eq!(read(Vertex::UV_OFFSET), 0.0);
+        assert_eq!(read(Vertex::COLOR_OFFSET + 12), 1.0);
+
+        let indices = mesh.as_index_bytes();
+        assert_eq!(indices.len(), 6 * Mesh::INDEX_STRIDE);
+        assert_eq!(&indices[4..8], &1u32.to_ne_bytes());
+    }
+
+    #[test]
+    #[cfg(feature = "bytemuck")]
+    fn vertex_bytemuck_cast() {
+        let mesh = quad_mesh();
+
+        let bytes: &[u8] = bytemuck::cast_slice(&mesh.vertex_buffer);
+        assert_eq!(std::mem::size_of::<Vertex>(), Vertex::STRIDE);
+        assert_eq!(bytes, &mesh.as_vertex_bytes()[..]);
+    }
+
+    #[test]
+    fn mesh_vertex_layout() {
+        let layout = quad_mesh().vertex_layout();
+        assert_eq!(layout.stride, Vertex::STRIDE);
 
-        assert_eq!(result.sectors.len(), map.sectors.len());
+        // The attributes cover the whole vertex without overlapping
+        let mut end = 0;
+        for attribute in &layout.attributes {
+            assert_eq!(attribute.offset, end);
+            end += attribute.format.size();
+        }
+        assert_eq!(end, layout.stride);
+
+        let color = layout.attribute(VertexSemantic::Color).unwrap();
+        assert_eq!(color.format, VertexFormat::Float32x4);
+        assert_eq!(color.offset, Vertex::COLOR_OFFSET);
+    }
+
+    #[test]
+    #[cfg(feature = "wgpu")]
+    fn wgpu_vertex_layout() {
+        let layout = Vertex::wgpu_layout();
+        assert_eq!(layout.array_stride, Vertex::STRIDE as u64);
+
+        let attributes = quad_mesh().vertex_layout().wgpu_attributes();
+        assert_eq!(layout.attributes, &attributes[..]);
+    }
+
+    #[test]
+    #[cfg(feature = "bevy")]
+    fn bevy_mesh_conversion() {
+        use bevy::render::mesh::{ Indices, VertexAttributeValues };
+
+        let mesh = bevy::render::mesh::Mesh::from(&quad_mesh());
+        assert_eq!(mesh.count_vertices(), 4);
+
+        let positions =
+            mesh.attribute(bevy::render::mesh::Mesh::ATTRIBUTE_POSITION);
+        match positions {
+            Some(VertexAttributeValues::Float32x3(positions)) => {
+                assert_eq!(positions[2], [1.0, 1.0, 0.0]);
+            }
+
+            _ => panic!("Missing positions"),
+        }
+
+        match mesh.indices() {
+            Some(Indices::U32(indices)) => {
+                assert_eq!(indices, &vec![0, 1, 2, 2, 3, 0]);
+            }
 
-        for i in 0..result.sectors.len() {
-            compare_sector(&result.sectors[i], &map.sectors[i]);
+            _ => panic!("Missing indices"),
         }
     }
+
+    #[test]
+    #[cfg(all(feature = "glam", feature = "mint", feature = "nalgebra"))]
+    fn math_library_interop() {
+        use crate::ToMat4;
+
+        let mut vertex = quad_mesh().vertex_buffer[2];
+        let pos: glam::Vec3 = vertex.position();
+        assert_eq!(pos, glam::Vec3::new(1.0, 1.0, 0.0));
+
+        vertex.set_position(mint::Vector3 { x: 1.0, y: 2.0, z: 3.0 });
+        let pos: nalgebra::Vector3<f32> = vertex.position();
+        assert_eq!(pos, nalgebra::Vector3::new(1.0, 2.0, 3.0));
+
+        let translation = glam::Mat4::from_translation(glam::Vec3::X);
+        let mint: mint::ColumnMatrix4<f32> = translation.into();
+        let nalgebra =
+            nalgebra::Matrix4::new_translation(&nalgebra::Vector3::x());
+        assert_eq!(translation.to_mat4(), mint.to_mat4());
+        assert_eq!(translation.to_mat4(), nalgebra.to_mat4());
+
+        let mut map = Map::new(Vec::new());
+        map.merge(Map::new(vec![quad_sector()]), Some(&translation))
+            .unwrap();
+        assert_eq!(map.sectors[0].floor_mesh.vertex_buffer[2].pos,
+                   [2.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn mesh_planar_attributes() {
+        let mut mesh = quad_mesh();
+        mesh.vertex_buffer[1].uv = [0.5, 0.25];
+        mesh.vertex_buffer[3].color = [0.0, 0.5, 1.0, 1.0];
+
+        let attributes = mesh.planar_attributes();
+        assert_eq!(attributes.positions.len(), 4);
+        assert_eq!(attributes.positions[2], [1.0, 1.0, 0.0]);
+        assert_eq!(attributes.uvs[1], [0.5, 0.25]);
+        assert_eq!(attributes.colors[3], [0.0, 0.5, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn map_many_sectors_keep_order() {
+        let sectors = (0..64)
+            .map(|tag| {
+                let mut sector = quad_sector();
+                sector.tag = tag;
+                sector
+            })
+            .collect();
+        let map = Map::new(sectors);
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+
+        let result = Map::deserialize(&buffer).unwrap();
+        let tags = result.sectors.iter()
+            .map(|sector| sector.tag)
+            .collect::<Vec<_>>();
+        assert_eq!(tags, (0..64).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn map_deserialize_bad_sector_slot() {
+        let map = Map::new(vec![quad_sector(), quad_sector()]);
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+
+        // Make the size of the second sector point past the end of the map
+        let size = 8 * 3 + 1 + 8 * 4 + 8;
+        buffer[size..size + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+
+        let error = Map::deserialize(&buffer).err().unwrap();
+        assert!(matches!(error.kind(), crate::Error::BufferToSmallMap(_)));
+
+        // The error points at the entry inside the sector table
+        let context = error.context().unwrap();
+        assert_eq!(context.sector, Some(1));
+        assert_eq!(context.offset, 8 * 3 + 1 + 8 * 4);
+    }
+
+    #[test]
+    fn map_deserialize_error_context() {
+        let map = Map::new(vec![quad_sector(), quad_sector()]);
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+
+        // Make the vertex count of the floor mesh of the second sector too
+        // large, the mesh starts after the compression flag, the bounds and
+        // its size prefix and the vertex count after the vertex descriptor
+        let entry = 8 * 3 + 1 + 8 * 4;
+        let sector = u64::from_le_bytes(
+            buffer[entry..entry + 8].try_into().unwrap()) as usize;
+        let mesh = sector + 1 + 24 + 8;
+        let count = mesh + 7;
+        buffer[count..count + 8].copy_from_slice(&1000u64.to_le_bytes());
+        update_checksums(&mut buffer);
+
+        let error = Map::deserialize(&buffer).err().unwrap();
+        assert!(matches!(error.kind(), crate::Error::BufferToSmallMesh(_)));
+
+        // The 196 bytes after the counts fit five vertices, the first
+        // vertex past the end of the mesh is the sixth one
+        let context = error.context().unwrap();
+        assert_eq!(context.sector, Some(1));
+        assert_eq!(context.lod, None);
+        assert_eq!(context.mesh, Some(MeshRole::Floor));
+        assert_eq!(context.element, Some(crate::ErrorElement::Vertex(5)));
+        assert_eq!(context.offset, count + 16 + 5 * 36);
+    }
+
+    /// A map using every optional part of the format
+    fn full_map() -> Map {
+        let mut sector = quad_sector();
+        sector.tag = 3;
+        sector.floor_mesh.lightmap_uvs = vec![[0.5, 0.5]; 4];
+        sector.add_lod(Lod::new(50.0, quad_mesh(), quad_mesh(), quad_mesh()));
+        sector.walls.push(Wall::new([0.0; 3], [1.0, 0.0, 0.0], "brick", 0..6));
+
+        let mut map = Map::new(vec![sector, quad_sector()]);
+        map.lightmap = Some(Lightmap::new(4, 2));
+        map.lights.push(Light::point([0.0; 3], [1.0; 3], 1.0, 4.0));
+        map.lights.push(Light::spot([0.0; 3], [0.0, -1.0, 0.0], 0.2, 0.4,
+                                    [1.0; 3], 1.0, 8.0));
+        map.lights.push(Light::directional([0.0, -1.0, 0.0], [1.0; 3], 0.5));
+        map.sky = Some(Sky::Skybox { texture: "sky/day".to_string() });
+        map.sound_emitters.push(SoundEmitter::new([1.0; 3], "amb/wind", 4.0));
+        map.triggers.push(Trigger::new(TriggerShape::Aabb {
+            min: [0.0; 3],
+            max: [1.0; 3],
+        }, 1, "door", 3));
+        map.triggers.push(Trigger::new(TriggerShape::Convex {
+            planes: vec![[0.0, 1.0, 0.0, 2.0]; 4],
+        }, 2, "", 0));
+        map.embed_asset("palette", vec![0, 1, 2]);
+
+        let mut material = Material::new("stone");
+        material.base_color_texture = Some("stone_albedo".to_string());
+        material.blend_mode = BlendMode::Masked { cutoff: 0.5 };
+        map.sectors[0].floor_mesh.material = Some(map.add_material(material));
+        map.update_dependencies();
+
+        let mut door = SectorAnimation::new("door", 3, SectorPlane::Ceiling);
+        door.keyframes.push(Keyframe::new("closed", 0.0, 0.0));
+        door.keyframes.push(Keyframe::new("open", 1.5, 2.0));
+        map.animations.push(door);
+
+        map
+    }
+
+    #[test]
+    fn serialized_size() {
+        let map = full_map();
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+        assert_eq!(map.serialized_size(), buffer.len());
+
+        let mut mime = Mime::new();
+        mime.add_map(map);
+        mime.add_map(Map::new(Vec::new()));
+
+        let mut buffer = Vec::new();
+        mime.serialize(&mut buffer).unwrap();
+        assert_eq!(mime.serialized_size(), buffer.len());
+    }
+
+    #[test]
+    fn map_serialize_into() {
+        let map = full_map();
+
+        let mut expected = Vec::new();
+        map.serialize(&mut expected).unwrap();
+
+        let mut out = vec![0xff; map.serialized_size() + 4];
+        let size = map.serialize_into(&mut out).unwrap();
+        assert_eq!(size, expected.len());
+        assert_eq!(&out[..size], &expected[..]);
+
+        let mut out = vec![0; map.serialized_size() - 1];
+        assert!(matches!(map.serialize_into(&mut out),
+                         Err(crate::Error::BufferToSmallOutput(_))));
+
+        let mut mime = Mime::new();
+        mime.add_map(map);
+
+        let mut out = vec![0; mime.serialized_size()];
+        assert_eq!(mime.serialize_into(&mut out).unwrap(), out.len());
+
+        let result = Mime::deserialize(&out).unwrap();
+        assert_eq!(result.maps()[0].lights.len(), 3);
+    }
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn map_async_load_save() {
+        let path = std::env::temp_dir()
+            .join(format!("mime_async_{}.mime", std::process::id()));
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        let loaded = runtime.block_on(async {
+            let mut map = full_map();
+            map.save_to_file_async(&path).await.unwrap();
+
+            Map::load_from_file_async(&path).await.unwrap()
+        });
+        assert_eq!(loaded.sectors.len(), 2);
+        assert_eq!(loaded.lights.len(), 3);
+
+        // The async save records the layout for incremental saves
+        let mut loaded = Map::load_from_file(&path).unwrap();
+        loaded.mark_sector_dirty(0);
+        assert_eq!(loaded.save_incremental(&path).unwrap(),
+                   SaveKind::Incremental { sectors_written: 1 });
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn map_load_save_bytes() {
+        let map = full_map();
+        let bytes = map.save_to_bytes().unwrap();
+
+        let loaded = Map::load_from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.sectors.len(), 2);
+        assert_eq!(loaded.lights.len(), 3);
+        assert_eq!(loaded.save_to_bytes().unwrap(), bytes);
+
+        let error = Map::load_from_bytes(&bytes[..bytes.len() - 1])
+            .err().unwrap();
+        assert!(matches!(error.kind(), crate::Error::BufferToSmallMap(_)));
+    }
+
+    #[test]
+    #[cfg(feature = "ffi")]
+    fn ffi_map_access() {
+        use crate::ffi::*;
+
+        let map = full_map();
+        let bytes = map.save_to_bytes().unwrap();
+
+        unsafe {
+            let handle = mime_map_load_bytes(bytes.as_ptr(), bytes.len());
+            assert!(!handle.is_null());
+            assert_eq!(mime_map_sector_count(handle), 2);
+
+            let floor = &map.sectors[0].floor_mesh;
+            let count = mime_map_vertex_count(handle, 0, MIME_MESH_FLOOR);
+            let vertices = mime_map_vertices(handle, 0, MIME_MESH_FLOOR);
+            assert_eq!(std::slice::from_raw_parts(vertices, count),
+                       &floor.vertex_buffer[..]);
+
+            let count = mime_map_index_count(handle, 0, MIME_MESH_FLOOR);
+            let indices = mime_map_indices(handle, 0, MIME_MESH_FLOOR);
+            assert_eq!(std::slice::from_raw_parts(indices, count),
+                       &floor.index_buffer[..]);
+            assert_eq!(mime_map_texture_id(handle, 0, MIME_MESH_FLOOR),
+                       floor.texture_id);
+
+            // Sectors and roles that don't exist
+            assert!(mime_map_vertices(handle, 2, MIME_MESH_FLOOR).is_null());
+            assert_eq!(mime_map_index_count(handle, 0, 3), 0);
+
+            mime_map_free(handle);
+
+            assert!(mime_map_load_bytes(bytes.as_ptr(), 4).is_null());
+            assert_eq!(mime_map_sector_count(std::ptr::null()), 0);
+        }
+    }
+
+    #[test]
+    fn error_sizes_and_version() {
+        use crate::{ Error, SizeMismatch };
+
+        let mut mime = Mime::new();
+        mime.add_map(Map::new(vec![quad_sector()]));
+
+        let mut buffer = Vec::new();
+        mime.serialize(&mut buffer).unwrap();
+
+        let error = Mime::deserialize(&buffer[..10]).err().unwrap();
+        assert!(matches!(error,
+                         Error::BufferToSmallHeader(SizeMismatch {
+                             expected: 16,
+                             actual: 10,
+                         })));
+
+        buffer[4..8].copy_from_slice(&99u32.to_le_bytes());
+        let error = Mime::deserialize(&buffer).err().unwrap();
+        assert!(matches!(error, Error::IncorrectVersion(99)));
+        assert_eq!(error.to_string(), "incorrect version 99");
+
+        let error = Vertex::deserialize(&[0; 10]).err().unwrap();
+        assert!(matches!(error,
+                         Error::BufferToSmallVertex(SizeMismatch {
+                             expected: 36,
+                             actual: 10,
+                         })));
+        assert_eq!(error.to_string(),
+                   "buffer too small for vertex, expected 36 bytes but got 10");
+    }
+
+    #[test]
+    fn lazy_map_sectors() {
+        let mut map = full_map();
+        map.sectors[1].tag = 7;
+        let bytes = map.save_to_bytes().unwrap();
+
+        let mut lazy = crate::LazyMap::from_bytes(bytes.clone()).unwrap();
+        assert_eq!(lazy.sector_count(), 2);
+        assert!(!lazy.is_loaded(1));
+
+        assert_eq!(lazy.sector(1).unwrap().tag, 7);
+        assert!(lazy.is_loaded(1));
+        assert!(!lazy.is_loaded(0));
+
+        lazy.unload(1);
+        assert!(!lazy.is_loaded(1));
+        assert!(matches!(lazy.sector(2), Err(crate::Error::SectorNotFound)));
+
+        let loaded = lazy.into_map().unwrap();
+        assert_eq!(loaded.lights.len(), 3);
+        assert_eq!(loaded.save_to_bytes().unwrap(), bytes);
+
+        // Reading the sectors from the file gives the same sectors
+        let path = std::env::temp_dir()
+            .join(format!("mime_lazy_{}.mime", std::process::id()));
+        std::fs::write(&path, &bytes).unwrap();
+
+        let mut lazy = crate::LazyMap::open(&path).unwrap();
+        assert_eq!(lazy.sector(1).unwrap().tag, 7);
+        assert_eq!(lazy.sector(0).unwrap(), &map.sectors[0]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn map_sector_stream() {
+        let mut map = full_map();
+        map.sectors[0].tag = 3;
+        map.sectors[1].tag = 7;
+        let bytes = map.save_to_bytes().unwrap();
+
+        let tags = Map::sector_stream(&bytes[..])
+            .map(|sector| sector.unwrap().tag)
+            .collect::<Vec<_>>();
+        assert_eq!(tags, vec![3, 7]);
+
+        // A truncated file yields the first sector and then the error
+        let mut stream = Map::sector_stream(&bytes[..bytes.len() / 2 + 100]);
+        assert_eq!(stream.next().unwrap().unwrap(), map.sectors[0]);
+
+        let error = stream.next().unwrap().err().unwrap();
+        assert!(matches!(error.kind(), crate::Error::BufferToSmallMap(_)));
+        assert_eq!(error.context().unwrap().sector, Some(1));
+        assert!(stream.next().is_none());
+
+        let error = Map::sector_stream(&b"MIME"[..]).next().unwrap()
+            .err().unwrap();
+        assert!(matches!(error.kind(), crate::Error::BufferToSmallHeader(_)));
+    }
+
+    #[test]
+    fn remove_degenerate_triangles() {
+        let mut mesh = quad_mesh();
+        mesh.vertex_buffer.push(
+            Vertex::new([0.5, 0.5, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]));
+
+        // Fine, duplicate index, fine, zero area
+        mesh.index_buffer = vec![0, 1, 2, 1, 1, 3, 2, 3, 0, 0, 4, 2];
+        assert_eq!(mesh.degenerate_triangles(), vec![1, 3]);
+
+        let mut sector = Sector::new(quad_mesh(), quad_mesh(), mesh);
+        sector.floor_mesh.index_buffer.extend([3, 3, 3]);
+        sector.walls = vec![
+            Wall::new([0.0; 3], [1.0, 0.0, 0.0], "a", 0..6),
+            Wall::new([1.0, 0.0, 0.0], [1.0; 3], "b", 6..12),
+        ];
+
+        let mut map = Map::new(vec![quad_sector(), sector]);
+        assert_eq!(map.remove_degenerate_triangles(), 3);
+        assert_eq!(map.dirty_sectors().collect::<Vec<_>>(), vec![1]);
+
+        let sector = &map.sectors[1];
+        assert_eq!(sector.wall_mesh.index_buffer, vec![0, 1, 2, 2, 3, 0]);
+        assert_eq!(sector.floor_mesh.index_buffer, quad_mesh().index_buffer);
+        assert_eq!((sector.walls[0].index_start, sector.walls[0].index_count),
+                   (0, 3));
+        assert_eq!((sector.walls[1].index_start, sector.walls[1].index_count),
+                   (3, 3));
+
+        assert_eq!(map.remove_degenerate_triangles(), 0);
+    }
+
+    #[test]
+    fn fix_winding() {
+        // The quad faces towards -z, flip the second triangle
+        let mut mesh = quad_mesh();
+        mesh.index_buffer = vec![0, 1, 2, 2, 0, 3];
+        assert_eq!(mesh.face_normal(0), Some([0.0, 0.0, -1.0]));
+        assert_eq!(mesh.face_normal(1), Some([0.0, 0.0, 1.0]));
+        assert_eq!(mesh.face_normal(2), None);
+        assert_eq!(mesh.dominant_normal(), None);
+
+        let down = [0.0, 0.0, -1.0];
+        assert_eq!(mesh.inconsistent_winding(down), vec![1]);
+        assert_eq!(mesh.fix_winding(down), 1);
+        assert!(mesh.is_winding_consistent(down));
+        assert_eq!(mesh.index_buffer, vec![0, 1, 2, 2, 3, 0]);
+        assert_eq!(mesh.dominant_normal(), Some(down));
+
+        // Floors face up and ceilings down
+        let up = [0.0, 0.0, 1.0];
+        let mut map = Map::new(vec![quad_sector()]);
+        assert_eq!(map.fix_winding(up), 2);
+        assert_eq!(map.dirty_sectors().collect::<Vec<_>>(), vec![0]);
+
+        let sector = &map.sectors[0];
+        assert!(sector.floor_mesh.is_winding_consistent(up));
+        assert_eq!(sector.ceiling_mesh.index_buffer,
+                   quad_mesh().index_buffer);
+        assert_eq!(sector.wall_mesh.index_buffer, quad_mesh().index_buffer);
+
+        assert_eq!(map.fix_winding(up), 0);
+    }
+
+    #[test]
+    fn scrub_non_finite() {
+        let mut sector = quad_sector();
+        sector.ceiling_mesh.vertex_buffer[2].pos[1] = f32::NAN;
+        sector.ceiling_mesh.vertex_buffer[3].color[0] = f32::NEG_INFINITY;
+        sector.ceiling_mesh.lightmap_uvs = vec![[0.0, f32::INFINITY]; 4];
+
+        let mut map = Map::new(vec![quad_sector(), sector]);
+        let error = map.scrub_non_finite(NonFinitePolicy::Error).unwrap_err();
+        assert!(matches!(error.kind(), crate::Error::NonFiniteValue));
+
+        let context = error.context().unwrap();
+        assert_eq!(context.sector, Some(1));
+        assert_eq!(context.mesh, Some(MeshRole::Ceiling));
+        assert_eq!(context.element, Some(ErrorElement::Vertex(2)));
+        assert_eq!(map.dirty_sectors().count(), 0);
+
+        assert_eq!(map.scrub_non_finite(NonFinitePolicy::Clamp).unwrap(), 6);
+        assert_eq!(map.dirty_sectors().collect::<Vec<_>>(), vec![1]);
+
+        let mesh = &map.sectors[1].ceiling_mesh;
+        assert_eq!(mesh.vertex_buffer[2].pos, [1.0, 0.0, 0.0]);
+        assert_eq!(mesh.vertex_buffer[3].color[0], f32::MIN);
+        assert_eq!(mesh.lightmap_uvs[0], [0.0, f32::MAX]);
+        assert!(mesh.check_finite().is_ok());
+
+        assert_eq!(map.scrub_non_finite(NonFinitePolicy::Error).unwrap(), 0);
+    }
+
+    #[test]
+    fn map_color_space() {
+        let mut map = Map::new(vec![quad_sector()]);
+        assert_eq!(map.color_space, ColorSpace::Srgb);
+
+        map.sectors[0].floor_mesh.vertex_buffer[0].color = [0.5, 0.0, 1.0, 0.5];
+        map.convert_color_space(ColorSpace::Linear);
+        assert_eq!(map.color_space, ColorSpace::Linear);
+        assert_eq!(map.dirty_sectors().collect::<Vec<_>>(), vec![0]);
+
+        let color = map.sectors[0].floor_mesh.vertex_buffer[0].color;
+        assert!((color[0] - 0.214).abs() < 0.001);
+        assert_eq!(&color[1..], &[0.0, 1.0, 0.5]);
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), map.serialized_size());
+
+        let result = Map::deserialize(&buffer).unwrap();
+        assert_eq!(result.color_space, ColorSpace::Linear);
+
+        // Merged colors are converted to the color space of the map
+        let mut srgb = Map::new(Vec::new());
+        srgb.merge(result, None).unwrap();
+        let color = srgb.sectors[0].floor_mesh.vertex_buffer[0].color;
+        assert!((color[0] - 0.5).abs() < 0.001);
+
+        assert!(matches!(ColorSpace::from_u8(2),
+                         Err(crate::Error::UnknownColorSpace(2))));
+    }
+
+    #[test]
+    fn map_materials() {
+        let map = full_map();
+        assert_eq!(map.find_material("stone"), Some(0));
+        assert_eq!(map.find_material("wood"), None);
+        assert!(map.dependencies.iter().any(|d| d.name == "stone_albedo"));
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+        let result = Map::deserialize(&buffer).unwrap();
+
+        assert_eq!(result.materials, map.materials);
+        assert_eq!(result.sectors[0].floor_mesh.material, Some(0));
+        assert_eq!(result.sectors[0].ceiling_mesh.material, None);
+
+        let material = result.mesh_material(&result.sectors[0].floor_mesh);
+        assert_eq!(material.unwrap().name, "stone");
+
+        // The materials of merged maps are appended after the existing ones
+        let mut merged = Map::new(Vec::new());
+        merged.add_material(Material::new("wood"));
+        merged.merge(result, None).unwrap();
+        assert_eq!(merged.find_material("stone"), Some(1));
+        assert_eq!(merged.sectors[0].floor_mesh.material, Some(1));
+    }
+
+    #[test]
+    fn mesh_uv_sets() {
+        let mut mesh = quad_mesh();
+        assert_eq!(mesh.uv_set_count(), 1);
+
+        mesh.lightmap_uvs = vec![[0.5, 0.5]; 4];
+        mesh.set_uv_set("detail", vec![[2.0, 4.0]; 4]);
+        mesh.set_uv_set("decal", vec![[1.0, 0.0]; 4]);
+        mesh.set_uv_set("detail", vec![[8.0, 8.0]; 4]);
+        assert_eq!(mesh.uv_set_count(), 4);
+        assert_eq!(mesh.uv_set("detail"), Some(&[[8.0, 8.0]; 4][..]));
+        assert_eq!(mesh.uv_set("missing"), None);
+
+        let mut buffer = Vec::new();
+        mesh.serialize(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), mesh.serialized_size());
+
+        let result = Mesh::deserialize(&buffer).unwrap();
+        assert_eq!(result.uv_sets, mesh.uv_sets);
+
+        // Every set needs a coordinate for every vertex
+        mesh.uv_sets[1].uvs.pop();
+        let mut buffer = Vec::new();
+        mesh.serialize(&mut buffer).unwrap();
+
+        let error = Mesh::deserialize(&buffer).err().unwrap();
+        assert!(matches!(error.kind(), crate::Error::UvSetCountMismatch));
+
+        // Truncated coordinates report the set and the vertex
+        let mut mesh = quad_mesh();
+        mesh.set_uv_set("detail", vec![[0.0; 2]; 4]);
+        let mut buffer = Vec::new();
+        mesh.serialize(&mut buffer).unwrap();
+
+        // Cut the submesh count, the custom attribute count and the last v
+        // coordinate
+        let error = Mesh::deserialize(&buffer[..buffer.len() - 8 - 8 - 4])
+            .err().unwrap();
+        let context = error.context().unwrap();
+        assert_eq!(context.element, Some(ErrorElement::UvSet(0, 3)));
+    }
+
+    #[test]
+    fn mesh_custom_attributes() {
+        let mut mesh = quad_mesh();
+        mesh.set_custom_attribute(
+            CustomAttribute::from_f32s("wind", &[0.0, 0.5, 1.0, 0.25]));
+        mesh.set_custom_attribute(CustomAttribute::new(
+            "ao", VertexFormat::Unorm8x4, vec![255; 16]));
+
+        let wind = mesh.custom_attribute("wind").unwrap();
+        assert_eq!(wind.len(), 4);
+        assert_eq!(wind.value(1), Some(&0.5f32.to_le_bytes()[..]));
+        assert_eq!(wind.to_f32s(), Some(vec![0.0, 0.5, 1.0, 0.25]));
+        assert_eq!(mesh.custom_attribute("ao").unwrap().to_f32s(), None);
+
+        let mut buffer = Vec::new();
+        mesh.serialize(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), mesh.serialized_size());
+
+        let result = Mesh::deserialize(&buffer).unwrap();
+        assert_eq!(result.custom_attributes, mesh.custom_attributes);
+
+        // Attributes with a format from a newer version are skipped, the
+        // format byte follows the name of the last attribute and the
+        // submesh count follows the attributes
+        let format = buffer.len() - 8 - 16 - 1;
+        assert_eq!(buffer[format], VertexFormat::Unorm8x4.to_u8());
+        buffer[format] = 200;
+
+        let result = Mesh::deserialize(&buffer).unwrap();
+        assert_eq!(result.custom_attributes.len(), 1);
+        assert_eq!(result.custom_attributes[0].name, "wind");
+
+        // Every vertex needs a value
+        mesh.custom_attributes[0].data.pop();
+        let mut buffer = Vec::new();
+        mesh.serialize(&mut buffer).unwrap();
+
+        let error = Mesh::deserialize(&buffer).err().unwrap();
+        assert!(matches!(error.kind(),
+                         crate::Error::CustomAttributeSizeMismatch));
+    }
+
+    #[test]
+    fn mesh_vertex_descriptor() {
+        let mut mesh = quad_mesh();
+        mesh.vertex_buffer[0].color = [0.5, 2.0, -1.0, 1.0];
+        mesh.vertex_descriptor = VertexDescriptor::compact();
+        assert_eq!(mesh.vertex_descriptor.stride(), 24);
+
+        let mut buffer = Vec::new();
+        mesh.serialize(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), mesh.serialized_size());
+
+        // The color is quantized to bytes
+        let result = Mesh::deserialize(&buffer).unwrap();
+        assert_eq!(result.vertex_descriptor, VertexDescriptor::compact());
+        assert_eq!(result.vertex_buffer[0].pos, mesh.vertex_buffer[0].pos);
+        assert_eq!(result.vertex_buffer[0].color,
+                   [128.0 / 255.0, 1.0, 0.0, 1.0]);
+
+        // Attributes left out of the descriptor get their default value
+        let position = (VertexSemantic::Position, VertexFormat::Float32x3);
+        mesh.vertex_descriptor = VertexDescriptor {
+            attributes: vec![position],
+        };
+        let mut buffer = Vec::new();
+        mesh.serialize(&mut buffer).unwrap();
+
+        let result = Mesh::deserialize(&buffer).unwrap();
+        assert_eq!(result.vertex_buffer[2],
+                   Vertex::new([1.0, 1.0, 0.0], [0.0, 0.0], [1.0; 4]));
+
+        // Attributes from newer versions are skipped
+        buffer[0] = 2;
+        buffer.splice(3..3, [99, VertexFormat::Float32.to_u8()]);
+        for vertex in (0..4).rev() {
+            let end = 1 + 4 + 16 + (vertex + 1) * 12;
+            buffer.splice(end..end, [0; 4]);
+        }
+
+        let result = Mesh::deserialize(&buffer).unwrap();
+        assert_eq!(result.vertex_buffer[2].pos, [1.0, 1.0, 0.0]);
+        assert_eq!(result.index_buffer, mesh.index_buffer);
+
+        // The position is required and every attribute has one format
+        mesh.vertex_descriptor.attributes.clear();
+        assert!(!mesh.vertex_descriptor.is_valid());
+        assert!(matches!(mesh.serialize(&mut Vec::new()),
+                         Err(crate::Error::InvalidVertexDescriptor)));
+
+        mesh.vertex_descriptor.attributes =
+            vec![(VertexSemantic::Position, VertexFormat::Unorm8x4)];
+        assert!(!mesh.vertex_descriptor.is_valid());
+    }
+
+    #[test]
+    fn sector_animations() {
+        let map = full_map();
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+        let result = Map::deserialize(&buffer).unwrap();
+        assert_eq!(result.animations, map.animations);
+
+        let door = result.animation("door").unwrap();
+        assert_eq!(door.duration(), 1.5);
+        assert_eq!(door.state("open").unwrap().offset, 2.0);
+        assert_eq!(door.offset_at(-1.0), 0.0);
+        assert_eq!(door.offset_at(0.75), 1.0);
+        assert_eq!(door.offset_at(10.0), 2.0);
+        assert_eq!(result.sector_animations(0).count(), 1);
+        assert_eq!(result.sector_animations(1).count(), 0);
+
+        // A looping lift wraps the time around the duration
+        let mut lift = SectorAnimation::new("lift", 3, SectorPlane::Floor);
+        lift.looping = true;
+        lift.keyframes.push(Keyframe::new("bottom", 0.0, 0.0));
+        lift.keyframes.push(Keyframe::new("top", 1.0, 4.0));
+        lift.keyframes.push(Keyframe::new("", 2.0, 0.0));
+        assert_eq!(lift.offset_at(2.5), 2.0);
+        assert_eq!(lift.offset_at(3.0), 4.0);
+
+        // The animations follow the tags of their sectors when merged
+        let mut merged = full_map();
+        merged.merge(result, None).unwrap();
+        assert_eq!(merged.animations[1].tag, merged.sectors[2].tag);
+        assert_eq!(merged.sector_animations(2).count(), 1);
+    }
+
+    #[test]
+    fn sector_planes() {
+        // A ramp rising half a unit for every unit along x
+        let vertex_buffer = vec![
+            Vertex::new([0.0, 0.0, 0.0], [0.0, 0.0], [1.0; 4]),
+            Vertex::new([0.0, 0.0, 2.0], [0.0, 0.0], [1.0; 4]),
+            Vertex::new([2.0, 1.0, 2.0], [0.0, 0.0], [1.0; 4]),
+            Vertex::new([2.0, 1.0, 0.0], [0.0, 0.0], [1.0; 4]),
+        ];
+        let ramp = Mesh::new(vertex_buffer, vec![0, 1, 2, 2, 3, 0], 0);
+
+        let mut sector = Sector::new(ramp.clone(), ramp, quad_mesh());
+        let mut map = Map::new(vec![sector.clone(), quad_sector()]);
+        map.compute_planes();
+
+        sector = map.sectors[0].clone();
+        assert_eq!(sector.floor_height(1.0, 1.0), Some(0.5));
+        assert_eq!(sector.ceiling_height(2.0, 0.0), Some(1.0));
+        assert!(sector.floor_plane.unwrap()[1] > 0.0);
+        assert!(sector.ceiling_plane.unwrap()[1] < 0.0);
+
+        // A vertical mesh doesn't have a plane to query
+        assert_eq!(map.sectors[1].floor_plane, None);
+        assert_eq!(map.sectors[1].floor_height(0.0, 0.0), None);
+
+        let mut buffer = Vec::new();
+        sector.serialize(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), sector.serialized_size());
+
+        let result = Sector::deserialize(&buffer).unwrap();
+        assert_eq!(result.floor_plane, sector.floor_plane);
+        assert_eq!(result.ceiling_plane, sector.ceiling_plane);
+
+        // The planes follow the sector when it is moved
+        let mut merged = Map::new(Vec::new());
+        let translation = [[1.0, 0.0, 0.0, 0.0],
+                           [0.0, 1.0, 0.0, 0.0],
+                           [0.0, 0.0, 1.0, 0.0],
+                           [0.0, 3.0, 0.0, 1.0]];
+        merged.merge(Map::new(vec![sector]), Some(&translation))
+            .unwrap();
+        let height = merged.sectors[0].floor_height(1.0, 1.0).unwrap();
+        assert!((height - 3.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn sector_groups() {
+        let mut map = Map::new(vec![quad_sector(), quad_sector()]);
+        let roof = map.add_sector(quad_sector());
+        let basement = map.sectors[0].id();
+
+        map.group_mut("basement").sectors.push(basement);
+        map.group_mut("roof").sectors.push(roof);
+        map.group_mut("roof").visible = false;
+        assert_eq!(map.groups.len(), 2);
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+        let mut result = Map::deserialize(&buffer).unwrap();
+        assert_eq!(result.groups, map.groups);
+
+        let sectors = result.group_sectors("roof").collect::<Vec<_>>();
+        assert_eq!(sectors.len(), 1);
+        assert_eq!(sectors[0].0, 2);
+        assert!(!result.is_sector_visible(roof));
+        assert!(result.is_sector_visible(basement));
+        assert_eq!(result.group_sectors("missing").count(), 0);
+
+        // Removed sectors are skipped
+        result.remove_sector(roof);
+        assert_eq!(result.group_sectors("roof").count(), 0);
+
+        // The sectors of merged groups get the new IDs
+        let mut merged = Map::new(vec![quad_sector()]);
+        let mut group = SectorGroup::new("basement");
+        group.sectors.push(merged.sectors[0].id());
+        merged.groups.push(group);
+        merged.merge(map, None).unwrap();
+
+        let basement = merged.group_sectors("basement")
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        assert_eq!(basement, vec![0, 1]);
+        assert!(!merged.is_sector_visible(merged.sectors[3].id()));
+    }
+
+    #[test]
+    fn potentially_visible_sets() {
+        let mut map = Map::new((0..4).map(|_| quad_sector()).collect());
+
+        // A bending corridor, the last opening can't be seen through the
+        // first one
+        let openings = [
+            ([1.0, 0.0, 0.0], [1.0, 0.0, 1.0]),
+            ([1.5, 0.0, 2.0], [2.0, 0.0, 2.0]),
+            ([3.0, 0.0, 10.0], [3.0, 0.0, 11.0]),
+        ];
+        for (index, (start, end)) in openings.into_iter().enumerate() {
+            let mut wall = Wall::new(start, end, "", 0..0);
+            wall.flags = crate::wall::WALL_FLAG_TWO_SIDED;
+            map.sectors[index].walls.push(wall.clone());
+
+            std::mem::swap(&mut wall.start, &mut wall.end);
+            map.sectors[index + 1].walls.push(wall);
+        }
+
+        assert_eq!(map.portals().len(), 6);
+        assert!(map.is_sector_potentially_visible(0, 3));
+
+        map.build_pvs();
+        let pvs = map.pvs.as_ref().unwrap();
+        assert_eq!(pvs.visible_sectors(0), vec![0, 1, 2]);
+        assert_eq!(pvs.visible_sectors(3), vec![1, 2, 3]);
+        assert!(!map.is_sector_potentially_visible(0, 3));
+        assert!(map.is_sector_potentially_visible(1, 3));
+        assert!(map.is_sector_potentially_visible(0, 10));
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), map.serialized_size());
+        let result = Map::deserialize(&buffer).unwrap();
+        assert_eq!(result.pvs, map.pvs);
+
+        // Large sets with few visible sectors compress the runs of zeros
+        let mut visible = vec![Vec::new(); 1000];
+        visible[0] = vec![0, 999];
+        let pvs = Pvs::new(&visible);
+        assert!(pvs.serialized_size() < 1000 * 1000 / 8);
+        assert_eq!(pvs.visible_sectors(0), vec![0, 999]);
+        assert!(!pvs.is_visible(0, 500));
+    }
+
+    #[test]
+    fn map_thumbnail() {
+        let mut map = full_map();
+        assert_eq!(map.thumbnail, None);
+
+        let thumbnail = Thumbnail::rgba(2, 1, vec![255, 0, 0, 255, 0, 0, 255,
+                                                   255]);
+        map.thumbnail = Some(thumbnail.clone());
+
+        let buffer = map.save_to_bytes().unwrap();
+        let result = Map::load_from_bytes(&buffer).unwrap();
+        assert_eq!(result.thumbnail, Some(thumbnail.clone()));
+
+        // The thumbnail can be read without loading any sectors
+        let mut lazy = LazyMap::from_bytes(buffer).unwrap();
+        assert_eq!(lazy.thumbnail().unwrap(), Some(thumbnail));
+        assert!(!lazy.is_loaded(0));
+
+        let buffer = Map::new(vec![quad_sector()]).save_to_bytes().unwrap();
+        let mut lazy = LazyMap::from_bytes(buffer).unwrap();
+        assert_eq!(lazy.thumbnail().unwrap(), None);
+    }
+
+    #[test]
+    fn minimap_svg() {
+        let vertex_buffer = vec![
+            Vertex::new([0.0, 0.0, 0.0], [0.0, 0.0], [1.0; 4]),
+            Vertex::new([0.0, 0.0, 2.0], [0.0, 0.0], [1.0; 4]),
+            Vertex::new([4.0, 0.0, 2.0], [0.0, 0.0], [1.0; 4]),
+            Vertex::new([4.0, 0.0, 0.0], [0.0, 0.0], [1.0; 4]),
+        ];
+        let floor = Mesh::new(vertex_buffer, vec![0, 1, 2, 2, 3, 0], 0);
+
+        let mut sector = Sector::new(floor, quad_mesh(), quad_mesh());
+        sector.walls.push(Wall::new([0.0; 3], [4.0, 0.0, 0.0], "", 0..0));
+        let mut portal = Wall::new([4.0, 0.0, 0.0], [4.0, 0.0, 2.0], "", 0..0);
+        portal.flags = crate::wall::WALL_FLAG_TWO_SIDED;
+        sector.walls.push(portal);
+        let map = Map::new(vec![sector]);
+
+        let minimap = map.minimap();
+        assert_eq!(minimap.floors[0].len(), 2);
+        assert_eq!(minimap.lines.len(), 2);
+        assert_eq!(minimap.bounds(), Some(([0.0, 0.0], [4.0, 2.0])));
+
+        let options = MinimapOptions {
+            width: 100.0,
+            padding: 10.0,
+            ..Default::default()
+        };
+        let svg = map.minimap_svg(&options);
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.contains("height=\"60.00\""));
+        assert!(svg.contains("x1=\"10.00\" y1=\"10.00\" \
+                              x2=\"90.00\" y2=\"10.00\""));
+        assert_eq!(svg.matches("<line ").count(), 2);
+
+        // Two sided walls can be left out
+        let options = MinimapOptions {
+            two_sided_color: None,
+            ..options
+        };
+        assert_eq!(map.minimap_svg(&options).matches("<line ").count(), 1);
+    }
+
+    #[test]
+    fn sector_bounds() {
+        let mut sector = quad_sector();
+        sector.wall_mesh.vertex_buffer[0].pos = [-1.0, -2.0, 3.0];
+        let expected = Bounds { min: [-1.0, -2.0, 0.0], max: [1.0, 1.0, 3.0] };
+        assert_eq!(sector.bounds(), expected);
+
+        let mut buffer = Vec::new();
+        sector.serialize(&mut buffer).unwrap();
+        assert_eq!(Sector::read_bounds(&buffer[..24]).unwrap(), expected);
+
+        // The bounds are read without loading the sector
+        let map = Map::new(vec![quad_sector(), sector]);
+        let bytes = map.save_to_bytes().unwrap();
+        let mut lazy = LazyMap::from_bytes(bytes).unwrap();
+        assert_eq!(lazy.sector_bounds(1).unwrap(), expected);
+        assert!(!lazy.is_loaded(1));
+        assert!(lazy.sector_bounds(2).is_err());
+
+        let query = Bounds { min: [-1.0, -1.0, 2.0], max: [0.0, 0.0, 4.0] };
+        let found = map.sectors_in_bounds(query)
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![1]);
+
+        // Sectors without vertices get an empty box at the origin
+        let empty = Mesh::new(Vec::new(), Vec::new(), 0);
+        let sector = Sector::new(empty.clone(), empty.clone(), empty);
+        assert_eq!(sector.bounds(), Bounds::default());
+    }
+
+    #[test]
+    fn canonical_serialization() {
+        let mut a = full_map();
+        let mut b = full_map();
+        b.sectors[0].floor_mesh.vertex_buffer[0].pos[0] = -0.0;
+        b.lights[0].position[1] = -0.0;
+        assert_ne!(a.save_to_bytes().unwrap(), b.save_to_bytes().unwrap());
+
+        // Negative zero and the unused space after the sectors are removed
+        let canonical = a.save_canonical_bytes().unwrap();
+        assert_eq!(b.save_canonical_bytes().unwrap(), canonical);
+        assert_eq!(b.dirty_sectors().collect::<Vec<_>>(), vec![0]);
+
+        // Loading and saving again gives the same bytes
+        let mut loaded = Map::load_from_bytes(&canonical).unwrap();
+        assert_eq!(loaded.save_canonical_bytes().unwrap(), canonical);
+
+        // Every NaN becomes the same NaN
+        let mut nan = Map::new(vec![quad_sector()]);
+        nan.sectors[0].wall_mesh.vertex_buffer[0].uv[0] =
+            f32::from_bits(0x7fc0_0001);
+        nan.sectors[0].canonicalize();
+        let uv = nan.sectors[0].wall_mesh.vertex_buffer[0].uv[0];
+        assert_eq!(uv.to_bits(), f32::NAN.to_bits());
+    }
+
+    #[test]
+    fn content_hash() {
+        let a = full_map();
+        let mut b = full_map();
+        b.sectors[0].floor_mesh.vertex_buffer[0].pos[0] = -0.0;
+
+        // Negative zero doesn't change the hash and the map isn't touched
+        let hash = a.content_hash().unwrap();
+        assert_eq!(b.content_hash().unwrap(), hash);
+        assert_eq!(b.sectors[0].floor_mesh.vertex_buffer[0].pos[0]
+                       .to_bits(), (-0.0f32).to_bits());
+
+        // Loaded maps hash the same as the saved map
+        let saved = full_map();
+        let loaded = Map::load_from_bytes(&saved.save_to_bytes().unwrap())
+            .unwrap();
+        assert_eq!(loaded.content_hash().unwrap(), hash);
+
+        b.lights[0].intensity += 1.0;
+        assert_ne!(b.content_hash().unwrap(), hash);
+    }
+
+    #[test]
+    fn signature_chunk() {
+        let mut map = full_map();
+        map.set_signature(Some([7; 64]));
+
+        let bytes = map.save_to_bytes().unwrap();
+        let loaded = Map::load_from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.signature(), Some(&[7; 64]));
+
+        // The payload doesn't include the signature
+        map.set_signature(None);
+        assert_eq!(loaded.signed_payload().unwrap(),
+                   map.signed_payload().unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "signing")]
+    fn signed_maps() {
+        let secret_key = [42; 32];
+        let public_key = ed25519_dalek::SigningKey::from_bytes(&secret_key)
+            .verifying_key()
+            .to_bytes();
+        let other_key = ed25519_dalek::SigningKey::from_bytes(&[1; 32])
+            .verifying_key()
+            .to_bytes();
+
+        let mut map = full_map();
+        map.sectors[0].floor_mesh.vertex_buffer[0].pos[1] = 123.25;
+        assert!(!map.verify(&public_key).unwrap());
+
+        map.sign(&secret_key).unwrap();
+        assert!(map.verify(&public_key).unwrap());
+        assert!(!map.verify(&other_key).unwrap());
+
+        // The signature survives saving and loading
+        let mut bytes = map.save_to_bytes().unwrap();
+        let loaded = Map::load_from_bytes(&bytes).unwrap();
+        assert!(loaded.verify(&public_key).unwrap());
+
+        // Tampering with the file breaks the signature
+        // NOTE(patrik): The first match is the bounding box of the sector
+        let height = 123.25f32.to_le_bytes();
+        let offset = bytes.windows(4).rposition(|w| w == height).unwrap();
+        bytes[offset..offset + 4].copy_from_slice(&124.0f32.to_le_bytes());
+
+        // The sector no longer matches its checksum
+        let error = Map::load_from_bytes(&bytes).err().unwrap();
+        assert!(matches!(error.kind(), crate::Error::SectorChecksumMismatch));
+
+        // A tamperer updating the checksums still breaks the signature
+        update_checksums(&mut bytes[24..]);
+        let tampered = Map::load_from_bytes(&bytes).unwrap();
+        assert!(!tampered.verify(&public_key).unwrap());
+
+        map.lights[0].intensity = 10.0;
+        assert!(!map.verify(&public_key).unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn sector_compression() {
+        let mut map = full_map();
+        let uncompressed = map.save_to_bytes().unwrap();
+
+        map.compression = SectorCompression::Lz4;
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), map.serialized_size());
+
+        let bytes = map.save_to_bytes().unwrap();
+        assert!(bytes.len() < uncompressed.len());
+
+        let loaded = Map::load_from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.compression, SectorCompression::Lz4);
+        assert_eq!(loaded.content_hash().unwrap(),
+                   map.content_hash().unwrap());
+
+        // Lazy loaders only decompress the sectors they read and the bounds
+        // are stored uncompressed
+        let mut lazy = LazyMap::from_bytes(bytes.clone()).unwrap();
+        assert_eq!(lazy.sector_bounds(0).unwrap(), map.sectors[0].bounds());
+        assert!(!lazy.is_loaded(0));
+        assert_eq!(lazy.sector(1).unwrap().id(), map.sectors[1].id());
+
+        let streamed = Map::sector_stream(&bytes[..])
+            .collect::<crate::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(streamed.len(), map.sectors.len());
+
+        // Corrupt compressed data is reported instead of read, the first
+        // sector starts after the file header and the map header
+        let entry = 24 + 8 * 3 + 1;
+        let sector = 24 + u64::from_le_bytes(
+            bytes[entry..entry + 8].try_into().unwrap()) as usize;
+        assert_eq!(bytes[sector], 1);
+
+        let mut corrupt = bytes.clone();
+        corrupt[sector + 1 + 24 + 8..][..16].fill(0xff);
+        update_checksums(&mut corrupt[24..]);
+        assert!(Map::load_from_bytes(&corrupt).is_err());
+
+        let mut corrupt = bytes.clone();
+        corrupt[sector] = 7;
+        update_checksums(&mut corrupt[24..]);
+        let error = Map::load_from_bytes(&corrupt).err().unwrap();
+        assert!(matches!(error.kind(),
+                         crate::Error::UnknownSectorCompression(7)));
+    }
+
+    #[test]
+    fn map_patches() {
+        let old = full_map();
+
+        // Change one sector, add one and move the lights around
+        let mut new = full_map();
+        new.sectors[1].floor_mesh.vertex_buffer[0].pos[1] = 2.0;
+        new.add_sector(quad_sector());
+        new.lights[0].intensity = 3.0;
+
+        let patch = Map::create_patch(&old, &new).unwrap();
+        assert!(patch.len() < new.save_to_bytes().unwrap().len());
+
+        let mut patched = full_map();
+        patched.apply_patch(&patch).unwrap();
+        assert_eq!(patched.content_hash().unwrap(),
+                   new.content_hash().unwrap());
+        assert_eq!(patched.dirty_sectors().collect::<Vec<_>>(), vec![1, 2]);
+
+        // Unchanged sectors aren't stored in the patch
+        let same = Map::create_patch(&old, &full_map()).unwrap();
+        assert!(same.len() < 128);
+
+        // A patch only applies to the revision it was created from
+        let error = patched.apply_patch(&patch).err().unwrap();
+        assert!(matches!(error, crate::Error::PatchMismatch));
+        assert_eq!(patched.content_hash().unwrap(),
+                   new.content_hash().unwrap());
+    }
+
+    #[test]
+    fn deserialize_limits() {
+        use crate::{ DeserializeOptions, DeserializeLimit, Error };
+
+        let map = full_map();
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+
+        let limit = |buffer: &[u8], options: DeserializeOptions| {
+            match Map::deserialize_with_options(buffer, &options) {
+                Ok(_) => None,
+                Err(error) => match error.kind() {
+                    Error::LimitExceeded(limit) => Some(*limit),
+                    error => panic!("unexpected error {}", error),
+                },
+            }
+        };
+
+        let options = DeserializeOptions::default();
+        assert_eq!(limit(&buffer, options), None);
+
+        let sectors = DeserializeOptions { max_sectors: 1, ..options };
+        assert_eq!(limit(&buffer, sectors), Some(DeserializeLimit::Sectors));
+
+        let vertices = DeserializeOptions { max_vertices: 20, ..options };
+        assert_eq!(limit(&buffer, vertices), Some(DeserializeLimit::Vertices));
+
+        let indices = DeserializeOptions { max_indices: 20, ..options };
+        assert_eq!(limit(&buffer, indices), Some(DeserializeLimit::Indices));
+
+        let bytes = DeserializeOptions { max_allocated_bytes: 1024, ..options };
+        assert_eq!(limit(&buffer, bytes),
+                   Some(DeserializeLimit::AllocatedBytes));
+
+        // A 16 byte file claiming u64::MAX maps is rejected before
+        // allocating
+        let mut hostile = b"MIME".to_vec();
+        hostile.extend_from_slice(&CURRENT_VERSION.to_le_bytes());
+        hostile.extend_from_slice(&u64::MAX.to_le_bytes());
+        let options = DeserializeOptions {
+            max_allocated_bytes: 1 << 20,
+            ..options
+        };
+        let error = Mime::deserialize_with_options(&hostile, &options)
+            .err().unwrap();
+        assert!(matches!(error.kind(), Error::LimitExceeded(
+            DeserializeLimit::AllocatedBytes)));
+
+        // A mesh claiming u64::MAX vertices is rejected before allocating
+        let entry = 8 * 3 + 1;
+        let sector = u64::from_le_bytes(
+            buffer[entry..entry + 8].try_into().unwrap()) as usize;
+        let count = sector + 1 + 24 + 8 + 7;
+        buffer[count..count + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        update_checksums(&mut buffer);
+        let options = DeserializeOptions {
+            max_vertices: 1 << 20,
+            ..options
+        };
+        assert_eq!(limit(&buffer, options), Some(DeserializeLimit::Vertices));
+    }
+
+    #[test]
+    fn offset_overflow() {
+        use crate::Error;
+
+        let map = full_map();
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+
+        let overflow = |buffer: &[u8]| {
+            let error = Map::deserialize(buffer).err().unwrap();
+            matches!(error.kind(), Error::OffsetOverflow)
+        };
+
+        // A mesh whose vertex count times the vertex size doesn't fit
+        // inside a usize
+        let entry = 8 * 3 + 1;
+        let sector = u64::from_le_bytes(
+            buffer[entry..entry + 8].try_into().unwrap()) as usize;
+        let count = sector + 1 + 24 + 8 + 7;
+        let mut mesh = buffer.clone();
+        mesh[count..count + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        update_checksums(&mut mesh);
+        assert!(overflow(&mesh));
+
+        // A map size past the end of the file
+        let mut mime = b"MIME".to_vec();
+        mime.extend_from_slice(&CURRENT_VERSION.to_le_bytes());
+        mime.extend_from_slice(&1u64.to_le_bytes());
+        mime.extend_from_slice(&u64::MAX.to_le_bytes());
+        let error = Mime::deserialize(&mime).err().unwrap();
+        assert!(matches!(error.kind(), Error::BufferToSmallMap(_)));
+    }
+
+    #[test]
+    fn standard_traits() {
+        assert_eq!(Mesh::default(), Mesh::new(Vec::new(), Vec::new(), 0));
+        assert_eq!(Sector::default().floor_mesh, Mesh::default());
+        assert_eq!(Map::default().sectors.len(), 0);
+
+        let map = full_map();
+        let mut copy = map.clone();
+        assert_eq!(copy, map);
+
+        copy.sectors[0].tag += 1;
+        assert_ne!(copy, map);
+        assert!(format!("{:?}", copy).contains("tag"));
+
+        // Saving the map doesn't change its content
+        let loaded = Map::load_from_bytes(&map.save_to_bytes().unwrap())
+            .unwrap();
+        assert_eq!(loaded, map);
+    }
+
+    #[test]
+    fn triangle_iterators() {
+        use crate::MeshRole;
+
+        let mut mesh = quad_mesh();
+        assert_eq!(mesh.triangles().count(), 2);
+
+        let [a, b, c] = mesh.triangles().nth(1).unwrap();
+        assert_eq!([a.pos, b.pos, c.pos],
+                   [[1.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 0.0]]);
+
+        // Triangles referencing missing vertices and leftover indices are
+        // skipped
+        mesh.index_buffer.extend_from_slice(&[0, 1, 9, 0]);
+        assert_eq!(mesh.triangle_positions().count(), 2);
+
+        let mut map = Map::new(vec![quad_sector(), quad_sector()]);
+        map.sectors[1].wall_mesh.index_buffer.clear();
+        let triangles = map.triangles()
+            .map(|(sector, role, _)| (sector, role))
+            .collect::<Vec<_>>();
+        assert_eq!(triangles, vec![
+            (0, MeshRole::Floor), (0, MeshRole::Floor),
+            (0, MeshRole::Ceiling), (0, MeshRole::Ceiling),
+            (0, MeshRole::Wall), (0, MeshRole::Wall),
+            (1, MeshRole::Floor), (1, MeshRole::Floor),
+            (1, MeshRole::Ceiling), (1, MeshRole::Ceiling),
+        ]);
+    }
+
+    #[test]
+    fn vertex_iterators() {
+        let mut map = Map::new(vec![quad_sector(), quad_sector()]);
+        map.sectors[1].add_lod(Lod::new(10.0, quad_mesh(), quad_mesh(),
+                                        quad_mesh()));
+        assert_eq!(map.sectors[1].meshes().count(), 6);
+        assert_eq!(map.vertices().count(), 4 * 3 * 3);
+
+        for vertex in map.vertices_mut() {
+            vertex.pos[1] += 2.0;
+            vertex.color = [0.0, 0.0, 0.0, 1.0];
+        }
+
+        assert!(map.vertices().all(|vertex| {
+            vertex.pos[1] >= 2.0 && vertex.color == [0.0, 0.0, 0.0, 1.0]
+        }));
+        assert_eq!(map.sectors[1].lod(0).unwrap().floor_mesh.vertex_buffer[1]
+                       .pos, [0.0, 3.0, 0.0]);
+        assert_eq!(map.dirty_sectors().collect::<Vec<_>>(), vec![0, 1]);
+    }
+
+    #[test]
+    fn map_transform() {
+        use crate::SoundEmitter;
+
+        let mut map = Map::new(vec![quad_sector()]);
+        map.sound_emitters.push(SoundEmitter::new([1.0, 2.0, 3.0], "wind",
+                                                  4.0));
+
+        // Move 10 units along x
+        let mut translation = crate::transform::IDENTITY;
+        translation[3] = [10.0, 0.0, 0.0, 1.0];
+        map.transform(&translation);
+        assert_eq!(map.sectors[0].floor_mesh.vertex_buffer[2].pos,
+                   [11.0, 1.0, 0.0]);
+        assert_eq!(map.sound_emitters[0].position, [11.0, 2.0, 3.0]);
+        assert_eq!(map.dirty_sectors().collect::<Vec<_>>(), vec![0]);
+
+        // Mirroring flips the winding so the faces keep facing the same way
+        let mut mesh = quad_mesh();
+        let normal = mesh.dominant_normal().unwrap();
+        let mut mirror = crate::transform::IDENTITY;
+        mirror[0][0] = -1.0;
+        mesh.transform(&mirror);
+        assert_eq!(mesh.vertex_buffer[2].pos, [-1.0, 1.0, 0.0]);
+        assert_eq!(mesh.index_buffer, vec![0, 2, 1, 2, 0, 3]);
+        assert_eq!(mesh.dominant_normal().unwrap(), normal);
+    }
+
+    #[test]
+    fn axis_conventions() {
+        use crate::AxisConvention;
+
+        for value in 0..4 {
+            let axes = AxisConvention::from_u8(value).unwrap();
+            assert_eq!(axes.to_u8(), value);
+        }
+        assert!(AxisConvention::from_u8(4).is_err());
+
+        let mut map = Map::new(vec![quad_sector()]);
+        let original = map.clone();
+        let normal = map.sectors[0].floor_mesh.dominant_normal().unwrap();
+
+        // Blender is Z-up, the Y axis becomes the Z axis
+        map.convert_axes(AxisConvention::Z_UP_RIGHT_HANDED);
+        assert_eq!(map.axes, AxisConvention::Z_UP_RIGHT_HANDED);
+        assert_eq!(map.sectors[0].floor_mesh.vertex_buffer[2].pos,
+                   [1.0, 0.0, 1.0]);
+
+        // Mirroring keeps the faces facing the same way
+        map.convert_axes(AxisConvention::Z_UP_LEFT_HANDED);
+        assert_eq!(map.sectors[0].floor_mesh.vertex_buffer[2].pos,
+                   [1.0, 0.0, 1.0]);
+        assert_eq!(map.sectors[0].floor_mesh.vertex_buffer[1].pos,
+                   [0.0, 0.0, 1.0]);
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+        let mut result = Map::deserialize(&buffer).unwrap();
+        assert_eq!(result.axes, AxisConvention::Z_UP_LEFT_HANDED);
+
+        // Converting back gives the original map
+        result.convert_axes(AxisConvention::default());
+        assert_eq!(result, original);
+        assert_eq!(result.sectors[0].floor_mesh.dominant_normal().unwrap(),
+                   normal);
+    }
+
+    #[test]
+    fn unit_scale() {
+        use crate::{ SoundEmitter, Error };
+
+        let mut map = Map::new(vec![quad_sector()]);
+        map.sectors[0].add_lod(Lod::new(10.0, quad_mesh(), quad_mesh(),
+                                        quad_mesh()));
+        map.sound_emitters.push(SoundEmitter::new([1.0, 2.0, 3.0], "wind",
+                                                  4.0));
+        assert_eq!(map.units_per_meter(), 1.0);
+
+        // Convert a map in meters to Doom scale
+        map.rescale(32.0).unwrap();
+        assert_eq!(map.units_per_meter(), 32.0);
+        assert_eq!(map.sectors[0].floor_mesh.vertex_buffer[2].pos,
+                   [32.0, 32.0, 0.0]);
+        assert_eq!(map.sectors[0].lod(0).unwrap().switch_distance, 320.0);
+        assert_eq!(map.sound_emitters[0].position, [32.0, 64.0, 96.0]);
+        assert_eq!(map.sound_emitters[0].radius, 128.0);
+
+        let error = map.rescale(0.0).err().unwrap();
+        assert!(matches!(error, Error::InvalidUnitScale(_)));
+        assert!(map.set_units_per_meter(f32::NAN).is_err());
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+        let result = Map::deserialize(&buffer).unwrap();
+        assert_eq!(result.units_per_meter(), 32.0);
+        assert_eq!(result, map);
+
+        // Merged maps are rescaled to the scale of the map they are merged
+        // into
+        let mut merged = Map::new(Vec::new());
+        merged.merge(map, None).unwrap();
+        assert_eq!(merged.sectors[0].floor_mesh.vertex_buffer[2].pos,
+                   [1.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn mesh_append() {
+        use crate::CustomAttribute;
+
+        let mut mesh = quad_mesh();
+        mesh.set_uv_set("detail", vec![[1.0, 1.0]; 4]);
+
+        let mut other = quad_mesh();
+        other.lightmap_uvs = vec![[0.5, 0.5]; 4];
+        other.set_custom_attribute(
+            CustomAttribute::from_f32s("wetness", &[1.0, 2.0, 3.0, 4.0]));
+
+        mesh.append(&other);
+        assert_eq!(mesh.vertex_buffer.len(), 8);
+        assert_eq!(mesh.index_buffer[6..], [4, 5, 6, 6, 7, 4]);
+        assert_eq!(mesh.triangles().count(), 4);
+
+        // The sets and attributes only one of the meshes has are filled
+        // with zeros
+        assert_eq!(mesh.lightmap_uvs[..5],
+                   [[0.0; 2], [0.0; 2], [0.0; 2], [0.0; 2], [0.5; 2]]);
+        assert_eq!(mesh.uv_set("detail").unwrap()[3..5],
+                   [[1.0, 1.0], [0.0, 0.0]]);
+        assert_eq!(mesh.custom_attribute("wetness").unwrap()
+                       .to_f32s().unwrap(),
+                   vec![0.0, 0.0, 0.0, 0.0, 1.0, 2.0, 3.0, 4.0]);
+
+        // The appended mesh is still valid to serialize
+        let mut buffer = Vec::new();
+        mesh.serialize(&mut buffer).unwrap();
+        assert_eq!(Mesh::deserialize(&buffer).unwrap(), mesh);
+    }
+
+    #[test]
+    fn mesh_submeshes() {
+        use crate::Submesh;
+
+        let mut mesh = quad_mesh();
+        mesh.submeshes.push(Submesh::new("left", 0..3, Some(0)));
+        mesh.submeshes.push(Submesh::new("right", 3..6, None));
+        assert_eq!(mesh.submesh("right").unwrap().indices(&mesh), [2, 3, 0]);
+
+        let mut buffer = Vec::new();
+        mesh.serialize(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), mesh.serialized_size());
+        assert_eq!(Mesh::deserialize(&buffer).unwrap(), mesh);
+
+        // Appending moves the ranges past the existing indices
+        let mut appended = quad_mesh();
+        appended.append(&mesh);
+        assert_eq!(appended.submesh("left").unwrap().range(), 6..9);
+
+        // Removing a triangle shrinks the range it was part of and moves
+        // the ranges after it
+        mesh.index_buffer[1] = 0;
+        assert_eq!(mesh.remove_degenerate_triangles(), 1);
+        assert_eq!(mesh.submesh("left").unwrap().range(), 0..0);
+        assert_eq!(mesh.submesh("right").unwrap().range(), 0..3);
+
+        // Merging maps moves the materials of the submeshes
+        let mut map = Map::new(Vec::new());
+        map.materials.push(crate::Material::new("stone"));
+        let mut other = Map::new(vec![quad_sector()]);
+        other.materials.push(crate::Material::new("wood"));
+        other.sectors[0].floor_mesh = mesh;
+        map.merge(other, None).unwrap();
+        let mesh = &map.sectors[0].floor_mesh;
+        assert_eq!(mesh.submesh("left").unwrap().material, Some(1));
+        assert_eq!(mesh.submesh("right").unwrap().material, None);
+    }
+
+    #[test]
+    fn mesh_topology() {
+        use crate::Topology;
+
+        for value in 0..4 {
+            assert_eq!(Topology::from_u8(value).unwrap().to_u8(), value);
+        }
+        assert!(Topology::from_u8(4).is_err());
+
+        // The quad as a strip
+        let mut strip = quad_mesh();
+        strip.topology = Topology::TriangleStrip;
+        strip.index_buffer = vec![1, 2, 0, 3];
+        assert_eq!(strip.triangle_indices().collect::<Vec<_>>(),
+                   vec![[1, 2, 0], [0, 2, 3]]);
+
+        let list = quad_mesh();
+        assert_eq!(strip.triangle_positions()
+                       .map(|[a, b, c]| crate::math::triangle_normal(a, b, c))
+                       .collect::<Vec<_>>(),
+                   list.triangle_positions()
+                       .map(|[a, b, c]| crate::math::triangle_normal(a, b, c))
+                       .collect::<Vec<_>>());
+
+        let mut buffer = Vec::new();
+        strip.serialize(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), strip.serialized_size());
+        assert_eq!(Mesh::deserialize(&buffer).unwrap(), strip);
+
+        // Appended strips are joined with degenerate triangles and keep
+        // their winding
+        let mut joined = strip.clone();
+        joined.append(&strip);
+        let normals = joined.triangle_positions()
+            .map(|[a, b, c]| crate::math::triangle_normal(a, b, c))
+            .filter(|normal| *normal != [0.0; 3])
+            .collect::<Vec<_>>();
+        assert_eq!(normals, vec![[0.0, 0.0, -1.0]; 4]);
+
+        // Lines don't have triangles
+        let mut lines = quad_mesh();
+        lines.topology = Topology::LineList;
+        assert_eq!(lines.triangles().count(), 0);
+        assert!(lines.degenerate_triangles().is_empty());
+    }
+
+    #[test]
+    fn edge_meshes() {
+        use crate::Topology;
+
+        // The diagonal of the quad is shared by both triangles
+        let mesh = quad_mesh();
+        assert_eq!(mesh.unique_edges(),
+                   vec![[0, 1], [1, 2], [2, 0], [2, 3], [3, 0]]);
+
+        let edges = mesh.edges();
+        assert_eq!(edges.topology, Topology::LineList);
+        assert_eq!(edges.vertex_buffer, mesh.vertex_buffer);
+        assert_eq!(edges.index_buffer.len(), 10);
+
+        let map = Map::new(vec![quad_sector(), quad_sector()]);
+        let edges = map.edges();
+        assert_eq!(edges.vertex_buffer.len(), 4 * 3 * 2);
+        assert_eq!(edges.index_buffer.len(), 10 * 3 * 2);
+        assert_eq!(edges.index_buffer[10..12], [4, 5]);
+    }
+
+    #[test]
+    fn sector_names() {
+        let mut map = Map::new(vec![quad_sector(), quad_sector()]);
+        map.sectors[1].name = Some("lobby".to_string());
+        assert!(map.sector_by_name("lobby").is_some_and(|(index, _)| {
+            index == 1
+        }));
+        assert!(map.sector_by_name("basement").is_none());
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+        let result = Map::deserialize(&buffer).unwrap();
+        assert_eq!(result.sectors[0].name, None);
+        assert_eq!(result.sector_by_name("lobby").unwrap().0, 1);
+
+        // An empty name is still a name
+        let mut sector = quad_sector();
+        sector.name = Some(String::new());
+        let mut buffer = Vec::new();
+        sector.serialize(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), sector.serialized_size());
+        assert_eq!(Sector::deserialize(&buffer).unwrap().name,
+                   Some(String::new()));
+    }
+
+    #[test]
+    fn string_validation() {
+        use crate::{ Error, MAX_STRING_SIZE };
+
+        // NUL bytes are ordinary characters of the strings
+        let mut sector = quad_sector();
+        sector.name = Some("a\0b".to_string());
+        let mut buffer = Vec::new();
+        sector.serialize(&mut buffer).unwrap();
+        assert_eq!(Sector::deserialize(&buffer).unwrap().name, sector.name);
+
+        sector.name = Some("a".repeat(MAX_STRING_SIZE + 1));
+        let error = sector.serialize(&mut Vec::new()).unwrap_err();
+        assert!(matches!(error, Error::StringTooLong(_)));
+
+        sector.name = Some("ab".to_string());
+        let mut buffer = Vec::new();
+        sector.serialize(&mut buffer).unwrap();
+
+        // The end of the name, the fields added after it follow
+        let end = buffer.windows(2)
+            .rposition(|bytes| bytes == b"ab")
+            .unwrap() + 2;
+        let mut corrupt = buffer.clone();
+        corrupt[end - 2] = 0xff;
+        let error = Sector::deserialize(&corrupt).unwrap_err();
+        assert!(matches!(error.kind(), Error::Utf8ConvertionError(_)));
+
+        let mut corrupt = buffer.clone();
+        corrupt[end - 10..end - 2].copy_from_slice(&u64::MAX.to_le_bytes());
+        let error = Sector::deserialize(&corrupt).unwrap_err();
+        assert!(matches!(error.kind(), Error::StringTooLong(_)));
+    }
+
+    #[test]
+    fn format_schema() {
+        use crate::{ schema, FieldKind };
+
+        let schema = schema();
+        assert_eq!(schema.version, CURRENT_VERSION);
+        assert_eq!(&schema.magic, b"MIME");
+
+        // Every structure referenced by a field exists
+        fn referenced(kind: FieldKind) -> Vec<&'static str> {
+            match kind {
+                FieldKind::Struct(name) | FieldKind::Sized(name) |
+                FieldKind::List(name) => vec![name],
+                FieldKind::Optional(item) | FieldKind::Repeat { item, .. } |
+                FieldKind::At { item, .. } => referenced(*item),
+                FieldKind::Variant { cases, .. } => {
+                    cases.iter().map(|&(_, name)| name).collect()
+                }
+                _ => Vec::new(),
+            }
+        }
+
+        let fields = schema.structs.iter()
+            .flat_map(|structure| &structure.fields)
+            .map(|field| field.kind);
+        let chunks = schema.chunks.iter().map(|chunk| chunk.content);
+        for name in fields.chain(chunks).flat_map(referenced) {
+            assert!(schema.structure(name).is_some(), "{}", name);
+        }
+
+        let entry = schema.structure("SectorEntry").unwrap();
+        assert_eq!(entry.size, Some(SECTOR_ENTRY_SIZE));
+        let sector = schema.structure("Sector").unwrap();
+        assert_eq!(sector.field("floor_mesh").unwrap().offset, Some(24));
+        assert_eq!(sector.field("ceiling_mesh").unwrap().offset, None);
+
+        // The empty texture name is only its length
+        let wall = Wall::new([0.0; 3], [1.0; 3], "", 0..6);
+        let wall_schema = schema.structure("Wall").unwrap();
+        assert_eq!(wall_schema.fields.iter()
+                       .map(|field| field.kind.size().unwrap_or(8))
+                       .sum::<usize>(),
+                   wall.serialized_size());
+
+        // Every chunk written for a map is part of the schema
+        let mut map = full_map();
+        map.build_pvs();
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+
+        let mut offset = u64::from_le_bytes(
+            buffer[8..16].try_into().unwrap()) as usize;
+        let count = parse_u64!(buffer, offset);
+        let mut tags = Vec::new();
+        for _ in 0..count {
+            let tag: [u8; 4] = buffer[offset..offset + 4].try_into().unwrap();
+            offset += 4;
+            let size = parse_u64!(buffer, offset) as usize;
+            offset += size;
+
+            let chunk = schema.chunk(&tag).unwrap();
+            if let Some(expected) = chunk.content.size() {
+                assert_eq!(size, expected);
+            }
+            tags.push(tag);
+        }
+        assert_eq!(offset, buffer.len());
+
+        for chunk in schema.chunks.iter().filter(|chunk| chunk.required) {
+            assert!(tags.contains(&chunk.tag));
+        }
+    }
+
+    #[test]
+    fn entity_properties() {
+        use crate::{ Entity, PropertyValue, PropertyType, Error };
+
+        let mut entity = Entity::new("light_torch", [1.0, 2.0, 3.0]);
+        entity.set_property("lit", true);
+        entity.set_property("count", 3i64);
+        entity.set_property("speed", 2.5f64);
+        entity.set_property("target", "door");
+        entity.set_property("offset", [0.0, 1.0, 0.0]);
+        entity.set_property("tint", [1.0, 0.5, 0.0, 1.0]);
+
+        assert_eq!(entity.property("lit").unwrap().as_bool(), Some(true));
+        assert_eq!(entity.property("count").unwrap().as_int(), Some(3));
+        assert_eq!(entity.property("count").unwrap().as_float(), Some(3.0));
+        assert_eq!(entity.property("target").unwrap().as_str(), Some("door"));
+        assert_eq!(entity.property("target").unwrap().as_int(), None);
+        assert_eq!(entity.property("tint").unwrap().property_type(),
+                   PropertyType::Color);
+        assert!(entity.property("missing").is_none());
+
+        let mut buffer = Vec::new();
+        entity.serialize(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), entity.serialized_size());
+        assert_eq!(Entity::deserialize(&buffer).unwrap(), entity);
+
+        let mut map = Map::new(vec![quad_sector()]);
+        map.entities.push(entity.clone());
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), map.serialized_size());
+        assert_eq!(Map::deserialize(&buffer).unwrap(), map);
+
+        let mut translation = crate::transform::IDENTITY;
+        translation[3] = [1.0, 0.0, 0.0, 1.0];
+        map.transform(&translation);
+        assert_eq!(map.entities[0].position, [2.0, 2.0, 3.0]);
+
+        // The value is the type followed by the value
+        let mut buffer = Vec::new();
+        PropertyValue::Float(0.5).serialize(&mut buffer).unwrap();
+        assert_eq!(buffer[0], PropertyType::Float.to_u8());
+        assert_eq!(&buffer[1..], &0.5f64.to_le_bytes());
+
+        buffer[0] = 200;
+        let error = PropertyValue::deserialize(&buffer).unwrap_err();
+        assert!(matches!(error, Error::UnknownPropertyType(200)));
+    }
+
+    #[test]
+    fn entity_validation() {
+        use crate::{ Entity, EntityDef, EntityDefs, EntityIssue, PropertyType };
+
+        let defs = [
+            EntityDef::new("light_torch")
+                .required("radius", PropertyType::Float)
+                .optional("tint", PropertyType::Color),
+            EntityDef::new("info_note"),
+        ].into_iter().collect::<EntityDefs>();
+        assert!(defs.get("info_note").is_some());
+
+        let mut map = Map::new(vec![quad_sector()]);
+
+        // An integer is fine for a float property
+        let mut torch = Entity::new("light_torch", [0.0; 3]);
+        torch.set_property("radius", 4i64);
+        map.entities.push(torch);
+        assert!(map.validate_entities(&defs).is_empty());
+
+        let mut torch = Entity::new("light_torch", [0.0; 3]);
+        torch.set_property("tint", "red");
+        torch.set_property("flicker", true);
+        map.entities.push(torch);
+        map.entities.push(Entity::new("monster_grunt", [0.0; 3]));
+
+        let issues = map.validate_entities(&defs);
+        assert_eq!(issues, vec![
+            EntityIssue::MissingProperty {
+                entity: 1,
+                name: "radius".to_string(),
+            },
+            EntityIssue::UnknownProperty {
+                entity: 1,
+                name: "flicker".to_string(),
+            },
+            EntityIssue::WrongType {
+                entity: 1,
+                name: "tint".to_string(),
+                expected: PropertyType::Color,
+                found: PropertyType::String,
+            },
+            EntityIssue::UnknownClass {
+                entity: 2,
+                class: "monster_grunt".to_string(),
+            },
+        ]);
+        assert_eq!(issues[3].entity(), 2);
+        assert_eq!(issues[0].to_string(),
+                   "entity 1: missing property 'radius'");
+    }
+
+    #[test]
+    fn spawn_points() {
+        use crate::{ Entity, EntityDefs, SpawnPoint };
+        use crate::spawn::SPAWN_TEAM_PROPERTY;
+
+        let mut map = Map::new(vec![quad_sector()]);
+        map.entities.push(Entity::new("light_torch", [0.0; 3]));
+
+        let mut red = SpawnPoint::new([1.0, 0.0, 2.0], 90.0);
+        red.team = Some(1);
+        red.player = Some(0);
+        assert_eq!(map.add_spawn_point(red), 1);
+        map.add_spawn_point(SpawnPoint::new([4.0, 0.0, 4.0], 180.0));
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+        let map = Map::deserialize(&buffer).unwrap();
+        assert_eq!(map.spawn_points(),
+                   vec![red, SpawnPoint::new([4.0, 0.0, 4.0], 180.0)]);
+
+        let defs = [SpawnPoint::def()].into_iter().collect::<EntityDefs>();
+        assert_eq!(map.validate_entities(&defs).len(), 1);
+
+        // A negative team is ignored
+        let mut entity = red.to_entity();
+        entity.set_property(SPAWN_TEAM_PROPERTY, -1i64);
+        assert_eq!(SpawnPoint::from_entity(&entity).unwrap().team, None);
+        assert!(SpawnPoint::from_entity(&map.entities[0]).is_none());
+    }
+
+    #[test]
+    fn waypoint_graph() {
+        use crate::{ WaypointGraph, Error };
+
+        let mut graph = WaypointGraph::new();
+        let a = graph.add_node([0.0; 3], 0);
+        let b = graph.add_node([3.0, 0.0, 4.0], 0);
+        let c = graph.add_node([3.0, 0.0, 8.0], 1);
+        graph.link_both(a, b, None, 0).unwrap();
+        graph.link(b, c, Some(10.0), 2).unwrap();
+        assert!(matches!(graph.link(a, 7, None, 0),
+                         Err(Error::InvalidWaypointLink(7))));
+
+        assert_eq!(graph.links(a)[0].cost, 5.0);
+        assert_eq!(graph.links(b).len(), 2);
+        assert_eq!(graph.links(b)[1].flags, 2);
+        assert!(graph.links(c).is_empty());
+        assert!(graph.links(9).is_empty());
+
+        let mut map = Map::new(vec![quad_sector()]);
+        map.waypoints = Some(graph.clone());
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), map.serialized_size());
+        assert_eq!(Map::deserialize(&buffer).unwrap(), map);
+
+        // Merging rebases the links past the nodes of the map
+        let other = map.clone();
+        map.merge(other, None).unwrap();
+        let waypoints = map.waypoints.as_ref().unwrap();
+        assert_eq!(waypoints.nodes.len(), 6);
+        assert_eq!(waypoints.links(3)[0].target, 4);
+
+        // A link to a node that doesn't exist is rejected
+        let mut buffer = Vec::new();
+        graph.nodes.pop();
+        graph.serialize(&mut buffer).unwrap();
+        let error = WaypointGraph::deserialize(&buffer).unwrap_err();
+        assert!(matches!(error, Error::InvalidWaypointLink(2)));
+    }
+
+    #[test]
+    fn sector_connectivity() {
+        // Sector 0 reaches 3 through the short way over 1 or the long way
+        // over 2, sector 4 isn't connected
+        let offsets = [[0.0, 0.0], [1.0, 1.0], [1.0, -9.0], [2.0, 0.0],
+                       [5.0, 5.0]];
+        let sectors = offsets.iter()
+            .map(|&[x, y]| {
+                let mut translation = crate::transform::IDENTITY;
+                translation[3] = [x, y, 0.0, 1.0];
+
+                let mut sector = quad_sector();
+                sector.transform(&translation);
+                sector
+            })
+            .collect();
+        let mut map = Map::new(sectors);
+
+        for (index, (a, b)) in [(0, 1), (0, 2), (1, 3), (2, 3)]
+            .into_iter().enumerate()
+        {
+            let start = [index as f32, 0.0, 0.0];
+            let end = [index as f32, 0.0, 1.0];
+            let mut wall = Wall::new(start, end, "", 0..0);
+            wall.flags = crate::wall::WALL_FLAG_TWO_SIDED;
+            map.sectors[a].walls.push(wall.clone());
+            map.sectors[b].walls.push(wall);
+        }
+
+        let graph = map.sector_graph();
+        assert_eq!(graph.len(), 5);
+        assert_eq!(graph.neighbors(0), &[1, 2]);
+        assert_eq!(graph.neighbors(3), &[1, 2]);
+        assert!(graph.neighbors(4).is_empty());
+        assert!(graph.neighbors(10).is_empty());
+        assert_eq!(graph.center(0), Some([0.5, 0.5, 0.0]));
+
+        assert!(graph.is_reachable(0, 3));
+        assert!(!graph.is_reachable(0, 4));
+        assert_eq!(graph.reachable(2), vec![true, true, true, true, false]);
+
+        assert_eq!(graph.find_path(0, 3), Some(vec![0, 1, 3]));
+        assert_eq!(graph.find_path(2, 2), Some(vec![2]));
+        assert_eq!(graph.find_path(0, 4), None);
+        assert_eq!(map.find_sector_path(3, 0), Some(vec![3, 1, 0]));
+    }
+
+    #[test]
+    fn heightmap_terrain() {
+        use crate::{ HeightmapOptions, Error };
+
+        // 5x3 samples are 4x2 cells, split into sectors of 2x2 cells
+        let samples: Vec<u8> = vec![
+            0, 0, 0, 0, 0,
+            0, 255, 0, 0, 0,
+            0, 0, 0, 0, 128,
+        ];
+        let options = HeightmapOptions {
+            cell_size: 2.0,
+            height_scale: 0.1,
+            cells_per_sector: 2,
+            color_ramp: vec![(0.0, [0.0, 0.0, 0.0, 1.0]),
+                             (1.0, [1.0, 1.0, 1.0, 1.0])],
+            ..Default::default()
+        };
+
+        let map = Map::from_heightmap(&samples, 5, 3, &options).unwrap();
+        assert_eq!(map.sectors.len(), 2);
+
+        let floor = &map.sectors[0].floor_mesh;
+        assert_eq!(floor.vertex_buffer.len(), 9);
+        assert_eq!(floor.index_buffer.len(), 2 * 2 * 6);
+        assert_eq!(floor.vertex_buffer[4].pos, [2.0, 25.5, 2.0]);
+        assert_eq!(floor.vertex_buffer[4].color, [1.0; 4]);
+        assert_eq!(floor.vertex_buffer[0].color, [0.0, 0.0, 0.0, 1.0]);
+        assert!(map.sectors[0].ceiling_mesh.vertex_buffer.is_empty());
+
+        // The floors face up
+        for sector in &map.sectors {
+            for triangle in 0..sector.floor_mesh.index_buffer.len() / 3 {
+                let normal = sector.floor_mesh.face_normal(triangle).unwrap();
+                assert!(normal[1] > 0.0);
+            }
+        }
+
+        // The second sector continues where the first one stops
+        let last = map.sectors[1].floor_mesh.vertex_buffer.last().unwrap();
+        assert_eq!(last.pos, [8.0, 12.8, 4.0]);
+        assert_eq!(last.uv, [1.0, 1.0]);
+
+        // 16 bit samples work as well
+        let samples = [0u16, 1000, 2000, 3000];
+        assert_eq!(Map::from_heightmap(&samples, 2, 2, &options).unwrap()
+                       .sectors.len(), 1);
+
+        let error = Map::from_heightmap(&samples, 3, 2, &options).unwrap_err();
+        assert!(matches!(error, Error::InvalidHeightmapSize(4)));
+    }
+
+    #[test]
+    fn csg_brushes() {
+        use crate::{ Plane, Brush, Solid, CsgOptions, Mesh };
+
+        let cuboid = |min: [f32; 3], max: [f32; 3]| {
+            Solid::from(Brush::new(vec![
+                Plane::new([1.0, 0.0, 0.0], max[0]),
+                Plane::new([-1.0, 0.0, 0.0], -min[0]),
+                Plane::new([0.0, 1.0, 0.0], max[1]),
+                Plane::new([0.0, -1.0, 0.0], -min[1]),
+                Plane::new([0.0, 0.0, 1.0], max[2]),
+                Plane::new([0.0, 0.0, -1.0], -min[2]),
+            ]))
+        };
+
+        let area = |mesh: &Mesh| {
+            (0..mesh.index_buffer.len() / 3)
+                .map(|triangle| {
+                    let [a, b, c] = [0, 1, 2].map(|i| {
+                        mesh.vertex_buffer[
+                            mesh.index_buffer[triangle * 3 + i] as usize].pos
+                    });
+                    let n = crate::math::triangle_normal(a, b, c);
+                    crate::math::length(n) * 0.5
+                })
+                .sum::<f32>()
+        };
+
+        let room = cuboid([0.0, 0.0, 0.0], [4.0, 3.0, 4.0]);
+        assert_eq!(room.brushes.len(), 1);
+        assert_eq!(room.faces().len(), 6);
+
+        // Planes that don't enclose a volume give an empty solid
+        let slab = cuboid([0.0, 0.0, 0.0], [4.0, 0.0, 4.0]);
+        assert!(slab.is_empty());
+
+        // A corridor leaving the room, the faces between them are removed
+        let corridor = cuboid([4.0, 0.0, 1.0], [8.0, 2.0, 3.0]);
+        let level = room.union(&corridor);
+        assert!(level.contains([6.0, 1.0, 2.0]));
+        assert!(!level.contains([6.0, 2.5, 2.0]));
+
+        let sector = level.compile(&CsgOptions::default());
+        assert!((area(&sector.floor_mesh) - 24.0).abs() < 1e-3);
+        assert!((area(&sector.ceiling_mesh) - 24.0).abs() < 1e-3);
+
+        // The room has 4 * 3 * 4 units of walls, the opening to the
+        // corridor is 2 * 2 units and the corridor adds 4 * 2 * 2 + 2 * 2
+        let walls = 4.0 * 3.0 * 4.0 - 2.0 * 2.0 + 4.0 * 2.0 * 2.0 + 2.0 * 2.0;
+        assert!((area(&sector.wall_mesh) - walls).abs() < 1e-3);
+
+        // Every face faces into the open space
+        let meshes = [(&sector.floor_mesh, 1.0), (&sector.ceiling_mesh, -1.0)];
+        for (mesh, up) in meshes {
+            for triangle in 0..mesh.index_buffer.len() / 3 {
+                let normal = mesh.face_normal(triangle).unwrap();
+                assert!((normal[1] - up).abs() < 1e-4);
+            }
+        }
+
+        // A pillar carved out of the room
+        let pillar = cuboid([1.0, 0.0, 1.0], [2.0, 3.0, 2.0]);
+        let level = level.subtract(&pillar);
+        assert!(!level.contains([1.5, 1.0, 1.5]));
+        assert!(level.contains([0.5, 1.0, 1.5]));
+
+        let sector = level.compile(&CsgOptions::default());
+        assert!((area(&sector.floor_mesh) - 23.0).abs() < 1e-3);
+        assert!((area(&sector.wall_mesh) - (walls + 4.0 * 3.0)).abs() < 1e-3);
+
+        let center = [1.5, 1.5, 1.5];
+        let mesh = &sector.wall_mesh;
+        for triangle in 0..mesh.index_buffer.len() / 3 {
+            let normal = mesh.face_normal(triangle).unwrap();
+            let point = mesh.vertex_buffer[
+                mesh.index_buffer[triangle * 3] as usize].pos;
+            let on_pillar = (1.0..=2.0).contains(&point[0]) &&
+                (1.0..=2.0).contains(&point[2]);
+            if on_pillar {
+                let to_pillar = crate::math::sub(center, point);
+                assert!(crate::math::dot(normal, to_pillar) < 0.0);
+            }
+        }
+
+        // Only the part of the corridor inside the box is left
+        let cut = cuboid([3.0, 0.0, 0.0], [6.0, 3.0, 4.0]);
+        let part = level.intersect(&cut);
+        assert!(part.contains([3.5, 2.5, 0.5]));
+        assert!(part.contains([5.5, 1.5, 2.0]));
+        assert!(!part.contains([6.5, 1.5, 2.0]));
+        assert!(!part.contains([2.5, 1.5, 2.0]));
+    }
+
+    #[test]
+    fn primitive_brushes() {
+        use crate::{ Brush, Solid, CsgOptions };
+
+        let cuboid = Brush::cuboid([0.0, 0.0, 0.0], [2.0, 1.0, 3.0]);
+        assert_eq!(cuboid.faces().len(), 6);
+        assert!(cuboid.contains([1.9, 0.9, 2.9]));
+        assert!(!cuboid.contains([2.1, 0.5, 1.0]));
+
+        let wedge = Brush::wedge([0.0, 0.0, 0.0], [4.0, 2.0, 1.0]);
+        assert_eq!(wedge.faces().len(), 5);
+        assert!(wedge.contains([3.0, 1.0, 0.5]));
+        assert!(!wedge.contains([1.0, 1.0, 0.5]));
+
+        let cylinder = Brush::cylinder([1.0, 0.0, 1.0], 2.0, 3.0, 8);
+        assert_eq!(cylinder.faces().len(), 10);
+        assert!(cylinder.contains([1.0, 1.5, 2.9]));
+        assert!(!cylinder.contains([1.0, 3.5, 1.0]));
+        assert!(!cylinder.contains([2.5, 1.0, 2.5]));
+
+        // Less than three sides still gives a volume
+        assert_eq!(Brush::cylinder([0.0; 3], 1.0, 1.0, 1).faces().len(), 5);
+
+        let options = CsgOptions { floor_texture: 7, ..Default::default() };
+        let sector = cylinder.compile_to_sector(&options);
+        assert_eq!(sector.floor_mesh.texture_id, 7);
+        assert_eq!(sector.floor_mesh.vertex_buffer.len(), 8);
+        assert_eq!(sector.wall_mesh.index_buffer.len(), 8 * 6);
+
+        // Seen from the open space the slope is a sloped ceiling
+        let sector = wedge.compile_to_sector(&options);
+        assert_eq!(sector.floor_mesh.index_buffer.len(), 6);
+        assert_eq!(sector.ceiling_mesh.index_buffer.len(), 6);
+
+        // A steep slope is a wall
+        let steep = Brush::wedge([0.0, 0.0, 0.0], [1.0, 4.0, 1.0]);
+        let sector = steep.compile_to_sector(&options);
+        assert_eq!(sector.ceiling_mesh.index_buffer.len(), 0);
+        assert_eq!(sector.wall_mesh.index_buffer.len(), 6 + 3 + 3 + 6);
+
+        // A room with a pillar
+        let room = Solid::from(Brush::cuboid([-4.0, 0.0, -4.0],
+                                             [4.0, 3.0, 4.0]));
+        let pillar = Solid::from(Brush::cylinder([0.0; 3], 1.0, 3.0, 6));
+        let level = room.subtract(&pillar);
+        assert!(!level.contains([0.0, 1.0, 0.0]));
+        assert!(level.contains([3.0, 1.0, 3.0]));
+    }
+
+    #[test]
+    fn grid_snapping() {
+        use crate::{ snap_to_grid, UvSet };
+
+        assert_eq!(snap_to_grid([0.26, -0.1, 1.74], 0.5), [0.5, 0.0, 1.5]);
+        assert!(snap_to_grid([-0.1; 3], 0.5)[0].is_sign_positive());
+        assert_eq!(snap_to_grid([0.26; 3], 0.0), [0.26; 3]);
+        assert_eq!(snap_to_grid([0.26; 3], f32::NAN), [0.26; 3]);
+
+        // Two triangles with their own corners that almost touch
+        let white = [1.0; 4];
+        let vertex_buffer = vec![
+            Vertex::new([0.0, 0.0, 0.0], [0.0, 0.0], white),
+            Vertex::new([0.0, 1.02, 0.0], [0.0, 0.0], white),
+            Vertex::new([0.98, 1.0, 0.0], [0.0, 0.0], white),
+            Vertex::new([1.01, 1.0, 0.0], [0.0, 0.0], white),
+            Vertex::new([1.0, 0.03, 0.0], [0.0, 0.0], white),
+            Vertex::new([0.01, 0.0, 0.0], [0.0, 0.0], white),
+        ];
+        let mut mesh = Mesh::new(vertex_buffer, vec![0, 1, 2, 3, 4, 5], 0);
+        mesh.lightmap_uvs = vec![[0.0, 0.0]; 6];
+        mesh.uv_sets.push(UvSet::new("detail",
+                                     vec![[0.0, 0.0], [0.0, 0.0], [0.0, 0.0],
+                                          [0.0, 0.0], [0.0, 0.0], [1.0, 0.0]]));
+
+        let mut sector = Sector::new(mesh, quad_mesh(), quad_mesh());
+        sector.floor_mesh.vertex_buffer[0].snap_to_grid(0.25);
+        assert_eq!(sector.floor_mesh.vertex_buffer[0].pos, [0.0; 3]);
+
+        // The last corner has other detail texture coordinates and stays
+        let mut map = Map::new(vec![sector]);
+        assert_eq!(map.snap_to_grid(0.25), 1);
+
+        let floor = &map.sectors[0].floor_mesh;
+        assert_eq!(floor.vertex_buffer.len(), 5);
+        assert_eq!(floor.index_buffer, vec![0, 1, 2, 2, 3, 4]);
+        assert_eq!(floor.vertex_buffer[2].pos, [1.0, 1.0, 0.0]);
+        assert_eq!(floor.vertex_buffer[4].pos, [0.0, 0.0, 0.0]);
+        assert_eq!(floor.lightmap_uvs.len(), 5);
+        assert_eq!(floor.uv_sets[0].uvs[4], [1.0, 0.0]);
+        assert!(map.dirty_sectors().any(|index| index == 0));
+
+        // Snapping again doesn't change anything
+        assert_eq!(map.snap_to_grid(0.25), 0);
+        assert_eq!(map.sectors[0].floor_mesh.vertex_buffer.len(), 5);
+    }
+
+    #[test]
+    fn static_props() {
+        use crate::{ PropInstance, Error };
+
+        let mut map = Map::new(vec![quad_sector()]);
+        let pillar = map.add_library_mesh(quad_mesh());
+        assert_eq!(map.add_library_mesh(quad_mesh()), pillar);
+        assert_eq!(map.mesh_library.len(), 1);
+
+        for i in 0..500 {
+            let mut translation = crate::transform::IDENTITY;
+            translation[3] = [i as f32 * 2.0, 0.0, 0.0, 1.0];
+            map.add_prop(pillar, &translation).unwrap();
+        }
+
+        let error = map.add_prop(1, &crate::transform::IDENTITY).unwrap_err();
+        assert!(matches!(error, Error::InvalidPropMesh(1)));
+
+        // The vertices of the pillar are stored once
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), map.serialized_size());
+        assert!(buffer.len() < 500 * quad_mesh().serialized_size());
+        assert_eq!(Map::deserialize(&buffer).unwrap(), map);
+
+        assert_eq!(map.prop_mesh(&map.props[3]), Some(&quad_mesh()));
+
+        // Moving the map moves the props but not the library meshes
+        let mut translation = crate::transform::IDENTITY;
+        translation[3] = [0.0, 5.0, 0.0, 1.0];
+        map.transform(&translation);
+        assert_eq!(map.props[3].transform[3], [6.0, 5.0, 0.0, 1.0]);
+        assert_eq!(map.mesh_library[0], quad_mesh());
+
+        // Merged props point at the appended library meshes
+        let mut other = Map::new(Vec::new());
+        let mut mesh = quad_mesh();
+        mesh.texture_id = 9;
+        let crate_mesh = other.add_library_mesh(mesh);
+        other.add_prop(crate_mesh, &crate::transform::IDENTITY).unwrap();
+        map.merge(other, Some(&translation)).unwrap();
+        assert_eq!(map.mesh_library.len(), 2);
+        assert_eq!(map.props[500].mesh, 1);
+        assert_eq!(map.props[500].transform[3], [0.0, 5.0, 0.0, 1.0]);
+
+        // A prop placing a mesh outside of the library is rejected
+        let mut map = Map::new(Vec::new());
+        map.props.push(PropInstance::new(0, &crate::transform::IDENTITY));
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+        let error = Map::deserialize(&buffer).unwrap_err();
+        assert!(matches!(error.kind(), Error::InvalidPropMesh(0)));
+    }
+
+    #[test]
+    fn mesh_library_dedup() {
+        use crate::{ PropInstance, Error };
+        use crate::transform::IDENTITY;
+
+        // Every tile of the floor places its own copy of the same mesh
+        let mut map = Map::new(vec![quad_sector(), quad_sector()]);
+        for _i in 0..10 {
+            map.mesh_library.push(quad_mesh());
+        }
+        let mut other = quad_mesh();
+        other.vertex_buffer[0].pos = [0.0, 0.0, 1.0];
+        map.mesh_library.push(other.clone());
+
+        for (index, sector) in map.sectors.iter_mut().enumerate() {
+            for mesh in 0..5 {
+                let mesh = (index * 5 + mesh) as u32;
+                sector.props.push(PropInstance::new(mesh, &IDENTITY));
+            }
+        }
+        map.add_prop(10, &IDENTITY).unwrap();
+
+        // The copies are saved once and loaded with the same indices
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), map.serialized_size());
+        assert!(buffer.len() < 3 * quad_mesh().serialized_size() +
+                map.sectors.iter()
+                    .map(|sector| sector.serialized_size())
+                    .sum::<usize>() + 1024);
+        let loaded = Map::deserialize(&buffer).unwrap();
+        assert_eq!(loaded, map);
+        assert_eq!(loaded.sectors[1].props[4].mesh, 9);
+
+        // Deduplicating the library in memory points the props at the
+        // first copy
+        assert_eq!(map.dedup_mesh_library(), 9);
+        assert_eq!(map.mesh_library, vec![quad_mesh(), other]);
+        assert!(map.sectors.iter()
+                .flat_map(|sector| &sector.props)
+                .all(|prop| prop.mesh == 0));
+        assert_eq!(map.props[0].mesh, 1);
+        assert_eq!(map.dedup_mesh_library(), 0);
+
+        // Sector props must place a mesh of the library
+        map.sectors[0].props.push(PropInstance::new(2, &IDENTITY));
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+        let error = Map::deserialize(&buffer).unwrap_err();
+        assert!(matches!(error.kind(), Error::InvalidPropMesh(2)));
+    }
+
+    #[test]
+    fn sector_occluders() {
+        use crate::Occluder;
+        use crate::transform::IDENTITY;
+
+        let mut sector = quad_sector();
+        let occluder = Occluder::from_mesh(&sector.wall_mesh);
+        assert_eq!(occluder.vertices.len(), 4);
+        assert_eq!(occluder.indices, vec![0, 1, 2, 2, 3, 0]);
+        assert_eq!(occluder.triangles().count(), 2);
+
+        // Occluders aren't render geometry
+        sector.occluders.push(occluder);
+        sector.occluders.push(Occluder::new(vec![[0.0; 3]], vec![0, 0, 5]));
+        assert_eq!(sector.occluders[1].triangles().count(), 0);
+        assert_eq!(sector.meshes().count(), 3);
+
+        let mut buffer = Vec::new();
+        sector.serialize(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), sector.serialized_size());
+        assert_eq!(Sector::deserialize(&buffer).unwrap(), sector);
+
+        // Mirroring moves the corners and keeps the triangles facing out
+        let mut m = IDENTITY;
+        m[0][0] = -1.0;
+        m[3] = [0.0, 2.0, 0.0, 1.0];
+        sector.transform(&m);
+        let occluder = &sector.occluders[0];
+        assert_eq!(occluder.vertices[2], [-1.0, 3.0, 0.0]);
+        assert_eq!(&occluder.indices[..3], &[0, 2, 1]);
+    }
+
+    #[test]
+    fn decals() {
+        use crate::{ Decal, SectorId };
+        use crate::transform::IDENTITY;
+
+        let mut map = full_map();
+        let target = map.sectors[0].id();
+        let decal = Decal::new(target, 0, [0.5, 0.5, 0.0], [0.0, 0.0, 1.0],
+                               [0.0, 1.0, 0.0], [0.25, 0.5, 0.1]);
+        assert_eq!(decal.right(), [1.0, 0.0, 0.0]);
+        map.decals.push(decal);
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), map.serialized_size());
+        let loaded = Map::deserialize(&buffer).unwrap();
+        assert_eq!(loaded.decals, vec![decal]);
+        assert_eq!(loaded.sector_decals(target).count(), 1);
+        assert_eq!(loaded.sector_decals(SectorId(99)).count(), 0);
+
+        // Scaling along the width of the decal only stretches the width
+        let mut scaled = map.clone();
+        let mut m = IDENTITY;
+        m[0][0] = 2.0;
+        scaled.transform(&m);
+        assert_eq!(scaled.decals[0].position, [1.0, 0.5, 0.0]);
+        assert_eq!(scaled.decals[0].size, [0.5, 0.5, 0.1]);
+        assert_eq!(scaled.decals[0].normal, [0.0, 0.0, 1.0]);
+
+        // Merged decals follow their sector and material
+        let mut merged = map.clone();
+        let materials = merged.materials.len() as u32;
+        let sectors = merged.merge(map.clone(), None).unwrap();
+        let decal = merged.decals[1];
+        assert_eq!(decal.sector, merged.sectors[sectors.start].id());
+        assert_eq!(decal.material, materials);
+    }
+
+    #[test]
+    fn sector_liquids() {
+        use crate::{ Liquid, LiquidKind, Error };
+
+        let mut sector = quad_sector();
+        let mut liquid = Liquid::new(LiquidKind::Lava, 0.5,
+                                     [1.0, 0.3, 0.0, 1.0]);
+        liquid.flow = [0.0, 0.0, 2.0];
+        liquid.fog_density = 0.8;
+        sector.liquid = Some(liquid);
+        assert_eq!(liquid.depth([0.0, 0.25, 0.0]), 0.25);
+        assert!(liquid.depth([0.0, 1.0, 0.0]) < 0.0);
+
+        let mut buffer = Vec::new();
+        sector.serialize(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), sector.serialized_size());
+        assert_eq!(Sector::deserialize(&buffer).unwrap(), sector);
+
+        // Raising the sector raises the surface, rotating it turns the flow
+        let mut m = crate::transform::IDENTITY;
+        m[0] = [0.0, 0.0, -1.0, 0.0];
+        m[2] = [1.0, 0.0, 0.0, 0.0];
+        m[3] = [0.0, 2.0, 0.0, 1.0];
+        sector.transform(&m);
+        let moved = sector.liquid.unwrap();
+        assert_eq!(moved.surface_height, 2.5);
+        assert_eq!(moved.flow, [2.0, 0.0, 0.0]);
+
+        // The liquid is followed by the environment, the acoustics and the
+        // flags
+        let at = buffer.len() - crate::liquid::LIQUID_SIZE -
+            crate::environment::ENVIRONMENT_OVERRIDE_SIZE -
+            crate::sound::ACOUSTICS_SIZE - 4;
+        buffer[at] = 9;
+        let error = Sector::deserialize(&buffer).unwrap_err();
+        assert!(matches!(error.kind(), Error::UnknownLiquidKind(9)));
+    }
+
+    #[test]
+    fn sector_environment() {
+        use crate::{ Environment, EnvironmentOverride };
+
+        let outdoor = Environment {
+            fog_color: [0.6, 0.7, 0.9],
+            fog_density: 0.01,
+            ambient_color: [0.4, 0.4, 0.4],
+        };
+
+        let mut map = Map::new(vec![quad_sector(), quad_sector()]);
+        map.environment = outdoor;
+        map.sectors[1].environment = EnvironmentOverride {
+            fog_density: Some(0.2),
+            ambient_color: Some([0.05, 0.05, 0.1]),
+            ..Default::default()
+        };
+        assert!(map.sectors[0].environment.is_empty());
+
+        // The cave inherits the fog color of the map
+        assert_eq!(map.sector_environment(0), Some(outdoor));
+        let cave = map.sector_environment(1).unwrap();
+        assert_eq!(cave.fog_color, outdoor.fog_color);
+        assert_eq!(cave.fog_density, 0.2);
+        assert_eq!(cave.ambient_color, [0.05, 0.05, 0.1]);
+        assert_eq!(map.sector_environment(2), None);
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), map.serialized_size());
+        let loaded = Map::deserialize(&buffer).unwrap();
+        assert_eq!(loaded, map);
+
+        // Merged sectors keep the environment of the map they came from
+        let mut merged = Map::new(vec![quad_sector()]);
+        let sectors = merged.merge(map, None).unwrap();
+        assert_eq!(merged.sector_environment(0), Some(Environment::default()));
+        assert_eq!(merged.sector_environment(sectors.start), Some(outdoor));
+        assert_eq!(merged.sector_environment(sectors.start + 1), Some(cave));
+    }
+
+    #[test]
+    fn sector_acoustics() {
+        use crate::{ Acoustics, ReverbPreset, Error };
+
+        let mut sector = quad_sector();
+        assert_eq!(sector.acoustics.reverb, ReverbPreset::Generic);
+        sector.acoustics = Acoustics {
+            reverb: ReverbPreset::Cave,
+            occlusion: 0.75,
+        };
+
+        let mut buffer = Vec::new();
+        sector.serialize(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), sector.serialized_size());
+        assert_eq!(Sector::deserialize(&buffer).unwrap().acoustics,
+                   sector.acoustics);
+
+        for value in 0..=6 {
+            let preset = ReverbPreset::from_u8(value).unwrap();
+            assert_eq!(preset.to_u8(), value);
+        }
+
+        // The acoustics are followed by the flags
+        let at = buffer.len() - crate::sound::ACOUSTICS_SIZE - 4;
+        buffer[at] = 7;
+        let error = Sector::deserialize(&buffer).unwrap_err();
+        assert!(matches!(error.kind(), Error::UnknownReverbPreset(7)));
+    }
+
+    #[test]
+    fn sector_flags() {
+        use crate::SectorFlags;
+
+        let mut map = Map::new(vec![quad_sector(), quad_sector()]);
+        let game = SectorFlags::game(3).unwrap();
+        assert_eq!(game.bits(), 1 << 19);
+        assert!(SectorFlags::GAME.contains(game));
+        assert_eq!(SectorFlags::game(16), None);
+
+        map.sectors[1].set_flags(SectorFlags::SECRET |
+                                 SectorFlags::DAMAGE_FLOOR, true);
+        map.sectors[1].set_flags(SectorFlags::DAMAGE_FLOOR, false);
+        map.sectors[1].set_flags(game, true);
+        assert!(map.sectors[1].has_flags(SectorFlags::SECRET | game));
+        assert!(!map.sectors[1].has_flags(SectorFlags::DAMAGE_FLOOR));
+
+        let secrets = map.sectors_with_flags(SectorFlags::SECRET)
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        assert_eq!(secrets, vec![1]);
+
+        // Reserved bits unknown to this version are kept
+        let unknown = SectorFlags::from_bits_retain(1 << 15);
+        map.sectors[0].flags = unknown;
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+        let loaded = Map::deserialize(&buffer).unwrap();
+        assert_eq!(loaded.sectors[0].flags, unknown);
+        assert_eq!(loaded.sectors[1].flags, SectorFlags::SECRET | game);
+
+        let diff = map.diff(&loaded);
+        assert!(diff.is_empty());
+        map.sectors[0].flags = SectorFlags::OUTDOOR;
+        assert!(map.diff(&loaded).changed_sectors[0].flags_changed);
+    }
+
+    #[test]
+    fn interactive_objects() {
+        use crate::{ Entity, Interactive, InteractiveKind, InteractionTarget,
+                     Error };
+
+        let mut map = Map::new(vec![quad_sector(), quad_sector()]);
+        map.sectors[1].tag = 5;
+        map.entities.push(Entity::new("func_door", [0.0; 3]));
+
+        let switch = Interactive::new(InteractiveKind::Switch,
+                                      [1.0, 0.0, 0.0], "open")
+            .with_target(InteractionTarget::Tag(5))
+            .with_target(InteractionTarget::Entity(0));
+        map.interactives.push(switch.clone());
+
+        assert_eq!(map.interactive_sectors(&switch), vec![1]);
+        let entities = map.interactive_entities(&switch)
+            .map(|entity| entity.class.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(entities, vec!["func_door"]);
+
+        let mut buffer = Vec::new();
+        switch.serialize(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), switch.serialized_size());
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+        assert_eq!(Map::deserialize(&buffer).unwrap(), map);
+
+        // Merging offsets the tags and the entities of the other map
+        let mut merged = map.clone();
+        merged.merge(map.clone(), None).unwrap();
+        let target = &merged.interactives[1];
+        assert_eq!(merged.interactive_sectors(target), vec![3]);
+        assert_eq!(target.targets[1], InteractionTarget::Entity(1));
+
+        map.interactives[0].targets.push(InteractionTarget::Entity(1));
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+        let error = Map::deserialize(&buffer).unwrap_err();
+        assert!(matches!(error.kind(), Error::InvalidTargetEntity(1)));
+    }
+
+    #[test]
+    fn script_hooks() {
+        use crate::{ Entity, Trigger, TriggerShape, ScriptHook, ScriptTarget,
+                     Error };
+
+        let mut map = Map::new(vec![quad_sector(), quad_sector()]);
+        let shape = TriggerShape::Aabb { min: [0.0; 3], max: [1.0; 3] };
+        map.triggers.push(Trigger::new(shape, 0, "", 0));
+        map.entities.push(Entity::new("npc_guard", [0.0; 3]));
+
+        let sector = ScriptTarget::Sector(map.sectors[1].id());
+        map.scripts.source = Some("fn on_enter() {}\n".repeat(10000));
+        map.scripts.hooks = vec![
+            ScriptHook::new(sector, "enter", "on_enter"),
+            ScriptHook::new(ScriptTarget::Trigger(0), "touch", "on_touch"),
+            ScriptHook::new(ScriptTarget::Entity(0), "death", "on_death"),
+        ];
+
+        assert_eq!(map.script_function(sector, "enter"), Some("on_enter"));
+        assert_eq!(map.script_function(sector, "leave"), None);
+        assert_eq!(map.script_hooks(ScriptTarget::Entity(0)).count(), 1);
+
+        // The source is longer than the other strings can be
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), map.serialized_size());
+        assert_eq!(Map::deserialize(&buffer).unwrap(), map);
+
+        let mut merged = map.clone();
+        let sectors = merged.merge(map.clone(), None).unwrap();
+        let id = merged.sectors[sectors.start + 1].id();
+        assert_eq!(merged.script_hooks(ScriptTarget::Sector(id)).count(), 1);
+        assert_eq!(merged.script_function(ScriptTarget::Trigger(1), "touch"),
+                   Some("on_touch"));
+        assert_eq!(merged.scripts.source.as_ref().unwrap().len(),
+                   2 * map.scripts.source.as_ref().unwrap().len() + 1);
+
+        map.scripts.hooks.push(
+            ScriptHook::new(ScriptTarget::Entity(3), "use", "on_use"));
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+        let error = Map::deserialize(&buffer).unwrap_err();
+        assert!(matches!(error.kind(),
+                         Error::InvalidScriptTarget(ScriptTarget::Entity(3))));
+    }
+
+    #[test]
+    fn compression_codecs() {
+        use crate::Error;
+
+        let mut map = full_map();
+        let codecs = [SectorCompression::None, SectorCompression::Lz4,
+                      SectorCompression::Zstd, SectorCompression::Deflate];
+        for (id, codec) in codecs.into_iter().enumerate() {
+            map.compression = codec;
+
+            if !codec.is_supported() {
+                let error = map.save_to_bytes().err().unwrap();
+                assert!(matches!(error.kind(),
+                                 Error::UnsupportedCompression(_)));
+                continue;
+            }
+
+            let bytes = map.save_to_bytes().unwrap();
+            let mut buffer = Vec::new();
+            map.serialize(&mut buffer).unwrap();
+            assert_eq!(buffer.len(), map.serialized_size());
+            assert!(buffer.len() <= map.max_serialized_size());
+
+            // The codec is recorded after the file header and the sector
+            // count, chunk offset and next sector ID of the map
+            assert_eq!(bytes[24 + 8 * 3], id as u8);
+
+            let loaded = Map::load_from_bytes(&bytes).unwrap();
+            assert_eq!(loaded.compression, codec);
+            assert_eq!(loaded.content_hash().unwrap(),
+                       map.content_hash().unwrap());
+
+            let lazy = LazyMap::from_bytes(bytes.clone()).unwrap();
+            assert_eq!(lazy.into_map().unwrap().compression, codec);
+
+            // Maps using a codec that isn't enabled fail before the sectors
+            // are read
+            for (other, codec) in codecs.into_iter().enumerate() {
+                let mut bytes = bytes.clone();
+                bytes[24 + 8 * 3] = other as u8;
+                if !codec.is_supported() {
+                    let error = Map::load_from_bytes(&bytes).err().unwrap();
+                    assert!(matches!(error.kind(),
+                                     Error::UnsupportedCompression(_)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sector_checksums() {
+        use crate::Error;
+
+        let map = full_map();
+        let bytes = map.save_to_bytes().unwrap();
+        let mut lazy = LazyMap::from_bytes(bytes.clone()).unwrap();
+        assert!(lazy.corrupt_sectors().unwrap().is_empty());
+
+        // Flip a byte inside the mesh of the second sector, the sector
+        // still deserializes but doesn't match its checksum
+        let entry = 24 + MAP_HEADER_SIZE + SECTOR_ENTRY_SIZE;
+        let sector = 24 + u64::from_le_bytes(
+            bytes[entry..entry + 8].try_into().unwrap()) as usize;
+        let mut corrupt = bytes.clone();
+        corrupt[sector + 60] ^= 0x01;
+
+        let error = Map::load_from_bytes(&corrupt).err().unwrap();
+        assert!(matches!(error.kind(), Error::SectorChecksumMismatch));
+        assert_eq!(error.context().unwrap().sector, Some(1));
+
+        let mut lazy = LazyMap::from_bytes(corrupt.clone()).unwrap();
+        assert_eq!(lazy.corrupt_sectors().unwrap(), vec![1]);
+        assert!(lazy.sector(0).is_ok());
+        assert!(lazy.sector(1).is_err());
+
+        let streamed = Map::sector_stream(&corrupt[..])
+            .collect::<Vec<_>>();
+        assert!(streamed[0].is_ok());
+        assert!(streamed[1].is_err());
+    }
+
+    #[test]
+    fn map_recovery() {
+        use crate::{ Error, Pvs };
+
+        let mut map = full_map();
+        map.add_sector(quad_sector());
+        map.pvs = Some(Pvs::new(&[vec![0], vec![1], vec![1, 2]]));
+        let bytes = map.save_to_bytes().unwrap();
+        let (recovered, report) = Map::recover_from_bytes(&bytes).unwrap();
+        assert!(report.is_empty());
+        assert_eq!(recovered.content_hash().unwrap(),
+                   map.content_hash().unwrap());
+
+        // Corrupt the second sector and make the units per meter invalid
+        let entry = 24 + MAP_HEADER_SIZE + SECTOR_ENTRY_SIZE;
+        let sector = 24 + u64::from_le_bytes(
+            bytes[entry..entry + 8].try_into().unwrap()) as usize;
+        let mut corrupt = bytes.clone();
+        corrupt[sector + 60] ^= 0x01;
+
+        let units = corrupt.windows(4).rposition(|tag| tag == b"UNIT")
+            .unwrap() + 4 + 8;
+        corrupt[units..units + 4].copy_from_slice(&(-1.0f32).to_le_bytes());
+        assert!(Map::load_from_bytes(&corrupt).is_err());
+
+        let (recovered, report) = Map::recover_from_bytes(&corrupt).unwrap();
+        assert_eq!(recovered.sectors.len(), map.sectors.len());
+        assert_eq!(recovered.sectors[0].id(), map.sectors[0].id());
+        assert_eq!(recovered.sectors[2].id(), map.sectors[2].id());
+        assert_eq!(recovered.lights, map.lights);
+
+        // The damaged sector is replaced so the sets keep their sectors
+        assert_eq!(recovered.sectors[1].floor_mesh, Mesh::default());
+        let placeholder = recovered.sectors[1].id();
+        assert!(map.sectors.iter().all(|sector| sector.id() != placeholder));
+        assert_eq!(recovered.pvs, map.pvs);
+        assert!(recovered.is_sector_potentially_visible(2, 1));
+        assert!(!recovered.is_sector_potentially_visible(2, 0));
+
+        assert_eq!(report.sectors.len(), 1);
+        assert_eq!(report.sectors[0].index, 1);
+        assert!(matches!(report.sectors[0].error.kind(),
+                         Error::SectorChecksumMismatch));
+
+        assert_eq!(report.chunks.len(), 1);
+        assert_eq!(report.chunks[0].tag, Some(*b"UNIT"));
+        assert_eq!(report.chunks[0].error.context().unwrap().offset,
+                   units - 24);
+
+        // A truncated file keeps the sectors before the cut
+        let (recovered, report) =
+            Map::recover_from_bytes(&bytes[..sector + 10]).unwrap();
+        assert_eq!(recovered.sectors.len(), map.sectors.len());
+        assert_eq!(recovered.sectors[0].id(), map.sectors[0].id());
+        assert_eq!(report.sectors.len(), map.sectors.len() - 1);
+        assert_eq!(report.chunks[0].tag, None);
+    }
+
+    #[test]
+    fn unknown_chunks() {
+        use crate::{ Error, UnknownChunk };
+
+        // A file written by a newer minor version with a chunk this version
+        // doesn't know
+        let mut newer = full_map();
+        newer.unknown_chunks.push(UnknownChunk {
+            tag: *b"NEW1",
+            data: vec![1, 2, 3, 4],
+        });
+        let mut bytes = newer.save_to_bytes().unwrap();
+        bytes[4..8].copy_from_slice(&(CURRENT_VERSION + 1).to_le_bytes());
+
+        let mut loaded = Map::load_from_bytes(&bytes).unwrap();
+        assert_eq!(loaded, newer);
+        assert_eq!(loaded.unknown_chunk(b"NEW1"), Some(&[1, 2, 3, 4][..]));
+        assert_eq!(loaded.unknown_chunk(b"NEW2"), None);
+
+        assert_eq!(Mime::deserialize(&bytes).unwrap().maps()[0], newer);
+        assert!(LazyMap::from_bytes(bytes.clone()).is_ok());
+        assert!(Map::recover_from_bytes(&bytes).unwrap().1.is_empty());
+
+        // Editing and saving again keeps the chunk
+        loaded.lights.clear();
+        let saved = loaded.save_to_bytes().unwrap();
+        assert_eq!(&saved[4..8], &CURRENT_VERSION.to_le_bytes());
+        let reloaded = Map::load_from_bytes(&saved).unwrap();
+        assert_eq!(reloaded.unknown_chunks, newer.unknown_chunks);
+
+        // A newer major version can't be read
+        let major = CURRENT_VERSION + (1 << 16);
+        bytes[4..8].copy_from_slice(&major.to_le_bytes());
+        let error = Map::load_from_bytes(&bytes).err().unwrap();
+        assert!(matches!(error, Error::IncorrectVersion(version)
+                         if version == major));
+
+        let mut buffer = Vec::new();
+        reloaded.serialize(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), reloaded.serialized_size());
+    }
+
+    #[test]
+    #[cfg(feature = "testgen")]
+    fn generated_maps() {
+        use crate::testgen::GeneratorOptions;
+
+        let options = GeneratorOptions {
+            sectors: 10,
+            vertices_per_mesh: 7,
+            lods: 1,
+            lights: 2,
+            entities: 3,
+            seed: Some(5),
+        };
+        let map = Map::generate(&options);
+        assert_eq!(map.sectors.len(), 10);
+        assert_eq!(map.lights.len(), 2);
+        assert_eq!(map.entities.len(), 3);
+
+        for sector in &map.sectors {
+            assert_eq!(sector.lods.len(), 1);
+            assert_eq!(sector.floor_mesh.vertex_buffer.len(), 7);
+            assert_eq!(sector.wall_mesh.index_buffer.len(), 5 * 3);
+            assert!(sector.floor_mesh.degenerate_triangles().is_empty());
+        }
+
+        // The seed decides the map
+        assert_eq!(Map::generate(&options), map);
+        let other = Map::generate(&GeneratorOptions {
+            seed: Some(6),
+            ..options.clone()
+        });
+        assert_ne!(other, map);
+
+        let flat = Map::generate(&GeneratorOptions::default());
+        assert_eq!(flat.sectors.len(), 16);
+        assert!(flat.sectors[0].floor_mesh.vertex_buffer.iter()
+            .all(|vertex| vertex.pos[1] == 0.0 && vertex.color == [1.0; 4]));
+
+        let bytes = map.save_to_bytes().unwrap();
+        assert_eq!(Map::load_from_bytes(&bytes).unwrap(), map);
+    }
+
+    #[cfg(feature = "test-util")]
+    proptest::proptest! {
+        #[test]
+        fn generated_maps_load(map in crate::test_util::map()) {
+            let bytes = map.save_to_bytes().unwrap();
+            proptest::prop_assert_eq!(Map::load_from_bytes(&bytes).unwrap(),
+                                      map);
+        }
+
+        #[test]
+        fn generated_meshes_are_valid(
+            mesh in crate::test_util::mesh_with(1..4, 0..8))
+        {
+            let count = mesh.vertex_buffer.len() as u32;
+            proptest::prop_assert!(mesh.index_buffer.len() % 3 == 0);
+            proptest::prop_assert!(mesh.index_buffer.iter()
+                .all(|index| *index < count));
+        }
+    }
+
+    #[test]
+    fn index_range_check() {
+        use crate::{ DeserializeOptions, Error, ErrorElement };
+
+        let mut map = full_map();
+        map.sectors[1].ceiling_mesh.index_buffer[4] = 7;
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+
+        // The indices are only checked when asked for
+        assert!(Map::deserialize(&buffer).is_ok());
+
+        let options = DeserializeOptions {
+            check_indices: true,
+            ..Default::default()
+        };
+        let error = Map::deserialize_with_options(&buffer, &options)
+            .err().unwrap();
+        assert!(matches!(error.kind(), Error::IndexOutOfRange {
+            mesh: Some(MeshRole::Ceiling),
+            index: 7,
+            max: 4,
+        }));
+
+        let context = error.context().unwrap();
+        assert_eq!(context.sector, Some(1));
+        assert_eq!(context.element, Some(ErrorElement::Index(4)));
+        assert_eq!(&buffer[context.offset..context.offset + 4],
+                   &7u32.to_le_bytes());
+
+        // A mesh on its own doesn't have a role
+        let mut mesh = Vec::new();
+        map.sectors[1].ceiling_mesh.serialize(&mut mesh).unwrap();
+        let error = Mesh::deserialize_with_options(&mesh, &options)
+            .err().unwrap();
+        assert!(matches!(error.kind(), Error::IndexOutOfRange {
+            mesh: None,
+            ..
+        }));
+
+        let mut valid = Vec::new();
+        full_map().serialize(&mut valid).unwrap();
+        assert!(Map::deserialize_with_options(&valid, &options).is_ok());
+    }
+
+    #[test]
+    fn deserialize_prefix() {
+        use crate::Error;
+
+        let map = full_map();
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+        let size = buffer.len();
+
+        // A map embedded inside a larger file
+        buffer.extend_from_slice(b"tail");
+        let (prefix, used) = Map::deserialize_prefix(&buffer).unwrap();
+        assert_eq!(prefix, map);
+        assert_eq!(used, size);
+        assert_eq!(&buffer[used..], b"tail");
+
+        let error = Map::deserialize_strict(&buffer).err().unwrap();
+        assert!(matches!(error.kind(), Error::TrailingBytes(4)));
+        assert_eq!(error.context().unwrap().offset, size);
+        assert_eq!(Map::deserialize_strict(&buffer[..size]).unwrap(), map);
+
+        // The slack after the sectors of saved files is part of the map
+        let file = map.save_to_bytes().unwrap();
+        let (_, used) = Map::deserialize_prefix(&file[24..]).unwrap();
+        assert_eq!(used, file.len() - 24);
+    }
+
+    #[test]
+    fn concatenated_maps() {
+        use crate::Error;
+
+        // Snapshots of a map written back to back
+        let mut snapshots = vec![full_map(), Map::new(vec![quad_sector()])];
+        let mut last = full_map();
+        last.lights.clear();
+        last.unknown_chunks.push(crate::UnknownChunk {
+            tag: *b"SAVE",
+            data: vec![7; 5],
+        });
+        snapshots.push(last);
+
+        let mut buffer = Vec::new();
+        for map in &snapshots {
+            map.serialize(&mut buffer).unwrap();
+        }
+
+        let maps = Map::concatenated(&buffer)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(maps, snapshots);
+
+        let maps = Map::concatenated_stream(&buffer[..])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(maps, snapshots);
+
+        assert_eq!(Map::concatenated(&[]).count(), 0);
+        assert_eq!(Map::concatenated_stream(&[][..]).count(), 0);
+
+        // A cut inside the last map fails after the first two maps
+        let cut = &buffer[..buffer.len() - 3];
+        let results = Map::concatenated(cut).collect::<Vec<_>>();
+        assert_eq!(results.len(), 3);
+        assert!(results[..2].iter().all(|map| map.is_ok()));
+
+        let results = Map::concatenated_stream(cut).collect::<Vec<_>>();
+        assert_eq!(results.len(), 3);
+        let error = results[2].as_ref().err().unwrap();
+        assert!(matches!(error.kind(), Error::BufferToSmallMap(_)));
+        assert_eq!(error.context().unwrap().offset, cut.len());
+    }
+
+    #[test]
+    fn lightmap_uvs_keep_vertex_data() {
+        // A cube corner, the shared vertices are split between the charts
+        let color = [1.0; 4];
+        let mut wall_mesh = Mesh::new(vec![
+            Vertex::new([0.0, 0.0, 0.0], [0.0, 0.0], color),
+            Vertex::new([1.0, 0.0, 0.0], [0.0, 0.0], color),
+            Vertex::new([1.0, 1.0, 0.0], [0.0, 0.0], color),
+            Vertex::new([0.0, 1.0, 0.0], [0.0, 0.0], color),
+            Vertex::new([1.0, 0.0, 1.0], [0.0, 0.0], color),
+            Vertex::new([1.0, 1.0, 1.0], [0.0, 0.0], color),
+        ], vec![0, 1, 2, 2, 3, 0, 1, 4, 5, 5, 2, 1], 0);
+        let detail = (0..6).map(|i| [i as f32, 0.5]).collect();
+        wall_mesh.set_uv_set("detail", detail);
+
+        let sector = Sector::new(quad_mesh(), quad_mesh(), wall_mesh);
+        let mut map = Map::new(vec![sector]);
+        map.generate_lightmap_uvs(&LightmapUvOptions::default());
+
+        let wall_mesh = &map.sectors[0].wall_mesh;
+        assert_eq!(wall_mesh.vertex_buffer.len(), 8);
+        let detail = wall_mesh.uv_set("detail").unwrap();
+        assert_eq!(detail.len(), 8);
+
+        // The copies keep the values of the vertex they were copied from
+        assert_eq!(detail[6], [1.0, 0.5]);
+        assert_eq!(detail[7], [2.0, 0.5]);
+
+        let bytes = map.save_to_bytes().unwrap();
+        assert_eq!(Map::load_from_bytes(&bytes).unwrap(), map);
+    }
+
+    #[test]
+    fn lightmap_uvs_keep_custom_attributes() {
+        use crate::{ CustomAttribute, VertexFormat };
+
+        let color = [1.0; 4];
+        let mut wall_mesh = Mesh::new(vec![
+            Vertex::new([0.0, 0.0, 0.0], [0.0, 0.0], color),
+            Vertex::new([1.0, 0.0, 0.0], [0.0, 0.0], color),
+            Vertex::new([1.0, 1.0, 0.0], [0.0, 0.0], color),
+            Vertex::new([0.0, 1.0, 0.0], [0.0, 0.0], color),
+            Vertex::new([1.0, 0.0, 1.0], [0.0, 0.0], color),
+            Vertex::new([1.0, 1.0, 1.0], [0.0, 0.0], color),
+        ], vec![0, 1, 2, 2, 3, 0, 1, 4, 5, 5, 2, 1], 0);
+        let data = (0..6u32).flat_map(|i| (i as f32).to_le_bytes()).collect();
+        wall_mesh.set_custom_attribute(
+            CustomAttribute::new("wetness", VertexFormat::Float32, data));
+
+        let sector = Sector::new(quad_mesh(), quad_mesh(), wall_mesh);
+        let mut map = Map::new(vec![sector]);
+        map.generate_lightmap_uvs(&LightmapUvOptions::default());
+
+        let wall_mesh = &map.sectors[0].wall_mesh;
+        assert_eq!(wall_mesh.vertex_buffer.len(), 8);
+        let wetness = &wall_mesh.custom_attribute("wetness").unwrap().data;
+        assert_eq!(wetness.len(), 8 * 4);
+
+        // The copies keep the values of the vertex they were copied from
+        assert_eq!(wetness[6 * 4..7 * 4], 1.0f32.to_le_bytes());
+        assert_eq!(wetness[7 * 4..8 * 4], 2.0f32.to_le_bytes());
+
+        let bytes = map.save_to_bytes().unwrap();
+        assert_eq!(Map::load_from_bytes(&bytes).unwrap(), map);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_hostile_size() {
+        use crate::{ Error, SectorCompression };
+        use crate::map::MAP_HEADER_SIZE;
+
+        let vertex = Vertex::new([1.0, 2.0, 3.0], [0.0, 0.0], [1.0; 4]);
+        let mesh = Mesh::new(vec![vertex; 256], vec![0; 3 * 256], 0);
+        let mut map = Map::new(vec![Sector::new(mesh.clone(), mesh.clone(),
+                                                mesh)]);
+        map.compression = SectorCompression::Zstd;
+        let bytes = map.save_to_bytes().unwrap();
+
+        let entry = 24 + MAP_HEADER_SIZE;
+        let offset = u64::from_le_bytes(
+            bytes[entry..entry + 8].try_into().unwrap()) as usize;
+        let block = 24 + offset;
+        assert_eq!(bytes[block], SectorCompression::Zstd.to_u8());
+
+        // The decompressed size follows the flag and the bounding box
+        let size = block + 1 + 6 * 4;
+        for hostile in [u64::MAX, u64::MAX - 8, 1 << 40] {
+            let mut bytes = bytes.clone();
+            bytes[size..size + 8].copy_from_slice(&hostile.to_le_bytes());
+            update_checksums(&mut bytes[24..]);
+
+            let error = Map::load_from_bytes(&bytes).unwrap_err();
+            assert!(matches!(error.kind(), Error::DecompressionFailed));
+        }
+
+        assert_eq!(Map::load_from_bytes(&bytes).unwrap(), map);
+    }
+
+    #[test]
+    fn bake_skips_invalid_indices() {
+        let mut mesh = quad_mesh();
+        mesh.index_buffer.extend_from_slice(&[0, 1, 100]);
+
+        let mut map = Map::new(vec![Sector::new(mesh, quad_mesh(),
+                                                quad_mesh())]);
+        map.color_space = ColorSpace::Linear;
+        let lights = [Light::point([0.5, 0.5, -2.0], [1.0; 3], 1.0, 10.0)];
+        map.bake_vertex_lighting(&lights, &BakeOptions::default());
+
+        let mut expected = Map::new(vec![quad_sector()]);
+        expected.color_space = ColorSpace::Linear;
+        expected.bake_vertex_lighting(&lights, &BakeOptions::default());
+        assert_eq!(map.sectors[0].floor_mesh.vertex_buffer,
+                   expected.sectors[0].floor_mesh.vertex_buffer);
+    }
+
+    #[test]
+    fn lightmap_uvs_skip_invalid_indices() {
+        let mut mesh = quad_mesh();
+        mesh.index_buffer.extend_from_slice(&[0, 1, 100]);
+
+        let mut map = Map::new(vec![Sector::new(mesh, quad_mesh(),
+                                                quad_mesh())]);
+        map.generate_lightmap_uvs(&LightmapUvOptions::default());
+
+        let floor_mesh = &map.sectors[0].floor_mesh;
+        assert_eq!(floor_mesh.lightmap_uvs.len(),
+                   floor_mesh.vertex_buffer.len());
+        assert_eq!(floor_mesh.index_buffer[6..], [0, 1, 100]);
+    }
+
+    #[test]
+    fn winding_and_lightmap_skip_strips() {
+        use crate::Topology;
+
+        // A strip whose second triangle faces down with the winding of a
+        // list
+        let color = [1.0; 4];
+        let mut strip = Mesh::new(vec![
+            Vertex::new([0.0, 0.0, 0.0], [0.0, 0.0], color),
+            Vertex::new([0.0, 0.0, 1.0], [0.0, 0.0], color),
+            Vertex::new([1.0, 0.0, 0.0], [0.0, 0.0], color),
+            Vertex::new([1.0, 0.0, 1.0], [0.0, 0.0], color),
+        ], vec![0, 1, 2, 3], 0);
+        strip.topology = Topology::TriangleStrip;
+
+        assert_eq!(strip.face_normal(0), None);
+        assert!(strip.inconsistent_winding([0.0, 1.0, 0.0]).is_empty());
+        assert_eq!(strip.fix_winding([0.0, -1.0, 0.0]), 0);
+        assert_eq!(strip.index_buffer, [0, 1, 2, 3]);
+
+        let mut map = Map::new(vec![Sector::new(strip.clone(), quad_mesh(),
+                                                quad_mesh())]);
+        map.generate_lightmap_uvs(&LightmapUvOptions::default());
+        assert_eq!(map.sectors[0].floor_mesh, strip);
+        assert!(!map.sectors[0].ceiling_mesh.lightmap_uvs.is_empty());
+    }
+
+    #[test]
+    fn map_editor_failed_modify() {
+        use crate::MAX_STRING_SIZE;
+
+        let mut editor = MapEditor::new(Map::new(vec![quad_sector()]));
+        let before = editor.map().sectors[0].clone();
+
+        // The name is too long to serialize
+        let name = "a".repeat(MAX_STRING_SIZE + 1);
+        let result = editor.modify_sector(SectorId(0), |sector| {
+            sector.tag = 5;
+            sector.name = Some(name);
+        });
+        assert!(result.is_err());
+        assert_eq!(editor.map().sectors[0], before);
+        assert!(!editor.can_undo());
+    }
+
+    #[test]
+    fn wall_reversed_range() {
+        let (start, end) = (6, 2);
+        let wall = Wall::new([0.0; 3], [1.0, 0.0, 0.0], "brick", start..end);
+        assert!(wall.indices(&quad_mesh()).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "python")]
+    fn python_mesh_from_arrays() {
+        use crate::python::mesh_from_arrays;
+        use numpy::ndarray::{ arr1, Array2 };
+
+        let quad = quad_mesh();
+        let rows = quad.vertex_buffer.iter()
+            .flat_map(|vertex| {
+                vertex.pos.iter()
+                    .chain(&vertex.uv)
+                    .chain(&vertex.color)
+                    .copied()
+            })
+            .collect::<Vec<_>>();
+        let vertices = Array2::from_shape_vec((4, 9), rows).unwrap();
+        let indices = arr1(&quad.index_buffer);
+
+        let mesh = mesh_from_arrays(vertices.view(), indices.view(),
+                                    quad.texture_id).unwrap();
+        assert_eq!(mesh, quad);
+
+        let map = Map::new(vec![Sector::new(mesh.clone(), mesh.clone(),
+                                            mesh)]);
+        let bytes = map.save_to_bytes().unwrap();
+        assert_eq!(Map::load_from_bytes(&bytes).unwrap(), map);
+
+        // Wrong shape, index count and out of range indices
+        let columns = Array2::<f32>::zeros((4, 8));
+        assert!(mesh_from_arrays(columns.view(), indices.view(), 0).is_err());
+        let indices = arr1(&[0, 1]);
+        assert!(mesh_from_arrays(vertices.view(), indices.view(), 0).is_err());
+        let indices = arr1(&[0, 1, 4]);
+        assert!(mesh_from_arrays(vertices.view(), indices.view(), 0).is_err());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn rayon_serialize_uncompressed() {
+        use crate::SectorCompression;
+        use crate::compression::sector_block;
+        use crate::map::SectorSlot;
+
+        let sectors = (0..64).map(|index| {
+            let mut sector = quad_sector();
+            sector.tag = index;
+            sector
+        }).collect();
+        let map = Map::new(sectors);
+        assert_eq!(map.compression, SectorCompression::None);
+
+        let mut buffer = Vec::new();
+        let layout = map.serialize_with_slack(&mut buffer, 25).unwrap();
+        assert_eq!(layout.size as usize, buffer.len());
+
+        // Every slot holds the block of its sector in order
+        for (sector, slot) in map.sectors.iter().zip(&layout.slots) {
+            let block = sector_block(sector, SectorCompression::None)
+                .unwrap();
+            let start = slot.offset as usize;
+            assert_eq!(buffer[start..start + block.len()], block[..]);
+            assert_eq!(*slot, SectorSlot::new(slot.offset, &block,
+                                              slot.capacity));
+        }
+
+        let bytes = map.save_to_bytes().unwrap();
+        assert_eq!(Map::load_from_bytes(&bytes).unwrap(), map);
+    }
+
+    #[test]
+    fn lightmap_hostile_size() {
+        use crate::Error;
+
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u32::MAX.to_le_bytes());
+        buffer.extend_from_slice(&u32::MAX.to_le_bytes());
+        let error = Lightmap::deserialize(&buffer).unwrap_err();
+        assert!(matches!(error, Error::LightmapSizeMismatch));
+
+        // The same lightmap reached through the chunk of a map
+        let mut map = Map::new(vec![quad_sector()]);
+        map.lightmap = Some(Lightmap::new(0, 0));
+
+        let mut buffer = Vec::new();
+        map.serialize(&mut buffer).unwrap();
+
+        let tag = buffer.windows(4).position(|tag| tag == b"LMAP").unwrap();
+        let data = tag + 4 + 8;
+        buffer[data..data + 8].fill(0xff);
+
+        let error = Map::deserialize(&buffer).unwrap_err();
+        assert!(matches!(error.kind(), Error::LightmapSizeMismatch));
+    }
+
+    #[test]
+    fn merge_tag_overflow() {
+        use crate::Error;
+
+        let mut sector = quad_sector();
+        sector.tag = u32::MAX;
+        let mut map = Map::new(vec![sector]);
+
+        let mut sector = quad_sector();
+        sector.tag = 1;
+        let other = Map::new(vec![sector]);
+
+        let error = map.merge(other.clone(), None).unwrap_err();
+        assert!(matches!(error, Error::TagOverflow));
+        assert_eq!(map.sectors.len(), 1);
+
+        // Untagged sectors keep tag 0 and don't need an offset
+        let range = map.merge(Map::new(vec![quad_sector()]), None).unwrap();
+        assert_eq!(range, 1..2);
+        assert_eq!(map.sectors[1].tag, 0);
+
+        let mut map = Map::new(vec![quad_sector()]);
+        map.units_per_meter = f32::NAN;
+        let error = map.merge(other, None).unwrap_err();
+        assert!(matches!(error, Error::InvalidUnitScale(_)));
+        assert_eq!(map.sectors.len(), 1);
+    }
+
+    #[test]
+    fn map_edit_duplicate_sector_id() {
+        use crate::Error;
+
+        let mut sector = quad_sector();
+        let mut bytes = Vec::new();
+        sector.serialize(&mut bytes).unwrap();
+
+        // The map already has a sector with ID 0
+        let mut map = Map::new(vec![quad_sector()]);
+        let edit = Edit::AddSector { index: 1, sector: bytes };
+        let error = map.replay(&[edit]).unwrap_err();
+        assert!(matches!(error, Error::EditMismatch));
+        assert_eq!(map.sectors.len(), 1);
+
+        // The highest ID doesn't overflow the next ID
+        sector.id = SectorId(u64::MAX);
+        let mut bytes = Vec::new();
+        sector.serialize(&mut bytes).unwrap();
+
+        let edit = Edit::AddSector { index: 1, sector: bytes };
+        map.apply_edit(&edit).unwrap();
+        assert_eq!(map.sectors[1].id(), SectorId(u64::MAX));
+    }
+
+    #[test]
+    fn stale_next_sector_id() {
+        let mut map = Map::new(vec![quad_sector(), quad_sector()]);
+        let bytes = map.save_to_bytes().unwrap();
+
+        // The header claims the next sector gets ID 1, which is used
+        let mut stale = bytes.clone();
+        assert_eq!(stale[24 + 16..24 + 24], 2u64.to_le_bytes());
+        stale[24 + 16..24 + 24].copy_from_slice(&1u64.to_le_bytes());
+
+        let expected = map.add_sector(quad_sector());
+        assert_eq!(expected, SectorId(2));
+
+        let mut result = Map::load_from_bytes(&stale).unwrap();
+        assert_eq!(result.add_sector(quad_sector()), expected);
+
+        let lazy = LazyMap::from_bytes(stale.clone()).unwrap();
+        let mut result = lazy.into_map().unwrap();
+        assert_eq!(result.add_sector(quad_sector()), expected);
+
+        let (mut result, report) = Map::recover_from_bytes(&stale).unwrap();
+        assert!(report.is_empty());
+        assert_eq!(result.add_sector(quad_sector()), expected);
+    }
+
+    #[test]
+    fn map_diff_matches_ids() {
+        let old = Map::new(vec![quad_sector(), quad_sector(), quad_sector()]);
+
+        // Removing the first sector doesn't change the sectors after it
+        let mut new = old.clone();
+        new.remove_sector(SectorId(0));
+        let id = new.add_sector(quad_sector());
+
+        let diff = old.diff(&new);
+        assert_eq!(diff.removed_sectors, vec![SectorId(0)]);
+        assert_eq!(diff.added_sectors, vec![id]);
+        assert!(diff.changed_sectors.is_empty());
+
+        new.sector_mut(SectorId(2)).unwrap().tag = 4;
+        let diff = old.diff(&new);
+        assert_eq!(diff.changed_sectors.len(), 1);
+        assert_eq!(diff.changed_sectors[0].id, SectorId(2));
+        assert!(diff.changed_sectors[0].tag_changed);
+    }
+
+    #[test]
+    fn map_diff_sector_fields() {
+        use crate::{ PropInstance, Occluder, Liquid, LiquidKind, Acoustics,
+                     ReverbPreset, SectorDiff };
+        use crate::transform::IDENTITY;
+
+        let old = Map::new(vec![quad_sector()]);
+        let changed = |f: &dyn Fn(&mut Sector)| -> SectorDiff {
+            let mut new = old.clone();
+            f(&mut new.sectors[0]);
+
+            let mut diff = old.diff(&new);
+            assert_eq!(diff.changed_sectors.len(), 1);
+            diff.changed_sectors.remove(0)
+        };
+
+        let diff = changed(&|sector| sector.name = Some("lobby".into()));
+        assert!(diff.name_changed && !diff.props_changed);
+
+        let diff = changed(&|sector| {
+            sector.props.push(PropInstance::new(0, &IDENTITY));
+        });
+        assert!(diff.props_changed && !diff.name_changed);
+
+        let diff = changed(&|sector| {
+            sector.occluders.push(Occluder::new(vec![[0.0; 3]; 3],
+                                                vec![0, 1, 2]));
+        });
+        assert!(diff.occluders_changed);
+
+        let diff = changed(&|sector| {
+            sector.liquid = Some(Liquid::new(LiquidKind::Water, 0.5,
+                                             [0.0, 0.2, 0.8, 0.5]));
+        });
+        assert!(diff.liquid_changed);
+
+        let diff = changed(&|sector| {
+            sector.environment.fog_density = Some(0.5);
+        });
+        assert!(diff.environment_changed);
+
+        let diff = changed(&|sector| {
+            sector.acoustics = Acoustics {
+                reverb: ReverbPreset::Room,
+                ..sector.acoustics
+            };
+        });
+        assert!(diff.acoustics_changed);
+
+        // Moving a vertex inside the box keeps the bounds
+        let diff = changed(&|sector| {
+            sector.floor_mesh.vertex_buffer[0].pos[1] = 5.0;
+        });
+        assert!(diff.bounds_changed && !diff.meshes.is_empty());
+        let diff = changed(&|sector| {
+            sector.floor_mesh.vertex_buffer[0].color = [0.5; 4];
+        });
+        assert!(!diff.bounds_changed && !diff.meshes.is_empty());
+
+        // The animations belong to the map
+        let mut new = old.clone();
+        new.animations.push(SectorAnimation::new("lift", 1,
+                                                 SectorPlane::Floor));
+        let diff = old.diff(&new);
+        assert!(diff.animations_changed && diff.changed_sectors.is_empty());
+        assert!(!diff.is_empty());
+    }
+
+    #[test]
+    fn concatenated_maps_with_options() {
+        use crate::{ Error, DeserializeOptions, DeserializeLimit };
+
+        let mut broken = quad_sector();
+        broken.floor_mesh.index_buffer[0] = 100;
+
+        let mut buffer = Vec::new();
+        Map::new(vec![quad_sector()]).serialize(&mut buffer).unwrap();
+        Map::new(vec![quad_sector(), quad_sector()])
+            .serialize(&mut buffer).unwrap();
+        let end = buffer.len();
+        Map::new(vec![broken]).serialize(&mut buffer).unwrap();
+
+        // Every map is limited on its own
+        let options = DeserializeOptions {
+            max_sectors: 1,
+            ..Default::default()
+        };
+        let (map, size) =
+            Map::deserialize_prefix_with_options(&buffer, &options).unwrap();
+        assert_eq!(map.sectors.len(), 1);
+
+        let error = Map::deserialize_strict_with_options(&buffer[size..end],
+                                                         &options)
+            .unwrap_err();
+        assert!(matches!(error.kind(),
+                         Error::LimitExceeded(DeserializeLimit::Sectors)));
+
+        let results = Map::concatenated_with_options(&buffer, &options)
+            .collect::<Vec<_>>();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        let error = results[1].as_ref().err().unwrap();
+        assert!(matches!(error.kind(),
+                         Error::LimitExceeded(DeserializeLimit::Sectors)));
+        assert_eq!(error.context().unwrap().offset, size);
+
+        // The broken indices are only found with the index check
+        let options = DeserializeOptions {
+            check_indices: true,
+            ..Default::default()
+        };
+        assert!(Map::concatenated(&buffer).all(|map| map.is_ok()));
+
+        let results = Map::concatenated_stream_with_options(&buffer[..],
+                                                            &options)
+            .collect::<Vec<_>>();
+        assert_eq!(results.len(), 3);
+        assert!(results[..2].iter().all(|map| map.is_ok()));
+        let error = results[2].as_ref().err().unwrap();
+        assert!(matches!(error.kind(), Error::IndexOutOfRange { .. }));
+    }
 }
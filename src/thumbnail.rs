@@ -0,0 +1,136 @@
+//! A small preview image of the map
+
+use crate::*;
+use crate::buffer::Reader;
+
+/// The size of the thumbnail header (format, width, height)
+const THUMBNAIL_HEADER_SIZE: usize = 1 + 4 + 4;
+
+/// How the pixels of a thumbnail are stored
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ThumbnailFormat {
+    /// Uncompressed pixels with 8 bits per channel (r, g, b, a), row by row
+    /// from the top
+    Rgba,
+
+    /// A PNG file
+    Png,
+}
+
+/// A small preview image map browsers can show without loading the
+/// geometry, see [LazyMap::thumbnail]
+#[derive(Clone, PartialEq, Debug)]
+pub struct Thumbnail {
+    /// How the pixels are stored
+    pub format: ThumbnailFormat,
+
+    /// The width of the image in pixels
+    pub width: u32,
+
+    /// The height of the image in pixels
+    pub height: u32,
+
+    /// The pixels or the PNG file
+    pub data: Vec<u8>,
+}
+
+impl Thumbnail {
+    /// Creates a thumbnail from uncompressed pixels
+    ///
+    /// # Arguments
+    ///
+    /// * `width`  - The width of the image in pixels
+    /// * `height` - The height of the image in pixels
+    /// * `data`   - The pixels (r, g, b, a), row by row from the top
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new thumbnail
+    pub fn rgba(width: u32, height: u32, data: Vec<u8>) -> Self {
+        Self {
+            format: ThumbnailFormat::Rgba,
+            width,
+            height,
+            data,
+        }
+    }
+
+    /// Creates a thumbnail from a PNG file, the size is only used by
+    /// browsers to lay out the preview before decoding it
+    ///
+    /// # Arguments
+    ///
+    /// * `width`  - The width of the image in pixels
+    /// * `height` - The height of the image in pixels
+    /// * `data`   - The content of the PNG file
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new thumbnail
+    pub fn png(width: u32, height: u32, data: Vec<u8>) -> Self {
+        Self {
+            format: ThumbnailFormat::Png,
+            width,
+            height,
+            data,
+        }
+    }
+
+    /// Get the number of bytes [Thumbnail::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        THUMBNAIL_HEADER_SIZE + self.data.len()
+    }
+
+    /// Serialize the thumbnail to a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - Successfully serialized the thumbnail
+    /// * `Err(`[Error]`)` - Failed to serialize the thumbnail
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        buffer.push(match self.format {
+            ThumbnailFormat::Rgba => 0,
+            ThumbnailFormat::Png => 1,
+        });
+        buffer.extend_from_slice(&self.width.to_le_bytes());
+        buffer.extend_from_slice(&self.height.to_le_bytes());
+        buffer.extend_from_slice(&self.data);
+
+        Ok(())
+    }
+
+    /// Deserialize the thumbnail from a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the thumbnail
+    /// * `Err(`[Error]`)` - Failed to deserialize the thumbnail
+    pub fn deserialize(buffer: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallChunk);
+
+        let format = match reader.u8()? {
+            0 => ThumbnailFormat::Rgba,
+            1 => ThumbnailFormat::Png,
+            format => return Err(Error::UnknownThumbnailFormat(format)),
+        };
+        let width = reader.u32()?;
+        let height = reader.u32()?;
+
+        Ok(Self {
+            format,
+            width,
+            height,
+            data: reader.remaining().to_vec(),
+        })
+    }
+}
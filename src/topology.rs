@@ -0,0 +1,92 @@
+//! How the indices of a mesh form primitives
+
+use crate::*;
+
+/// How the index buffer of a mesh is turned into primitives
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Default, Debug)]
+pub enum Topology {
+    /// Every 3 indices form a triangle, the default for meshes saved before
+    /// the topology was stored
+    #[default]
+    TriangleList,
+
+    /// Every index forms a triangle with the 2 indices before it, the
+    /// winding of every other triangle is reversed
+    TriangleStrip,
+
+    /// Every 2 indices form a line, like the overlays of an editor
+    LineList,
+
+    /// Every index is a point
+    PointList,
+}
+
+impl Topology {
+    /// Get the byte the topology is serialized as
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Topology::TriangleList => 0,
+            Topology::TriangleStrip => 1,
+            Topology::LineList => 2,
+            Topology::PointList => 3,
+        }
+    }
+
+    /// Get the topology from its serialized byte
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The serialized byte
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - The topology
+    /// * `Err(`[Error]`)` - [Error::UnknownTopology] if the byte isn't a
+    ///   known topology
+    pub fn from_u8(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(Topology::TriangleList),
+            1 => Ok(Topology::TriangleStrip),
+            2 => Ok(Topology::LineList),
+            3 => Ok(Topology::PointList),
+            value => Err(Error::UnknownTopology(value)),
+        }
+    }
+
+    /// Check if the primitives are triangles
+    pub fn is_triangles(self) -> bool {
+        matches!(self, Topology::TriangleList | Topology::TriangleStrip)
+    }
+}
+
+impl Mesh {
+    /// Get the indices of every triangle of the mesh, the triangles of a
+    /// strip are returned with the winding of the first triangle
+    ///
+    /// # Returns
+    ///
+    /// * An iterator over the 3 indices of every triangle, empty for line
+    ///   and point meshes
+    pub fn triangle_indices(&self) -> impl Iterator<Item = [u32; 3]> + '_ {
+        let indices = &self.index_buffer;
+
+        let (count, step) = match self.topology {
+            Topology::TriangleList => (indices.len() / 3, 3),
+            Topology::TriangleStrip => (indices.len().saturating_sub(2), 1),
+            Topology::LineList | Topology::PointList => (0, 0),
+        };
+
+        let strip = self.topology == Topology::TriangleStrip;
+        (0..count).map(move |triangle| {
+            let start = triangle * step;
+            let [a, b, c] = [indices[start], indices[start + 1],
+                             indices[start + 2]];
+
+            if strip && triangle % 2 == 1 {
+                [b, a, c]
+            } else {
+                [a, b, c]
+            }
+        })
+    }
+}
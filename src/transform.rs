@@ -0,0 +1,362 @@
+//! Affine transforms of map geometry
+
+use crate::*;
+use crate::trigger::TriggerShape;
+use crate::math::{ length, normalize };
+
+/// A 4x4 matrix stored as four columns, the same layout as
+/// `glam::Mat4::to_cols_array_2d`
+pub type Mat4 = [[f32; 4]; 4];
+
+/// The identity matrix
+pub const IDENTITY: Mat4 = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+/// Conversion of matrices from math libraries to [Mat4]
+///
+/// Implemented for [Mat4] and, behind the `glam`, `mint` and `nalgebra`
+/// features, for the 4x4 matrices of those libraries.
+pub trait ToMat4 {
+    /// Convert the matrix to a column-major [Mat4]
+    fn to_mat4(&self) -> Mat4;
+}
+
+impl ToMat4 for Mat4 {
+    fn to_mat4(&self) -> Mat4 {
+        *self
+    }
+}
+
+#[cfg(feature = "glam")]
+impl ToMat4 for glam::Mat4 {
+    fn to_mat4(&self) -> Mat4 {
+        self.to_cols_array_2d()
+    }
+}
+
+#[cfg(feature = "mint")]
+impl ToMat4 for mint::ColumnMatrix4<f32> {
+    fn to_mat4(&self) -> Mat4 {
+        (*self).into()
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl ToMat4 for nalgebra::Matrix4<f32> {
+    fn to_mat4(&self) -> Mat4 {
+        // NOTE(patrik): nalgebra stores the matrices in column-major order
+        (*self).into()
+    }
+}
+
+/// Transform a point by a matrix
+pub(crate) fn transform_point(m: &Mat4, p: [f32; 3]) -> [f32; 3] {
+    [
+        m[0][0] * p[0] + m[1][0] * p[1] + m[2][0] * p[2] + m[3][0],
+        m[0][1] * p[0] + m[1][1] * p[1] + m[2][1] * p[2] + m[3][1],
+        m[0][2] * p[0] + m[1][2] * p[1] + m[2][2] * p[2] + m[3][2],
+    ]
+}
+
+/// Transform a direction by a matrix, the translation is ignored
+pub(crate) fn transform_direction(m: &Mat4, d: [f32; 3]) -> [f32; 3] {
+    [
+        m[0][0] * d[0] + m[1][0] * d[1] + m[2][0] * d[2],
+        m[0][1] * d[0] + m[1][1] * d[1] + m[2][1] * d[2],
+        m[0][2] * d[0] + m[1][2] * d[1] + m[2][2] * d[2],
+    ]
+}
+
+/// Multiply two matrices, the result applies `b` first and then `a`
+pub(crate) fn multiply(a: &Mat4, b: &Mat4) -> Mat4 {
+    let mut r = [[0.0; 4]; 4];
+    for (column, b) in r.iter_mut().zip(b) {
+        for (row, value) in column.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a[k][row] * b[k]).sum();
+        }
+    }
+
+    r
+}
+
+/// The determinant of the upper 3x3 part of a matrix, negative when the
+/// transform mirrors the geometry
+pub(crate) fn determinant3(m: &Mat4) -> f32 {
+    m[0][0] * (m[1][1] * m[2][2] - m[2][1] * m[1][2]) -
+        m[1][0] * (m[0][1] * m[2][2] - m[2][1] * m[0][2]) +
+        m[2][0] * (m[0][1] * m[1][2] - m[1][1] * m[0][2])
+}
+
+/// Invert an affine matrix
+///
+/// # Returns
+///
+/// * `Some(`[Mat4]`)` - The inverted matrix
+/// * `None` - The matrix can't be inverted
+pub(crate) fn inverse_affine(m: &Mat4) -> Option<Mat4> {
+    let det = determinant3(m);
+    if det.abs() < f32::EPSILON {
+        return None;
+    }
+
+    let inv_det = 1.0 / det;
+
+    // The inverse of the 3x3 part is the transposed cofactor matrix
+    let mut r = IDENTITY;
+    r[0][0] = (m[1][1] * m[2][2] - m[2][1] * m[1][2]) * inv_det;
+    r[1][0] = (m[2][0] * m[1][2] - m[1][0] * m[2][2]) * inv_det;
+    r[2][0] = (m[1][0] * m[2][1] - m[2][0] * m[1][1]) * inv_det;
+    r[0][1] = (m[2][1] * m[0][2] - m[0][1] * m[2][2]) * inv_det;
+    r[1][1] = (m[0][0] * m[2][2] - m[2][0] * m[0][2]) * inv_det;
+    r[2][1] = (m[2][0] * m[0][1] - m[0][0] * m[2][1]) * inv_det;
+    r[0][2] = (m[0][1] * m[1][2] - m[1][1] * m[0][2]) * inv_det;
+    r[1][2] = (m[1][0] * m[0][2] - m[0][0] * m[1][2]) * inv_det;
+    r[2][2] = (m[0][0] * m[1][1] - m[1][0] * m[0][1]) * inv_det;
+
+    let t = transform_direction(&r, [m[3][0], m[3][1], m[3][2]]);
+    r[3] = [-t[0], -t[1], -t[2], 1.0];
+
+    Some(r)
+}
+
+/// Transform a plane (a, b, c, d) where `a * x + b * y + c * z <= d` is
+/// inside
+pub(crate) fn transform_plane(m: &Mat4, plane: [f32; 4]) -> [f32; 4] {
+    let inv = match inverse_affine(m) {
+        Some(inv) => inv,
+        None => return plane,
+    };
+
+    // Planes transform by the inverse transpose, the plane is stored with
+    // d on the other side of the equation
+    let p = [plane[0], plane[1], plane[2], -plane[3]];
+    let r = [
+        inv[0][0] * p[0] + inv[0][1] * p[1] + inv[0][2] * p[2] + inv[0][3] * p[3],
+        inv[1][0] * p[0] + inv[1][1] * p[1] + inv[1][2] * p[2] + inv[1][3] * p[3],
+        inv[2][0] * p[0] + inv[2][1] * p[1] + inv[2][2] * p[2] + inv[2][3] * p[3],
+        inv[3][0] * p[0] + inv[3][1] * p[1] + inv[3][2] * p[2] + inv[3][3] * p[3],
+    ];
+
+    [r[0], r[1], r[2], -r[3]]
+}
+
+/// Transform the positions of a mesh and flip the winding of the triangles
+/// if the transform mirrors the geometry
+pub(crate) fn transform_mesh(mesh: &mut Mesh, m: &Mat4) {
+    for vertex in &mut mesh.vertex_buffer {
+        vertex.pos = transform_point(m, vertex.pos);
+    }
+
+    if determinant3(m) < 0.0 {
+        match mesh.topology {
+            Topology::TriangleList => {
+                for triangle in mesh.index_buffer.chunks_exact_mut(3) {
+                    triangle.swap(1, 2);
+                }
+            }
+
+            // NOTE(patrik): Repeating the first index adds a degenerate
+            // triangle and flips the winding of every triangle after it
+            Topology::TriangleStrip => {
+                if let Some(&first) = mesh.index_buffer.first() {
+                    mesh.index_buffer.insert(0, first);
+                }
+            }
+
+            Topology::LineList | Topology::PointList => {}
+        }
+    }
+}
+
+/// Transform all the geometry of a sector
+pub(crate) fn transform_sector(sector: &mut Sector, m: &Mat4) {
+    for mesh in sector.meshes_mut() {
+        transform_mesh(mesh, m);
+    }
+
+    for wall in &mut sector.walls {
+        wall.start = transform_point(m, wall.start);
+        wall.end = transform_point(m, wall.end);
+    }
+
+    for plane in [&mut sector.floor_plane, &mut sector.ceiling_plane] {
+        *plane = plane.map(|plane| transform_plane(m, plane));
+    }
+
+    for prop in &mut sector.props {
+        prop.transform = multiply(m, &prop.transform);
+    }
+
+    // NOTE(patrik): The surface of the liquid stays flat, only the part of
+    // the transform moving the surface along the Y axis is applied
+    if let Some(liquid) = &mut sector.liquid {
+        liquid.surface_height =
+            transform_point(m, [0.0, liquid.surface_height, 0.0])[1];
+        liquid.flow = transform_direction(m, liquid.flow);
+    }
+
+    for occluder in &mut sector.occluders {
+        for vertex in &mut occluder.vertices {
+            *vertex = transform_point(m, *vertex);
+        }
+
+        if determinant3(m) < 0.0 {
+            for triangle in occluder.indices.chunks_exact_mut(3) {
+                triangle.swap(1, 2);
+            }
+        }
+    }
+}
+
+/// Transform a light
+pub(crate) fn transform_light(light: &mut Light, m: &Mat4) {
+    light.position = transform_point(m, light.position);
+
+    match &mut light.kind {
+        LightKind::Point => {}
+
+        LightKind::Spot { direction, .. } |
+        LightKind::Directional { direction } => {
+            *direction = transform_direction(m, *direction);
+        }
+    }
+}
+
+/// Transform a decal, the size follows the scale of the transform along the
+/// edges of the decal
+pub(crate) fn transform_decal(decal: &mut Decal, m: &Mat4) {
+    let axes = [decal.right(), decal.up, decal.normal];
+    for (size, axis) in decal.size.iter_mut().zip(axes) {
+        *size *= length(transform_direction(m, normalize(axis)));
+    }
+
+    decal.position = transform_point(m, decal.position);
+    decal.normal = normalize(transform_direction(m, decal.normal));
+    decal.up = normalize(transform_direction(m, decal.up));
+}
+
+/// Transform a trigger, boxes are replaced with the box around the
+/// transformed corners
+pub(crate) fn transform_trigger(trigger: &mut Trigger, m: &Mat4) {
+    match &mut trigger.shape {
+        TriggerShape::Aabb { min, max } => {
+            let mut new_min = [f32::MAX; 3];
+            let mut new_max = [f32::MIN; 3];
+
+            for corner in 0..8 {
+                let p = [
+                    if corner & 1 == 0 { min[0] } else { max[0] },
+                    if corner & 2 == 0 { min[1] } else { max[1] },
+                    if corner & 4 == 0 { min[2] } else { max[2] },
+                ];
+                let p = transform_point(m, p);
+
+                for i in 0..3 {
+                    new_min[i] = new_min[i].min(p[i]);
+                    new_max[i] = new_max[i].max(p[i]);
+                }
+            }
+
+            *min = new_min;
+            *max = new_max;
+        }
+
+        TriggerShape::Convex { planes } => {
+            for plane in planes {
+                *plane = transform_plane(m, *plane);
+            }
+        }
+    }
+}
+
+impl Mesh {
+    /// Apply an affine transform to the positions of the mesh, the winding
+    /// of the triangles is flipped when the transform mirrors the geometry
+    ///
+    /// # Arguments
+    ///
+    /// * `transform` - The transform, any matrix implementing [ToMat4]
+    pub fn transform<M>(&mut self, transform: &M)
+        where M: ToMat4 + ?Sized
+    {
+        transform_mesh(self, &transform.to_mat4());
+    }
+}
+
+impl Sector {
+    /// Apply an affine transform to the meshes, levels of detail, walls,
+    /// planes, props, occluders and liquid of the sector, see
+    /// [Mesh::transform]
+    ///
+    /// # Arguments
+    ///
+    /// * `transform` - The transform, any matrix implementing [ToMat4]
+    pub fn transform<M>(&mut self, transform: &M)
+        where M: ToMat4 + ?Sized
+    {
+        transform_sector(self, &transform.to_mat4());
+    }
+}
+
+impl Map {
+    /// Apply an affine transform to the sectors, lights, sound emitters,
+    /// triggers, entities, waypoints, props, decals and interactive objects
+    /// of the map, like placing a prefab or converting units, the library
+    /// meshes stay in their own space
+    ///
+    /// The winding of the triangles is flipped when the transform mirrors
+    /// the geometry. Every sector is marked for the next
+    /// [Map::save_incremental].
+    ///
+    /// # Arguments
+    ///
+    /// * `transform` - The transform, any matrix implementing [ToMat4]
+    pub fn transform<M>(&mut self, transform: &M)
+        where M: ToMat4 + ?Sized
+    {
+        let m = transform.to_mat4();
+
+        for (index, sector) in self.sectors.iter_mut().enumerate() {
+            transform_sector(sector, &m);
+            self.dirty_sectors.insert(index);
+        }
+
+        for light in &mut self.lights {
+            transform_light(light, &m);
+        }
+
+        for emitter in &mut self.sound_emitters {
+            emitter.position = transform_point(&m, emitter.position);
+        }
+
+        for trigger in &mut self.triggers {
+            transform_trigger(trigger, &m);
+        }
+
+        for entity in &mut self.entities {
+            entity.position = transform_point(&m, entity.position);
+        }
+
+        let waypoints = self.waypoints.iter_mut()
+            .flat_map(|waypoints| &mut waypoints.nodes);
+        for node in waypoints {
+            node.position = transform_point(&m, node.position);
+        }
+
+        for prop in &mut self.props {
+            prop.transform = multiply(&m, &prop.transform);
+        }
+
+        for decal in &mut self.decals {
+            transform_decal(decal, &m);
+        }
+
+        for interactive in &mut self.interactives {
+            interactive.position = transform_point(&m, interactive.position);
+        }
+    }
+}
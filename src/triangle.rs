@@ -0,0 +1,58 @@
+//! Iterating over the triangles of the meshes
+//!
+//! The index buffer of a mesh forms triangles as described by its
+//! [Topology]. [Mesh::triangles] and [Map::triangles] look up the vertices
+//! of the triangles so the callers don't have to chunk the index buffers.
+
+use crate::*;
+
+impl Mesh {
+    /// Get the vertices of every triangle of the mesh
+    ///
+    /// # Returns
+    ///
+    /// * An iterator over the 3 vertices of every triangle in the order of
+    ///   the index buffer, see [Mesh::triangle_indices], triangles
+    ///   referencing vertices outside the vertex buffer and indices left
+    ///   over at the end are skipped
+    pub fn triangles(&self) -> impl Iterator<Item = [&Vertex; 3]> + '_ {
+        let vertex = |index: u32| self.vertex_buffer.get(index as usize);
+
+        self.triangle_indices()
+            .filter_map(move |triangle| {
+                Some([vertex(triangle[0])?, vertex(triangle[1])?,
+                      vertex(triangle[2])?])
+            })
+    }
+
+    /// Get the positions of every triangle of the mesh, see
+    /// [Mesh::triangles]
+    pub fn triangle_positions(&self)
+        -> impl Iterator<Item = [[f32; 3]; 3]> + '_
+    {
+        self.triangles()
+            .map(|[a, b, c]| [a.pos, b.pos, c.pos])
+    }
+}
+
+impl Map {
+    /// Get the vertices of every triangle of the floor, ceiling and wall
+    /// meshes of every sector, the levels of detail are left out
+    ///
+    /// # Returns
+    ///
+    /// * An iterator over the index of the sector, the role of the mesh
+    ///   and the 3 vertices of every triangle, see [Mesh::triangles]
+    pub fn triangles(&self)
+        -> impl Iterator<Item = (usize, MeshRole, [&Vertex; 3])>
+    {
+        self.sectors.iter()
+            .enumerate()
+            .flat_map(|(index, sector)| {
+                MeshRole::ALL.into_iter().flat_map(move |role| {
+                    sector.mesh(role).triangles()
+                        .map(move |triangle| (index, role, triangle))
+                })
+            })
+    }
+}
@@ -0,0 +1,173 @@
+//! Trigger volumes placed inside the map
+
+use crate::*;
+use crate::buffer::{ Reader, write_f32s, write_string, write_usize,
+                     string_size };
+use crate::math::dot;
+
+/// The shape of a trigger volume
+#[derive(Clone, PartialEq, Debug)]
+pub enum TriggerShape {
+    /// An axis aligned box
+    Aabb {
+        /// The minimum corner of the box
+        min: [f32; 3],
+
+        /// The maximum corner of the box
+        max: [f32; 3],
+    },
+
+    /// A convex volume bounded by planes (a, b, c, d), a point is inside
+    /// when `a * x + b * y + c * z <= d` for every plane
+    Convex {
+        /// The planes bounding the volume, the normals point outwards
+        planes: Vec<[f32; 4]>,
+    },
+}
+
+/// A volume that triggers an action when something enters it
+#[derive(Clone, PartialEq, Debug)]
+pub struct Trigger {
+    /// The shape of the volume
+    pub shape: TriggerShape,
+
+    /// The type of the trigger, the meaning is decided by the engine
+    pub kind: u32,
+
+    /// The name of the target the trigger activates
+    pub target: String,
+
+    /// The tag of the sectors the trigger activates, 0 means no sectors
+    pub tag: u32,
+}
+
+impl Trigger {
+    /// Creates a new trigger
+    ///
+    /// # Arguments
+    ///
+    /// * `shape`  - The shape of the volume
+    /// * `kind`   - The type of the trigger
+    /// * `target` - The name of the target the trigger activates
+    /// * `tag`    - The tag of the sectors the trigger activates
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new trigger
+    pub fn new(shape: TriggerShape, kind: u32, target: &str, tag: u32)
+        -> Self
+    {
+        Self {
+            shape,
+            kind,
+            target: target.to_string(),
+            tag,
+        }
+    }
+
+    /// Check if a point is inside the trigger volume
+    pub fn contains(&self, point: [f32; 3]) -> bool {
+        match &self.shape {
+            TriggerShape::Aabb { min, max } => {
+                (0..3).all(|i| point[i] >= min[i] && point[i] <= max[i])
+            }
+
+            TriggerShape::Convex { planes } => {
+                planes.iter().all(|plane| {
+                    dot([plane[0], plane[1], plane[2]], point) <= plane[3]
+                })
+            }
+        }
+    }
+
+    /// Get the number of bytes [Trigger::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        let shape = match &self.shape {
+            TriggerShape::Aabb { .. } => 1 + 6 * std::mem::size_of::<f32>(),
+            TriggerShape::Convex { planes } => {
+                1 + 8 + planes.len() * 4 * std::mem::size_of::<f32>()
+            }
+        };
+
+        // Kind and tag
+        shape + 2 * std::mem::size_of::<u32>() + string_size(&self.target)
+    }
+
+    /// Serialize the trigger to a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - Successfully serialized the trigger
+    /// * `Err(`[Error]`)` - Failed to serialize the trigger
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        match &self.shape {
+            TriggerShape::Aabb { min, max } => {
+                buffer.push(0);
+                write_f32s(buffer, min);
+                write_f32s(buffer, max);
+            }
+
+            TriggerShape::Convex { planes } => {
+                buffer.push(1);
+                write_usize(buffer, planes.len())?;
+                for plane in planes {
+                    write_f32s(buffer, plane);
+                }
+            }
+        }
+
+        buffer.extend_from_slice(&self.kind.to_le_bytes());
+        write_string(buffer, &self.target)?;
+        buffer.extend_from_slice(&self.tag.to_le_bytes());
+
+        Ok(())
+    }
+
+    /// Deserialize the trigger from a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the trigger
+    /// * `Err(`[Error]`)` - Failed to deserialize the trigger
+    pub fn deserialize(buffer: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallChunk);
+
+        let shape = match reader.u8()? {
+            0 => TriggerShape::Aabb {
+                min: reader.vec3()?,
+                max: reader.vec3()?,
+            },
+
+            1 => {
+                let count = reader.usize()?;
+
+                let mut planes = Vec::new();
+                for _i in 0..count {
+                    planes.push([reader.f32()?, reader.f32()?,
+                                 reader.f32()?, reader.f32()?]);
+                }
+
+                TriggerShape::Convex { planes }
+            }
+
+            shape => return Err(Error::UnknownTriggerShape(shape)),
+        };
+
+        Ok(Self {
+            shape,
+            kind: reader.u32()?,
+            target: reader.string()?,
+            tag: reader.u32()?,
+        })
+    }
+}
@@ -0,0 +1,114 @@
+//! The scale of the map coordinates
+//!
+//! Maps are authored at different scales, a Doom style map uses around 32
+//! units per meter while most engines use meters. The map stores how many
+//! units make up a meter and [Map::rescale] converts the map to another
+//! scale.
+
+use crate::*;
+use crate::transform::IDENTITY;
+
+/// The units per meter of maps saved before the scale was stored
+pub const DEFAULT_UNITS_PER_METER: f32 = 1.0;
+
+/// Check that a scale can be used for the map coordinates
+///
+/// # Returns
+///
+/// * `Ok(f32)` - The scale
+/// * `Err(`[Error]`)` - [Error::InvalidUnitScale] if the scale isn't
+///   finite and positive
+pub(crate) fn check_units_per_meter(units_per_meter: f32) -> Result<f32> {
+    if !units_per_meter.is_finite() || units_per_meter <= 0.0 {
+        return Err(Error::InvalidUnitScale(units_per_meter));
+    }
+
+    Ok(units_per_meter)
+}
+
+impl Map {
+    /// Get the number of units that make up a meter
+    pub fn units_per_meter(&self) -> f32 {
+        self.units_per_meter
+    }
+
+    /// Set the number of units that make up a meter without changing the
+    /// content, use [Map::rescale] to keep the size of the map
+    ///
+    /// # Arguments
+    ///
+    /// * `units_per_meter` - The new scale
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The scale was set
+    /// * `Err(`[Error]`)` - [Error::InvalidUnitScale] if the scale isn't
+    ///   finite and positive
+    pub fn set_units_per_meter(&mut self, units_per_meter: f32)
+        -> Result<()>
+    {
+        self.units_per_meter = check_units_per_meter(units_per_meter)?;
+
+        Ok(())
+    }
+
+    /// Scale the content of the map to another number of units per meter,
+    /// so the map keeps its size in meters
+    ///
+    /// The geometry, lights, sound emitters, triggers, entity positions,
+    /// waypoints, props, decals, interactive objects, level of detail
+    /// distances and animation offsets are scaled. The changed sectors are
+    /// marked for the next [Map::save_incremental].
+    ///
+    /// # Arguments
+    ///
+    /// * `target_units` - The number of units per meter to convert to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - Successfully rescaled the map
+    /// * `Err(`[Error]`)` - [Error::InvalidUnitScale] if the target isn't
+    ///   finite and positive
+    pub fn rescale(&mut self, target_units: f32) -> Result<()> {
+        let target_units = check_units_per_meter(target_units)?;
+        if target_units == self.units_per_meter {
+            return Ok(());
+        }
+
+        let scale = target_units / self.units_per_meter;
+
+        let mut m = IDENTITY;
+        m[0][0] = scale;
+        m[1][1] = scale;
+        m[2][2] = scale;
+        self.transform(&m);
+
+        for sector in &mut self.sectors {
+            for lod in &mut sector.lods {
+                lod.switch_distance *= scale;
+            }
+        }
+
+        for light in &mut self.lights {
+            light.radius *= scale;
+        }
+
+        for emitter in &mut self.sound_emitters {
+            emitter.radius *= scale;
+        }
+
+        for interactive in &mut self.interactives {
+            interactive.radius *= scale;
+        }
+
+        for animation in &mut self.animations {
+            for keyframe in &mut animation.keyframes {
+                keyframe.offset *= scale;
+            }
+        }
+
+        self.units_per_meter = target_units;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+//! Chunks written by newer versions of the format
+//!
+//! A new chunk only bumps [crate::map::MINOR_VERSION], so an older tool
+//! with the same major version still reads the file. The chunks it doesn't
+//! know are kept in [Map::unknown_chunks] when a map is loaded and written
+//! again when it is saved, so opening and saving a map with an older tool
+//! doesn't strip the data of a newer tool.
+//! The unknown chunks are written after the known chunks and before the
+//! signature, in the order they were read.
+
+use crate::*;
+
+/// A chunk with a tag this version doesn't know, kept as opaque bytes
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct UnknownChunk {
+    /// The tag of the chunk
+    pub tag: [u8; 4],
+
+    /// The content of the chunk
+    pub data: Vec<u8>,
+}
+
+impl Map {
+    /// Get an unknown chunk by its tag
+    ///
+    /// # Arguments
+    ///
+    /// * `tag` - The tag of the chunk
+    ///
+    /// # Returns
+    ///
+    /// * `Some(&[u8])` - The content of the first chunk with the tag
+    /// * `None` - The map doesn't have an unknown chunk with the tag
+    pub fn unknown_chunk(&self, tag: &[u8; 4]) -> Option<&[u8]> {
+        self.unknown_chunks.iter()
+            .find(|chunk| chunk.tag == *tag)
+            .map(|chunk| chunk.data.as_slice())
+    }
+}
@@ -0,0 +1,61 @@
+//! Iterating over the vertices of every mesh
+//!
+//! Global operations like recoloring, offsetting or scaling the whole map
+//! touch every vertex of every mesh, including the meshes of the levels of
+//! detail. [Map::vertices] and [Map::vertices_mut] walk all of them.
+
+use crate::*;
+
+impl Sector {
+    /// Get the meshes of the sector followed by the meshes of its levels of
+    /// detail
+    pub fn meshes(&self) -> impl Iterator<Item = &Mesh> {
+        [&self.floor_mesh, &self.ceiling_mesh, &self.wall_mesh].into_iter()
+            .chain(self.lods.iter().flat_map(|lod| {
+                [&lod.floor_mesh, &lod.ceiling_mesh, &lod.wall_mesh]
+            }))
+    }
+
+    /// Get the meshes of the sector followed by the meshes of its levels of
+    /// detail as mutable
+    pub fn meshes_mut(&mut self) -> impl Iterator<Item = &mut Mesh> {
+        [&mut self.floor_mesh, &mut self.ceiling_mesh, &mut self.wall_mesh]
+            .into_iter()
+            .chain(self.lods.iter_mut().flat_map(|lod| {
+                [&mut lod.floor_mesh, &mut lod.ceiling_mesh,
+                 &mut lod.wall_mesh]
+            }))
+    }
+
+    /// Get every vertex of the meshes of the sector and its levels of
+    /// detail
+    pub fn vertices(&self) -> impl Iterator<Item = &Vertex> {
+        self.meshes().flat_map(|mesh| mesh.vertex_buffer.iter())
+    }
+
+    /// Get every vertex of the meshes of the sector and its levels of
+    /// detail as mutable
+    pub fn vertices_mut(&mut self) -> impl Iterator<Item = &mut Vertex> {
+        self.meshes_mut().flat_map(|mesh| mesh.vertex_buffer.iter_mut())
+    }
+}
+
+impl Map {
+    /// Get every vertex of every sector, see [Sector::vertices]
+    pub fn vertices(&self) -> impl Iterator<Item = &Vertex> {
+        self.sectors.iter().flat_map(|sector| sector.vertices())
+    }
+
+    /// Get every vertex of every sector as mutable, see
+    /// [Sector::vertices_mut]
+    ///
+    /// Every sector is marked for the next [Map::save_incremental] since
+    /// any of the vertices can change.
+    pub fn vertices_mut(&mut self) -> impl Iterator<Item = &mut Vertex> {
+        for index in 0..self.sectors.len() {
+            self.dirty_sectors.insert(index);
+        }
+
+        self.sectors.iter_mut().flat_map(|sector| sector.vertices_mut())
+    }
+}
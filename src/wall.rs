@@ -0,0 +1,153 @@
+//! Wall segments of a sector
+
+use crate::*;
+use crate::buffer::{ Reader, write_f32s, write_string, string_size };
+
+use std::ops::Range;
+
+/// The wall can be seen and passed from both sides
+pub const WALL_FLAG_TWO_SIDED: u32 = 1 << 0;
+
+/// The wall blocks players and monsters even if it's two sided
+pub const WALL_FLAG_BLOCKING: u32 = 1 << 1;
+
+/// The texture of the wall is aligned to the top of the wall
+pub const WALL_FLAG_UPPER_UNPEGGED: u32 = 1 << 2;
+
+/// The texture of the wall is aligned to the bottom of the wall
+pub const WALL_FLAG_LOWER_UNPEGGED: u32 = 1 << 3;
+
+/// A single wall segment of a sector
+#[derive(Clone, PartialEq, Debug)]
+pub struct Wall {
+    /// The start of the wall at the bottom of the wall
+    pub start: [f32; 3],
+
+    /// The end of the wall at the bottom of the wall
+    pub end: [f32; 3],
+
+    /// The name of the texture used by the wall
+    pub texture: String,
+
+    /// The offset applied to the texture coordinates of the wall (u, v)
+    pub uv_offset: [f32; 2],
+
+    /// The flags of the wall, see the `WALL_FLAG_*` constants
+    pub flags: u32,
+
+    /// The first index inside the index buffer of the wall mesh that
+    /// belongs to this wall
+    pub index_start: u32,
+
+    /// The number of indices inside the index buffer of the wall mesh that
+    /// belongs to this wall
+    pub index_count: u32,
+}
+
+impl Wall {
+    /// Creates a new wall segment without any flags
+    ///
+    /// # Arguments
+    ///
+    /// * `start`   - The start of the wall
+    /// * `end`     - The end of the wall
+    /// * `texture` - The name of the texture used by the wall
+    /// * `indices` - The range of indices inside the wall mesh that belongs
+    ///   to this wall, a reversed range gives a wall without indices
+    ///
+    /// # Returns
+    ///
+    /// * [Self] - The new wall
+    pub fn new(start: [f32; 3],
+               end: [f32; 3],
+               texture: &str,
+               indices: Range<u32>)
+        -> Self
+    {
+        Self {
+            start,
+            end,
+            texture: texture.to_string(),
+            uv_offset: [0.0, 0.0],
+            flags: 0,
+            index_start: indices.start,
+            index_count: indices.end.saturating_sub(indices.start),
+        }
+    }
+
+    /// Check if the wall is two sided
+    pub fn is_two_sided(&self) -> bool {
+        self.flags & WALL_FLAG_TWO_SIDED != 0
+    }
+
+    /// Get the indices of the wall mesh that belongs to this wall
+    ///
+    /// # Arguments
+    ///
+    /// * `wall_mesh` - The wall mesh of the sector the wall belongs to
+    ///
+    /// # Returns
+    ///
+    /// * The indices, empty if the range is outside the index buffer
+    pub fn indices<'a>(&self, wall_mesh: &'a Mesh) -> &'a [u32] {
+        let start = self.index_start as usize;
+        let end = start + self.index_count as usize;
+
+        wall_mesh.index_buffer.get(start..end).unwrap_or(&[])
+    }
+
+    /// Get the number of bytes [Wall::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        // Start, end, texture offset, flags, index start and index count
+        8 * std::mem::size_of::<f32>() + 3 * std::mem::size_of::<u32>() +
+            string_size(&self.texture)
+    }
+
+    /// Serialize the wall to a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - Successfully serialized the wall
+    /// * `Err(`[Error]`)` - Failed to serialize the wall
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        write_f32s(buffer, &self.start);
+        write_f32s(buffer, &self.end);
+        write_string(buffer, &self.texture)?;
+        write_f32s(buffer, &self.uv_offset);
+        buffer.extend_from_slice(&self.flags.to_le_bytes());
+        buffer.extend_from_slice(&self.index_start.to_le_bytes());
+        buffer.extend_from_slice(&self.index_count.to_le_bytes());
+
+        Ok(())
+    }
+
+    /// Deserialize the wall from a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the wall
+    /// * `Err(`[Error]`)` - Failed to deserialize the wall
+    pub fn deserialize(buffer: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallSector);
+
+        Ok(Self {
+            start: reader.vec3()?,
+            end: reader.vec3()?,
+            texture: reader.string()?,
+            uv_offset: [reader.f32()?, reader.f32()?],
+            flags: reader.u32()?,
+            index_start: reader.u32()?,
+            index_count: reader.u32()?,
+        })
+    }
+}
@@ -0,0 +1,255 @@
+//! Hand placed waypoints for the AI, nodes connected by directed links
+//!
+//! Games where the AI walks between placed path nodes instead of a
+//! navigation mesh store the nodes inside the map, see [Map::waypoints].
+
+use crate::*;
+use crate::buffer::{ Reader, write_f32s, write_usize, write_list,
+                     list_size };
+use crate::math::{ length, sub };
+
+/// A directed link from one waypoint to another
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct WaypointLink {
+    /// The index of the waypoint the link leads to
+    pub target: u32,
+
+    /// The cost of taking the link, the distance between the waypoints
+    /// unless another cost was given
+    pub cost: f32,
+
+    /// The flags of the link, the meaning is decided by the engine, like
+    /// links that need a jump or a door to open
+    pub flags: u32,
+}
+
+/// The size of a serialized link (target, cost, flags)
+const LINK_SIZE: usize = 3 * std::mem::size_of::<u32>();
+
+/// A single node of the waypoint graph
+#[derive(Clone, PartialEq, Debug)]
+pub struct Waypoint {
+    /// The position of the node
+    pub position: [f32; 3],
+
+    /// The flags of the node, the meaning is decided by the engine, like
+    /// cover spots or sniper positions
+    pub flags: u32,
+
+    /// The links leaving the node
+    pub links: Vec<WaypointLink>,
+}
+
+impl Waypoint {
+    /// Get the number of bytes [Waypoint::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        // Position, flags and the link count
+        3 * std::mem::size_of::<f32>() + std::mem::size_of::<u32>() + 8 +
+            self.links.len() * LINK_SIZE
+    }
+
+    /// Serialize the waypoint to a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - Successfully serialized the waypoint
+    /// * `Err(`[Error]`)` - Failed to serialize the waypoint
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        write_f32s(buffer, &self.position);
+        buffer.extend_from_slice(&self.flags.to_le_bytes());
+
+        write_usize(buffer, self.links.len())?;
+        for link in &self.links {
+            buffer.extend_from_slice(&link.target.to_le_bytes());
+            buffer.extend_from_slice(&link.cost.to_le_bytes());
+            buffer.extend_from_slice(&link.flags.to_le_bytes());
+        }
+
+        Ok(())
+    }
+
+    /// Deserialize the waypoint from a buffer, the targets of the links are
+    /// checked by [WaypointGraph::deserialize]
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the waypoint
+    /// * `Err(`[Error]`)` - Failed to deserialize the waypoint
+    pub fn deserialize(buffer: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallChunk);
+
+        let position = reader.vec3()?;
+        let flags = reader.u32()?;
+
+        let count = reader.usize()?;
+        let mut links = Vec::new();
+        for _i in 0..count {
+            links.push(WaypointLink {
+                target: reader.u32()?,
+                cost: reader.f32()?,
+                flags: reader.u32()?,
+            });
+        }
+
+        Ok(Self {
+            position,
+            flags,
+            links,
+        })
+    }
+}
+
+/// The waypoints of a map
+#[derive(Clone, PartialEq, Default, Debug)]
+pub struct WaypointGraph {
+    /// The nodes of the graph, the links reference them by index
+    pub nodes: Vec<Waypoint>,
+}
+
+impl WaypointGraph {
+    /// Creates a new graph without any nodes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a node without any links
+    ///
+    /// # Arguments
+    ///
+    /// * `position` - The position of the node
+    /// * `flags`    - The flags of the node
+    ///
+    /// # Returns
+    ///
+    /// * The index of the node
+    pub fn add_node(&mut self, position: [f32; 3], flags: u32) -> u32 {
+        self.nodes.push(Waypoint {
+            position,
+            flags,
+            links: Vec::new(),
+        });
+
+        (self.nodes.len() - 1) as u32
+    }
+
+    /// Add a directed link between two nodes
+    ///
+    /// # Arguments
+    ///
+    /// * `from`  - The index of the node the link leaves
+    /// * `to`    - The index of the node the link leads to
+    /// * `cost`  - The cost of the link, `None` for the distance between the
+    ///   nodes
+    /// * `flags` - The flags of the link
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - The link was added
+    /// * `Err(`[Error]`)` - [Error::InvalidWaypointLink] if one of the nodes
+    ///   doesn't exist
+    pub fn link(&mut self, from: u32, to: u32, cost: Option<f32>, flags: u32)
+        -> Result<()>
+    {
+        let (Some(start), Some(end)) =
+            (self.nodes.get(from as usize), self.nodes.get(to as usize))
+        else {
+            return Err(Error::InvalidWaypointLink(from.max(to)));
+        };
+
+        let cost = cost.unwrap_or_else(|| {
+            length(sub(end.position, start.position))
+        });
+
+        self.nodes[from as usize].links.push(WaypointLink {
+            target: to,
+            cost,
+            flags,
+        });
+
+        Ok(())
+    }
+
+    /// Add a link in both directions between two nodes, see
+    /// [WaypointGraph::link]
+    pub fn link_both(&mut self,
+                     a: u32,
+                     b: u32,
+                     cost: Option<f32>,
+                     flags: u32)
+        -> Result<()>
+    {
+        self.link(a, b, cost, flags)?;
+        self.link(b, a, cost, flags)
+    }
+
+    /// Get the links leaving a node
+    ///
+    /// # Arguments
+    ///
+    /// * `node` - The index of the node
+    ///
+    /// # Returns
+    ///
+    /// * The links, empty if the node doesn't exist
+    pub fn links(&self, node: u32) -> &[WaypointLink] {
+        self.nodes.get(node as usize)
+            .map_or(&[], |node| node.links.as_slice())
+    }
+
+    /// Get the number of bytes [WaypointGraph::serialize] writes
+    pub fn serialized_size(&self) -> usize {
+        list_size(&self.nodes, Waypoint::serialized_size)
+    }
+
+    /// Serialize the graph to a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we use to append the data to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok()` - Successfully serialized the graph
+    /// * `Err(`[Error]`)` - Failed to serialize the graph
+    pub fn serialize<O>(&self, buffer: &mut O) -> Result<()>
+        where O: Output + ?Sized
+    {
+        write_list(buffer, &self.nodes, Waypoint::serialize)
+    }
+
+    /// Deserialize the graph from a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer we should deserialize
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(`[Self]`)` - Successfully deserialized the graph
+    /// * `Err(`[Error]`)` - Failed to deserialize the graph or
+    ///   [Error::InvalidWaypointLink] if a link leads to a node that doesn't
+    ///   exist
+    pub fn deserialize(buffer: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(buffer, Error::BufferToSmallChunk);
+        let nodes = reader.list(Waypoint::deserialize)?;
+
+        let links = nodes.iter().flat_map(|node| &node.links);
+        for link in links {
+            if link.target as usize >= nodes.len() {
+                return Err(Error::InvalidWaypointLink(link.target));
+            }
+        }
+
+        Ok(Self { nodes })
+    }
+}
@@ -0,0 +1,119 @@
+//! Helpers for rendering meshes with wgpu, enabled by the `wgpu` feature
+
+use crate::*;
+
+use wgpu::util::DeviceExt;
+
+/// The vertex attributes of [Vertex] for wgpu, the position uses shader
+/// location 0, the texture coordinates location 1 and the color location 2
+const WGPU_ATTRIBUTES: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![
+    0 => Float32x3,
+    1 => Float32x2,
+    2 => Float32x4,
+];
+
+/// The vertex and index buffers of a mesh uploaded to the GPU
+pub struct MeshBuffers {
+    /// The vertex buffer, laid out as described by [Vertex::wgpu_layout]
+    pub vertex_buffer: wgpu::Buffer,
+
+    /// The index buffer using [wgpu::IndexFormat::Uint32]
+    pub index_buffer: wgpu::Buffer,
+
+    /// The number of indices inside the index buffer
+    pub index_count: u32,
+}
+
+impl Vertex {
+    /// Get the vertex buffer layout for the buffers created by
+    /// [Mesh::create_wgpu_buffers]
+    ///
+    /// The position uses shader location 0, the texture coordinates
+    /// location 1 and the color location 2.
+    pub fn wgpu_layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: Vertex::STRIDE as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &WGPU_ATTRIBUTES,
+        }
+    }
+}
+
+impl VertexFormat {
+    /// Convert the format to the matching wgpu format
+    pub fn to_wgpu(self) -> wgpu::VertexFormat {
+        match self {
+            VertexFormat::Float32 => wgpu::VertexFormat::Float32,
+            VertexFormat::Float32x2 => wgpu::VertexFormat::Float32x2,
+            VertexFormat::Float32x3 => wgpu::VertexFormat::Float32x3,
+            VertexFormat::Float32x4 => wgpu::VertexFormat::Float32x4,
+            VertexFormat::Uint32 => wgpu::VertexFormat::Uint32,
+            VertexFormat::Unorm8x4 => wgpu::VertexFormat::Unorm8x4,
+        }
+    }
+}
+
+impl Topology {
+    /// Convert the topology to the matching wgpu topology
+    pub fn to_wgpu(self) -> wgpu::PrimitiveTopology {
+        match self {
+            Topology::TriangleList => wgpu::PrimitiveTopology::TriangleList,
+            Topology::TriangleStrip => wgpu::PrimitiveTopology::TriangleStrip,
+            Topology::LineList => wgpu::PrimitiveTopology::LineList,
+            Topology::PointList => wgpu::PrimitiveTopology::PointList,
+        }
+    }
+}
+
+impl VertexLayout {
+    /// Convert the attributes to wgpu attributes, the attributes get the
+    /// shader locations in order starting from 0
+    pub fn wgpu_attributes(&self) -> Vec<wgpu::VertexAttribute> {
+        self.attributes.iter()
+            .enumerate()
+            .map(|(location, attribute)| wgpu::VertexAttribute {
+                format: attribute.format.to_wgpu(),
+                offset: attribute.offset as wgpu::BufferAddress,
+                shader_location: location as u32,
+            })
+            .collect()
+    }
+}
+
+impl Mesh {
+    /// Upload the mesh to the GPU
+    ///
+    /// # Arguments
+    ///
+    /// * `device` - The device to create the buffers on
+    /// * `label`  - The debug label of the buffers
+    ///
+    /// # Returns
+    ///
+    /// * [MeshBuffers] - The vertex and index buffers of the mesh
+    pub fn create_wgpu_buffers(&self,
+                               device: &wgpu::Device,
+                               label: Option<&str>)
+        -> MeshBuffers
+    {
+        let vertex_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label,
+                contents: &self.as_vertex_bytes(),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+
+        let index_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label,
+                contents: &self.as_index_bytes(),
+                usage: wgpu::BufferUsages::INDEX,
+            });
+
+        MeshBuffers {
+            vertex_buffer,
+            index_buffer,
+            index_count: self.index_buffer.len() as u32,
+        }
+    }
+}
@@ -0,0 +1,186 @@
+//! Checking and fixing the winding order of triangles
+//!
+//! The front face of a triangle is the side where its vertices are ordered
+//! counter-clockwise. Floors should face up and ceilings down so backface
+//! culling removes the side the player can't see.
+
+use crate::*;
+use crate::math::*;
+
+impl Mesh {
+    /// Get the normal of the front face of a triangle
+    ///
+    /// # Arguments
+    ///
+    /// * `triangle` - The index of the triangle
+    ///
+    /// # Returns
+    ///
+    /// * `Some([f32; 3])` - The normalized face normal
+    /// * `None` - The triangle doesn't exist, references vertices that
+    ///   don't exist or has no area, or the mesh isn't a
+    ///   [Topology::TriangleList]
+    pub fn face_normal(&self, triangle: usize) -> Option<[f32; 3]> {
+        if self.topology != Topology::TriangleList {
+            return None;
+        }
+
+        let start = triangle * 3;
+        let indices = self.index_buffer.get(start..start + 3)?;
+
+        let position = |index: u32| {
+            self.vertex_buffer.get(index as usize).map(|vertex| vertex.pos)
+        };
+
+        let normal = triangle_normal(position(indices[0])?,
+                                     position(indices[1])?,
+                                     position(indices[2])?);
+        if length(normal) == 0.0 {
+            return None;
+        }
+
+        Some(normalize(normal))
+    }
+
+    /// Get the direction most of the surface of the mesh faces, the face
+    /// normals weighted by the area of the triangles
+    ///
+    /// # Returns
+    ///
+    /// * `Some([f32; 3])` - The normalized direction
+    /// * `None` - The faces cancel each other out or the mesh has no area
+    pub fn dominant_normal(&self) -> Option<[f32; 3]> {
+        let mut sum = [0.0; 3];
+        for [a, b, c] in self.triangle_positions() {
+            sum = add(sum, triangle_normal(a, b, c));
+        }
+
+        if length(sum) == 0.0 {
+            return None;
+        }
+
+        Some(normalize(sum))
+    }
+
+    /// Get the triangles facing away from a reference direction
+    ///
+    /// # Arguments
+    ///
+    /// * `reference` - The direction every triangle should face, like the
+    ///   up direction for a floor or [Mesh::dominant_normal]
+    ///
+    /// # Returns
+    ///
+    /// * The indices of the triangles in ascending order, triangles without
+    ///   a face normal are skipped, empty if the mesh isn't a
+    ///   [Topology::TriangleList]
+    pub fn inconsistent_winding(&self, reference: [f32; 3]) -> Vec<usize> {
+        if self.topology != Topology::TriangleList {
+            return Vec::new();
+        }
+
+        (0..self.index_buffer.len() / 3)
+            .filter(|&triangle| {
+                self.face_normal(triangle)
+                    .is_some_and(|normal| dot(normal, reference) < 0.0)
+            })
+            .collect()
+    }
+
+    /// Check if every triangle faces the reference direction, see
+    /// [Mesh::inconsistent_winding]
+    pub fn is_winding_consistent(&self, reference: [f32; 3]) -> bool {
+        self.inconsistent_winding(reference).is_empty()
+    }
+
+    /// Reverse the winding order of triangles, does nothing if the mesh
+    /// isn't a [Topology::TriangleList] since the triangles of a strip share
+    /// their indices
+    ///
+    /// # Arguments
+    ///
+    /// * `triangles` - The indices of the triangles to flip
+    pub fn flip_triangles(&mut self, triangles: &[usize]) {
+        if self.topology != Topology::TriangleList {
+            return;
+        }
+
+        for &triangle in triangles {
+            let start = triangle * 3;
+            if let Some(indices) = self.index_buffer.get_mut(start..start + 3) {
+                indices.swap(1, 2);
+            }
+        }
+    }
+
+    /// Flip the triangles facing away from a reference direction, only
+    /// [Topology::TriangleList] meshes are fixed
+    ///
+    /// # Arguments
+    ///
+    /// * `reference` - The direction every triangle should face
+    ///
+    /// # Returns
+    ///
+    /// * The number of triangles flipped
+    pub fn fix_winding(&mut self, reference: [f32; 3]) -> usize {
+        let triangles = self.inconsistent_winding(reference);
+        self.flip_triangles(&triangles);
+
+        triangles.len()
+    }
+}
+
+impl Sector {
+    /// Make the floors of the sector and its levels of detail face up and
+    /// the ceilings face down, the walls are left as they are
+    ///
+    /// # Arguments
+    ///
+    /// * `up` - The up direction of the map
+    ///
+    /// # Returns
+    ///
+    /// * The number of triangles flipped
+    pub fn fix_winding(&mut self, up: [f32; 3]) -> usize {
+        let down = scale(up, -1.0);
+
+        let mut flipped = self.floor_mesh.fix_winding(up);
+        flipped += self.ceiling_mesh.fix_winding(down);
+
+        for lod in &mut self.lods {
+            flipped += lod.floor_mesh.fix_winding(up);
+            flipped += lod.ceiling_mesh.fix_winding(down);
+        }
+
+        flipped
+    }
+}
+
+impl Map {
+    /// Fix the winding order of the floors and ceilings of every sector,
+    /// see [Sector::fix_winding]
+    ///
+    /// The changed sectors are marked for the next [Map::save_incremental].
+    ///
+    /// # Arguments
+    ///
+    /// * `up` - The up direction of the map
+    ///
+    /// # Returns
+    ///
+    /// * The number of triangles flipped
+    pub fn fix_winding(&mut self, up: [f32; 3]) -> usize {
+        let mut flipped = 0;
+        for index in 0..self.sectors.len() {
+            let count = self.sectors[index].fix_winding(up);
+            if count > 0 {
+                self.mark_sector_dirty(index);
+            }
+
+            flipped += count;
+        }
+
+        flipped
+    }
+}